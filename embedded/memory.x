@@ -1,6 +1,8 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* The last two 4K sectors are reserved for the display settings and */
+    /* the high score table                                              */
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100 - 8K
 
     /* Pick one of the two options for RAM layout     */
 
@@ -0,0 +1,99 @@
+//! Piezo buzzer on a PWM pin that plays short sounds for game events.
+//!
+//! Sounds are sequences of square wave notes advanced from the main loop,
+//! so playing one never blocks rendering. A new sound replaces the one
+//! that is playing.
+
+use embassy_rp::clocks;
+use embassy_rp::pwm::{Config, Pwm};
+use fixed::traits::ToFixed;
+use teotile::GameEvent;
+
+// Keeps `top` within u16 for every note frequency used below
+const DIVIDER: u8 = 64;
+
+/// Frequency in Hz and duration in milliseconds.
+type Note = (u32, u32);
+
+fn event_sound(event: GameEvent) -> &'static [Note] {
+    match event {
+        GameEvent::MenuMoved => &[(660, 30)],
+        GameEvent::GameStarted => &[(523, 60), (784, 100)],
+        GameEvent::GameQuit => &[(392, 60), (262, 100)],
+        GameEvent::PiecePlaced(_) => &[(220, 40)],
+        GameEvent::LinesCleared(_) => &[(523, 60), (659, 60), (784, 120)],
+        GameEvent::FoodEaten(_) => &[(880, 50)],
+        GameEvent::PaddleHit(_) => &[(440, 40)],
+        GameEvent::Scored(_) => &[(784, 50), (1047, 80)],
+        GameEvent::Jumped(_) => &[(330, 30), (494, 50)],
+        GameEvent::Shot(_) => &[(1200, 30)],
+        GameEvent::Explosion => &[(110, 120)],
+        GameEvent::PlayerDied(_) => &[(196, 100), (131, 200)],
+        GameEvent::GameOver(_) => &[(392, 120), (330, 120), (262, 250)],
+        GameEvent::NewHighScore => &[(523, 80), (659, 80), (784, 80), (1047, 200)],
+    }
+}
+
+pub struct Buzzer<'d> {
+    pwm: Pwm<'d>,
+    config: Config,
+    sound: &'static [Note],
+    note: usize,
+    remaining_ms: u32,
+}
+
+impl<'d> Buzzer<'d> {
+    pub fn new(pwm: Pwm<'d>) -> Self {
+        let mut config = Config::default();
+        config.divider = DIVIDER.to_fixed();
+        let mut buzzer = Self {
+            pwm,
+            config,
+            sound: &[],
+            note: 0,
+            remaining_ms: 0,
+        };
+        buzzer.silence();
+        buzzer
+    }
+
+    pub fn play(&mut self, event: GameEvent) {
+        self.sound = event_sound(event);
+        self.note = 0;
+        self.start_note();
+    }
+
+    /// Advances the playing sound, called once per frame.
+    pub fn update(&mut self, elapsed_ms: u32) {
+        if self.sound.is_empty() {
+            return;
+        }
+        self.remaining_ms = self.remaining_ms.saturating_sub(elapsed_ms);
+        if self.remaining_ms == 0 {
+            self.note += 1;
+            self.start_note();
+        }
+    }
+
+    fn start_note(&mut self) {
+        match self.sound.get(self.note) {
+            Some(&(frequency, duration_ms)) => {
+                let top = clocks::clk_sys_freq() / DIVIDER as u32 / frequency - 1;
+                self.config.top = top as u16;
+                // 50% duty cycle is the loudest for a piezo
+                self.config.compare_a = self.config.top / 2;
+                self.pwm.set_config(&self.config);
+                self.remaining_ms = duration_ms;
+            }
+            None => {
+                self.sound = &[];
+                self.silence();
+            }
+        }
+    }
+
+    fn silence(&mut self) {
+        self.config.compare_a = 0;
+        self.pwm.set_config(&self.config);
+    }
+}
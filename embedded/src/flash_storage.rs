@@ -0,0 +1,55 @@
+use embassy_rp::flash::{Blocking, Error, Flash, ERASE_SIZE};
+use embassy_rp::peripherals::FLASH;
+use teotile::{DisplaySettings, ScoreStorage, Scoreboard, SettingsStorage};
+
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+// The last two sectors of flash are kept out of the program area in memory.x
+const SCORES_OFFSET: u32 = (FLASH_SIZE - ERASE_SIZE) as u32;
+const SETTINGS_OFFSET: u32 = SCORES_OFFSET - ERASE_SIZE as u32;
+
+/// Stores the high score table in the last flash sector and the display
+/// settings in the one before it.
+pub struct FlashStorage<'d> {
+    flash: Flash<'d, FLASH, Blocking, FLASH_SIZE>,
+}
+
+impl<'d> FlashStorage<'d> {
+    pub fn new(flash: Flash<'d, FLASH, Blocking, FLASH_SIZE>) -> Self {
+        Self { flash }
+    }
+
+    fn write_sector(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+        self.flash
+            .blocking_erase(offset, offset + ERASE_SIZE as u32)?;
+        self.flash.blocking_write(offset, bytes)
+    }
+}
+
+impl ScoreStorage for FlashStorage<'_> {
+    type Error = Error;
+
+    fn load(&mut self) -> Result<Option<Scoreboard>, Self::Error> {
+        let mut bytes = [0u8; Scoreboard::ENCODED_LEN];
+        self.flash.blocking_read(SCORES_OFFSET, &mut bytes)?;
+        Ok(Scoreboard::from_bytes(&bytes))
+    }
+
+    fn save(&mut self, scoreboard: &Scoreboard) -> Result<(), Self::Error> {
+        self.write_sector(SCORES_OFFSET, &scoreboard.to_bytes())
+    }
+}
+
+impl SettingsStorage for FlashStorage<'_> {
+    type Error = Error;
+
+    fn load(&mut self) -> Result<Option<DisplaySettings>, Self::Error> {
+        let mut bytes = [0u8; DisplaySettings::ENCODED_LEN];
+        self.flash.blocking_read(SETTINGS_OFFSET, &mut bytes)?;
+        Ok(DisplaySettings::from_bytes(&bytes))
+    }
+
+    fn save(&mut self, settings: &DisplaySettings) -> Result<(), Self::Error> {
+        self.write_sector(SETTINGS_OFFSET, &settings.to_bytes())
+    }
+}
@@ -1,6 +1,7 @@
 use embassy_rp::gpio::{AnyPin, Input, Pull};
 use embassy_time::{Duration, Instant, Timer};
 use heapless::spsc::Queue;
+use teotile::TapHold;
 
 #[derive(Clone, Copy)]
 pub enum GamepadEvent {
@@ -10,6 +11,8 @@ pub enum GamepadEvent {
     DPadRight(usize),
     South(usize),
     East(usize),
+    /// South held down for `teotile::LONG_PRESS`
+    SouthHeld(usize),
 }
 
 pub struct GamepadHandler {
@@ -34,6 +37,9 @@ struct PlayerInputs {
     left: Button,
     right: Button,
     south: Button,
+    // There is no spare button for pausing, so holding South pauses instead
+    // and South only selects when it is let go early
+    south_tap_hold: TapHold<GamepadEvent>,
     east: Button,
     event_queue: Queue<GamepadEvent, 16>,
 }
@@ -50,11 +56,8 @@ impl Button {
         }
     }
 
-    fn check(
-        &mut self,
-        event: fn(usize) -> GamepadEvent,
-        event_queue: &mut Queue<GamepadEvent, 16>,
-    ) {
+    // Returns the new state when the button was pressed or released
+    fn debounce(&mut self) -> Option<bool> {
         let now = Instant::now();
         let is_pressed = self.input.is_low();
 
@@ -63,10 +66,18 @@ impl Button {
         {
             self.state.is_pressed = is_pressed;
             self.state.last_change = now;
+            return Some(is_pressed);
+        }
+        None
+    }
 
-            if is_pressed {
-                event_queue.enqueue(event(self.player_id)).ok();
-            }
+    fn check(
+        &mut self,
+        event: fn(usize) -> GamepadEvent,
+        event_queue: &mut Queue<GamepadEvent, 16>,
+    ) {
+        if self.debounce() == Some(true) {
+            event_queue.enqueue(event(self.player_id)).ok();
         }
     }
 }
@@ -87,6 +98,10 @@ impl PlayerInputs {
             left: Button::new(left, player_id),
             right: Button::new(right, player_id),
             south: Button::new(south, player_id),
+            south_tap_hold: TapHold::new(
+                GamepadEvent::South(player_id),
+                GamepadEvent::SouthHeld(player_id),
+            ),
             east: Button::new(east, player_id),
             event_queue: Queue::new(),
         }
@@ -100,7 +115,18 @@ impl PlayerInputs {
             .check(GamepadEvent::DPadLeft, &mut self.event_queue);
         self.right
             .check(GamepadEvent::DPadRight, &mut self.event_queue);
-        self.south.check(GamepadEvent::South, &mut self.event_queue);
+        let now = core::time::Duration::from_micros(Instant::now().as_micros());
+        let south = match self.south.debounce() {
+            Some(true) => {
+                self.south_tap_hold.press(now);
+                None
+            }
+            Some(false) => self.south_tap_hold.release(now),
+            None => self.south_tap_hold.poll(now),
+        };
+        if let Some(event) = south {
+            self.event_queue.enqueue(event).ok();
+        }
         self.east.check(GamepadEvent::East, &mut self.event_queue);
     }
 }
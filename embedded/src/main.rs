@@ -6,12 +6,14 @@ extern crate alloc;
 use defmt::*;
 use embassy_executor::Spawner;
 use embassy_rp::dma::{AnyChannel, Channel};
+use embassy_rp::flash::Flash;
 use embassy_rp::gpio::Pin;
 use embassy_rp::peripherals::PIO0;
 use embassy_rp::pio::{
     Common, Config, FifoJoin, Instance, InterruptHandler, Pio, PioPin, ShiftConfig, ShiftDirection,
     StateMachine,
 };
+use embassy_rp::pwm::{Config as PwmConfig, Pwm};
 use embassy_rp::{bind_interrupts, clocks, into_ref, Peripheral, PeripheralRef};
 use embassy_time::{Duration, Ticker, Timer};
 use fixed::types::U24F8;
@@ -21,10 +23,17 @@ use {defmt_rtt as _, panic_probe as _};
 
 use core::mem::MaybeUninit;
 use core::time::Duration as StdDuration;
-use teotile::{ButtonState, CommandType, GameCommand, GameEngine, Player};
+use teotile::{
+    ButtonState, Chain, ColorPipeline, CommandType, Corner, GameCommand, GameEngine, LedLayout,
+    Order, Player, Wiring, GRID_HEIGHT, GRID_WIDTH,
+};
 
+mod buzzer;
+use buzzer::Buzzer;
 mod gamepad;
 use gamepad::{GamepadEvent, GamepadHandler};
+mod flash_storage;
+use flash_storage::FlashStorage;
 
 use embedded_alloc::Heap;
 
@@ -34,6 +43,18 @@ static HEAP: Heap = Heap::empty();
 const HEAP_SIZE: usize = 32768;
 static mut HEAP_MEM: [MaybeUninit<u8>; HEAP_SIZE] = [MaybeUninit::uninit(); HEAP_SIZE];
 
+// How the LED strip runs through the panel, change this to match its
+// wiring. Rows from the bottom left that all start on the left.
+const LAYOUT: LedLayout = LedLayout {
+    pixels: Chain::new(Corner::BottomLeft, Order::Rows, Wiring::Progressive),
+    ..LedLayout::new()
+};
+const _: () = assert!(LAYOUT.validate().is_ok());
+const GAMMA: f32 = teotile::DEFAULT_GAMMA;
+// Frames are dimmed to stay within what the power supply of the strip can
+// deliver, in milliamps
+const POWER_BUDGET: Option<u32> = Some(2000);
+
 bind_interrupts!(struct Irqs {
     PIO0_IRQ_0 => InterruptHandler<PIO0>;
 });
@@ -156,12 +177,30 @@ async fn main(_spawner: Spawner) {
     );
 
     // This is the number of leds in the string.
-    const NUM_LEDS: usize = 144; // Adjusted to 12x12 grid
+    const NUM_LEDS: usize = GRID_WIDTH * GRID_HEIGHT;
     let mut data = [RGB8::default(); NUM_LEDS];
 
     let mut ws2812 = Ws2812::new(&mut common, sm0, p.DMA_CH0, p.PIN_16);
 
+    // Piezo buzzer on GPIO 14, which is output A of PWM slice 7
+    let mut buzzer = Buzzer::new(Pwm::new_output_a(
+        p.PWM_SLICE7,
+        p.PIN_14,
+        PwmConfig::default(),
+    ));
+
+    let mut pipeline = ColorPipeline::new(GAMMA);
+    pipeline.set_power_budget(POWER_BUDGET);
+
+    // The Pico has no clock, so night mode can only be turned on and off
     let mut game_engine = GameEngine::default();
+    let mut storage = FlashStorage::new(Flash::new_blocking(p.FLASH));
+    if game_engine.load_scores(&mut storage).is_err() {
+        warn!("Failed to load high scores");
+    }
+    if game_engine.load_settings(&mut storage).is_err() {
+        warn!("Failed to load settings");
+    }
 
     // Main game loop
     let mut ticker = Ticker::every(Duration::from_millis(16)); // ~60 FPS
@@ -177,15 +216,27 @@ async fn main(_spawner: Spawner) {
 
         // Update game state
         game_engine.update(StdDuration::from_millis(16)).unwrap();
+        for event in game_engine.drain_events() {
+            buzzer.play(event);
+        }
+        buzzer.update(16);
+        if game_engine.save_scores(&mut storage).is_err() {
+            warn!("Failed to save high scores");
+        }
+        if game_engine.save_settings(&mut storage).is_err() {
+            warn!("Failed to save settings");
+        }
+        pipeline.set_brightness(game_engine.display_settings().brightness);
+        pipeline.set_night_mode(game_engine.is_night());
 
         // Render game state
         if let Ok(render_board) = game_engine.render() {
+            let render_board = pipeline.apply(&render_board);
             // Convert render_board to LED data
-            for col in 0..12 {
-                for row in 0..12 {
-                    let i = col + row * 12;
+            for col in 0..GRID_WIDTH {
+                for row in 0..GRID_HEIGHT {
                     let pixel = render_board.get(col, row);
-                    data[i] = RGB8::new(pixel.r, pixel.g, pixel.b);
+                    data[LAYOUT.led_index(col, row)] = RGB8::new(pixel.r, pixel.g, pixel.b);
                 }
             }
             ws2812.write(&data).await;
@@ -227,13 +278,15 @@ fn gamepad_event_to_command(event: GamepadEvent) -> Option<GameCommand> {
             ButtonState::Pressed,
             player_from_id(id),
         )),
+        GamepadEvent::SouthHeld(id) => Some(GameCommand::new(
+            CommandType::Pause,
+            ButtonState::Pressed,
+            player_from_id(id),
+        )),
     }
 }
 
 fn player_from_id(id: usize) -> Player {
-    match id {
-        0 => Player::Player1,
-        1 => Player::Player2,
-        _ => Player::Player1, // Default to Player1 for any additional controllers
-    }
+    // Default to Player1 for any additional controllers
+    Player::from_index(id).unwrap_or(Player::Player1)
 }
@@ -5,8 +5,21 @@ use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
 
+const DEFAULT_GRID_SIZE: usize = 12;
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
 fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=static/pixel-art");
+    println!("cargo:rerun-if-changed=static/font");
+    println!("cargo:rerun-if-env-changed=TEOTILE_GRID_WIDTH");
+    println!("cargo:rerun-if-env-changed=TEOTILE_GRID_HEIGHT");
+
     let out_dir = env::var("OUT_DIR").unwrap();
+    write_grid_config(&out_dir);
+    write_font(&out_dir);
+
     let image_dir = "static/pixel-art";
     let output_file_path = Path::new(&out_dir).join("pixel_art.rs");
     let mut output_file = File::create(output_file_path).unwrap();
@@ -48,3 +61,74 @@ fn main() {
 
     writeln!(&mut output_file, "}}").unwrap();
 }
+
+// The font image has one glyph after another in a single row, in the order
+// of the characters listed in the accompanying text file. Each glyph is
+// stored as one bitmask per row from the top, with bit 2 as the left column.
+fn write_font(out_dir: &str) {
+    let chars = fs::read_to_string("static/font/font_3x5.txt").unwrap();
+    let chars = chars.trim_end_matches('\n');
+    let img = ImageReader::open("static/font/font_3x5.png")
+        .unwrap()
+        .decode()
+        .unwrap();
+    let (width, height) = img.dimensions();
+
+    if width != GLYPH_WIDTH * chars.chars().count() as u32 || height != GLYPH_HEIGHT {
+        panic!("Font image must be 3x5 pixels per character in font_3x5.txt");
+    }
+
+    let img = img.to_luma8();
+    let output_file_path = Path::new(out_dir).join("font.rs");
+    let mut output_file = File::create(output_file_path).unwrap();
+
+    writeln!(&mut output_file, "pub mod font {{").unwrap();
+    writeln!(&mut output_file, "    pub const CHARS: &str = {:?};", chars).unwrap();
+    writeln!(
+        &mut output_file,
+        "    pub const GLYPHS: [[u8; {}]; {}] = [",
+        GLYPH_HEIGHT,
+        chars.chars().count()
+    )
+    .unwrap();
+
+    for i in 0..chars.chars().count() as u32 {
+        let rows: Vec<String> = (0..GLYPH_HEIGHT)
+            .map(|y| {
+                let mask = (0..GLYPH_WIDTH).fold(0u8, |mask, x| {
+                    let lit = img.get_pixel(i * GLYPH_WIDTH + x, y)[0] > 127;
+                    (mask << 1) | lit as u8
+                });
+                format!("{:#05b}", mask)
+            })
+            .collect();
+        writeln!(&mut output_file, "        [{}],", rows.join(", ")).unwrap();
+    }
+
+    writeln!(&mut output_file, "    ];").unwrap();
+    writeln!(&mut output_file, "}}").unwrap();
+}
+
+fn write_grid_config(out_dir: &str) {
+    let width = grid_dimension("TEOTILE_GRID_WIDTH");
+    let height = grid_dimension("TEOTILE_GRID_HEIGHT");
+
+    let output_file_path = Path::new(out_dir).join("grid_config.rs");
+    let mut output_file = File::create(output_file_path).unwrap();
+    writeln!(&mut output_file, "pub const GRID_WIDTH: usize = {};", width).unwrap();
+    writeln!(
+        &mut output_file,
+        "pub const GRID_HEIGHT: usize = {};",
+        height
+    )
+    .unwrap();
+}
+
+fn grid_dimension(var: &str) -> usize {
+    match env::var(var) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a positive integer", var)),
+        Err(_) => DEFAULT_GRID_SIZE,
+    }
+}
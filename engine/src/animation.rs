@@ -1,4 +1,5 @@
 use crate::game::RGB;
+use crate::snapshot::snapshot_struct;
 use core::f64::consts::PI;
 use core::time::Duration;
 use libm::{fabs, sin};
@@ -9,6 +10,12 @@ pub struct Animation {
     animation_speed: Duration,
 }
 
+snapshot_struct!(Animation {
+    state,
+    last_update_time,
+    animation_speed,
+});
+
 impl Animation {
     pub fn new(animation_speed: Duration) -> Self {
         Self {
@@ -18,19 +25,22 @@ impl Animation {
         }
     }
 
+    // Advances a state for every `animation_speed` since the last one, so
+    // the animation runs equally fast at any frame rate. Returns false if it
+    // started over.
     pub fn update(&mut self, current_time: Duration) -> bool {
-        if current_time - self.last_update_time > self.animation_speed {
-            self.last_update_time = current_time;
-
-            if self.state >= 20 {
-                self.state = 0;
-                return false;
-            } else {
-                self.state += 1;
-                return true;
-            }
+        let speed = self.animation_speed.as_nanos().max(1);
+        let elapsed = current_time.saturating_sub(self.last_update_time);
+        let steps = elapsed.as_nanos() / speed;
+        if steps == 0 {
+            return true;
         }
-        true
+        self.last_update_time =
+            current_time - Duration::from_nanos((elapsed.as_nanos() % speed) as u64);
+
+        let state = self.state as u128 + steps;
+        self.state = (state % 21) as usize;
+        state <= 20
     }
 
     pub fn get_color(&self) -> RGB {
@@ -0,0 +1,167 @@
+//! Computer players for games that are normally played by two people.
+//!
+//! A `Bot` looks at the game every tick and answers with the button its
+//! player would press. `BotPlayer` wraps a game and one bot so the pair can
+//! be run like any other game.
+
+use crate::event::GameEvents;
+use crate::game::{DEFAULT_TICK, Game, GameCommand, GameStatus, Player};
+use crate::snapshot::{Reader, Snapshot, SnapshotError, Writer};
+use crate::timestep::FixedStep;
+use crate::{GameError, RenderBoard};
+use core::time::Duration;
+
+pub trait Bot<G: Game> {
+    /// Called once per tick of `DEFAULT_TICK` before the game is updated,
+    /// so bots play the same at any frame rate. Bots only press buttons,
+    /// they never send `ButtonState::Released`.
+    fn next_command(
+        &mut self,
+        game: &G,
+        player: Player,
+        delta_time: Duration,
+    ) -> Option<GameCommand>;
+}
+
+/// A game where `player` is controlled by `bot`. Input for that player is
+/// ignored so a second controller can't fight the bot.
+pub struct BotPlayer<G, B> {
+    game: G,
+    bot: B,
+    player: Player,
+    fixed_step: FixedStep,
+}
+
+impl<G: Game, B: Bot<G>> BotPlayer<G, B> {
+    pub fn new(game: G, bot: B, player: Player) -> Self {
+        Self {
+            game,
+            bot,
+            player,
+            fixed_step: FixedStep::new(DEFAULT_TICK),
+        }
+    }
+
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+}
+
+impl<G: Game, B: Bot<G>> Game for BotPlayer<G, B> {
+    fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
+        if input_command.player == self.player {
+            return Ok(());
+        }
+        self.game.process_input(input_command)
+    }
+
+    fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
+        for _ in 0..self.fixed_step.advance(delta_time) {
+            if let Some(command) = self.bot.next_command(&self.game, self.player, DEFAULT_TICK) {
+                self.game.process_input(command)?;
+            }
+            self.game.update(DEFAULT_TICK)?;
+        }
+        Ok(())
+    }
+
+    fn render(&self) -> Result<RenderBoard, GameError> {
+        self.game.render()
+    }
+
+    fn final_score(&self) -> Option<u32> {
+        self.game.final_score()
+    }
+
+    fn status(&self) -> GameStatus {
+        self.game.status()
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        self.game.drain_events()
+    }
+}
+
+impl<G: Snapshot, B: Snapshot> Snapshot for BotPlayer<G, B> {
+    fn save(&self, w: &mut Writer) {
+        self.game.save(w);
+        self.bot.save(w);
+        self.player.save(w);
+        self.fixed_step.save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        let bot_player = Self {
+            game: G::restore(r)?,
+            bot: B::restore(r)?,
+            player: Player::restore(r)?,
+            fixed_step: FixedStep::restore(r)?,
+        };
+        if bot_player.fixed_step.step() != DEFAULT_TICK {
+            return Err(SnapshotError::InvalidData);
+        }
+        Ok(bot_player)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{ButtonState, CommandType};
+
+    // Counts the inputs and updates it receives
+    #[derive(Default)]
+    struct Counter {
+        inputs: usize,
+        updates: usize,
+    }
+
+    impl Game for Counter {
+        fn process_input(&mut self, _input: GameCommand) -> Result<(), GameError> {
+            self.inputs += 1;
+            Ok(())
+        }
+
+        fn update(&mut self, _delta_time: Duration) -> Result<(), GameError> {
+            self.updates += 1;
+            Ok(())
+        }
+
+        fn render(&self) -> Result<RenderBoard, GameError> {
+            Ok(RenderBoard::new())
+        }
+    }
+
+    struct Presser;
+
+    impl Bot<Counter> for Presser {
+        fn next_command(
+            &mut self,
+            game: &Counter,
+            player: Player,
+            _delta_time: Duration,
+        ) -> Option<GameCommand> {
+            // Presses before every update, so inputs never fall behind
+            assert_eq!(game.inputs, game.updates);
+            Some(GameCommand::new(
+                CommandType::Select,
+                ButtonState::Pressed,
+                player,
+            ))
+        }
+    }
+
+    #[test]
+    fn test_bot_input_replaces_its_players_input() {
+        let mut game = BotPlayer::new(Counter::default(), Presser, Player::Player2);
+        for _ in 0..3 {
+            game.update(DEFAULT_TICK).unwrap();
+        }
+        let press = |player| GameCommand::new(CommandType::Select, ButtonState::Pressed, player);
+        game.process_input(press(Player::Player2)).unwrap();
+        assert_eq!(game.game().inputs, 3);
+        game.process_input(press(Player::Player1)).unwrap();
+        assert_eq!(game.game().inputs, 4);
+        assert_eq!(game.game().updates, 3);
+    }
+}
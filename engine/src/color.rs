@@ -0,0 +1,216 @@
+use crate::game::{GRID_HEIGHT, GRID_WIDTH, RGB, RenderBoard};
+
+/// Current a WS2812 draws for one fully lit color channel.
+pub const MILLIAMPS_PER_CHANNEL: u32 = 20;
+/// Current a WS2812 draws while it is dark.
+pub const IDLE_MILLIAMPS_PER_LED: u32 = 1;
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+const LED_COUNT: u32 = (GRID_WIDTH * GRID_HEIGHT) as u32;
+// Night mode divides the brightness by this and scales the red, green and
+// blue channels by these fractions of 255, for a dim warm white
+const NIGHT_DIM: u8 = 4;
+const NIGHT_TINT: [u8; 3] = [255, 160, 70];
+const NO_TINT: [u8; 3] = [255; 3];
+
+/// Turns rendered frames into the colors sent to the LEDs.
+///
+/// Colors are gamma corrected, so that half of 255 looks half as bright,
+/// and scaled by the global brightness. Channels that were lit stay at
+/// least at 1, dim colors would disappear otherwise. Night mode dims them
+/// further and makes them warmer. When a power budget is set, frames that
+/// would draw more current than it allows are scaled down as a whole.
+#[derive(Debug, Clone)]
+pub struct ColorPipeline {
+    // Gamma corrected channel values with 16 bits of precision, so that
+    // dimming doesn't lose detail in dark colors
+    gamma: [u16; 256],
+    brightness: u8,
+    night_mode: bool,
+    power_budget: Option<u32>,
+}
+
+impl ColorPipeline {
+    /// A pipeline at full brightness without a power budget. A `gamma` of
+    /// 1.0 leaves colors as they are.
+    pub fn new(gamma: f32) -> Self {
+        let mut table = [0; 256];
+        for (value, corrected) in table.iter_mut().enumerate() {
+            *corrected =
+                libm::roundf(libm::powf(value as f32 / 255.0, gamma) * u16::MAX as f32) as u16;
+        }
+        Self {
+            gamma: table,
+            brightness: u8::MAX,
+            night_mode: false,
+            power_budget: None,
+        }
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn set_night_mode(&mut self, night_mode: bool) {
+        self.night_mode = night_mode;
+    }
+
+    pub fn night_mode(&self) -> bool {
+        self.night_mode
+    }
+
+    /// Limits the estimated current of the LEDs in milliamps, `None` to
+    /// not limit it.
+    pub fn set_power_budget(&mut self, milliamps: Option<u32>) {
+        self.power_budget = milliamps;
+    }
+
+    pub fn power_budget(&self) -> Option<u32> {
+        self.power_budget
+    }
+
+    fn channel(&self, value: u8, brightness: u8, tint: u8) -> u8 {
+        let scaled = self.gamma[value as usize] as u64 * brightness as u64 * tint as u64
+            / (u16::MAX as u64 * u8::MAX as u64);
+        if value > 0 && brightness > 0 && tint > 0 {
+            scaled.max(1) as u8
+        } else {
+            scaled as u8
+        }
+    }
+
+    /// Estimated current in milliamps of the LEDs showing `board`, which is
+    /// expected to have gone through `apply` already.
+    pub fn estimate_milliamps(board: &RenderBoard) -> u32 {
+        let channels: u32 = board
+            .cells
+            .iter()
+            .flatten()
+            .map(|color| color.r as u32 + color.g as u32 + color.b as u32)
+            .sum();
+        LED_COUNT * IDLE_MILLIAMPS_PER_LED + channels * MILLIAMPS_PER_CHANNEL / u8::MAX as u32
+    }
+
+    pub fn apply(&self, board: &RenderBoard) -> RenderBoard {
+        let (brightness, [r, g, b]) = if self.night_mode {
+            (self.brightness / NIGHT_DIM, NIGHT_TINT)
+        } else {
+            (self.brightness, NO_TINT)
+        };
+        let mut output = *board;
+        for color in output.cells.iter_mut().flatten() {
+            *color = RGB {
+                r: self.channel(color.r, brightness, r),
+                g: self.channel(color.g, brightness, g),
+                b: self.channel(color.b, brightness, b),
+            };
+        }
+        let Some(budget) = self.power_budget else {
+            return output;
+        };
+        let idle = LED_COUNT * IDLE_MILLIAMPS_PER_LED;
+        let drawn = Self::estimate_milliamps(&output) - idle;
+        let available = budget.saturating_sub(idle);
+        if drawn > available {
+            let scale = |value: u8| (value as u32 * available / drawn) as u8;
+            for color in output.cells.iter_mut().flatten() {
+                *color = RGB {
+                    r: scale(color.r),
+                    g: scale(color.g),
+                    b: scale(color.b),
+                };
+            }
+        }
+        output
+    }
+}
+
+impl Default for ColorPipeline {
+    fn default() -> Self {
+        Self::new(DEFAULT_GAMMA)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(color: RGB) -> RenderBoard {
+        let mut board = RenderBoard::new();
+        for column in board.cells.iter_mut() {
+            column.fill(color);
+        }
+        board
+    }
+
+    const WHITE: RGB = RGB {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    #[test]
+    fn test_linear_gamma_at_full_brightness_changes_nothing() {
+        let pipeline = ColorPipeline::new(1.0);
+        for value in 0..=255 {
+            let color = RGB {
+                r: value,
+                g: 255 - value,
+                b: value / 2,
+            };
+            assert_eq!(pipeline.apply(&filled(color)).get(0, 0), color);
+        }
+    }
+
+    #[test]
+    fn test_gamma_and_brightness() {
+        let mut pipeline = ColorPipeline::default();
+        let half = pipeline.apply(&filled(RGB { r: 128, g: 0, b: 0 }));
+        assert!((50..60).contains(&half.get(0, 0).r));
+        assert_eq!(pipeline.apply(&filled(WHITE)).get(0, 0), WHITE);
+
+        pipeline.set_brightness(51);
+        let dimmed = pipeline.apply(&filled(WHITE)).get(0, 0);
+        assert_eq!(dimmed.r, 51);
+        // A dim color is still lit
+        let dark = pipeline.apply(&filled(RGB { r: 3, g: 0, b: 0 })).get(0, 0);
+        assert_eq!(dark, RGB { r: 1, g: 0, b: 0 });
+
+        pipeline.set_brightness(0);
+        assert_eq!(pipeline.apply(&filled(WHITE)).get(0, 0), RGB::default());
+    }
+
+    #[test]
+    fn test_night_mode_is_dimmer_and_warmer() {
+        let mut pipeline = ColorPipeline::new(1.0);
+        pipeline.set_night_mode(true);
+        let night = pipeline.apply(&filled(WHITE)).get(0, 0);
+        assert_eq!(night.r, 255 / NIGHT_DIM);
+        assert!(night.r > night.g && night.g > night.b && night.b > 0);
+    }
+
+    #[test]
+    fn test_frames_are_scaled_to_the_power_budget() {
+        let mut pipeline = ColorPipeline::new(1.0);
+        let full = ColorPipeline::estimate_milliamps(&pipeline.apply(&filled(WHITE)));
+        assert_eq!(
+            full,
+            LED_COUNT * (IDLE_MILLIAMPS_PER_LED + 3 * MILLIAMPS_PER_CHANNEL)
+        );
+
+        pipeline.set_power_budget(Some(full / 4));
+        let limited = pipeline.apply(&filled(WHITE));
+        assert!(ColorPipeline::estimate_milliamps(&limited) <= full / 4);
+        let color = limited.get(0, 0);
+        assert!(color.r > 0 && color.r == color.g && color.g == color.b);
+
+        // Frames within the budget are left alone
+        let mut board = RenderBoard::new();
+        board.set(0, 0, WHITE);
+        assert_eq!(pipeline.apply(&board), board);
+    }
+}
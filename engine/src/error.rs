@@ -3,6 +3,8 @@ pub enum GameError {
     InvalidMove,
     OutOfBounds,
     GameOver,
+    /// A time step of zero, which would never advance the game
+    ZeroStep,
     // Add more error variants as needed
 }
 
@@ -12,6 +14,7 @@ impl core::fmt::Display for GameError {
             GameError::InvalidMove => write!(f, "Invalid move"),
             GameError::OutOfBounds => write!(f, "Out of bounds"),
             GameError::GameOver => write!(f, "Game is over"),
+            GameError::ZeroStep => write!(f, "Step must be greater than zero"),
             // Add more error messages as needed
         }
     }
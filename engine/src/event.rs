@@ -0,0 +1,115 @@
+//! Discrete things that happen during a game, for frontends to turn into
+//! sound effects, gamepad rumble or a buzzer.
+
+use crate::game::Player;
+use smallvec::SmallVec;
+
+/// Events beyond this many per frame are dropped.
+pub const MAX_EVENTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    /// The selection in the menu changed
+    MenuMoved,
+    GameStarted,
+    /// The player left the game for the menu
+    GameQuit,
+    /// A piece was placed, dropped or locked into place
+    PiecePlaced(Player),
+    /// Tetris lines, with the number cleared at once
+    LinesCleared(u8),
+    FoodEaten(Player),
+    PaddleHit(Player),
+    /// A point, round or obstacle was won
+    Scored(Player),
+    Jumped(Player),
+    Shot(Player),
+    /// Something other than a player was destroyed
+    Explosion,
+    PlayerDied(Player),
+    GameOver(Option<Player>),
+    NewHighScore,
+}
+
+/// The events of one frame in the order they happened.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameEvents {
+    events: SmallVec<[GameEvent; MAX_EVENTS]>,
+}
+
+impl GameEvents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: GameEvent) {
+        if self.events.len() < MAX_EVENTS {
+            self.events.push(event);
+        }
+    }
+
+    /// Appends `other` after the events already queued.
+    pub fn append(&mut self, other: GameEvents) {
+        for event in other {
+            self.push(event);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, GameEvent> {
+        self.events.iter()
+    }
+}
+
+impl IntoIterator for GameEvents {
+    type Item = GameEvent;
+    type IntoIter = smallvec::IntoIter<[GameEvent; MAX_EVENTS]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a GameEvents {
+    type Item = &'a GameEvent;
+    type IntoIter = core::slice::Iter<'a, GameEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_bounded() {
+        let mut events = GameEvents::new();
+        events.push(GameEvent::GameStarted);
+        for _ in 0..MAX_EVENTS {
+            events.push(GameEvent::Explosion);
+        }
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(events.iter().next(), Some(&GameEvent::GameStarted));
+
+        let mut other = GameEvents::new();
+        other.push(GameEvent::MenuMoved);
+        let mut first = GameEvents::new();
+        first.push(GameEvent::GameQuit);
+        first.append(other);
+        let collected: Vec<_> = first.into_iter().collect();
+        assert_eq!(collected, [GameEvent::GameQuit, GameEvent::MenuMoved]);
+    }
+}
@@ -1,13 +1,22 @@
 use crate::error::GameError;
+use crate::event::GameEvents;
+use crate::scoreboard::ScoreStorage;
+use crate::settings::{DisplaySettings, SettingsStorage};
+use crate::snapshot::{Reader, Snapshot, SnapshotError, Writer};
+use crate::timestep::FixedStep;
 use core::time::Duration;
 
+mod attract;
+mod computer;
 mod connect_four;
 mod doodle_jump;
 mod flappy_bird;
+mod heatmap;
 mod maze;
 mod menu;
 mod paint;
 mod pong;
+mod settings_menu;
 mod shooter;
 mod snake;
 mod space_invaders;
@@ -16,24 +25,38 @@ mod tictactoe;
 mod tugofwar;
 mod walldodger;
 
-use connect_four::ConnectFour;
-use doodle_jump::DoodleJump;
-use flappy_bird::FlappyBird;
-use maze::{MazeGame, MazeGameMode};
-use menu::Menu;
-use paint::PaintGame;
-use pong::PongGame;
-use shooter::MultiplayerShooter;
-use snake::{SnakeGame, SnakeGameMode};
-use space_invaders::SpaceInvaders;
-use tetris::TetrisGame;
-use tictactoe::TicTacToe;
-use tugofwar::ButtonWar;
-use walldodger::WallDodger;
-
-pub const GRID_SIZE: usize = 12;
-
-pub type RenderBoard = Board<RGB, 12, 12>;
+pub(crate) use connect_four::ConnectFour;
+pub(crate) use doodle_jump::DoodleJump;
+pub(crate) use flappy_bird::{FlappyBird, FlappyBot};
+pub(crate) use heatmap::HeatmapGame;
+pub(crate) use maze::{MazeGame, MazeGameMode};
+pub use menu::GameInfo;
+pub(crate) use menu::Menu;
+pub(crate) use paint::PaintGame;
+pub(crate) use pong::{CpuSettings, PongGame};
+pub(crate) use shooter::{MultiplayerShooter, ShooterBot};
+pub(crate) use snake::{SnakeBot, SnakeGame, SnakeGameMode};
+pub(crate) use space_invaders::SpaceInvaders;
+pub(crate) use tetris::{TetrisBot, TetrisGame};
+pub(crate) use tictactoe::TicTacToe;
+pub(crate) use tugofwar::ButtonWar;
+pub(crate) use walldodger::{WallDodger, WallDodgerBot};
+
+// GRID_WIDTH and GRID_HEIGHT are generated by build.rs and can be
+// overridden with the TEOTILE_GRID_WIDTH and TEOTILE_GRID_HEIGHT
+// environment variables at build time. They can't change at runtime.
+include!(concat!(env!("OUT_DIR"), "/grid_config.rs"));
+
+// The menu needs room for the 8x8 pixel art icons.
+const _: () = assert!(GRID_WIDTH >= 8 && GRID_HEIGHT >= 8);
+
+/// Whether the grid has room for a game that needs at least
+/// `(width, height)` cells.
+pub(crate) const fn fits_grid(min_grid_size: (usize, usize)) -> bool {
+    GRID_WIDTH >= min_grid_size.0 && GRID_HEIGHT >= min_grid_size.1
+}
+
+pub type RenderBoard = Board<RGB, GRID_WIDTH, GRID_HEIGHT>;
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct RGB {
@@ -50,8 +73,107 @@ impl RGB {
 
 pub trait Game {
     fn process_input(&mut self, input: GameCommand) -> Result<(), GameError>;
+
+    /// Advances the game by `delta_time`, which can be any length. Timers
+    /// that fire every period subtract the period instead of starting over,
+    /// so the rest carries over and the game keeps its pace at any frame
+    /// rate.
     fn update(&mut self, delta_time: Duration) -> Result<(), GameError>;
     fn render(&self) -> Result<RenderBoard, GameError>;
+
+    /// The score of a finished single player game, used for the high score
+    /// table. Returns `None` while the game is running or has no score.
+    fn final_score(&self) -> Option<u32> {
+        None
+    }
+
+    /// What frontends can show next to the grid, e.g. in a status bar.
+    fn status(&self) -> GameStatus {
+        GameStatus::playing()
+    }
+
+    /// Takes the events that happened since the last call.
+    fn drain_events(&mut self) -> GameEvents {
+        GameEvents::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamePhase {
+    Menu,
+    Playing,
+    Paused,
+    GameOver,
+    /// The menu was left alone and games are playing themselves
+    Demo,
+}
+
+/// A snapshot of the running game for frontends. Fields a game does not
+/// track are `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameStatus {
+    /// Name of the running game, or of the highlighted one in the menu
+    pub game: Option<&'static str>,
+    pub phase: GamePhase,
+    pub winner: Option<Player>,
+    /// Indexed by player, single player games only fill in the first one
+    pub scores: [Option<u32>; MAX_PLAYERS],
+    pub level: Option<u32>,
+}
+
+impl GameStatus {
+    pub fn playing() -> Self {
+        Self {
+            game: None,
+            phase: GamePhase::Playing,
+            winner: None,
+            scores: [None; MAX_PLAYERS],
+            level: None,
+        }
+    }
+
+    pub fn game_over(winner: Option<Player>) -> Self {
+        Self {
+            phase: GamePhase::GameOver,
+            winner,
+            ..Self::playing()
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.phase == GamePhase::GameOver
+    }
+}
+
+/// Formats the status as a single line, e.g. `Pong | Playing | Score 3-1`.
+impl core::fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.game.unwrap_or("-"))?;
+        match (self.phase, self.winner) {
+            (GamePhase::Menu, _) => f.write_str(" | Menu")?,
+            (GamePhase::Playing, _) => f.write_str(" | Playing")?,
+            (GamePhase::Paused, _) => f.write_str(" | Paused")?,
+            (GamePhase::Demo, _) => f.write_str(" | Demo")?,
+            (GamePhase::GameOver, None) => f.write_str(" | Game over")?,
+            (GamePhase::GameOver, Some(player)) => {
+                write!(f, " | Player {} wins", player.index() + 1)?
+            }
+        }
+        for (i, score) in self.scores.iter().map_while(|score| *score).enumerate() {
+            f.write_str(if i == 0 { " | Score " } else { "-" })?;
+            write!(f, "{}", score)?;
+        }
+        if let Some(level) = self.level {
+            write!(f, " | Level {}", level)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for GameStatus {
+    fn default() -> Self {
+        Self::playing()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -74,6 +196,8 @@ pub enum CommandType {
     Up,
     Select,
     Quit,
+    /// Pauses or resumes the running game, handled by `GameEngine`
+    Pause,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -93,10 +217,44 @@ impl GameCommand {
     }
 }
 
+/// Most games are for one or two players, a few take up to this many.
+pub const MAX_PLAYERS: usize = 4;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Player {
     Player1,
     Player2,
+    Player3,
+    Player4,
+}
+
+impl Player {
+    pub const ALL: [Player; MAX_PLAYERS] = [
+        Player::Player1,
+        Player::Player2,
+        Player::Player3,
+        Player::Player4,
+    ];
+
+    /// Zero based, Player1 is 0.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+
+    /// The other player of a two player game. With four players Player3
+    /// and Player4 face each other the same way.
+    pub fn opponent(self) -> Self {
+        match self {
+            Player::Player1 => Player::Player2,
+            Player::Player2 => Player::Player1,
+            Player::Player3 => Player::Player4,
+            Player::Player4 => Player::Player3,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -134,30 +292,437 @@ impl<T: Copy + Default, const COLS: usize, const ROWS: usize> Default for Board<
     }
 }
 
+// The paused frame is dimmed to this fraction of its brightness
+const PAUSE_DIM: u8 = 4;
+
+/// Updates per second of the game in a `GameEngine`, whatever the frame
+/// rate of the frontend.
+pub const DEFAULT_TICK_RATE: u32 = 60;
+/// A tick at `DEFAULT_TICK_RATE`. Games whose physics only play the same
+/// when stepped evenly run on ticks of this length themselves, so they
+/// don't depend on the engine's tick rate either.
+pub(crate) const DEFAULT_TICK: Duration =
+    Duration::from_nanos(1_000_000_000 / DEFAULT_TICK_RATE as u64);
+// Ticks run at most per call to update, the rest of a longer stall is
+// dropped instead of running the game in fast forward
+const MAX_TICKS_PER_UPDATE: u32 = 10;
+
 pub struct GameEngine<T: Game = Menu> {
     game: T,
+    paused: bool,
+    // None passes the frame times of the frontend straight to the game
+    fixed_step: Option<FixedStep>,
+    // Set by inputs and by updates of a game in play, the clocks of the
+    // menu, attract mode and game over screens don't count
+    state_changed: bool,
 }
 
 impl Default for GameEngine<Menu> {
     fn default() -> Self {
-        Self { game: Menu::new() }
+        Self::new(Menu::new())
+    }
+}
+
+impl GameEngine<Menu> {
+    pub fn with_seed(seed: u64) -> Self {
+        Self::new(Menu::with_seed(seed))
+    }
+
+    /// Loads the stored high scores. `Replayer` and `Lockstep` don't hand
+    /// out their engine mutably, so replays and network games can't load
+    /// or save them and never change the stored table.
+    pub fn load_scores<S: ScoreStorage>(&mut self, storage: &mut S) -> Result<(), S::Error> {
+        if let Some(scoreboard) = storage.load()? {
+            self.game.set_scoreboard(scoreboard);
+        }
+        Ok(())
+    }
+
+    /// Saves the high scores if they changed since the last call. Meant to
+    /// be called every frame, a failed save is retried on the next change.
+    pub fn save_scores<S: ScoreStorage>(&mut self, storage: &mut S) -> Result<(), S::Error> {
+        if self.game.scoreboard_changed() {
+            self.game.mark_scoreboard_saved();
+            storage.save(self.game.scoreboard())?;
+        }
+        Ok(())
+    }
+
+    pub fn load_settings<S: SettingsStorage>(&mut self, storage: &mut S) -> Result<(), S::Error> {
+        if let Some(settings) = storage.load()? {
+            self.game.set_display_settings(settings);
+        }
+        Ok(())
+    }
+
+    /// Saves the display settings if they were changed in the menu since
+    /// the last call. Meant to be called every frame like `save_scores`.
+    pub fn save_settings<S: SettingsStorage>(&mut self, storage: &mut S) -> Result<(), S::Error> {
+        if self.game.display_settings_changed() {
+            self.game.mark_display_settings_saved();
+            storage.save(self.game.display_settings())?;
+        }
+        Ok(())
+    }
+
+    /// Brightness and night mode as picked in the menu, for the frontend
+    /// to apply to its `ColorPipeline`.
+    pub fn display_settings(&self) -> DisplaySettings {
+        *self.game.display_settings()
+    }
+
+    /// Use `Recorder::set_display_settings` while recording.
+    pub fn set_display_settings(&mut self, settings: DisplaySettings) {
+        self.game.set_display_settings(settings);
+    }
+
+    /// Sets the time since local midnight for scheduled night mode. Without
+    /// it, which is the default, night mode can only be turned on and off.
+    pub fn set_time_of_day(&mut self, time_of_day: Option<Duration>) {
+        self.game.set_time_of_day(time_of_day);
+    }
+
+    /// Whether the LEDs should be in night mode right now.
+    pub fn is_night(&self) -> bool {
+        self.game.is_night()
+    }
+
+    /// Tells the menu how many controllers are connected, two by default.
+    /// With one, Snake, Shooter and Wall Dodger start against a bot. With
+    /// three or four, Snake, Shooter, Button War and the multiplayer mazes
+    /// start for that many players. Games for one person, alone or against
+    /// the computer, take input from every controller as the first player.
+    /// The count is not part of the save state, use
+    /// `Recorder::set_controllers` while recording, replays need it.
+    pub fn set_controllers(&mut self, count: usize) {
+        self.game.set_controllers(count);
+    }
+
+    /// The games in the menu that fit the grid, in menu order.
+    pub fn games() -> impl Iterator<Item = GameInfo> {
+        Menu::games()
+    }
+
+    /// Starts the game with the `id` from `games`, quitting the running
+    /// game. Returns false if there is no such game. Use
+    /// `Recorder::start_game` while recording.
+    pub fn start_game(&mut self, id: &str) -> bool {
+        let started = self.game.start_game_by_id(id);
+        if started {
+            self.paused = false;
+            self.state_changed = true;
+        }
+        started
+    }
+
+    /// Lets games play themselves after the menu has been idle for
+    /// `delay`. Off by default. Use `Recorder::set_attract_delay` while
+    /// recording.
+    pub fn set_attract_delay(&mut self, delay: Option<Duration>) {
+        self.game.set_attract_delay(delay);
+    }
+
+    /// Writes the state of the menu and the running game into `buf` and
+    /// returns the number of bytes used. High scores are not included.
+    /// When `buf` is too small the error holds the size that is needed.
+    pub fn save_state(&self, buf: &mut [u8]) -> Result<usize, SnapshotError> {
+        let mut w = Writer::new(buf);
+        w.write_header();
+        self.paused.save(&mut w);
+        self.fixed_step.save(&mut w);
+        self.game.save_snapshot(&mut w);
+        w.finish()
+    }
+
+    #[cfg(any(test, feature = "std"))]
+    pub fn save_state_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        if let Err(SnapshotError::BufferTooSmall { needed }) = self.save_state(&mut buf) {
+            buf.resize(needed, 0);
+            self.save_state(&mut buf)
+                .expect("buffer has the size the state needs");
+        }
+        buf
+    }
+
+    /// Continues from a state written by `save_state`, keeping the loaded
+    /// high scores. The tick rate is restored with the state. Corrupt
+    /// states, down to a cell off the grid, are rejected and leave the
+    /// engine unchanged, so frontends carry on with a fresh game.
+    pub fn restore_state(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+        let mut r = Reader::new(bytes);
+        r.read_header()?;
+        let paused = bool::restore(&mut r)?;
+        let fixed_step = Option::<FixedStep>::restore(&mut r)?;
+        self.game.restore_snapshot(r)?;
+        self.paused = paused;
+        self.fixed_step = fixed_step;
+        self.state_changed = false;
+        Ok(())
+    }
+
+    /// Whether the state changed in a way worth saving since the last
+    /// `mark_state_saved`. An idle menu only moves its clocks, so a
+    /// frontend that saves on changes leaves its storage alone meanwhile.
+    pub fn state_changed(&self) -> bool {
+        self.state_changed
+    }
+
+    pub fn mark_state_saved(&mut self) {
+        self.state_changed = false;
     }
 }
 
 impl<T: Game> GameEngine<T> {
     pub fn new(game: T) -> Self {
-        Self { game }
+        Self {
+            game,
+            paused: false,
+            fixed_step: Some(FixedStep::new(DEFAULT_TICK)),
+            state_changed: false,
+        }
+    }
+
+    /// Sets how many times per second the game is updated. Frame times
+    /// passed to `update` are collected and the game is updated in ticks
+    /// of equal length, so it plays the same at any frame rate. `None`
+    /// updates the game once per frame with the frame time instead. Use
+    /// `Recorder::set_tick_rate` while recording.
+    pub fn set_tick_rate(&mut self, tick_rate: Option<u32>) {
+        self.fixed_step = tick_rate.map(|rate| FixedStep::new(tick_duration(rate)));
+    }
+
+    pub fn tick_rate(&self) -> Option<u32> {
+        self.fixed_step
+            .map(|fixed_step| (1_000_000_000 / fixed_step.step().as_nanos()) as u32)
     }
 
+    /// Pause toggles pausing of a running game. While paused every other
+    /// input is ignored, except Quit which resumes and is passed on.
     pub fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
-        self.game.process_input(input_command)
+        self.state_changed = true;
+        match (input_command.command_type, input_command.button_state) {
+            // The menu opens the display settings with Pause and wakes up
+            // from attract mode with it
+            (CommandType::Pause, _)
+                if matches!(self.game.status().phase, GamePhase::Menu | GamePhase::Demo) =>
+            {
+                self.game.process_input(input_command)
+            }
+            (CommandType::Pause, ButtonState::Pressed) => {
+                self.paused = !self.paused && self.game.status().phase == GamePhase::Playing;
+                Ok(())
+            }
+            (CommandType::Pause, ButtonState::Released) => Ok(()),
+            (CommandType::Quit, _) if self.paused => {
+                self.paused = false;
+                self.game.process_input(input_command)
+            }
+            // Releases still reach the game, so no button is held after
+            // it resumes
+            (_, ButtonState::Pressed) if self.paused => Ok(()),
+            _ => self.game.process_input(input_command),
+        }
     }
 
-    pub fn update(&mut self, current_time: Duration) -> Result<(), GameError> {
-        self.game.update(current_time)
+    pub fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
+        if self.paused {
+            return Ok(());
+        }
+        if self.game.status().phase == GamePhase::Playing {
+            self.state_changed = true;
+        }
+        let Some(fixed_step) = &mut self.fixed_step else {
+            return self.game.update(delta_time);
+        };
+        let ticks = fixed_step.advance(delta_time).min(MAX_TICKS_PER_UPDATE);
+        for _ in 0..ticks {
+            self.game.update(fixed_step.step())?;
+        }
+        Ok(())
     }
 
     pub fn render(&self) -> Result<RenderBoard, GameError> {
-        self.game.render()
+        let mut render_board = self.game.render()?;
+        if self.paused {
+            render_pause_overlay(&mut render_board);
+        }
+        Ok(render_board)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn status(&self) -> GameStatus {
+        let mut status = self.game.status();
+        if self.paused {
+            status.phase = GamePhase::Paused;
+        }
+        status
+    }
+
+    /// Takes the events of the frames since the last call, meant to be
+    /// called once per frame after `update`.
+    pub fn drain_events(&mut self) -> GameEvents {
+        self.game.drain_events()
+    }
+}
+
+fn tick_duration(tick_rate: u32) -> Duration {
+    Duration::from_nanos(1_000_000_000 / u64::from(tick_rate.max(1)))
+}
+
+// Dims the frozen frame and draws a pause symbol, two bars, in the middle
+fn render_pause_overlay(render_board: &mut RenderBoard) {
+    for col in 0..GRID_WIDTH {
+        for row in 0..GRID_HEIGHT {
+            let RGB { r, g, b } = render_board.get(col, row);
+            let dimmed = RGB::new(r / PAUSE_DIM, g / PAUSE_DIM, b / PAUSE_DIM);
+            render_board.set(col, row, dimmed);
+        }
+    }
+    let bottom = GRID_HEIGHT / 2 - 2;
+    for col in [GRID_WIDTH / 2 - 2, GRID_WIDTH / 2 + 1] {
+        for row in bottom..bottom + 4 {
+            render_board.set(col, row, RGB::new(255, 255, 255));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(command_type: CommandType) -> GameCommand {
+        GameCommand::new(command_type, ButtonState::Pressed, Player::Player1)
+    }
+
+    #[test]
+    fn test_pause_freezes_the_running_game() {
+        let mut engine = GameEngine::new(FlappyBird::new(7));
+        engine.update(Duration::from_millis(100)).unwrap();
+        let frame = engine.render().unwrap();
+
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        assert!(engine.is_paused());
+        assert_eq!(engine.status().phase, GamePhase::Paused);
+        engine.process_input(press(CommandType::Select)).unwrap();
+        for _ in 0..100 {
+            engine.update(Duration::from_millis(16)).unwrap();
+        }
+        let paused_frame = engine.render().unwrap();
+        assert_eq!(
+            paused_frame.get(GRID_WIDTH / 2 - 2, GRID_HEIGHT / 2),
+            RGB::new(255, 255, 255)
+        );
+
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        assert!(!engine.is_paused());
+        assert_eq!(engine.render().unwrap(), frame);
+    }
+
+    #[test]
+    fn test_buttons_released_while_paused_are_not_held() {
+        // Holds Down like the soft drop of Tetris
+        #[derive(Default)]
+        struct Holder {
+            held: bool,
+        }
+
+        impl Game for Holder {
+            fn process_input(&mut self, input: GameCommand) -> Result<(), GameError> {
+                if input.command_type == CommandType::Down {
+                    self.held = input.button_state == ButtonState::Pressed;
+                }
+                Ok(())
+            }
+
+            fn update(&mut self, _delta_time: Duration) -> Result<(), GameError> {
+                Ok(())
+            }
+
+            fn render(&self) -> Result<RenderBoard, GameError> {
+                Ok(RenderBoard::new())
+            }
+        }
+
+        let mut engine = GameEngine::new(Holder::default());
+        engine.process_input(press(CommandType::Down)).unwrap();
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        let release = GameCommand::new(CommandType::Down, ButtonState::Released, Player::Player1);
+        engine.process_input(release).unwrap();
+        // Presses still wait for the game to resume
+        engine.process_input(press(CommandType::Down)).unwrap();
+        engine.process_input(release).unwrap();
+        engine.process_input(press(CommandType::Up)).unwrap();
+        assert!(!engine.game.held);
+
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        assert!(!engine.is_paused());
+        assert!(!engine.game.held);
+    }
+
+    #[test]
+    fn test_pause_only_applies_while_playing() {
+        let mut engine = GameEngine::default();
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        assert!(!engine.is_paused());
+        // Pause opened the display settings, close them again
+        engine.process_input(press(CommandType::Pause)).unwrap();
+
+        engine.process_input(press(CommandType::Select)).unwrap();
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        assert!(engine.is_paused());
+        engine.process_input(press(CommandType::Quit)).unwrap();
+        assert!(!engine.is_paused());
+        assert_eq!(engine.status().phase, GamePhase::Menu);
+    }
+
+    #[test]
+    fn test_pause_wakes_up_attract_mode() {
+        let mut engine = GameEngine::default();
+        engine.set_attract_delay(Some(Duration::from_secs(1)));
+        for _ in 0..100 {
+            engine.update(Duration::from_millis(16)).unwrap();
+        }
+        assert_eq!(engine.status().phase, GamePhase::Demo);
+
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        assert!(!engine.is_paused());
+        assert_eq!(engine.status().phase, GamePhase::Menu);
+        for _ in 0..10 {
+            engine.update(Duration::from_millis(16)).unwrap();
+        }
+        assert_eq!(engine.status().phase, GamePhase::Menu);
+    }
+
+    #[test]
+    fn test_only_inputs_and_play_change_the_state() {
+        let mut engine = GameEngine::default();
+        engine.set_attract_delay(Some(Duration::from_secs(1)));
+        for _ in 0..100 {
+            engine.update(Duration::from_millis(16)).unwrap();
+        }
+        assert_eq!(engine.status().phase, GamePhase::Demo);
+        assert!(!engine.state_changed());
+
+        engine.process_input(press(CommandType::Select)).unwrap();
+        engine.process_input(press(CommandType::Select)).unwrap();
+        assert_eq!(engine.status().phase, GamePhase::Playing);
+        assert!(engine.state_changed());
+        engine.mark_state_saved();
+        engine.update(Duration::from_millis(16)).unwrap();
+        assert!(engine.state_changed());
+
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        engine.mark_state_saved();
+        engine.update(Duration::from_millis(16)).unwrap();
+        assert!(!engine.state_changed());
+
+        let state = engine.save_state_to_vec();
+        engine.process_input(press(CommandType::Pause)).unwrap();
+        engine.restore_state(&state).unwrap();
+        assert!(!engine.state_changed());
     }
 }
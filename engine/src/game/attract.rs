@@ -0,0 +1,143 @@
+//! Games that play themselves after the menu has been left alone for a
+//! while, to show passers-by what the display can do.
+
+use crate::bot::BotPlayer;
+use crate::game::{
+    CpuSettings, FlappyBird, FlappyBot, Game, GamePhase, GameStatus, Player, PongGame, SnakeBot,
+    SnakeGame, SnakeGameMode, TetrisBot, TetrisGame,
+};
+use crate::snapshot::{snapshot_enum, snapshot_struct};
+use crate::{GameError, RenderBoard};
+use core::time::Duration;
+
+const DEMO_DURATION: Duration = Duration::from_secs(30);
+// Long enough to see the game over animation before the next demo
+const GAME_OVER_DURATION: Duration = Duration::from_secs(3);
+const DEMO_COUNT: usize = 4;
+
+// The engine is no_std without an allocator, so the demos are stored
+// inline instead of boxed.
+#[allow(clippy::large_enum_variant)]
+enum Demo {
+    Snake(BotPlayer<SnakeGame, SnakeBot>),
+    Tetris(BotPlayer<TetrisGame, TetrisBot>),
+    Pong(PongGame),
+    FlappyBird(BotPlayer<FlappyBird, FlappyBot>),
+}
+
+snapshot_enum!(Demo {
+    Snake(game),
+    Tetris(game),
+    Pong(game),
+    FlappyBird(game),
+});
+
+impl Demo {
+    fn new(index: usize, seed: u64) -> Self {
+        match index % DEMO_COUNT {
+            0 => Demo::Snake(BotPlayer::new(
+                SnakeGame::new(seed, SnakeGameMode::SinglePlayer),
+                SnakeBot,
+                Player::Player1,
+            )),
+            1 => Demo::Tetris(BotPlayer::new(
+                TetrisGame::new(seed),
+                TetrisBot::new(),
+                Player::Player1,
+            )),
+            2 => Demo::Pong(PongGame::with_cpus(
+                seed,
+                [Some(CpuSettings::default()), Some(CpuSettings::default())],
+            )),
+            _ => Demo::FlappyBird(BotPlayer::new(
+                FlappyBird::new(seed),
+                FlappyBot,
+                Player::Player1,
+            )),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Demo::Snake(_) => "Snake",
+            Demo::Tetris(_) => "Tetris",
+            Demo::Pong(_) => "Pong",
+            Demo::FlappyBird(_) => "Flappy Bird",
+        }
+    }
+
+    fn game(&self) -> &dyn Game {
+        match self {
+            Demo::Snake(game) => game,
+            Demo::Tetris(game) => game,
+            Demo::Pong(game) => game,
+            Demo::FlappyBird(game) => game,
+        }
+    }
+
+    fn game_mut(&mut self) -> &mut dyn Game {
+        match self {
+            Demo::Snake(game) => game,
+            Demo::Tetris(game) => game,
+            Demo::Pong(game) => game,
+            Demo::FlappyBird(game) => game,
+        }
+    }
+}
+
+/// Cycles through the demos, each for a fixed time or until it is over.
+pub(crate) struct AttractMode {
+    demo: Demo,
+    index: usize,
+    seed: u64,
+    remaining: Duration,
+}
+
+snapshot_struct!(AttractMode {
+    demo,
+    index,
+    seed,
+    remaining,
+});
+
+impl AttractMode {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            demo: Demo::new(0, seed),
+            index: 0,
+            seed,
+            remaining: DEMO_DURATION,
+        }
+    }
+
+    pub(crate) fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
+        let game = self.demo.game_mut();
+        game.update(delta_time)?;
+        // Nobody is playing, so the demos are silent
+        game.drain_events();
+        if game.status().is_over() {
+            self.remaining = self.remaining.min(GAME_OVER_DURATION);
+        }
+
+        self.remaining = self.remaining.saturating_sub(delta_time);
+        if self.remaining.is_zero() {
+            self.index = (self.index + 1) % DEMO_COUNT;
+            self.seed = self.seed.wrapping_add(1);
+            self.demo = Demo::new(self.index, self.seed);
+            self.remaining = DEMO_DURATION;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn render(&self) -> Result<RenderBoard, GameError> {
+        self.demo.game().render()
+    }
+
+    pub(crate) fn status(&self) -> GameStatus {
+        GameStatus {
+            game: Some(self.demo.name()),
+            phase: GamePhase::Demo,
+            ..self.demo.game().status()
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! The computer opponent of the turn based games, it always plays Player2.
+
+use crate::game::Player;
+use crate::snapshot::snapshot_struct;
+use core::time::Duration;
+
+// Lets the player see their piece before the computer answers
+pub const COMPUTER_MOVE_DELAY: Duration = Duration::from_millis(400);
+
+/// Counts down the computer's pause before each of its moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComputerTurn {
+    delay: Duration,
+}
+
+snapshot_struct!(ComputerTurn { delay });
+
+impl Default for ComputerTurn {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComputerTurn {
+    pub const PLAYER: Player = Player::Player2;
+
+    pub fn new() -> Self {
+        Self {
+            delay: COMPUTER_MOVE_DELAY,
+        }
+    }
+
+    /// Call when the turn passes, so the computer pauses again.
+    pub fn restart(&mut self) {
+        self.delay = COMPUTER_MOVE_DELAY;
+    }
+
+    /// Call every update while it is the computer's turn, true once it
+    /// should move.
+    pub fn wait(&mut self, delta_time: Duration) -> bool {
+        self.delay = self.delay.saturating_sub(delta_time);
+        self.delay.is_zero()
+    }
+}
@@ -1,19 +1,59 @@
-use crate::GRID_SIZE;
 use crate::RGB;
 use crate::animation::Animation;
-use crate::game::{Board, ButtonState, CommandType, Game, GameCommand};
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{Board, ButtonState, CommandType, GRID_HEIGHT, Game, GameCommand, GameStatus};
+use crate::snapshot::{snapshot_enum, snapshot_struct};
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 use smallvec::SmallVec;
 
 const WIN_ANIMATION_SPEED: Duration = Duration::from_millis(50);
+const MAX_LINE_LENGTH: usize = 7;
+
+// Search depth in plies of each computer difficulty, easiest first
+const COMPUTER_DEPTHS: [usize; 4] = [1, 3, 5, 7];
+const DEFAULT_DIFFICULTY: usize = 1;
+const MAX_COMPUTER_DEPTH: usize = COMPUTER_DEPTHS[COMPUTER_DEPTHS.len() - 1];
+// Caps the positions searched per move. A deeper search that runs out falls
+// back to the last finished one.
+const COMPUTER_NODE_BUDGET: u32 = 20_000;
+// Positions searched per update. Each position only rescans the lines
+// through its move, so this bounds the work of an update.
+const COMPUTER_NODES_PER_UPDATE: u32 = 250;
+const WIN_SCORE: i32 = 1_000_000;
+const COLS: usize = 7;
+const ROWS: usize = 6;
+// Center columns are part of more lines, searching them first prunes more
+const COLUMN_ORDER: [usize; COLS] = center_out_order();
+
+// Columns sorted by their distance from the center, left first on ties
+const fn center_out_order() -> [usize; COLS] {
+    let mut order = [0; COLS];
+    let mut i = 0;
+    let mut distance = 0;
+    while i < COLS {
+        let mut col = 0;
+        while col < COLS {
+            if (2 * col).abs_diff(COLS - 1) == distance {
+                order[i] = col;
+                i += 1;
+            }
+            col += 1;
+        }
+        distance += 1;
+    }
+    order
+}
 
 use super::Player;
+use super::computer::ComputerTurn;
+
+type WinningLine = SmallVec<[(usize, usize); MAX_LINE_LENGTH]>;
 
 #[derive(Debug, PartialEq)]
 pub enum ConnectFourState {
     Playing,
-    Win(SmallVec<[(usize, usize); GRID_SIZE]>),
+    Win(WinningLine),
     Tie,
     Finished,
 }
@@ -26,7 +66,91 @@ pub enum Cell {
     PlayerO,
 }
 
-pub type GameBoard = Board<Cell, 7, 6>;
+pub type GameBoard = Board<Cell, COLS, ROWS>;
+
+// Running totals of the computer's evaluation, indexed by player. A move
+// only changes the lines through it, so the search updates these instead
+// of scanning the whole board at every leaf.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Evaluation {
+    // Pieces in the center column
+    center: [i32; 2],
+    // Lines only this player has pieces in, scored for them
+    open: [i32; 2],
+    // The same lines scored as a threat to the opponent
+    threat: [i32; 2],
+}
+
+impl Evaluation {
+    fn index(player: Player) -> usize {
+        match player {
+            Player::Player1 => 0,
+            _ => 1,
+        }
+    }
+
+    // Adds (or with a `sign` of -1 removes) a line holding `counts` pieces
+    // of each player
+    fn add_line(&mut self, counts: [usize; 2], sign: i32) {
+        for player in 0..2 {
+            if counts[1 - player] == 0 {
+                let (open, threat) = match counts[player] {
+                    3 => (5, 4),
+                    2 => (2, 1),
+                    _ => (0, 0),
+                };
+                self.open[player] += sign * open;
+                self.threat[player] += sign * threat;
+            }
+        }
+    }
+
+    // Score of the board for `player`, who is about to move
+    fn score(&self, player: Player) -> i32 {
+        let (own, opponent) = (Self::index(player), 1 - Self::index(player));
+        3 * (self.center[own] - self.center[opponent]) + self.open[own] - self.threat[opponent]
+    }
+}
+
+// A position in the computer's search, its moves are tried in COLUMN_ORDER
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frame {
+    // Index into COLUMN_ORDER of the next move to try
+    next: usize,
+    alpha: i32,
+    beta: i32,
+    best: i32,
+}
+
+impl Frame {
+    fn new(alpha: i32, beta: i32) -> Self {
+        Self {
+            next: 0,
+            alpha,
+            beta,
+            best: -WIN_SCORE * 2,
+        }
+    }
+}
+
+// The computer's iterative deepening minimax with alpha-beta pruning. It is
+// kept between updates so a search can be spread over many of them.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Search {
+    // The game's board with the moves being searched
+    board: GameBoard,
+    evaluation: Evaluation,
+    // One frame per move being searched, the moves of all but the last are
+    // on the board in the column before their `next`
+    frames: SmallVec<[Frame; MAX_COMPUTER_DEPTH]>,
+    // Depth of the search in progress or the last finished one
+    depth: usize,
+    // Best column of the last finished depth
+    best: Option<usize>,
+    // Best column so far of the depth in progress
+    candidate: Option<usize>,
+    nodes: u32,
+}
 
 pub struct ConnectFour {
     pub board: GameBoard,
@@ -36,9 +160,77 @@ pub struct ConnectFour {
     pub state: ConnectFourState,
     win_animation: Animation,
     current_time: Duration,
+    // Index into COMPUTER_DEPTHS, None for two players
+    computer: Option<usize>,
+    computer_turn: ComputerTurn,
+    search: Search,
+    events: GameEvents,
 }
 
+snapshot_enum!(ConnectFourState {
+    Playing,
+    Win(line),
+    Tie,
+    Finished,
+});
+snapshot_enum!(Cell {
+    Empty,
+    PlayerX,
+    PlayerO,
+});
+snapshot_struct!(Evaluation {
+    center,
+    open,
+    threat
+});
+snapshot_struct!(Frame { next, alpha, beta, best } check |frame| {
+    let score = -WIN_SCORE * 2..=WIN_SCORE * 2;
+    frame.next <= COLS
+        && score.contains(&frame.alpha)
+        && score.contains(&frame.beta)
+        && score.contains(&frame.best)
+});
+snapshot_struct!(Search {
+    board,
+    evaluation,
+    frames,
+    depth,
+    best,
+    candidate,
+    nodes,
+} check |search| {
+    search.depth <= MAX_COMPUTER_DEPTH
+        && search.frames.len() <= search.depth
+        && search.nodes <= COMPUTER_NODE_BUDGET
+        && search.best.is_none_or(|col| col < COLS)
+        && search.candidate.is_none_or(|col| col < COLS)
+});
+snapshot_struct!(ConnectFour {
+    board,
+    in_a_row,
+    active_player,
+    active_col,
+    state,
+    win_animation,
+    current_time,
+    computer,
+    computer_turn,
+    search,
+} skip { events } check |game| {
+    let on_board = |(col, row): (usize, usize)| col < COLS && row < ROWS;
+    (1..=MAX_LINE_LENGTH).contains(&game.in_a_row)
+        && game.active_col < COLS
+        && game.computer.is_none_or(|difficulty| difficulty < COMPUTER_DEPTHS.len())
+        && game.search_matches_board()
+        && match &game.state {
+            ConnectFourState::Win(line) => line.iter().all(|&cell| on_board(cell)),
+            _ => true,
+        }
+});
+
 impl ConnectFour {
+    pub const MIN_GRID_SIZE: (usize, usize) = (COLS, ROWS);
+
     pub fn new() -> Self {
         Self {
             board: GameBoard::new(),
@@ -48,6 +240,275 @@ impl ConnectFour {
             state: ConnectFourState::Playing,
             win_animation: Animation::new(WIN_ANIMATION_SPEED),
             current_time: Duration::from_millis(0),
+            computer: None,
+            computer_turn: ComputerTurn::new(),
+            search: Search::default(),
+            events: GameEvents::new(),
+        }
+    }
+
+    /// A single player game against a minimax search. Up and Down change
+    /// how many moves ahead it looks.
+    pub fn with_computer() -> Self {
+        Self {
+            computer: Some(DEFAULT_DIFFICULTY),
+            ..Self::new()
+        }
+    }
+
+    fn is_computer_turn(&self) -> bool {
+        self.computer.is_some() && self.active_player == ComputerTurn::PLAYER
+    }
+
+    fn change_difficulty(&mut self, direction: CommandType) {
+        if let Some(difficulty) = &mut self.computer {
+            *difficulty = match direction {
+                CommandType::Up => (*difficulty + 1).min(COMPUTER_DEPTHS.len() - 1),
+                _ => difficulty.saturating_sub(1),
+            };
+        }
+    }
+
+    fn drop_piece(&mut self, col: usize) {
+        if let Ok(place) = self.make_move(col, self.active_player) {
+            self.events.push(GameEvent::PiecePlaced(self.active_player));
+            let win = self.check_win(place, self.in_a_row);
+            if let Some((_, winning_line)) = win {
+                self.state = ConnectFourState::Win(winning_line);
+            } else if self.check_draw() {
+                self.state = ConnectFourState::Tie;
+            } else {
+                self.active_player = self.active_player.opponent();
+                self.computer_turn.restart();
+            }
+            self.search = Search::default();
+        }
+    }
+
+    /// Continues the computer's search for at most
+    /// `COMPUTER_NODES_PER_UPDATE` positions. Returns its column once it has
+    /// searched `max_depth` or ran out of nodes.
+    fn think(&mut self, max_depth: usize) -> Option<usize> {
+        // The search plays its moves on its own copy of the board, swapped in
+        // so the game's moves and checks can be used
+        core::mem::swap(&mut self.board, &mut self.search.board);
+        let mut nodes = 0;
+        while nodes < COMPUTER_NODES_PER_UPDATE
+            && (self.search.depth < max_depth || !self.search.frames.is_empty())
+        {
+            if self.search.frames.is_empty() {
+                self.board = self.search.board;
+                self.search.evaluation = self.evaluate(&self.board);
+                self.search.depth += 1;
+                self.search.candidate = None;
+                let root = Frame::new(-WIN_SCORE * 2, WIN_SCORE * 2);
+                self.search.frames.push(root);
+            }
+            nodes += self.search_step();
+            if self.search.nodes > COMPUTER_NODE_BUDGET {
+                self.search.frames.clear();
+                self.search.depth = max_depth;
+            }
+        }
+        core::mem::swap(&mut self.board, &mut self.search.board);
+
+        if self.search.depth < max_depth || !self.search.frames.is_empty() {
+            return None;
+        }
+        let free = |&col: &usize| self.board.get(col, self.board.rows() - 1) == Cell::Empty;
+        self.search
+            .best
+            .filter(free)
+            .or_else(|| COLUMN_ORDER.into_iter().find(free))
+    }
+
+    // The player to move in the frame at `index`
+    fn frame_player(&self, index: usize) -> Player {
+        match index % 2 {
+            0 => self.active_player,
+            _ => self.active_player.opponent(),
+        }
+    }
+
+    // Tries the next move of the last frame, returns the positions searched
+    fn search_step(&mut self) -> u32 {
+        let index = self.search.frames.len() - 1;
+        let player = self.frame_player(index);
+        let remaining = self.search.depth - index;
+        let frame = self.search.frames[index];
+
+        if frame.next == COLS {
+            self.search.frames.pop();
+            if index == 0 {
+                // The depth is finished
+                self.search.best = self.search.candidate;
+            } else {
+                self.undo_move(index - 1);
+                self.record_score(index - 1, -frame.best);
+            }
+            return 0;
+        }
+        self.search.frames[index].next += 1;
+        let Ok(place) = self.make_move(COLUMN_ORDER[frame.next], player) else {
+            return 0;
+        };
+        if self.check_win(place, self.in_a_row).is_some() {
+            self.board.set(place.0, place.1, Cell::Empty);
+            // Quicker wins score higher
+            self.record_score(index, WIN_SCORE + remaining as i32);
+            return 0;
+        }
+
+        self.search.nodes += 1;
+        let mut evaluation = self.search.evaluation;
+        self.evaluate_move(place, player, 1, &mut evaluation);
+        self.search.evaluation = evaluation;
+        let opponent = player.opponent();
+        let score = if self.check_draw() {
+            0
+        } else if remaining == 1 {
+            -evaluation.score(opponent)
+        } else {
+            let child = Frame::new(-frame.beta, -frame.alpha);
+            self.search.frames.push(child);
+            return 1;
+        };
+        self.undo_move(index);
+        self.record_score(index, score);
+        1
+    }
+
+    // Takes back the move of the frame at `index`
+    fn undo_move(&mut self, index: usize) {
+        let col = COLUMN_ORDER[self.search.frames[index].next - 1];
+        let Some(row) = (0..self.board.rows())
+            .rev()
+            .find(|&row| self.board.get(col, row) != Cell::Empty)
+        else {
+            return;
+        };
+        let mut evaluation = self.search.evaluation;
+        self.evaluate_move((col, row), self.frame_player(index), -1, &mut evaluation);
+        self.search.evaluation = evaluation;
+        self.board.set(col, row, Cell::Empty);
+    }
+
+    // Scores the last tried move of the frame at `index` for its player
+    fn record_score(&mut self, index: usize, score: i32) {
+        let frame = &mut self.search.frames[index];
+        if score > frame.best {
+            frame.best = score;
+            if index == 0 {
+                self.search.candidate = Some(COLUMN_ORDER[frame.next - 1]);
+            }
+        }
+        frame.alpha = frame.alpha.max(score);
+        if frame.alpha >= frame.beta {
+            // Cut off, the opponent won't allow this position
+            frame.next = COLS;
+        }
+    }
+
+    // Whether a restored search's board is the game's board with the moves
+    // of its frames on it, so taking them back can't go wrong
+    fn search_matches_board(&self) -> bool {
+        let frames = &self.search.frames;
+        if frames.is_empty() {
+            return true;
+        }
+        let mut board = self.search.board;
+        for (index, frame) in frames.iter().enumerate().take(frames.len() - 1).rev() {
+            let Some(&col) = frame.next.checked_sub(1).and_then(|i| COLUMN_ORDER.get(i)) else {
+                return false;
+            };
+            let Some(row) = (0..ROWS)
+                .rev()
+                .find(|&row| board.get(col, row) != Cell::Empty)
+            else {
+                return false;
+            };
+            if board.get(col, row) != self._get_cell_from_player(self.frame_player(index)) {
+                return false;
+            }
+            board.set(col, row, Cell::Empty);
+        }
+        board == self.board && self.search.evaluation == self.evaluate(&self.search.board)
+    }
+
+    // Pieces of each player in the line of `in_a_row` cells from (x, y),
+    // None if it leaves the board
+    fn line_counts(
+        &self,
+        board: &GameBoard,
+        (x, y): (i32, i32),
+        (dx, dy): (i32, i32),
+    ) -> Option<[usize; 2]> {
+        let length = self.in_a_row as i32;
+        let (cols, rows) = (board.cols() as i32, board.rows() as i32);
+        let (end_x, end_y) = (x + dx * (length - 1), y + dy * (length - 1));
+        if ![x, end_x].iter().all(|x| (0..cols).contains(x))
+            || ![y, end_y].iter().all(|y| (0..rows).contains(y))
+        {
+            return None;
+        }
+        let mut counts = [0; 2];
+        for i in 0..length {
+            match board.get((x + dx * i) as usize, (y + dy * i) as usize) {
+                Cell::PlayerX => counts[0] += 1,
+                Cell::PlayerO => counts[1] += 1,
+                Cell::Empty => {}
+            }
+        }
+        Some(counts)
+    }
+
+    // Counts the lines each player could still complete on the whole board
+    fn evaluate(&self, board: &GameBoard) -> Evaluation {
+        let mut evaluation = Evaluation::default();
+        for row in 0..board.rows() {
+            match board.get(board.cols() / 2, row) {
+                Cell::PlayerX => evaluation.center[0] += 1,
+                Cell::PlayerO => evaluation.center[1] += 1,
+                Cell::Empty => {}
+            }
+        }
+        for direction in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+            for x in 0..board.cols() as i32 {
+                for y in 0..board.rows() as i32 {
+                    if let Some(counts) = self.line_counts(board, (x, y), direction) {
+                        evaluation.add_line(counts, 1);
+                    }
+                }
+            }
+        }
+        evaluation
+    }
+
+    // Adds (or with a `sign` of -1 removes) the piece `player` has at
+    // `place` to `evaluation`, only the lines through it change
+    fn evaluate_move(
+        &self,
+        place: (usize, usize),
+        player: Player,
+        sign: i32,
+        evaluation: &mut Evaluation,
+    ) {
+        let index = Evaluation::index(player);
+        if place.0 == self.board.cols() / 2 {
+            evaluation.center[index] += sign;
+        }
+        let (x, y) = (place.0 as i32, place.1 as i32);
+        for (dx, dy) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+            for i in 0..self.in_a_row as i32 {
+                let start = (x - dx * i, y - dy * i);
+                let Some(counts) = self.line_counts(&self.board, start, (dx, dy)) else {
+                    continue;
+                };
+                let mut without = counts;
+                without[index] -= 1;
+                evaluation.add_line(without, -sign);
+                evaluation.add_line(counts, sign);
+            }
         }
     }
 
@@ -64,7 +525,7 @@ impl ConnectFour {
     pub fn _get_cell_from_player(&self, player: Player) -> Cell {
         match player {
             Player::Player1 => Cell::PlayerX,
-            Player::Player2 => Cell::PlayerO,
+            _ => Cell::PlayerO,
         }
     }
 
@@ -104,8 +565,8 @@ impl ConnectFour {
         dy: i32,
         player: Cell,
         in_a_row: usize,
-    ) -> Option<SmallVec<[(usize, usize); GRID_SIZE]>> {
-        let mut positions = SmallVec::<[(usize, usize); GRID_SIZE]>::new();
+    ) -> Option<WinningLine> {
+        let mut positions = WinningLine::new();
 
         for i in -(in_a_row as i32 - 1)..(in_a_row as i32) {
             let nx = x + i * dx;
@@ -132,7 +593,7 @@ impl ConnectFour {
         &self,
         last_move: (usize, usize),
         in_a_row: usize,
-    ) -> Option<(Cell, SmallVec<[(usize, usize); GRID_SIZE]>)> {
+    ) -> Option<(Cell, WinningLine)> {
         let (x, y) = last_move;
         let directions = [
             (0, 1),  // up
@@ -175,7 +636,7 @@ impl Game for ConnectFour {
     fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
         match &self.state {
             ConnectFourState::Playing => {
-                if input_command.player != self.active_player {
+                if input_command.player != self.active_player || self.is_computer_turn() {
                     return Ok(());
                 }
 
@@ -187,21 +648,10 @@ impl Game for ConnectFour {
                         CommandType::Right => {
                             let _ = self.move_col(CommandType::Right);
                         }
-                        CommandType::Select => {
-                            if let Ok(place) = self.make_move(self.active_col, self.active_player) {
-                                let win = self.check_win(place, self.in_a_row);
-                                if let Some((_, winning_line)) = win {
-                                    self.state = ConnectFourState::Win(winning_line);
-                                } else if self.check_draw() {
-                                    self.state = ConnectFourState::Tie;
-                                } else {
-                                    self.active_player = match self.active_player {
-                                        Player::Player1 => Player::Player2,
-                                        Player::Player2 => Player::Player1,
-                                    };
-                                }
-                            }
+                        CommandType::Up | CommandType::Down => {
+                            self.change_difficulty(input_command.command_type);
                         }
+                        CommandType::Select => self.drop_piece(self.active_col),
                         CommandType::Quit => {
                             self.state = ConnectFourState::Finished;
                         }
@@ -217,6 +667,7 @@ impl Game for ConnectFour {
                             self.board = GameBoard::new();
                             self.active_player = Player::Player1;
                             self.active_col = 0;
+                            self.search = Search::default();
                         }
                         _ => return Ok(()),
                     }
@@ -232,7 +683,20 @@ impl Game for ConnectFour {
         self.current_time += delta_time;
 
         match &self.state {
-            ConnectFourState::Playing => {}
+            ConnectFourState::Playing => {
+                if let Some(difficulty) = self.computer
+                    && self.is_computer_turn()
+                {
+                    // It thinks while it waits, so no update searches for long
+                    let waited = self.computer_turn.wait(delta_time);
+                    if let Some(col) = self.think(COMPUTER_DEPTHS[difficulty])
+                        && waited
+                    {
+                        self.active_col = col;
+                        self.drop_piece(col);
+                    }
+                }
+            }
             ConnectFourState::Win(_) | ConnectFourState::Tie => {
                 self.win_animation.update(self.current_time);
             }
@@ -255,7 +719,7 @@ impl Game for ConnectFour {
                                 Player::Player1 => {
                                     rgb = RGB::new(230, 166, 83);
                                 }
-                                Player::Player2 => {
+                                _ => {
                                     rgb = RGB::new(125, 113, 191);
                                 }
                             }
@@ -272,6 +736,12 @@ impl Game for ConnectFour {
                         render_board.set(col, row, rgb);
                     }
                 }
+                // One dot per difficulty level along the top edge
+                if let Some(difficulty) = self.computer {
+                    for col in 0..=difficulty {
+                        render_board.set(col, GRID_HEIGHT - 1, RGB::new(125, 113, 191));
+                    }
+                }
             }
             ConnectFourState::Win(winning_line) => {
                 // reset color in active column
@@ -301,11 +771,37 @@ impl Game for ConnectFour {
         }
         Ok(render_board)
     }
+
+    fn status(&self) -> GameStatus {
+        match self.state {
+            ConnectFourState::Playing => GameStatus::playing(),
+            // The active player is not switched after the winning move
+            ConnectFourState::Win(_) => GameStatus::game_over(Some(self.active_player)),
+            ConnectFourState::Tie | ConnectFourState::Finished => GameStatus::game_over(None),
+        }
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::game::DEFAULT_TICK;
+    use crate::game::computer::COMPUTER_MOVE_DELAY;
+    use crate::snapshot::{Reader, Snapshot, Writer};
+
+    // Runs the computer's whole search in one go
+    fn best_move(game: &mut ConnectFour, depth: usize) -> usize {
+        game.search = Search::default();
+        loop {
+            if let Some(col) = game.think(depth) {
+                return col;
+            }
+        }
+    }
 
     #[test]
     fn test_new_game() {
@@ -438,4 +934,163 @@ mod tests {
         // The game should be in a Tie state
         assert!(game.check_draw());
     }
+
+    #[test]
+    fn test_computer_wins_or_blocks() {
+        let mut game = ConnectFour::with_computer();
+        game.active_player = Player::Player2;
+        for col in [0, 1, 2] {
+            game.make_move(col, Player::Player1).unwrap();
+        }
+        // Player 1 threatens to complete the bottom row
+        assert_eq!(best_move(&mut game, COMPUTER_DEPTHS[0]), 3);
+
+        for _ in 0..3 {
+            game.make_move(6, Player::Player2).unwrap();
+        }
+        // Winning beats blocking
+        for depth in COMPUTER_DEPTHS {
+            assert_eq!(best_move(&mut game, depth), 6);
+        }
+        // The search leaves the board as it found it
+        assert_eq!(game.board.get(3, 0), Cell::Empty);
+        assert_eq!(game.board.get(6, 3), Cell::Empty);
+    }
+
+    #[test]
+    fn test_column_order_starts_in_the_center() {
+        assert_eq!(COLUMN_ORDER, [3, 2, 4, 1, 5, 0, 6]);
+    }
+
+    #[test]
+    fn test_evaluation_follows_moves() {
+        let mut game = ConnectFour::new();
+        let mut evaluation = Evaluation::default();
+        let mut player = Player::Player1;
+        let mut places = Vec::new();
+        for col in [3, 3, 2, 4, 4, 1, 5, 2, 6, 0, 3, 3, 2, 5] {
+            let place = game.make_move(col, player).unwrap();
+            game.evaluate_move(place, player, 1, &mut evaluation);
+            assert_eq!(evaluation, game.evaluate(&game.board));
+            places.push((place, player));
+            player = player.opponent();
+        }
+        for (place, player) in places.into_iter().rev() {
+            game.evaluate_move(place, player, -1, &mut evaluation);
+            game.board.set(place.0, place.1, Cell::Empty);
+        }
+        assert_eq!(evaluation, Evaluation::default());
+    }
+
+    // The computer plays itself at the highest difficulty, every update
+    // searches a bounded number of positions and leaves the board alone
+    #[test]
+    fn test_computer_thinks_a_little_every_update() {
+        let max_updates = (COMPUTER_NODE_BUDGET / COMPUTER_NODES_PER_UPDATE) as usize + 2;
+        let mut game = ConnectFour::with_computer();
+        while game.state == ConnectFourState::Playing {
+            let board = game.board;
+            let mut updates = 0;
+            let col = loop {
+                let nodes = game.search.nodes;
+                let col = game.think(MAX_COMPUTER_DEPTH);
+                assert!(game.search.nodes - nodes <= COMPUTER_NODES_PER_UPDATE);
+                assert_eq!(game.board, board);
+                updates += 1;
+                if let Some(col) = col {
+                    break col;
+                }
+            };
+            assert!(updates <= max_updates);
+            game.drop_piece(col);
+        }
+    }
+
+    #[test]
+    fn test_computer_resumes_its_search() {
+        let mut game = ConnectFour::with_computer();
+        for col in [3, 2, 3] {
+            game.drop_piece(col);
+        }
+        // Stop half way through a depth
+        while game.search.frames.len() < 3 {
+            assert_eq!(game.think(MAX_COMPUTER_DEPTH), None);
+        }
+        let mut buf = [0; 1024];
+        let mut w = Writer::new(&mut buf);
+        game.save(&mut w);
+        let len = w.finish().unwrap();
+        let mut restored = ConnectFour::restore(&mut Reader::new(&buf[..len])).unwrap();
+        assert_eq!(restored.search, game.search);
+
+        let col = loop {
+            let col = game.think(MAX_COMPUTER_DEPTH);
+            assert_eq!(restored.think(MAX_COMPUTER_DEPTH), col);
+            if let Some(col) = col {
+                break col;
+            }
+        };
+        assert_eq!(col, best_move(&mut game, MAX_COMPUTER_DEPTH));
+    }
+
+    #[test]
+    fn test_computer_answers_after_delay() {
+        let mut game = ConnectFour::with_computer();
+        let press =
+            |command_type, player| GameCommand::new(command_type, ButtonState::Pressed, player);
+
+        game.process_input(press(CommandType::Up, Player::Player1))
+            .unwrap();
+        assert_eq!(game.computer, Some(DEFAULT_DIFFICULTY + 1));
+        game.process_input(press(CommandType::Select, Player::Player1))
+            .unwrap();
+        assert_eq!(game.active_player, Player::Player2);
+
+        // The second controller can't move for the computer
+        game.process_input(press(CommandType::Select, Player::Player2))
+            .unwrap();
+        game.update(COMPUTER_MOVE_DELAY / 2).unwrap();
+        assert_eq!(game.active_player, Player::Player2);
+
+        // It thinks a little every update and answers once the delay is over
+        let mut waited = COMPUTER_MOVE_DELAY / 2;
+        while game.active_player == Player::Player2 {
+            assert!(waited < Duration::from_secs(2));
+            game.update(DEFAULT_TICK).unwrap();
+            waited += DEFAULT_TICK;
+        }
+        assert!(waited >= COMPUTER_MOVE_DELAY);
+        let pieces = game
+            .board
+            .cells
+            .iter()
+            .flatten()
+            .filter(|&&cell| cell != Cell::Empty)
+            .count();
+        assert_eq!(pieces, 2);
+    }
+
+    #[test]
+    fn test_status_reports_winner() {
+        let mut game = ConnectFour::new();
+        let press =
+            |command_type, player| GameCommand::new(command_type, ButtonState::Pressed, player);
+
+        // Player 1 stacks column 0 while player 2 plays column 1
+        for _ in 0..3 {
+            game.process_input(press(CommandType::Select, Player::Player1))
+                .unwrap();
+            game.process_input(press(CommandType::Right, Player::Player2))
+                .unwrap();
+            game.process_input(press(CommandType::Select, Player::Player2))
+                .unwrap();
+            game.process_input(press(CommandType::Left, Player::Player1))
+                .unwrap();
+        }
+        assert_eq!(game.status(), GameStatus::playing());
+
+        game.process_input(press(CommandType::Select, Player::Player1))
+            .unwrap();
+        assert_eq!(game.status(), GameStatus::game_over(Some(Player::Player1)));
+    }
 }
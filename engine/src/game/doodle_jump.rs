@@ -1,13 +1,18 @@
 use crate::ButtonState;
 use crate::GameError;
 use crate::animation::Animation;
-use crate::game::{CommandType, Game, GameCommand, RGB, RenderBoard};
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    CommandType, DEFAULT_TICK, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus,
+    Player as GamePlayer, RGB, RenderBoard,
+};
 use crate::random::CustomRng;
 
+use crate::snapshot::{snapshot_enum, snapshot_struct};
+use crate::timestep::FixedStep;
 use core::time::Duration;
 use smallvec::SmallVec;
 
-const GRID_SIZE: usize = 12;
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
 
 const GRAVITY: f64 = -0.02;
@@ -33,9 +38,9 @@ impl Player {
         self.y += self.velocity_y * delta_time * SPEED_MULTIPLIER;
 
         while self.x < 0.0 {
-            self.x += GRID_SIZE as f64;
+            self.x += GRID_WIDTH as f64;
         }
-        self.x %= GRID_SIZE as f64;
+        self.x %= GRID_WIDTH as f64;
     }
 
     fn bounce(&mut self) {
@@ -45,9 +50,9 @@ impl Player {
     fn wrapped_x(&self) -> f64 {
         let mut x = self.x;
         while x < 0.0 {
-            x += GRID_SIZE as f64;
+            x += GRID_WIDTH as f64;
         }
-        x % GRID_SIZE as f64
+        x % GRID_WIDTH as f64
     }
 
     fn col(&self) -> usize {
@@ -116,9 +121,9 @@ impl Platform {
     fn update_moving(&mut self, dt: f64) {
         const SPEED: f64 = 2.0;
         self.x += self.state.direction * SPEED * dt;
-        if self.x <= 0.0 || self.x + self.width as f64 >= GRID_SIZE as f64 {
+        if self.x <= 0.0 || self.x + self.width as f64 >= GRID_WIDTH as f64 {
             self.state.direction *= -1.0;
-            self.x = self.x.clamp(0.0, GRID_SIZE as f64 - self.width as f64);
+            self.x = self.x.clamp(0.0, GRID_WIDTH as f64 - self.width as f64);
         }
     }
 
@@ -144,26 +149,84 @@ enum GameState {
 pub struct DoodleJump {
     state: GameState,
     player: Player,
-    platforms: SmallVec<[Platform; GRID_SIZE * 2]>,
+    platforms: SmallVec<[Platform; GRID_HEIGHT * 2]>,
     current_time: Duration,
+    // The player jumps and lands in ticks of equal length, so a bounce is
+    // the same at any frame rate
+    fixed_step: FixedStep,
     rng: CustomRng,
     score: usize,
     camera_offset: usize,
+    events: GameEvents,
 }
 
+snapshot_struct!(Player { x, y, velocity_y } check |player| {
+    // Inputs move the player a column at a time between the updates that
+    // wrap it around
+    let width = GRID_WIDTH as f64;
+    (-width..2.0 * width).contains(&player.x) && player.velocity_y.is_finite()
+});
+snapshot_enum!(PlatformType {
+    Static,
+    Moving,
+    Breaking,
+    Switching,
+});
+snapshot_struct!(Platform {
+    x,
+    y,
+    width,
+    platform_type,
+    color,
+    state,
+} check |platform| {
+    (0.0..=GRID_WIDTH as f64).contains(&platform.x) && platform.width <= GRID_WIDTH
+});
+snapshot_struct!(PlatformState {
+    direction,
+    broken,
+    switch_timer,
+});
+snapshot_enum!(GameState {
+    Playing,
+    GameOver(animation),
+});
+snapshot_struct!(DoodleJump {
+    state,
+    player,
+    platforms,
+    current_time,
+    fixed_step,
+    rng,
+    score,
+    camera_offset,
+} skip { events } check |game| {
+    // Everything is within a few screens of the camera, which only ever
+    // moves up as far as the player climbed
+    game.fixed_step.step() == DEFAULT_TICK
+        && game.camera_offset.checked_add(GRID_HEIGHT * 4).is_some_and(|top| {
+            let near = game.camera_offset as f64 - GRID_HEIGHT as f64..top as f64;
+            near.contains(&game.player.y)
+                && !game.platforms.is_empty()
+                && game.platforms.iter().all(|platform| near.contains(&platform.y))
+        })
+});
+
 impl DoodleJump {
     pub fn new(seed: u64) -> Self {
         let rng = CustomRng::seed_from_u64(seed);
-        let initial_platform = Platform::new(GRID_SIZE as f64 / 2.0, 1.0, 3, PlatformType::Static);
+        let initial_platform = Platform::new(GRID_WIDTH as f64 / 2.0, 1.0, 3, PlatformType::Static);
 
         let mut game = Self {
             state: GameState::Playing,
             player: Player::new(initial_platform.x + 1.0, initial_platform.y + 1.0),
             platforms: SmallVec::new(),
             current_time: Duration::ZERO,
+            fixed_step: FixedStep::new(DEFAULT_TICK),
             rng,
             score: 0,
             camera_offset: 0,
+            events: GameEvents::new(),
         };
 
         game.platforms.push(initial_platform);
@@ -172,10 +235,10 @@ impl DoodleJump {
     }
 
     fn initialize_platforms(&mut self) {
-        for y in (3..GRID_SIZE * 2).step_by(2) {
+        for y in (3..GRID_HEIGHT * 2).step_by(2) {
             let platform_type = self.random_platform_type();
             let width = self.rng.gen_range(3, 6);
-            let max_x = GRID_SIZE as f64 - width as f64;
+            let max_x = GRID_WIDTH as f64 - width as f64;
             let x = self.rng.gen_range_f64(0.0, max_x);
             self.platforms
                 .push(Platform::new(x, y as f64, width as usize, platform_type));
@@ -186,11 +249,11 @@ impl DoodleJump {
         let y = self
             .platforms
             .last()
-            .map_or(GRID_SIZE as f64 * 2.0, |p| p.y + 2.0);
+            .map_or(GRID_HEIGHT as f64 * 2.0, |p| p.y + 2.0);
         let platform_type = self.random_platform_type();
         let width = self.rng.gen_range(3, 6);
 
-        let max_x = GRID_SIZE as f64 - width as f64;
+        let max_x = GRID_WIDTH as f64 - width as f64;
         let x = self.rng.gen_range_f64(0.0, max_x);
 
         self.platforms
@@ -206,48 +269,84 @@ impl DoodleJump {
         }
     }
 
-    fn check_and_handle_collision(&mut self) -> bool {
-        let player_bottom = self.player.y;
-        let player_top = self.player.y + 1.0;
+    // Lands on a platform whose top the player fell through since
+    // `previous_y`. Red switching platforms let the player fall through.
+    fn check_and_handle_collision(&mut self, previous_y: f64) -> bool {
+        if self.player.velocity_y >= 0.0 {
+            return false;
+        }
         let player_col = self.player.col();
+        let player_y = self.player.y;
 
-        for platform in &mut self.platforms {
+        let platform = self.platforms.iter_mut().find(|platform| {
             let platform_top = platform.y + 1.0;
-            let start_col = libm::floor(platform.x) as usize % GRID_SIZE;
+            let start_col = libm::floor(platform.x) as usize % GRID_WIDTH;
 
             let horizontal_collision = (0..platform.width).any(|i| {
-                let platform_col = (start_col + i) % GRID_SIZE;
+                let platform_col = (start_col + i) % GRID_WIDTH;
                 platform_col == player_col
             });
+            let vertical_collision = player_y < platform_top && platform_top <= previous_y;
+            let solid = match platform.platform_type {
+                PlatformType::Switching => platform.color.g == 255,
+                _ => true,
+            };
 
-            let vertical_collision = player_bottom <= platform_top && player_top > platform_top;
-
-            if horizontal_collision && vertical_collision && self.player.velocity_y < 0.0 {
-                self.player.y = platform_top;
-                self.player.bounce();
-
-                match platform.platform_type {
-                    PlatformType::Breaking => {
-                        platform.state.broken = true;
-                    }
-                    PlatformType::Switching => {
-                        if platform.color.g != 255 {
-                            continue; // fall through if red
-                        }
-                    }
-                    _ => {}
-                }
+            horizontal_collision && vertical_collision && solid
+        });
+        let Some(platform) = platform else {
+            return false;
+        };
 
-                return true;
-            }
+        self.player.y = platform.y + 1.0;
+        self.player.bounce();
+        if let PlatformType::Breaking = platform.platform_type {
+            platform.state.broken = true;
+        }
+        true
+    }
+
+    fn tick(&mut self, dt: f64) {
+        self.player.velocity_y += GRAVITY * dt * SPEED_MULTIPLIER;
+
+        for platform in &mut self.platforms {
+            platform.update(dt);
+        }
+
+        let previous_y = self.player.y;
+        self.player.update_position(dt);
+        if self.check_and_handle_collision(previous_y) {
+            self.events.push(GameEvent::Jumped(GamePlayer::Player1));
+        }
+
+        if self.player.y < self.camera_offset as f64 - 1.0 {
+            self.state = GameState::GameOver(Animation::new(GAME_OVER_ANIMATION_SPEED));
+            self.events.push(GameEvent::PlayerDied(GamePlayer::Player1));
+            return;
+        }
+
+        if self.player.y > (self.camera_offset + GRID_HEIGHT / 2) as f64 {
+            let new_offset = self.player.row() - GRID_HEIGHT / 2;
+            self.score += new_offset - self.camera_offset;
+            self.camera_offset = new_offset;
+        }
+
+        self.platforms
+            .retain(|p| p.y >= self.camera_offset as f64 && !p.state.broken);
+
+        while self
+            .platforms
+            .last()
+            .is_none_or(|p| p.y < (self.camera_offset + GRID_HEIGHT * 2) as f64)
+        {
+            self.generate_platform();
         }
-        false
     }
 
     fn reset_game(&mut self) {
         self.state = GameState::Playing;
         self.platforms.clear();
-        let initial_platform = Platform::new(GRID_SIZE as f64 / 2.0, 1.0, 3, PlatformType::Static);
+        let initial_platform = Platform::new(GRID_WIDTH as f64 / 2.0, 1.0, 3, PlatformType::Static);
         self.player = Player::new(initial_platform.x + 1.0, initial_platform.y + 1.0);
         self.platforms.push(initial_platform);
         self.score = 0;
@@ -275,46 +374,16 @@ impl Game for DoodleJump {
 
     fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
         self.current_time += delta_time;
-        let dt = delta_time.as_secs_f64();
-
-        match &mut self.state {
-            GameState::Playing => {
-                self.player.velocity_y += GRAVITY * dt * SPEED_MULTIPLIER;
-
-                for platform in &mut self.platforms {
-                    platform.update(dt);
-                }
-
-                if !self.check_and_handle_collision() {
-                    self.player.update_position(dt);
-
-                    if self.player.y < self.camera_offset as f64 - 1.0 {
-                        self.state = GameState::GameOver(Animation::new(GAME_OVER_ANIMATION_SPEED));
-                        return Ok(());
-                    }
-                }
-
-                if self.player.y > (self.camera_offset + GRID_SIZE / 2) as f64 {
-                    let new_offset = self.player.row() - GRID_SIZE / 2;
-                    self.score += new_offset - self.camera_offset;
-                    self.camera_offset = new_offset;
-                }
 
-                self.platforms
-                    .retain(|p| p.y >= self.camera_offset as f64 && !p.state.broken);
-
-                while self
-                    .platforms
-                    .last()
-                    .is_none_or(|p| p.y < (self.camera_offset + GRID_SIZE * 2) as f64)
-                {
-                    self.generate_platform();
-                }
-            }
-            GameState::GameOver(animation) => {
-                animation.update(self.current_time);
+        for _ in 0..self.fixed_step.advance(delta_time) {
+            match &mut self.state {
+                GameState::Playing => self.tick(DEFAULT_TICK.as_secs_f64()),
+                GameState::GameOver(_) => break,
             }
         }
+        if let GameState::GameOver(animation) = &mut self.state {
+            animation.update(self.current_time);
+        }
         Ok(())
     }
 
@@ -323,7 +392,7 @@ impl Game for DoodleJump {
 
         for platform in &self.platforms {
             if platform.y >= self.camera_offset as f64
-                && platform.y < (self.camera_offset + GRID_SIZE) as f64
+                && platform.y < (self.camera_offset + GRID_HEIGHT) as f64
             {
                 let platform_color = match &self.state {
                     GameState::Playing => platform.color,
@@ -332,7 +401,7 @@ impl Game for DoodleJump {
 
                 for x in platform.x as usize..(platform.x + platform.width as f64) as usize {
                     render_board.set(
-                        x % GRID_SIZE,
+                        x % GRID_WIDTH,
                         (platform.y - self.camera_offset as f64) as usize,
                         platform_color,
                     );
@@ -348,10 +417,30 @@ impl Game for DoodleJump {
         let player_render_x = self.player.col();
         let player_render_y = self.player.row().saturating_sub(self.camera_offset);
 
-        if player_render_y < GRID_SIZE {
+        if player_render_y < GRID_HEIGHT {
             render_board.set(player_render_x, player_render_y, player_color);
         }
 
         Ok(render_board)
     }
+
+    fn final_score(&self) -> Option<u32> {
+        match self.state {
+            GameState::GameOver(_) => Some(self.score as u32),
+            _ => None,
+        }
+    }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Playing => GameStatus::playing(),
+            GameState::GameOver(_) => GameStatus::game_over(None),
+        };
+        status.scores[0] = Some(self.score as u32);
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
 }
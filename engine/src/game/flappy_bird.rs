@@ -1,12 +1,18 @@
 use crate::GameError;
 use crate::animation::Animation;
-use crate::game::{ButtonState, CommandType, Game, GameCommand, RGB, RenderBoard};
+use crate::bot::Bot;
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    ButtonState, CommandType, DEFAULT_TICK, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus,
+    Player as GamePlayer, RGB, RenderBoard,
+};
 
 use crate::random::CustomRng;
+use crate::snapshot::{snapshot_enum, snapshot_struct};
+use crate::timestep::FixedStep;
 use core::time::Duration;
 use smallvec::SmallVec;
 
-const GRID_SIZE: usize = 12;
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
 
 struct Player {
@@ -28,19 +34,23 @@ impl Player {
         self.velocity = 0.8;
     }
 
+    // Half of the tick's gravity is added before the bird moves and half
+    // after, so it follows the arc of a flap closely
     fn update_position(&mut self, delta_time: f64) {
         const GRAVITY: f64 = -0.2;
         const SPEED_MULTIPLIER: f64 = 15.0;
         const TOP_BOUNCE_VELOCITY: f64 = -0.3;
 
+        let gravity = GRAVITY * delta_time * SPEED_MULTIPLIER;
+        self.velocity += gravity / 2.0;
         self.pos += self.velocity * delta_time * SPEED_MULTIPLIER;
-        self.velocity += GRAVITY * delta_time * SPEED_MULTIPLIER;
+        self.velocity += gravity / 2.0;
 
         if self.pos <= 0.0 {
             self.pos = 0.0;
             self.velocity = 0.0;
-        } else if self.pos >= (GRID_SIZE - 1) as f64 {
-            self.pos = (GRID_SIZE - 1) as f64;
+        } else if self.pos >= (GRID_HEIGHT - 1) as f64 {
+            self.pos = (GRID_HEIGHT - 1) as f64;
             self.velocity = TOP_BOUNCE_VELOCITY;
         }
     }
@@ -74,16 +84,51 @@ enum GameState {
 pub struct FlappyBird {
     state: GameState,
     player: Player,
-    walls: SmallVec<[Wall; GRID_SIZE]>,
+    walls: SmallVec<[Wall; GRID_WIDTH]>,
     current_time: Duration,
+    // The bird flies and hits walls in ticks of equal length, so it flies
+    // the same at any frame rate
+    fixed_step: FixedStep,
     wall_gap: usize,
     wall_period: f64,
     last_wall_time: f64,
     rng: CustomRng,
     game_over_animation: Animation,
     score: usize,
+    events: GameEvents,
 }
 
+snapshot_struct!(Player { col, pos, velocity } check |player| {
+    player.col < GRID_WIDTH && (0.0..=(GRID_HEIGHT - 1) as f64).contains(&player.pos)
+});
+snapshot_struct!(Wall {
+    col,
+    gap_row,
+    gap_size,
+} check |wall| {
+    wall.col < GRID_WIDTH
+        && wall
+            .gap_row
+            .checked_add(wall.gap_size)
+            .is_some_and(|end| end <= GRID_HEIGHT)
+});
+snapshot_enum!(GameState { Playing, GameOver });
+snapshot_struct!(FlappyBird {
+    state,
+    player,
+    walls,
+    current_time,
+    fixed_step,
+    wall_gap,
+    wall_period,
+    last_wall_time,
+    rng,
+    game_over_animation,
+    score,
+} skip { events } check |game| {
+    game.fixed_step.step() == DEFAULT_TICK && game.wall_gap <= GRID_WIDTH
+});
+
 impl FlappyBird {
     pub fn new(seed: u64) -> Self {
         Self {
@@ -91,12 +136,14 @@ impl FlappyBird {
             player: Player::new(),
             walls: SmallVec::new(),
             current_time: Duration::ZERO,
+            fixed_step: FixedStep::new(DEFAULT_TICK),
             wall_gap: 8,
             wall_period: 0.18,
             last_wall_time: 0.0,
             rng: CustomRng::seed_from_u64(seed),
             game_over_animation: Animation::new(GAME_OVER_ANIMATION_SPEED),
             score: 0,
+            events: GameEvents::new(),
         }
     }
 
@@ -108,16 +155,26 @@ impl FlappyBird {
                 wall.col -= 1;
                 if wall.col == self.player.col {
                     self.score += 1;
+                    self.events.push(GameEvent::Scored(GamePlayer::Player1));
                 }
                 true
             }
         });
     }
 
+    // The bird climbs about a row for every column between two walls, so on
+    // tall grids a gap is never more rows away from the last one than that
     fn add_wall(&mut self) {
         const GAP_SIZE: usize = 4;
-        let gap_row = self.rng.gen_range(0, (GRID_SIZE - GAP_SIZE + 1) as u32) as usize;
-        self.walls.push(Wall::new(GRID_SIZE - 1, gap_row, GAP_SIZE));
+        let last_gap_row = self
+            .walls
+            .last()
+            .map_or(self.player.row(), |wall| wall.gap_row);
+        let lowest = last_gap_row.saturating_sub(self.wall_gap);
+        let highest = (last_gap_row + self.wall_gap).min(GRID_HEIGHT - GAP_SIZE);
+        let gap_row = self.rng.gen_range(lowest as u32, highest as u32 + 1) as usize;
+        self.walls
+            .push(Wall::new(GRID_WIDTH - 1, gap_row, GAP_SIZE));
     }
 
     fn detect_collision(&self) -> bool {
@@ -128,6 +185,31 @@ impl FlappyBird {
         })
     }
 
+    fn tick(&mut self, dt: f64) {
+        self.player.update_position(dt);
+
+        if self.detect_collision() {
+            self.state = GameState::GameOver;
+            self.events.push(GameEvent::PlayerDied(GamePlayer::Player1));
+            return;
+        }
+
+        self.last_wall_time += dt;
+        if self.last_wall_time > self.wall_period {
+            self.move_walls_left();
+            self.last_wall_time -= self.wall_period;
+        }
+
+        if self.walls.is_empty()
+            || self
+                .walls
+                .last()
+                .is_some_and(|w| w.col == GRID_WIDTH - self.wall_gap)
+        {
+            self.add_wall();
+        }
+    }
+
     fn reset_game(&mut self) {
         self.state = GameState::Playing;
         self.walls.clear();
@@ -136,11 +218,42 @@ impl FlappyBird {
     }
 }
 
+/// Flaps whenever the bird falls below the gap of the next wall.
+pub(crate) struct FlappyBot;
+
+snapshot_struct!(FlappyBot);
+
+impl Bot<FlappyBird> for FlappyBot {
+    fn next_command(
+        &mut self,
+        game: &FlappyBird,
+        player: GamePlayer,
+        _delta_time: Duration,
+    ) -> Option<GameCommand> {
+        if !matches!(game.state, GameState::Playing) || game.player.velocity > 0.0 {
+            return None;
+        }
+        // A flap lifts the bird about one and a half rows, so it flaps from
+        // the bottom of the gap and stays clear of the top
+        let bottom = game
+            .walls
+            .iter()
+            .filter(|wall| wall.col >= game.player.col)
+            .min_by_key(|wall| wall.col)
+            .map_or(GRID_HEIGHT / 2, |wall| wall.gap_row);
+        (game.player.pos < bottom as f64 + 0.5)
+            .then(|| GameCommand::new(CommandType::Up, ButtonState::Pressed, player))
+    }
+}
+
 impl Game for FlappyBird {
     fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
         if let ButtonState::Pressed = input_command.button_state {
             match (&self.state, input_command.command_type) {
-                (GameState::Playing, CommandType::Up | CommandType::Select) => self.player.jump(),
+                (GameState::Playing, CommandType::Up | CommandType::Select) => {
+                    self.player.jump();
+                    self.events.push(GameEvent::Jumped(GamePlayer::Player1));
+                }
                 (GameState::GameOver, CommandType::Select) => self.reset_game(),
                 _ => {}
             }
@@ -150,36 +263,16 @@ impl Game for FlappyBird {
 
     fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
         self.current_time += delta_time;
-        let dt = delta_time.as_secs_f64();
-
-        match self.state {
-            GameState::Playing => {
-                self.player.update_position(dt);
-
-                if self.detect_collision() {
-                    self.state = GameState::GameOver;
-                    return Ok(());
-                }
-
-                self.last_wall_time += dt;
-                if self.last_wall_time > self.wall_period {
-                    self.move_walls_left();
-                    self.last_wall_time = 0.0;
-                }
 
-                if self.walls.is_empty()
-                    || self
-                        .walls
-                        .last()
-                        .is_some_and(|w| w.col == GRID_SIZE - self.wall_gap)
-                {
-                    self.add_wall();
-                }
-            }
-            GameState::GameOver => {
-                self.game_over_animation.update(self.current_time);
+        for _ in 0..self.fixed_step.advance(delta_time) {
+            match self.state {
+                GameState::Playing => self.tick(DEFAULT_TICK.as_secs_f64()),
+                GameState::GameOver => break,
             }
         }
+        if let GameState::GameOver = self.state {
+            self.game_over_animation.update(self.current_time);
+        }
         Ok(())
     }
 
@@ -187,7 +280,7 @@ impl Game for FlappyBird {
         let mut render_board = RenderBoard::new();
 
         for wall in &self.walls {
-            for row in (0..wall.gap_row).chain(wall.gap_row + wall.gap_size..GRID_SIZE) {
+            for row in (0..wall.gap_row).chain(wall.gap_row + wall.gap_size..GRID_HEIGHT) {
                 render_board.set(wall.col, row, RGB::new(255, 0, 0));
             }
         }
@@ -198,7 +291,7 @@ impl Game for FlappyBird {
         {
             let color = self.game_over_animation.get_color();
             for row in
-                (0..first_wall.gap_row).chain(first_wall.gap_row + first_wall.gap_size..GRID_SIZE)
+                (0..first_wall.gap_row).chain(first_wall.gap_row + first_wall.gap_size..GRID_HEIGHT)
             {
                 render_board.set(0, row, color);
             }
@@ -212,4 +305,40 @@ impl Game for FlappyBird {
 
         Ok(render_board)
     }
+
+    fn final_score(&self) -> Option<u32> {
+        match self.state {
+            GameState::GameOver => Some(self.score as u32),
+            _ => None,
+        }
+    }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Playing => GameStatus::playing(),
+            GameState::GameOver => GameStatus::game_over(None),
+        };
+        status.scores[0] = Some(self.score as u32);
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotPlayer;
+
+    #[test]
+    fn test_bot_flies_through_walls() {
+        let mut game = BotPlayer::new(FlappyBird::new(4), FlappyBot, GamePlayer::Player1);
+        for _ in 0..3000 {
+            game.update(Duration::from_millis(16)).unwrap();
+        }
+        assert!(!game.status().is_over());
+        assert!(game.game().score >= 20, "only scored {}", game.game().score);
+    }
 }
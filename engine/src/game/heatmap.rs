@@ -0,0 +1,440 @@
+use crate::RGB;
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus, Player,
+};
+use crate::random::CustomRng;
+use crate::snapshot::{on_grid, snapshot_enum, snapshot_struct};
+use crate::text::{GLYPH_HEIGHT, Marquee, draw_text_centered};
+use crate::{GameError, RenderBoard};
+use core::time::Duration;
+use libm::{expf, fabsf, floorf};
+
+const ROUNDS_TO_WIN: u8 = 3; // Best of 5
+const REVEAL_DURATION: Duration = Duration::from_secs(2);
+const SCORE_DURATION: Duration = Duration::from_millis(1500);
+const CURSOR_BLINK_SPEED: Duration = Duration::from_millis(250);
+// Hue of the coldest square, the hottest is red at 0
+const COLD_HUE: f32 = 0.66;
+const PLAYER_COLORS: [RGB; 2] = [
+    RGB { r: 0, g: 255, b: 0 },
+    RGB {
+        r: 255,
+        g: 255,
+        b: 255,
+    },
+];
+
+#[derive(Debug, PartialEq)]
+enum GameState {
+    Playing,
+    RoundOver { winner: Player, elapsed: Duration },
+    MatchOver(Player),
+}
+
+/// A single Gaussian hump, normalized so the hottest square is 1.0 and the
+/// coldest is 0.0.
+struct HeatField {
+    heat: [[f32; GRID_HEIGHT]; GRID_WIDTH],
+    hottest: (usize, usize),
+}
+
+impl HeatField {
+    fn generate(rng: &mut CustomRng) -> Self {
+        let x0 = rng.gen_range_f32(0.0, (GRID_WIDTH - 1) as f32);
+        let y0 = rng.gen_range_f32(0.0, (GRID_HEIGHT - 1) as f32);
+        // The spread is scaled from the 3 to 5 squares used on a 12x12 grid
+        let sigma_x = rng.gen_range_f32(GRID_WIDTH as f32 / 4.0, GRID_WIDTH as f32 * 5.0 / 12.0);
+        let sigma_y = rng.gen_range_f32(GRID_HEIGHT as f32 / 4.0, GRID_HEIGHT as f32 * 5.0 / 12.0);
+
+        let mut heat = [[0.0; GRID_HEIGHT]; GRID_WIDTH];
+        let mut hottest = (0, 0);
+        let (mut min, mut max) = (f32::MAX, f32::MIN);
+        for (x, column) in heat.iter_mut().enumerate() {
+            for (y, cell) in column.iter_mut().enumerate() {
+                let dx = x as f32 - x0;
+                let dy = y as f32 - y0;
+                *cell = expf(
+                    -(dx * dx / (2.0 * sigma_x * sigma_x) + dy * dy / (2.0 * sigma_y * sigma_y)),
+                );
+                if *cell > max {
+                    max = *cell;
+                    hottest = (x, y);
+                }
+                min = min.min(*cell);
+            }
+        }
+
+        for cell in heat.iter_mut().flatten() {
+            *cell = (*cell - min) / (max - min);
+        }
+        Self { heat, hottest }
+    }
+
+    fn color(&self, x: usize, y: usize) -> RGB {
+        hsv_to_rgb(COLD_HUE * (1.0 - self.heat[x][y]))
+    }
+}
+
+// Fully saturated and bright color for a hue in 0.0..1.0
+fn hsv_to_rgb(hue: f32) -> RGB {
+    let h = hue * 6.0;
+    let x = 1.0 - fabsf(h % 2.0 - 1.0);
+    let (r, g, b) = match floorf(h) as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    RGB::new((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+}
+
+pub struct HeatmapGame {
+    state: GameState,
+    field: HeatField,
+    revealed: [[bool; GRID_HEIGHT]; GRID_WIDTH],
+    positions: [(usize, usize); 2],
+    rounds_won: [u8; 2],
+    rng: CustomRng,
+    current_time: Duration,
+    winner_text: Marquee,
+    events: GameEvents,
+}
+
+snapshot_enum!(GameState {
+    Playing,
+    RoundOver { winner, elapsed },
+    MatchOver(winner),
+});
+snapshot_struct!(HeatField { heat, hottest } check |field| on_grid(field.hottest));
+snapshot_struct!(HeatmapGame {
+    state,
+    field,
+    revealed,
+    positions,
+    rounds_won,
+    rng,
+    current_time,
+    winner_text,
+} skip { events } check |game| {
+    game.positions.iter().all(|&position| on_grid(position))
+        && game.rounds_won.iter().all(|&rounds| rounds <= ROUNDS_TO_WIN)
+});
+
+impl HeatmapGame {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = CustomRng::seed_from_u64(seed);
+        let field = HeatField::generate(&mut rng);
+        let mut game = Self {
+            state: GameState::Playing,
+            field,
+            revealed: [[false; GRID_HEIGHT]; GRID_WIDTH],
+            positions: [(0, 0); 2],
+            rounds_won: [0; 2],
+            rng,
+            current_time: Duration::ZERO,
+            winner_text: Marquee::new("", (GRID_HEIGHT - GLYPH_HEIGHT) / 2, RGB::default()),
+            events: GameEvents::new(),
+        };
+        game.place_players();
+        game
+    }
+
+    // Both players start on the same revealed square, away from the hottest
+    fn place_players(&mut self) {
+        let min_distance = (GRID_WIDTH + GRID_HEIGHT) / 4;
+        let start = loop {
+            let x = self.rng.gen_range(0, GRID_WIDTH as u32) as usize;
+            let y = self.rng.gen_range(0, GRID_HEIGHT as u32) as usize;
+            let (hx, hy) = self.field.hottest;
+            if x.abs_diff(hx) + y.abs_diff(hy) >= min_distance {
+                break (x, y);
+            }
+        };
+        self.revealed = [[false; GRID_HEIGHT]; GRID_WIDTH];
+        self.revealed[start.0][start.1] = true;
+        self.positions = [start; 2];
+    }
+
+    fn start_round(&mut self) {
+        self.field = HeatField::generate(&mut self.rng);
+        self.place_players();
+        self.state = GameState::Playing;
+    }
+
+    fn reset_match(&mut self) {
+        self.rounds_won = [0; 2];
+        self.start_round();
+    }
+
+    fn move_player(&mut self, player: Player, command_type: CommandType) {
+        let index = player_index(player);
+        let (x, y) = self.positions[index];
+        let (x, y) = match command_type {
+            CommandType::Left => (x.saturating_sub(1), y),
+            CommandType::Right => ((x + 1).min(GRID_WIDTH - 1), y),
+            CommandType::Down => (x, y.saturating_sub(1)),
+            CommandType::Up => (x, (y + 1).min(GRID_HEIGHT - 1)),
+            _ => return,
+        };
+        self.positions[index] = (x, y);
+        self.revealed[x][y] = true;
+
+        if (x, y) == self.field.hottest {
+            self.rounds_won[index] += 1;
+            self.events.push(GameEvent::Scored(player));
+            self.state = GameState::RoundOver {
+                winner: player,
+                elapsed: Duration::ZERO,
+            };
+        }
+    }
+
+    fn finish_round(&mut self, winner: Player) {
+        if self.rounds_won[player_index(winner)] >= ROUNDS_TO_WIN {
+            let name = match winner {
+                Player::Player1 => "P1 WINS",
+                _ => "P2 WINS",
+            };
+            self.winner_text = Marquee::new(
+                name,
+                (GRID_HEIGHT - GLYPH_HEIGHT) / 2,
+                PLAYER_COLORS[player_index(winner)],
+            );
+            self.state = GameState::MatchOver(winner);
+        } else {
+            self.start_round();
+        }
+    }
+
+    fn render_field(&self, render_board: &mut RenderBoard, reveal_all: bool) {
+        for x in 0..GRID_WIDTH {
+            for y in 0..GRID_HEIGHT {
+                if reveal_all || self.revealed[x][y] {
+                    render_board.set(x, y, self.field.color(x, y));
+                }
+            }
+        }
+    }
+}
+
+fn player_index(player: Player) -> usize {
+    match player {
+        Player::Player1 => 0,
+        _ => 1,
+    }
+}
+
+impl Game for HeatmapGame {
+    fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
+        if input_command.button_state != ButtonState::Pressed {
+            return Ok(());
+        }
+        match (&self.state, input_command.command_type) {
+            (GameState::Playing, command_type) => {
+                self.move_player(input_command.player, command_type);
+            }
+            (GameState::MatchOver(_), CommandType::Select) => self.reset_match(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
+        self.current_time += delta_time;
+        match &mut self.state {
+            GameState::Playing => {}
+            GameState::RoundOver { winner, elapsed } => {
+                *elapsed += delta_time;
+                if *elapsed >= REVEAL_DURATION + SCORE_DURATION {
+                    let winner = *winner;
+                    self.finish_round(winner);
+                }
+            }
+            GameState::MatchOver(_) => self.winner_text.update(delta_time),
+        }
+        Ok(())
+    }
+
+    fn render(&self) -> Result<RenderBoard, GameError> {
+        let mut render_board = RenderBoard::new();
+
+        match &self.state {
+            GameState::Playing => {
+                self.render_field(&mut render_board, false);
+                // Blink the players, taking turns if they share a square
+                let phase =
+                    (self.current_time.as_millis() / CURSOR_BLINK_SPEED.as_millis()) as usize;
+                let shared = self.positions[0] == self.positions[1];
+                for (i, &(x, y)) in self.positions.iter().enumerate() {
+                    let visible = if shared {
+                        phase % 4 == i * 2
+                    } else {
+                        phase.is_multiple_of(2)
+                    };
+                    if visible {
+                        render_board.set(x, y, PLAYER_COLORS[i]);
+                    }
+                }
+            }
+            GameState::RoundOver { winner, elapsed } if *elapsed < REVEAL_DURATION => {
+                self.render_field(&mut render_board, true);
+                let (x, y) = self.field.hottest;
+                render_board.set(x, y, PLAYER_COLORS[player_index(*winner)]);
+            }
+            GameState::RoundOver { .. } => {
+                // Round counts are single digits in a best of 5
+                let score = [b'0' + self.rounds_won[0], b'-', b'0' + self.rounds_won[1]];
+                draw_text_centered(
+                    &mut render_board,
+                    core::str::from_utf8(&score).unwrap_or_default(),
+                    (GRID_HEIGHT - GLYPH_HEIGHT) / 2,
+                    RGB::new(255, 255, 255),
+                );
+            }
+            GameState::MatchOver(_) => {
+                self.winner_text.render(&mut render_board);
+            }
+        }
+
+        Ok(render_board)
+    }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Playing | GameState::RoundOver { .. } => GameStatus::playing(),
+            GameState::MatchOver(winner) => GameStatus::game_over(Some(winner)),
+        };
+        for (score, rounds) in status.scores.iter_mut().zip(self.rounds_won) {
+            *score = Some(rounds as u32);
+        }
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(command_type: CommandType, player: Player) -> GameCommand {
+        GameCommand::new(command_type, ButtonState::Pressed, player)
+    }
+
+    // Walks a player to the hottest square, one step at a time
+    fn walk_to_hottest(game: &mut HeatmapGame, player: Player) {
+        while game.state == GameState::Playing {
+            let (x, y) = game.positions[player_index(player)];
+            let (hx, hy) = game.field.hottest;
+            let command_type = if x < hx {
+                CommandType::Right
+            } else if x > hx {
+                CommandType::Left
+            } else if y < hy {
+                CommandType::Up
+            } else {
+                CommandType::Down
+            };
+            game.process_input(press(command_type, player)).unwrap();
+        }
+    }
+
+    fn finish_round_over(game: &mut HeatmapGame) {
+        game.update(REVEAL_DURATION + SCORE_DURATION).unwrap();
+    }
+
+    #[test]
+    fn test_field_is_normalized() {
+        for seed in 0..20 {
+            let game = HeatmapGame::new(seed);
+            let (hx, hy) = game.field.hottest;
+            assert_eq!(game.field.heat[hx][hy], 1.0);
+            let cells = game.field.heat.iter().flatten();
+            assert!(cells.clone().all(|&h| (0.0..=1.0).contains(&h)));
+            assert!(cells.clone().any(|&h| h == 0.0));
+            assert_eq!(game.field.color(hx, hy), RGB::new(255, 0, 0));
+        }
+    }
+
+    #[test]
+    fn test_hsv_to_rgb() {
+        assert_eq!(hsv_to_rgb(0.0), RGB::new(255, 0, 0));
+        assert_eq!(hsv_to_rgb(1.0 / 3.0), RGB::new(0, 255, 0));
+        assert_eq!(hsv_to_rgb(2.0 / 3.0), RGB::new(0, 0, 255));
+    }
+
+    #[test]
+    fn test_players_start_on_single_revealed_square() {
+        let game = HeatmapGame::new(5);
+        let start = game.positions[0];
+        assert_eq!(game.positions[1], start);
+        assert_ne!(start, game.field.hottest);
+        let revealed = game.revealed.iter().flatten().filter(|&&r| r).count();
+        assert_eq!(revealed, 1);
+        assert!(game.revealed[start.0][start.1]);
+    }
+
+    #[test]
+    fn test_moving_reveals_squares() {
+        let mut game = HeatmapGame::new(1);
+        let (x, y) = game.positions[1];
+        let command_type = if x > 0 {
+            CommandType::Left
+        } else {
+            CommandType::Right
+        };
+        game.process_input(press(command_type, Player::Player2))
+            .unwrap();
+        let moved = game.positions[1];
+        assert_eq!(moved.1, y);
+        assert_eq!(moved.0.abs_diff(x), 1);
+        assert!(game.revealed[moved.0][moved.1]);
+        assert_eq!(game.positions[0], (x, y));
+    }
+
+    #[test]
+    fn test_first_to_hottest_wins_round() {
+        let mut game = HeatmapGame::new(2);
+        walk_to_hottest(&mut game, Player::Player2);
+        assert_eq!(
+            game.state,
+            GameState::RoundOver {
+                winner: Player::Player2,
+                elapsed: Duration::ZERO
+            }
+        );
+        assert_eq!(game.rounds_won, [0, 1]);
+
+        // Moves are ignored until the next round starts
+        let position = game.positions[0];
+        game.process_input(press(CommandType::Up, Player::Player1))
+            .unwrap();
+        assert_eq!(game.positions[0], position);
+
+        finish_round_over(&mut game);
+        assert_eq!(game.state, GameState::Playing);
+        assert_eq!(game.revealed.iter().flatten().filter(|&&r| r).count(), 1);
+    }
+
+    #[test]
+    fn test_best_of_five_match() {
+        let mut game = HeatmapGame::new(3);
+        for round in 0..ROUNDS_TO_WIN {
+            walk_to_hottest(&mut game, Player::Player1);
+            assert_eq!(game.rounds_won, [round + 1, 0]);
+            finish_round_over(&mut game);
+        }
+        assert_eq!(game.state, GameState::MatchOver(Player::Player1));
+        assert_eq!(game.winner_text.text(), "P1 WINS");
+
+        game.process_input(press(CommandType::Select, Player::Player2))
+            .unwrap();
+        assert_eq!(game.state, GameState::Playing);
+        assert_eq!(game.rounds_won, [0, 0]);
+    }
+}
@@ -1,17 +1,35 @@
 use crate::{
     GameError, Player as GamePlayer,
     animation::Animation,
-    game::{ButtonState, CommandType, Game, GameCommand, RGB, RenderBoard},
+    game::{
+        ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus,
+        MAX_PLAYERS, RGB, RenderBoard,
+    },
 };
 
 use crate::random::CustomRng;
+use crate::snapshot::{on_grid, snapshot_enum, snapshot_struct};
 use core::time::Duration;
 use libm::sqrt;
 use smallvec::SmallVec;
 
 const VICTORY_ANIMATION_DURATION: Duration = Duration::from_secs(5);
 const VICTORY_ANIMATION_SPEED: Duration = Duration::from_millis(100);
-const GRID_SIZE: usize = 12;
+// Green, blue, yellow and magenta
+const PLAYER_COLORS: [RGB; MAX_PLAYERS] = [
+    RGB { r: 0, g: 255, b: 0 },
+    RGB { r: 0, g: 0, b: 255 },
+    RGB {
+        r: 255,
+        g: 255,
+        b: 0,
+    },
+    RGB {
+        r: 255,
+        g: 0,
+        b: 255,
+    },
+];
 
 struct Player {
     position: (usize, usize),
@@ -28,13 +46,30 @@ pub struct MazeGame {
     board: MazeBoard,
     state: MazeGameState,
     mode: MazeGameMode,
-    players: SmallVec<[Player; 2]>,
+    players: SmallVec<[Player; MAX_PLAYERS]>,
     exit_pos: (usize, usize),
     victory_animation: Animation,
     current_time: Duration,
     winning_player: Option<usize>,
 }
 
+snapshot_struct!(Player { position, color });
+snapshot_struct!(MazeGame {
+    board,
+    state,
+    mode,
+    players,
+    exit_pos,
+    victory_animation,
+    current_time,
+    winning_player,
+} check |game| {
+    game.players.len() <= MAX_PLAYERS
+        && game.players.iter().all(|player| on_grid(player.position))
+        && on_grid(game.exit_pos)
+        && game.winning_player.is_none_or(|index| index < game.players.len())
+});
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum MazeGameState {
     Playing,
@@ -56,20 +91,37 @@ enum MazeTile {
 }
 
 struct MazeBoard {
-    tiles: [[MazeTile; GRID_SIZE]; GRID_SIZE],
+    tiles: [[MazeTile; GRID_HEIGHT]; GRID_WIDTH],
     seed: u64,
 }
 
+snapshot_enum!(MazeGameState {
+    Playing,
+    Victory(elapsed),
+});
+snapshot_enum!(MazeGameMode {
+    Normal,
+    Multiplayer,
+    FlashLight,
+    FlashLightMultiplayer,
+});
+snapshot_enum!(MazeTile { Empty, Wall });
+snapshot_struct!(MazeBoard { tiles, seed });
+
 impl MazeBoard {
     fn new(seed: u64) -> Self {
-        let mut tiles = [[MazeTile::Wall; GRID_SIZE]; GRID_SIZE];
-        let mut size = GRID_SIZE;
-        if GRID_SIZE.is_multiple_of(2) {
-            size += 1;
+        let mut tiles = [[MazeTile::Wall; GRID_HEIGHT]; GRID_WIDTH];
+        let mut width = GRID_WIDTH;
+        if GRID_WIDTH.is_multiple_of(2) {
+            width += 1;
+        }
+        let mut height = GRID_HEIGHT;
+        if GRID_HEIGHT.is_multiple_of(2) {
+            height += 1;
         }
         let mut rng = CustomRng::seed_from_u64(seed);
         let start_pos = (1, 1);
-        tiles[start_pos.1 as usize][start_pos.0 as usize] = MazeTile::Empty;
+        tiles[start_pos.0 as usize][start_pos.1 as usize] = MazeTile::Empty;
         let mut directions: [(isize, isize); 4] = [(-2, 0), (2, 0), (0, -2), (0, 2)];
         let mut stack = SmallVec::<[(isize, isize); 128]>::new();
 
@@ -85,13 +137,13 @@ impl MazeBoard {
                 let ny = y + dy;
 
                 if 0 < nx
-                    && nx < size as isize
+                    && nx < width as isize
                     && 0 < ny
-                    && ny < size as isize
-                    && tiles[ny as usize][nx as usize] == MazeTile::Wall
+                    && ny < height as isize
+                    && tiles[nx as usize][ny as usize] == MazeTile::Wall
                 {
-                    tiles[ny as usize][nx as usize] = MazeTile::Empty;
-                    tiles[(y + dy / 2) as usize][(x + dx / 2) as usize] = MazeTile::Empty;
+                    tiles[nx as usize][ny as usize] = MazeTile::Empty;
+                    tiles[(x + dx / 2) as usize][(y + dy / 2) as usize] = MazeTile::Empty;
                     stack.push((nx, ny));
                     moved = true;
                     break;
@@ -106,7 +158,7 @@ impl MazeBoard {
 
     fn find_furthest_tile(&self, start_pos: (usize, usize)) -> (usize, usize) {
         let mut stack = SmallVec::<[(usize, usize, usize); 128]>::new();
-        let mut visited = [[false; GRID_SIZE]; GRID_SIZE];
+        let mut visited = [[false; GRID_HEIGHT]; GRID_WIDTH];
         let mut max_distance = 0;
         let mut furthest_tile = start_pos;
 
@@ -123,8 +175,8 @@ impl MazeBoard {
                 let nx = (x as isize + dx) as usize;
                 let ny = (y as isize + dy) as usize;
 
-                if nx < GRID_SIZE
-                    && ny < GRID_SIZE
+                if nx < GRID_WIDTH
+                    && ny < GRID_HEIGHT
                     && self.tiles[nx][ny] == MazeTile::Empty
                     && !visited[nx][ny]
                 {
@@ -139,16 +191,26 @@ impl MazeBoard {
 
 impl MazeGame {
     pub fn new(seed: u64, mode: MazeGameMode) -> Self {
+        Self::with_players(seed, mode, 2)
+    }
+
+    /// The multiplayer modes are for two to four `players`, the others for
+    /// one.
+    pub fn with_players(seed: u64, mode: MazeGameMode, players: usize) -> Self {
         let board = MazeBoard::new(seed);
         let start_pos = (1, 1);
         let exit_pos = board.find_furthest_tile(start_pos);
 
-        let mut players = SmallVec::new();
-        players.push(Player::new(start_pos, RGB::new(0, 255, 0))); // Player 1: Green
-
-        if let MazeGameMode::Multiplayer | MazeGameMode::FlashLightMultiplayer = mode {
-            players.push(Player::new(start_pos, RGB::new(0, 0, 255))); // Player 2: Blue
-        }
+        let count = match mode {
+            MazeGameMode::Normal | MazeGameMode::FlashLight => 1,
+            MazeGameMode::Multiplayer | MazeGameMode::FlashLightMultiplayer => {
+                players.clamp(2, MAX_PLAYERS)
+            }
+        };
+        let players = PLAYER_COLORS[..count]
+            .iter()
+            .map(|&color| Player::new(start_pos, color))
+            .collect();
 
         Self {
             board,
@@ -194,6 +256,20 @@ impl MazeGame {
         )
     }
 
+    // Players on the same tile are shown in a blend of their colors
+    fn render_players(&self, render_board: &mut RenderBoard) {
+        for player in &self.players {
+            let color = self
+                .players
+                .iter()
+                .filter(|other| other.position == player.position)
+                .map(|other| other.color)
+                .reduce(Self::blend_colors)
+                .unwrap_or(player.color);
+            render_board.set(player.position.0, player.position.1, color);
+        }
+    }
+
     fn reset_game(&mut self) {
         self.board = MazeBoard::new(self.board.seed + 1);
         self.state = MazeGameState::Playing;
@@ -221,10 +297,9 @@ impl Game for MazeGame {
                         _ => (0, 0),
                     };
 
-                    let player_index = match (self.mode, input.player) {
-                        (MazeGameMode::Normal | MazeGameMode::FlashLight, _) => 0,
-                        (_, GamePlayer::Player1) => 0,
-                        (_, GamePlayer::Player2) => 1,
+                    let player_index = match self.mode {
+                        MazeGameMode::Normal | MazeGameMode::FlashLight => 0,
+                        _ => input.player.index(),
                     };
 
                     if let Some(player) = self.players.get_mut(player_index) {
@@ -233,9 +308,9 @@ impl Game for MazeGame {
                         let ny = y as isize + dy;
 
                         if nx >= 0
-                            && nx < GRID_SIZE as isize
+                            && nx < GRID_WIDTH as isize
                             && ny >= 0
-                            && ny < GRID_SIZE as isize
+                            && ny < GRID_HEIGHT as isize
                             && self.board.tiles[nx as usize][ny as usize] != MazeTile::Wall
                         {
                             player.position = (nx as usize, ny as usize);
@@ -282,8 +357,8 @@ impl Game for MazeGame {
                 match &self.mode {
                     MazeGameMode::Normal | MazeGameMode::Multiplayer => {
                         // Render maze
-                        for x in 0..GRID_SIZE {
-                            for y in 0..GRID_SIZE {
+                        for x in 0..GRID_WIDTH {
+                            for y in 0..GRID_HEIGHT {
                                 let rgb = match self.board.tiles[x][y] {
                                     MazeTile::Empty => RGB::new(0, 0, 0),
                                     MazeTile::Wall => RGB::new(255, 255, 255),
@@ -301,8 +376,8 @@ impl Game for MazeGame {
                             sqrt((dx * dx + dy * dy) as f64)
                         };
 
-                        for x in 0..GRID_SIZE {
-                            for y in 0..GRID_SIZE {
+                        for x in 0..GRID_WIDTH {
+                            for y in 0..GRID_HEIGHT {
                                 let mut max_intensity = 0;
                                 for player in &self.players {
                                     let d = distance(x, y, player);
@@ -336,26 +411,13 @@ impl Game for MazeGame {
                     }
                 }
 
-                // Render players with blending
-                if self.players.len() == 2 && self.players[0].position == self.players[1].position {
-                    let blended_color =
-                        Self::blend_colors(self.players[0].color, self.players[1].color);
-                    render_board.set(
-                        self.players[0].position.0,
-                        self.players[0].position.1,
-                        blended_color,
-                    );
-                } else {
-                    for player in &self.players {
-                        render_board.set(player.position.0, player.position.1, player.color);
-                    }
-                }
+                self.render_players(&mut render_board);
             }
             MazeGameState::Victory(elapsed_time) => {
                 if let Some(winner_index) = self.winning_player {
                     let winner_color = self.players[winner_index].color;
-                    for row in 0..GRID_SIZE {
-                        for col in 0..GRID_SIZE {
+                    for row in 0..GRID_HEIGHT {
+                        for col in 0..GRID_WIDTH {
                             let color = self.generate_psychedelic_color(
                                 row,
                                 col,
@@ -367,24 +429,24 @@ impl Game for MazeGame {
                     }
                 }
 
-                // Render players with blending
-                if self.players.len() == 2 && self.players[0].position == self.players[1].position {
-                    let blended_color =
-                        Self::blend_colors(self.players[0].color, self.players[1].color);
-                    render_board.set(
-                        self.players[0].position.0,
-                        self.players[0].position.1,
-                        blended_color,
-                    );
-                } else {
-                    for player in &self.players {
-                        render_board.set(player.position.0, player.position.1, player.color);
-                    }
-                }
+                self.render_players(&mut render_board);
             }
         }
         Ok(render_board)
     }
+
+    fn status(&self) -> GameStatus {
+        match self.state {
+            MazeGameState::Playing => GameStatus::playing(),
+            MazeGameState::Victory(_) => {
+                let winner = match self.mode {
+                    MazeGameMode::Normal | MazeGameMode::FlashLight => None,
+                    _ => self.winning_player.and_then(GamePlayer::from_index),
+                };
+                GameStatus::game_over(winner)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -406,8 +468,8 @@ mod tests {
         let board = MazeBoard::new(seed);
         let start_pos = (1, 1);
         let furthest_tile = board.find_furthest_tile(start_pos);
-        assert!(furthest_tile.0 < GRID_SIZE);
-        assert!(furthest_tile.1 < GRID_SIZE);
+        assert!(furthest_tile.0 < GRID_WIDTH);
+        assert!(furthest_tile.1 < GRID_HEIGHT);
     }
 
     #[test]
@@ -415,7 +477,7 @@ mod tests {
         let seed = 42;
         let game = MazeGame::new(seed, MazeGameMode::Normal);
         assert_eq!(game.state, MazeGameState::Playing);
-        assert_eq!(game.player_pos[0], (1, 1));
+        assert_eq!(game.players[0].position, (1, 1));
         assert_ne!(game.exit_pos, (1, 1));
     }
 
@@ -430,14 +492,15 @@ mod tests {
             player: Player::Player1,
         };
 
-        let up_tile = game.board.tiles[1][2];
+        let left_tile = game.board.tiles[0][1];
         game.process_input(left_command).unwrap();
-        if up_tile == MazeTile::Wall {
-            assert_eq!(game.player_pos[0], (1, 1));
+        if left_tile == MazeTile::Wall {
+            assert_eq!(game.players[0].position, (1, 1));
         } else {
-            assert_eq!(game.player_pos[0], (0, 1));
+            assert_eq!(game.players[0].position, (0, 1));
         }
-        assert_eq!(game.player_pos[0], (1, 1));
+        // The outer column is never carved out
+        assert_eq!(game.players[0].position, (1, 1));
     }
 
     #[test]
@@ -445,27 +508,38 @@ mod tests {
         let seed = 42;
         let mut game = MazeGame::new(seed, MazeGameMode::Normal);
 
-        // Simulate reaching the exit
-        game.player_pos[0] = game.exit_pos;
-        let right_command = GameCommand {
-            command_type: CommandType::Right,
+        // Simulate reaching the exit, Select checks it without moving
+        game.players[0].position = game.exit_pos;
+        let select_command = GameCommand {
+            command_type: CommandType::Select,
             button_state: ButtonState::Pressed,
             player: Player::Player1,
         };
 
-        game.process_input(right_command).unwrap();
-        assert_eq!(game.state, MazeGameState::GameOver);
+        game.process_input(select_command).unwrap();
+        assert_eq!(game.state, MazeGameState::Victory(Duration::ZERO));
 
         // Restart game
+        game.update(VICTORY_ANIMATION_DURATION).unwrap();
+        assert_eq!(game.state, MazeGameState::Playing);
+        assert_eq!(game.players[0].position, (1, 1));
+    }
+
+    #[test]
+    fn test_third_player_wins() {
+        let mut game = MazeGame::with_players(42, MazeGameMode::Multiplayer, 3);
+        assert_eq!(game.players.len(), 3);
+
+        game.players[2].position = game.exit_pos;
         let select_command = GameCommand {
             command_type: CommandType::Select,
             button_state: ButtonState::Pressed,
-            player: Player::Player1,
+            player: Player::Player3,
         };
 
         game.process_input(select_command).unwrap();
-        assert_eq!(game.state, MazeGameState::Playing);
-        assert_eq!(game.player_pos[0], (1, 1));
+        assert_eq!(game.winning_player, Some(2));
+        assert_eq!(game.status().winner, Some(Player::Player3));
     }
 
     #[test]
@@ -474,16 +548,16 @@ mod tests {
         let mut game = MazeGame::new(seed, MazeGameMode::Normal);
 
         // Simulate reaching the exit
-        game.player_pos[0] = game.exit_pos;
-        let right_command = GameCommand {
-            command_type: CommandType::Right,
+        game.players[0].position = game.exit_pos;
+        let select_command = GameCommand {
+            command_type: CommandType::Select,
             button_state: ButtonState::Pressed,
             player: Player::Player1,
         };
 
-        game.process_input(right_command).unwrap();
+        game.process_input(select_command).unwrap();
         game.update(Duration::from_millis(100)).unwrap();
 
-        assert!(game.win_animation.get_color().r > 0); // Assuming win_animation changes color over time
+        assert!(game.victory_animation.get_color().r > 0);
     }
 }
@@ -1,5 +1,9 @@
 use crate::RGB;
-use crate::game::{ButtonState, CommandType, Game, GameCommand};
+use crate::bot::BotPlayer;
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    ButtonState, CommandType, Game, GameCommand, GamePhase, GameStatus, MAX_PLAYERS, Player,
+};
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 
@@ -8,219 +12,759 @@ use crate::game::ConnectFour;
 use crate::game::DoodleJump;
 use crate::game::FlappyBird;
 use crate::game::GameMode;
-use crate::game::MultiplayerShooter;
+use crate::game::HeatmapGame;
 use crate::game::PaintGame;
-use crate::game::PongGame;
 use crate::game::SpaceInvaders;
 use crate::game::TetrisGame;
 use crate::game::TicTacToe;
-use crate::game::WallDodger;
+use crate::game::attract::AttractMode;
+use crate::game::fits_grid;
+use crate::game::settings_menu::SettingsMenu;
+use crate::game::{CpuSettings, PongGame};
 use crate::game::{MazeGame, MazeGameMode};
-use crate::game::{SnakeGame, SnakeGameMode};
+use crate::game::{MultiplayerShooter, ShooterBot};
+use crate::game::{SnakeBot, SnakeGame, SnakeGameMode};
+use crate::game::{WallDodger, WallDodgerBot};
 
 use crate::pixel_art;
+use crate::scoreboard::{MAX_GAMES, SCORES_PER_GAME, Scoreboard};
+use crate::settings::DisplaySettings;
+use crate::snapshot::{Reader, Snapshot, SnapshotError, Writer, snapshot_enum, snapshot_struct};
+use crate::text::{GLYPH_HEIGHT, Marquee};
+use core::fmt::Write;
 
-use crate::GRID_SIZE;
+use crate::{GRID_HEIGHT, GRID_WIDTH};
 
-const NUM_GAMES: usize = 19;
+// Lets the game over animation play before the high scores are shown
+const HIGH_SCORE_DELAY: Duration = Duration::from_millis(1500);
 
+// The engine is no_std without an allocator, so the running game
+// is stored inline instead of boxed.
+#[allow(clippy::large_enum_variant)]
 enum MenuState {
     Selecting,
-    RunningGame(GameType),
+    // The game and how many people it was started for
+    RunningGame(GameType, usize),
+    Attract(AttractMode),
+}
+
+snapshot_enum!(MenuState {
+    Selecting,
+    RunningGame(game, players),
+    Attract(demo),
+});
+
+// Scrolls the final score once and then shows the table
+struct HighScoreScreen {
+    score: u32,
+    rank: Option<usize>,
+    delay: Duration,
+    marquee: Marquee,
+}
+
+snapshot_struct!(HighScoreScreen {
+    score,
+    rank,
+    delay,
+    marquee,
+});
+
+impl HighScoreScreen {
+    fn new(score: u32, rank: Option<usize>) -> Self {
+        let (title, color) = match rank {
+            Some(0) => ("NEW RECORD", RGB::new(255, 190, 0)),
+            _ => ("SCORE", RGB::new(255, 255, 255)),
+        };
+        let mut marquee = Marquee::new(title, (GRID_HEIGHT - GLYPH_HEIGHT) / 2, color);
+        let _ = write!(marquee, " {}", score);
+        Self {
+            score,
+            rank,
+            delay: HIGH_SCORE_DELAY,
+            marquee,
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        self.delay.is_zero()
+    }
+
+    fn update(&mut self, delta_time: Duration) {
+        if self.is_visible() {
+            self.marquee.update(delta_time);
+        }
+        self.delay = self.delay.saturating_sub(delta_time);
+    }
 }
 
 pub struct Menu {
     active_game_index: usize,
     state: MenuState,
     current_time: Duration,
+    seed: u64,
+    scoreboard: Scoreboard,
+    scoreboard_changed: bool,
+    // Set once the final score of the current game has been recorded
+    score_recorded: bool,
+    high_score_screen: Option<HighScoreScreen>,
+    events: GameEvents,
+    // Whether the running game was over at the last update
+    game_over: bool,
+    // Two player games with a bot start against it when fewer are connected
+    controllers: usize,
+    // Time without input while selecting, for attract mode
+    idle: Duration,
+    attract_delay: Option<Duration>,
+    // Like the scoreboard the display settings are not part of the save state
+    display_settings: DisplaySettings,
+    display_settings_changed: bool,
+    settings_menu: Option<SettingsMenu>,
+    // Time since local midnight, for scheduled night mode
+    time_of_day: Option<Duration>,
+}
+
+// Starts a game for the given number of players
+type PartyStart = fn(u64, usize) -> GameType;
+
+/// A game that can be picked from the menu.
+struct GameEntry {
+    id: &'static str,
+    name: &'static str,
+    icon: [[u8; 3]; 64],
+    players: usize,
+    max_players: usize,
+    min_grid_size: (usize, usize),
+    start: fn(u64) -> GameType,
+    // Starts the game with a bot in place of the second player
+    solo_start: Option<fn(u64) -> GameType>,
+    // Starts the game for more than `players`, up to `max_players`
+    party_start: Option<PartyStart>,
+}
+
+/// A game in the menu, as listed by `Menu::games`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameInfo {
+    /// Stable name without spaces, for starting the game with
+    /// `Menu::start_game_by_id`
+    pub id: &'static str,
+    pub name: &'static str,
+    pub players: usize,
+    /// More players can join when enough controllers are connected
+    pub max_players: usize,
 }
 
-macro_rules! define_game_type_and_impl {
-    ($($variant:ident($game:ty)),+ $(,)?) => {
-        enum GameTypeInfo {
-            $($variant),+
+type PixelArtImage = [[RGB; 8]; 8];
+
+impl GameEntry {
+    fn fits_grid(&self) -> bool {
+        fits_grid(self.min_grid_size)
+    }
+
+    fn pixel_art(&self) -> PixelArtImage {
+        let image = &self.icon;
+        let mut pixel_art = [[RGB::default(); 8]; 8];
+
+        // Rotate image 90 degrees clockwise
+        // to workaround build.rs limitation
+        for i in 0..8 {
+            for j in (0..8).rev() {
+                let index = i * 8 + j;
+                pixel_art[j][7 - i] = RGB {
+                    r: image[index][0],
+                    g: image[index][1],
+                    b: image[index][2],
+                };
+            }
         }
-        #[allow(dead_code)]
+        pixel_art
+    }
+}
+
+// Declares every game in the menu once, in menu order. Each entry becomes a
+// GameType variant and a GameEntry in GAMES. The position in the list is
+// also the key of the game in the high score table, so new games go last.
+// A `solo` variant is the same game against a bot, it is added after all
+// the others so save states stay compatible. Games with `max_players` are
+// started for more players when more controllers are connected.
+macro_rules! define_games {
+    (@min_grid_size) => { (0, 0) };
+    (@min_grid_size $min_grid_size:expr) => { $min_grid_size };
+    (@solo_start) => { None };
+    (@solo_start $variant:ident $start:expr) => {
+        Some(|seed| GameType::$variant(($start)(seed)))
+    };
+    (@max_players $players:expr) => { $players };
+    (@max_players $players:expr, $max_players:expr) => { $max_players };
+    (@party_start $variant:ident) => { None };
+    (@party_start $variant:ident $start:expr) => {
+        Some(|seed, players| GameType::$variant(($start)(seed, players)))
+    };
+    ($($variant:ident($game:ty) {
+        name: $name:expr,
+        icon: $icon:expr,
+        players: $players:expr,
+        $(max_players: $max_players:expr => $party_start:expr,)?
+        $(min_grid_size: $min_grid_size:expr,)?
+        $(solo: $solo_variant:ident($solo_game:ty) => $solo_start:expr,)?
+        start: $start:expr $(,)?
+    }),+ $(,)?) => {
         enum GameType {
-            $($variant($game)),+
+            $($variant($game),)+
+            $($($solo_variant($solo_game),)?)+
+        }
+
+        impl GameType {
+            #[cfg(test)]
+            fn id(&self) -> &'static str {
+                match self {
+                    $(GameType::$variant(_) => stringify!($variant),)+
+                    $($(GameType::$solo_variant(_) => stringify!($solo_variant),)?)+
+                }
+            }
+
+            // Whether this is the `solo` variant of a game, against a bot
+            #[cfg(test)]
+            fn is_solo(&self) -> bool {
+                match self {
+                    $(GameType::$variant(_) => false,)+
+                    $($(GameType::$solo_variant(_) => true,)?)+
+                }
+            }
         }
 
         impl Game for GameType {
             fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
                 match self {
-                    $(GameType::$variant(game) => game.process_input(input_command)),+
+                    $(GameType::$variant(game) => game.process_input(input_command),)+
+                    $($(GameType::$solo_variant(game) => game.process_input(input_command),)?)+
                 }
             }
 
             fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
                 match self {
-                    $(GameType::$variant(game) => game.update(delta_time)),+
+                    $(GameType::$variant(game) => game.update(delta_time),)+
+                    $($(GameType::$solo_variant(game) => game.update(delta_time),)?)+
                 }
             }
 
             fn render(&self) -> Result<RenderBoard, GameError> {
                 match self {
-                    $(GameType::$variant(game) => game.render()),+
+                    $(GameType::$variant(game) => game.render(),)+
+                    $($(GameType::$solo_variant(game) => game.render(),)?)+
                 }
             }
-        }
-    };
-}
-
-define_game_type_and_impl!(
-    ConnectFour(ConnectFour),
-    TicTacToe(TicTacToe),
-    FlappyBird(FlappyBird),
-    Snake(SnakeGame),
-    SnakeMultiPlayer(SnakeGame),
-    Maze(MazeGame),
-    MazeMultiplayer(MazeGame),
-    MazeFlashLight(MazeGame),
-    MazeFlashLightMultiplayer(MazeGame),
-    SpaceInvaders(SpaceInvaders),
-    SpaceInvadersMultiPlayer(SpaceInvaders),
-    DoodleJump(DoodleJump),
-    Tetris(TetrisGame),
-    MultiplayerShooter(MultiplayerShooter),
-    PongGame(PongGame),
-    ButtonWar(ButtonWar),
-    WallDodger(WallDodger),
-    WallDodgerMultiplayer(WallDodger),
-    PaintGame(PaintGame),
-);
 
-type PixelArtImage = [[RGB; 8]; 8];
+            fn final_score(&self) -> Option<u32> {
+                match self {
+                    $(GameType::$variant(game) => game.final_score(),)+
+                    $($(GameType::$solo_variant(game) => game.final_score(),)?)+
+                }
+            }
 
-impl GameTypeInfo {
-    fn pixel_art(&self) -> PixelArtImage {
-        let image = match self {
-            GameTypeInfo::ConnectFour => pixel_art::CONNECT_FOUR,
-            GameTypeInfo::TicTacToe => pixel_art::TICTACTOE,
-            GameTypeInfo::FlappyBird => pixel_art::FLAPPY_BIRD,
-            GameTypeInfo::Snake => pixel_art::SNAKE,
-            GameTypeInfo::SnakeMultiPlayer => pixel_art::SNAKE_MULTIPLAYER,
-            GameTypeInfo::Maze => pixel_art::MAZE,
-            GameTypeInfo::MazeMultiplayer => pixel_art::MAZE_MULTIPLAYER,
-            GameTypeInfo::MazeFlashLight => pixel_art::MAZE_FLASHLIGHT,
-            GameTypeInfo::MazeFlashLightMultiplayer => pixel_art::MAZE_FLASHLIGHT_MULTIPLAYER,
-            GameTypeInfo::SpaceInvaders => pixel_art::SPACE_INVADERS,
-            GameTypeInfo::SpaceInvadersMultiPlayer => pixel_art::SPACE_INVADERS_MULTIPLAYER,
-            GameTypeInfo::DoodleJump => pixel_art::DOODLE_JUMP,
-            GameTypeInfo::Tetris => pixel_art::TETRIS,
-            GameTypeInfo::MultiplayerShooter => pixel_art::SHOOTER,
-            GameTypeInfo::PongGame => pixel_art::PONG,
-            GameTypeInfo::ButtonWar => pixel_art::TUGOFWAR,
-            GameTypeInfo::WallDodger => pixel_art::WALLDODGER,
-            GameTypeInfo::WallDodgerMultiplayer => pixel_art::WALLDODGER_MULTIPLAYER,
-            GameTypeInfo::PaintGame => pixel_art::PAINT,
-        };
-        let mut pixel_art = [[RGB::default(); 8]; 8];
+            fn status(&self) -> GameStatus {
+                match self {
+                    $(GameType::$variant(game) => game.status(),)+
+                    $($(GameType::$solo_variant(game) => game.status(),)?)+
+                }
+            }
 
-        // Rotate image 90 degrees clockwise
-        // to workaround build.rs limitation
-        for i in 0..8 {
-            for j in (0..8).rev() {
-                let index = i * 8 + j;
-                pixel_art[j][7 - i] = RGB {
-                    r: image[index][0],
-                    g: image[index][1],
-                    b: image[index][2],
-                };
+            fn drain_events(&mut self) -> GameEvents {
+                match self {
+                    $(GameType::$variant(game) => game.drain_events(),)+
+                    $($(GameType::$solo_variant(game) => game.drain_events(),)?)+
+                }
             }
         }
-        pixel_art
-    }
+
+        // Saved by menu position, like the high scores
+        snapshot_enum!(GameType {
+            $($variant(game),)+
+            $($($solo_variant(game),)?)+
+        });
+
+        const GAMES: &[GameEntry] = &[
+            $(GameEntry {
+                id: stringify!($variant),
+                name: $name,
+                icon: $icon,
+                players: $players,
+                max_players: define_games!(@max_players $players $(, $max_players)?),
+                min_grid_size: define_games!(@min_grid_size $($min_grid_size)?),
+                start: |seed| GameType::$variant(($start)(seed)),
+                solo_start: define_games!(@solo_start $($solo_variant $solo_start)?),
+                party_start: define_games!(@party_start $variant $($party_start)?),
+            }),+
+        ];
+    };
 }
 
+define_games!(
+    ConnectFour(ConnectFour) {
+        name: "Connect Four",
+        icon: pixel_art::CONNECT_FOUR,
+        players: 2,
+        min_grid_size: ConnectFour::MIN_GRID_SIZE,
+        start: |_| ConnectFour::new(),
+    },
+    TicTacToe(TicTacToe) {
+        name: "Tic-Tac-Toe",
+        icon: pixel_art::TICTACTOE,
+        players: 2,
+        min_grid_size: TicTacToe::MIN_GRID_SIZE,
+        start: |_| TicTacToe::new(),
+    },
+    FlappyBird(FlappyBird) {
+        name: "Flappy Bird",
+        icon: pixel_art::FLAPPY_BIRD,
+        players: 1,
+        start: FlappyBird::new,
+    },
+    Snake(SnakeGame) {
+        name: "Snake",
+        icon: pixel_art::SNAKE,
+        players: 1,
+        start: |seed| SnakeGame::new(seed, SnakeGameMode::SinglePlayer),
+    },
+    SnakeMultiPlayer(SnakeGame) {
+        name: "Snake Multiplayer",
+        icon: pixel_art::SNAKE_MULTIPLAYER,
+        players: 2,
+        max_players: 4 => |seed, players| {
+            SnakeGame::with_players(seed, SnakeGameMode::MultiPlayer, players)
+        },
+        solo: SnakeMultiPlayerBot(BotPlayer<SnakeGame, SnakeBot>) => |seed| {
+            let game = SnakeGame::new(seed, SnakeGameMode::MultiPlayer);
+            BotPlayer::new(game, SnakeBot, Player::Player2)
+        },
+        start: |seed| SnakeGame::new(seed, SnakeGameMode::MultiPlayer),
+    },
+    Maze(MazeGame) {
+        name: "Maze",
+        icon: pixel_art::MAZE,
+        players: 1,
+        start: |seed| MazeGame::new(seed, MazeGameMode::Normal),
+    },
+    MazeMultiplayer(MazeGame) {
+        name: "Maze Multiplayer",
+        icon: pixel_art::MAZE_MULTIPLAYER,
+        players: 2,
+        max_players: 4 => |seed, players| {
+            MazeGame::with_players(seed, MazeGameMode::Multiplayer, players)
+        },
+        start: |seed| MazeGame::new(seed, MazeGameMode::Multiplayer),
+    },
+    MazeFlashLight(MazeGame) {
+        name: "Flashlight Maze",
+        icon: pixel_art::MAZE_FLASHLIGHT,
+        players: 1,
+        start: |seed| MazeGame::new(seed, MazeGameMode::FlashLight),
+    },
+    MazeFlashLightMultiplayer(MazeGame) {
+        name: "Flashlight Maze Multiplayer",
+        icon: pixel_art::MAZE_FLASHLIGHT_MULTIPLAYER,
+        players: 2,
+        max_players: 4 => |seed, players| {
+            MazeGame::with_players(seed, MazeGameMode::FlashLightMultiplayer, players)
+        },
+        start: |seed| MazeGame::new(seed, MazeGameMode::FlashLightMultiplayer),
+    },
+    SpaceInvaders(SpaceInvaders) {
+        name: "Space Invaders",
+        icon: pixel_art::SPACE_INVADERS,
+        players: 1,
+        start: |seed| SpaceInvaders::new(seed, true, 2, GameMode::SinglePlayer),
+    },
+    SpaceInvadersMultiPlayer(SpaceInvaders) {
+        name: "Space Invaders Multiplayer",
+        icon: pixel_art::SPACE_INVADERS_MULTIPLAYER,
+        players: 2,
+        start: |seed| SpaceInvaders::new(seed, false, 4, GameMode::MultiPlayer),
+    },
+    DoodleJump(DoodleJump) {
+        name: "Doodle Jump",
+        icon: pixel_art::DOODLE_JUMP,
+        players: 1,
+        start: DoodleJump::new,
+    },
+    Tetris(TetrisGame) {
+        name: "Tetris",
+        icon: pixel_art::TETRIS,
+        players: 1,
+        start: TetrisGame::new,
+    },
+    MultiplayerShooter(MultiplayerShooter) {
+        name: "Shooter",
+        icon: pixel_art::SHOOTER,
+        players: 2,
+        max_players: 4 => |seed, players| MultiplayerShooter::with_players(seed, 10, players),
+        solo: MultiplayerShooterBot(BotPlayer<MultiplayerShooter, ShooterBot>) => |seed| {
+            let game = MultiplayerShooter::new(seed, 10);
+            BotPlayer::new(game, ShooterBot::new(), Player::Player2)
+        },
+        start: |seed| MultiplayerShooter::new(seed, 10),
+    },
+    PongGame(PongGame) {
+        name: "Pong",
+        icon: pixel_art::PONG,
+        players: 2,
+        start: PongGame::new,
+    },
+    ButtonWar(ButtonWar) {
+        name: "Button War",
+        icon: pixel_art::TUGOFWAR,
+        players: 2,
+        max_players: 4 => |_, players| ButtonWar::with_players(players),
+        start: |_| ButtonWar::new(),
+    },
+    WallDodger(WallDodger) {
+        name: "Wall Dodger",
+        icon: pixel_art::WALLDODGER,
+        players: 1,
+        start: |seed| WallDodger::new(seed, false),
+    },
+    WallDodgerMultiplayer(WallDodger) {
+        name: "Wall Dodger Multiplayer",
+        icon: pixel_art::WALLDODGER_MULTIPLAYER,
+        players: 2,
+        solo: WallDodgerMultiplayerBot(BotPlayer<WallDodger, WallDodgerBot>) => |seed| {
+            let game = WallDodger::new(seed, true);
+            BotPlayer::new(game, WallDodgerBot::new(), Player::Player2)
+        },
+        start: |seed| WallDodger::new(seed, true),
+    },
+    PaintGame(PaintGame) {
+        name: "Paint",
+        icon: pixel_art::PAINT,
+        players: 1,
+        start: |_| PaintGame::new(),
+    },
+    HottestSpot(HeatmapGame) {
+        name: "Hottest Spot",
+        icon: pixel_art::HEATMAP,
+        players: 2,
+        start: HeatmapGame::new,
+    },
+    ConnectFourComputer(ConnectFour) {
+        name: "Connect Four vs CPU",
+        icon: pixel_art::CONNECT_FOUR_COMPUTER,
+        players: 1,
+        min_grid_size: ConnectFour::MIN_GRID_SIZE,
+        start: |_| ConnectFour::with_computer(),
+    },
+    TicTacToeComputer(TicTacToe) {
+        name: "Tic-Tac-Toe vs CPU",
+        icon: pixel_art::TICTACTOE_COMPUTER,
+        players: 1,
+        min_grid_size: TicTacToe::MIN_GRID_SIZE,
+        start: TicTacToe::with_computer,
+    },
+    PongComputer(PongGame) {
+        name: "Pong vs CPU",
+        icon: pixel_art::PONG_COMPUTER,
+        players: 1,
+        start: |seed| PongGame::with_cpus(seed, [None, Some(CpuSettings::default())]),
+    },
+);
+
+const NUM_GAMES: usize = GAMES.len();
+const _: () = assert!(NUM_GAMES <= MAX_GAMES);
+
 impl Menu {
     pub fn new() -> Self {
-        Self {
+        Self::with_seed(0)
+    }
+
+    // Games are seeded from the time spent in the menu offset by this seed,
+    // so a session is reproducible from the seed and the stream of updates.
+    pub fn with_seed(seed: u64) -> Self {
+        let mut menu = Self {
             active_game_index: 0,
             state: MenuState::Selecting,
             current_time: Duration::from_millis(0),
+            seed,
+            scoreboard: Scoreboard::new(),
+            scoreboard_changed: false,
+            score_recorded: false,
+            high_score_screen: None,
+            events: GameEvents::new(),
+            game_over: false,
+            controllers: 2,
+            idle: Duration::ZERO,
+            attract_delay: None,
+            display_settings: DisplaySettings::new(),
+            display_settings_changed: false,
+            settings_menu: None,
+            time_of_day: None,
+        };
+        if !menu.active_game().fits_grid() {
+            menu.cycle_right();
         }
+        menu
     }
 
+    // Games that do not fit on the configured grid are skipped
     fn cycle_left(&mut self) {
-        self.active_game_index = (self.active_game_index + NUM_GAMES - 1) % NUM_GAMES;
+        loop {
+            self.active_game_index = (self.active_game_index + NUM_GAMES - 1) % NUM_GAMES;
+            if self.active_game().fits_grid() {
+                break;
+            }
+        }
     }
 
     fn cycle_right(&mut self) {
-        self.active_game_index = (self.active_game_index + 1) % NUM_GAMES;
-    }
-
-    fn get_game_type_from_index(&self) -> GameTypeInfo {
-        match self.active_game_index {
-            0 => GameTypeInfo::ConnectFour,
-            1 => GameTypeInfo::TicTacToe,
-            2 => GameTypeInfo::FlappyBird,
-            3 => GameTypeInfo::Snake,
-            4 => GameTypeInfo::SnakeMultiPlayer,
-            5 => GameTypeInfo::Maze,
-            6 => GameTypeInfo::MazeMultiplayer,
-            7 => GameTypeInfo::MazeFlashLight,
-            8 => GameTypeInfo::MazeFlashLightMultiplayer,
-            9 => GameTypeInfo::SpaceInvaders,
-            10 => GameTypeInfo::SpaceInvadersMultiPlayer,
-            11 => GameTypeInfo::DoodleJump,
-            12 => GameTypeInfo::Tetris,
-            13 => GameTypeInfo::MultiplayerShooter,
-            14 => GameTypeInfo::PongGame,
-            15 => GameTypeInfo::ButtonWar,
-            16 => GameTypeInfo::WallDodger,
-            17 => GameTypeInfo::WallDodgerMultiplayer,
-            18 => GameTypeInfo::PaintGame,
-            _ => unreachable!(),
+        loop {
+            self.active_game_index = (self.active_game_index + 1) % NUM_GAMES;
+            if self.active_game().fits_grid() {
+                break;
+            }
         }
     }
 
+    fn active_game(&self) -> &'static GameEntry {
+        &GAMES[self.active_game_index]
+    }
+
     fn pixel_art(&self) -> PixelArtImage {
-        self.get_game_type_from_index().pixel_art()
+        self.active_game().pixel_art()
+    }
+
+    /// Sets how many controllers are connected. With fewer than a game
+    /// needs, games that have a bot are started against it, and games for
+    /// more players are started for everyone connected. Like the high
+    /// scores the count is not part of the save state, replays record it.
+    pub fn set_controllers(&mut self, count: usize) {
+        self.controllers = count;
+    }
+
+    /// Starts attract mode after the menu has been idle for `delay`, or
+    /// never with `None`. Not part of the save state, replays record it.
+    pub fn set_attract_delay(&mut self, delay: Option<Duration>) {
+        self.attract_delay = delay;
+    }
+
+    // The bot version of the active game, if that is what Select starts
+    fn solo_start(&self) -> Option<fn(u64) -> GameType> {
+        let entry = self.active_game();
+        entry
+            .solo_start
+            .filter(|_| self.controllers < entry.players)
+    }
+
+    // The active game for everyone connected, if more than its usual number
+    // of players can join
+    fn party_start(&self) -> Option<(PartyStart, usize)> {
+        let entry = self.active_game();
+        let players = self.controllers.min(entry.max_players);
+        entry
+            .party_start
+            .filter(|_| players > entry.players)
+            .map(|start| (start, players))
     }
 
     fn start_game(&mut self) {
-        let game_type = self.get_game_type_from_index();
-        let seed = self.current_time.as_millis() as u64;
-        let game = match game_type {
-            GameTypeInfo::ConnectFour => GameType::ConnectFour(ConnectFour::new()),
-            GameTypeInfo::TicTacToe => GameType::TicTacToe(TicTacToe::new()),
-            GameTypeInfo::FlappyBird => GameType::FlappyBird(FlappyBird::new(seed)),
-            GameTypeInfo::Snake => {
-                GameType::Snake(SnakeGame::new(seed, SnakeGameMode::SinglePlayer))
-            }
-            GameTypeInfo::SnakeMultiPlayer => {
-                GameType::Snake(SnakeGame::new(seed, SnakeGameMode::MultiPlayer))
-            }
-            GameTypeInfo::Maze => GameType::Maze(MazeGame::new(seed, MazeGameMode::Normal)),
-            GameTypeInfo::MazeMultiplayer => {
-                GameType::MazeMultiplayer(MazeGame::new(seed, MazeGameMode::Multiplayer))
-            }
-            GameTypeInfo::MazeFlashLight => {
-                GameType::Maze(MazeGame::new(seed, MazeGameMode::FlashLight))
-            }
-            GameTypeInfo::MazeFlashLightMultiplayer => {
-                GameType::Maze(MazeGame::new(seed, MazeGameMode::FlashLightMultiplayer))
-            }
-            GameTypeInfo::SpaceInvaders => {
-                GameType::SpaceInvaders(SpaceInvaders::new(seed, true, 2, GameMode::SinglePlayer))
+        let seed = self.seed.wrapping_add(self.current_time.as_millis() as u64);
+        let (game, players) = match (self.solo_start(), self.party_start()) {
+            (Some(start), _) => (start(seed), 1),
+            (None, Some((start, players))) => (start(seed, players), players),
+            (None, None) => ((self.active_game().start)(seed), self.active_game().players),
+        };
+        self.state = MenuState::RunningGame(game, players);
+        self.score_recorded = false;
+        self.high_score_screen = None;
+        self.game_over = false;
+        self.events.push(GameEvent::GameStarted);
+    }
+
+    /// The games in the menu that fit the grid, in menu order.
+    pub fn games() -> impl Iterator<Item = GameInfo> {
+        GAMES
+            .iter()
+            .filter(|entry| entry.fits_grid())
+            .map(|entry| GameInfo {
+                id: entry.id,
+                name: entry.name,
+                players: entry.players,
+                max_players: entry.max_players,
+            })
+    }
+
+    /// Starts the game with `id` as if it was picked in the menu, quitting
+    /// the running game. Returns false if there is no such game.
+    pub fn start_game_by_id(&mut self, id: &str) -> bool {
+        let Some(index) = GAMES
+            .iter()
+            .position(|entry| entry.id == id && entry.fits_grid())
+        else {
+            return false;
+        };
+        if let MenuState::RunningGame(_, _) = self.state {
+            self.events.push(GameEvent::GameQuit);
+        }
+        self.active_game_index = index;
+        self.idle = Duration::ZERO;
+        self.start_game();
+        true
+    }
+
+    #[cfg(test)]
+    pub(crate) fn game_count() -> usize {
+        NUM_GAMES
+    }
+
+    // The scoreboard is not part of the save state
+    pub(crate) fn save_snapshot(&self, w: &mut Writer) {
+        self.active_game_index.save(w);
+        self.state.save(w);
+        self.current_time.save(w);
+        self.seed.save(w);
+        self.score_recorded.save(w);
+        self.high_score_screen.save(w);
+        self.game_over.save(w);
+    }
+
+    /// Replaces everything except the high scores with the rest of the
+    /// save state. Nothing changes if the state can't be read.
+    pub(crate) fn restore_snapshot(&mut self, mut r: Reader) -> Result<(), SnapshotError> {
+        let active_game_index = usize::restore(&mut r)?;
+        if active_game_index >= NUM_GAMES {
+            return Err(SnapshotError::InvalidData);
+        }
+        let state = MenuState::restore(&mut r)?;
+        if matches!(state, MenuState::RunningGame(_, players) if !(1..=MAX_PLAYERS).contains(&players))
+        {
+            return Err(SnapshotError::InvalidData);
+        }
+        let current_time = Duration::restore(&mut r)?;
+        let seed = u64::restore(&mut r)?;
+        let score_recorded = bool::restore(&mut r)?;
+        let high_score_screen = Option::restore(&mut r)?;
+        let game_over = bool::restore(&mut r)?;
+        r.finish()?;
+
+        self.active_game_index = active_game_index;
+        self.state = state;
+        self.current_time = current_time;
+        self.seed = seed;
+        self.score_recorded = score_recorded;
+        self.high_score_screen = high_score_screen;
+        self.game_over = game_over;
+        self.close_settings_menu();
+        self.events.clear();
+        Ok(())
+    }
+
+    pub fn scoreboard(&self) -> &Scoreboard {
+        &self.scoreboard
+    }
+
+    pub fn set_scoreboard(&mut self, scoreboard: Scoreboard) {
+        self.scoreboard = scoreboard;
+        self.scoreboard_changed = false;
+    }
+
+    pub fn scoreboard_changed(&self) -> bool {
+        self.scoreboard_changed
+    }
+
+    pub fn mark_scoreboard_saved(&mut self) {
+        self.scoreboard_changed = false;
+    }
+
+    pub fn display_settings(&self) -> &DisplaySettings {
+        &self.display_settings
+    }
+
+    pub fn set_display_settings(&mut self, display_settings: DisplaySettings) {
+        self.display_settings = display_settings;
+        self.display_settings_changed = false;
+    }
+
+    /// Whether the settings were changed from the menu since they were
+    /// last set or saved.
+    pub fn display_settings_changed(&self) -> bool {
+        self.display_settings_changed
+    }
+
+    pub fn mark_display_settings_saved(&mut self) {
+        self.display_settings_changed = false;
+    }
+
+    // Settings are only stored when the overlay closes, so trying out a
+    // few brightness levels costs a single write
+    fn close_settings_menu(&mut self) {
+        if let Some(settings_menu) = self.settings_menu.take() {
+            self.display_settings_changed |= settings_menu.changed(&self.display_settings);
+        }
+    }
+
+    /// Sets the time since local midnight, `None` if the frontend has no
+    /// clock. Scheduled night mode can only be picked with a clock.
+    pub fn set_time_of_day(&mut self, time_of_day: Option<Duration>) {
+        self.time_of_day = time_of_day;
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.display_settings.is_night(self.time_of_day)
+    }
+
+    fn record_final_score(&mut self, final_score: Option<u32>) {
+        match final_score {
+            Some(score) if !self.score_recorded => {
+                let rank = self.scoreboard.insert(self.active_game_index, score);
+                self.scoreboard_changed |= rank.is_some();
+                if rank == Some(0) {
+                    self.events.push(GameEvent::NewHighScore);
+                }
+                self.score_recorded = true;
+                self.high_score_screen = Some(HighScoreScreen::new(score, rank));
             }
-            GameTypeInfo::SpaceInvadersMultiPlayer => {
-                GameType::SpaceInvaders(SpaceInvaders::new(seed, false, 4, GameMode::MultiPlayer))
+            Some(_) => {}
+            // The game has been restarted
+            None => {
+                self.score_recorded = false;
+                self.high_score_screen = None;
             }
-            GameTypeInfo::DoodleJump => GameType::DoodleJump(DoodleJump::new(seed)),
-            GameTypeInfo::Tetris => GameType::Tetris(TetrisGame::new(seed)),
-            GameTypeInfo::MultiplayerShooter => {
-                GameType::MultiplayerShooter(MultiplayerShooter::new(seed, 10))
+        }
+    }
+
+    // One bar per table entry scaled to the best score. The new score is
+    // highlighted, or shown in red on the bottom row if it did not make it.
+    fn render_high_scores(&self, screen: &HighScoreScreen) -> RenderBoard {
+        let mut render_board = RenderBoard::new();
+        if screen.marquee.passes() == 0 {
+            screen.marquee.render(&mut render_board);
+            return render_board;
+        }
+
+        let scores = self.scoreboard.scores(self.active_game_index);
+        let best = scores[0].max(screen.score).max(1);
+        let bar_length = |score: u32| {
+            let length = (score as usize * GRID_WIDTH).div_ceil(best as usize);
+            length.clamp(1, GRID_WIDTH)
+        };
+        let spacing = GRID_HEIGHT / SCORES_PER_GAME;
+
+        for (rank, &score) in scores.iter().enumerate().filter(|(_, s)| **s > 0) {
+            let color = if screen.rank == Some(rank) {
+                RGB::new(255, 255, 255)
+            } else if rank == 0 {
+                RGB::new(255, 190, 0)
+            } else {
+                RGB::new(0, 90, 200)
+            };
+            let row = GRID_HEIGHT - 1 - rank * spacing;
+            for col in 0..bar_length(score) {
+                render_board.set(col, row, color);
             }
-            GameTypeInfo::PongGame => GameType::PongGame(PongGame::new(seed)),
-            GameTypeInfo::ButtonWar => GameType::ButtonWar(ButtonWar::new()),
-            GameTypeInfo::WallDodger => GameType::WallDodger(WallDodger::new(seed, false)),
-            GameTypeInfo::WallDodgerMultiplayer => {
-                GameType::WallDodgerMultiplayer(WallDodger::new(seed, true))
+        }
+
+        if screen.rank.is_none() && screen.score > 0 {
+            for col in 0..bar_length(screen.score) {
+                render_board.set(col, 0, RGB::new(200, 0, 0));
             }
-            GameTypeInfo::PaintGame => GameType::PaintGame(PaintGame::new()),
-        };
-        self.state = MenuState::RunningGame(game);
+        }
+        render_board
     }
 }
 
@@ -234,27 +778,81 @@ impl Game for Menu {
     fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
         match &mut self.state {
             MenuState::Selecting => {
+                self.idle = Duration::ZERO;
+                if let Some(settings_menu) = &mut self.settings_menu {
+                    let open = settings_menu.process_input(
+                        input_command,
+                        &mut self.display_settings,
+                        self.time_of_day.is_some(),
+                        &mut self.events,
+                    );
+                    if !open {
+                        self.close_settings_menu();
+                    }
+                    return Ok(());
+                }
                 if let ButtonState::Pressed = input_command.button_state {
                     match input_command.command_type {
                         CommandType::Left => {
                             self.cycle_left();
+                            self.events.push(GameEvent::MenuMoved);
                         }
                         CommandType::Right => {
                             self.cycle_right();
+                            self.events.push(GameEvent::MenuMoved);
                         }
                         CommandType::Select => {
                             self.start_game();
                         }
+                        CommandType::Pause => {
+                            self.settings_menu = Some(SettingsMenu::new(self.display_settings));
+                            self.events.push(GameEvent::MenuMoved);
+                        }
                         _ => {}
                     }
                 }
             }
-            MenuState::RunningGame(game_state) => {
-                game_state.process_input(input_command)?;
+            MenuState::RunningGame(game_state, players) => {
+                // Any button dismisses the high scores and returns to the game
+                if self
+                    .high_score_screen
+                    .as_ref()
+                    .is_some_and(HighScoreScreen::is_visible)
+                {
+                    if let ButtonState::Pressed = input_command.button_state {
+                        self.high_score_screen = None;
+                    }
+                    return Ok(());
+                }
+                // Games for one person, alone or against the computer, take
+                // input from every controller as the first player.
+                // Multiplayer games only from the players they were started
+                // for. Anyone can quit.
+                let player = if *players == 1 {
+                    Some(Player::Player1)
+                } else {
+                    Some(input_command.player).filter(|player| player.index() < *players)
+                };
+                if let Some(player) = player {
+                    game_state.process_input(GameCommand {
+                        player,
+                        ..input_command
+                    })?;
+                    self.events.append(game_state.drain_events());
+                }
                 if let ButtonState::Pressed = input_command.button_state
                     && input_command.command_type == CommandType::Quit
                 {
                     self.state = MenuState::Selecting;
+                    self.high_score_screen = None;
+                    self.events.push(GameEvent::GameQuit);
+                }
+            }
+            // Any button wakes the menu up without doing anything else
+            MenuState::Attract(_) => {
+                if let ButtonState::Pressed = input_command.button_state {
+                    self.state = MenuState::Selecting;
+                    self.idle = Duration::ZERO;
                 }
             }
         }
@@ -265,10 +863,28 @@ impl Game for Menu {
         match &mut self.state {
             MenuState::Selecting => {
                 self.current_time += delta_time;
+                self.idle += delta_time;
+                if self.attract_delay.is_some_and(|delay| self.idle >= delay) {
+                    self.close_settings_menu();
+                    let seed = self.seed.wrapping_add(self.current_time.as_millis() as u64);
+                    self.state = MenuState::Attract(AttractMode::new(seed));
+                }
             }
-            MenuState::RunningGame(game_state) => {
+            MenuState::RunningGame(game_state, _) => {
                 game_state.update(delta_time)?;
+                self.events.append(game_state.drain_events());
+                let status = game_state.status();
+                if status.is_over() && !self.game_over {
+                    self.events.push(GameEvent::GameOver(status.winner));
+                }
+                self.game_over = status.is_over();
+                let final_score = game_state.final_score();
+                self.record_final_score(final_score);
+                if let Some(screen) = &mut self.high_score_screen {
+                    screen.update(delta_time);
+                }
             }
+            MenuState::Attract(attract) => attract.update(delta_time)?,
         }
         Ok(())
     }
@@ -277,35 +893,100 @@ impl Game for Menu {
         let mut render_board = RenderBoard::new();
         match &self.state {
             MenuState::Selecting => {
+                if let Some(settings_menu) = &self.settings_menu {
+                    return Ok(settings_menu.render(&self.display_settings));
+                }
+
                 //render menu items
-                for i in 0..NUM_GAMES {
-                    let rgb = if i == self.active_game_index {
+                let available_games = (0..NUM_GAMES).filter(|&i| GAMES[i].fits_grid());
+                for (slot, i) in available_games.enumerate() {
+                    let rgb = if i == self.active_game_index && self.solo_start().is_some() {
+                        // Yellow like the eyes of the computer icons
+                        RGB::new(255, 210, 0)
+                    } else if i == self.active_game_index {
                         RGB::new(255, 255, 255)
                     } else {
                         RGB::new(20, 20, 20)
                     };
-                    render_board.set(i % GRID_SIZE, i / GRID_SIZE, rgb);
+                    render_board.set(slot % GRID_WIDTH, slot / GRID_WIDTH, rgb);
                 }
 
-                //render pixel art
+                //render pixel art centered on the grid
+                let offset_x = (GRID_WIDTH - 8) / 2;
+                let offset_y = (GRID_HEIGHT - 8) / 2;
                 for (i, row) in self.pixel_art().iter().enumerate() {
                     for (j, &pixel) in row.iter().enumerate() {
-                        render_board.set(i + 2, j + 2, pixel);
+                        render_board.set(i + offset_x, j + offset_y, pixel);
+                    }
+                }
+
+                // Games for more players show a dot per player above the
+                // icon, dim for players that can join with more controllers
+                let entry = self.active_game();
+                if entry.party_start.is_some() && offset_y + 8 < GRID_HEIGHT {
+                    let players = self.party_start().map_or(entry.players, |(_, n)| n);
+                    let left = GRID_WIDTH.saturating_sub(2 * entry.max_players - 1) / 2;
+                    for player in 0..entry.max_players {
+                        let rgb = if player < players {
+                            RGB::new(255, 255, 255)
+                        } else {
+                            RGB::new(40, 40, 40)
+                        };
+                        render_board.set(left + 2 * player, GRID_HEIGHT - 1, rgb);
                     }
                 }
             }
-            MenuState::RunningGame(game_state) => {
-                render_board = game_state.render()?;
+            MenuState::RunningGame(game_state, _) => match &self.high_score_screen {
+                Some(screen) if screen.is_visible() => {
+                    render_board = self.render_high_scores(screen);
+                }
+                _ => {
+                    render_board = game_state.render()?;
+                }
+            },
+            MenuState::Attract(attract) => {
+                render_board = attract.render()?;
             }
         }
         Ok(render_board)
     }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match &self.state {
+            MenuState::Selecting => GameStatus {
+                phase: GamePhase::Menu,
+                ..GameStatus::playing()
+            },
+            MenuState::RunningGame(game_state, _) => game_state.status(),
+            MenuState::Attract(attract) => return attract.status(),
+        };
+        status.game = Some(self.active_game().name);
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::Player;
+    use crate::game::{DEFAULT_TICK_RATE, GameEngine};
+
+    // The indices of the games that fit the grid, in menu order
+    fn games_on_grid() -> Vec<usize> {
+        (0..NUM_GAMES).filter(|&i| GAMES[i].fits_grid()).collect()
+    }
+
+    // The index of a game from `GameEngine::games`, looked up by id so the
+    // tests don't depend on the order of the registry. None if the game
+    // doesn't fit the grid.
+    fn game_index(id: &str) -> Option<usize> {
+        GameEngine::games().find(|game| game.id == id)?;
+        GAMES.iter().position(|entry| entry.id == id)
+    }
 
     #[test]
     fn test_new_main_menu() {
@@ -316,44 +997,71 @@ mod tests {
 
     #[test]
     fn test_cycle_left() {
+        let games = games_on_grid();
         let mut menu = Menu::new();
-        menu.active_game_index = 1;
+        menu.active_game_index = games[1];
         menu.cycle_left();
-        assert_eq!(menu.active_game_index, 0);
+        assert_eq!(menu.active_game_index, games[0]);
 
-        menu.active_game_index = 0;
+        menu.active_game_index = games[0];
         menu.cycle_left();
-        assert_eq!(menu.active_game_index, NUM_GAMES - 1);
+        assert_eq!(menu.active_game_index, games[games.len() - 1]);
     }
 
     #[test]
     fn test_cycle_right() {
+        let games = games_on_grid();
         let mut menu = Menu::new();
-        menu.active_game_index = 0;
+        menu.active_game_index = games[0];
         menu.cycle_right();
-        assert_eq!(menu.active_game_index, 1);
+        assert_eq!(menu.active_game_index, games[1]);
 
-        menu.active_game_index = NUM_GAMES - 1;
+        menu.active_game_index = games[games.len() - 1];
         menu.cycle_right();
-        assert_eq!(menu.active_game_index, 0);
+        assert_eq!(menu.active_game_index, games[0]);
     }
 
     #[test]
     fn test_start_game() {
         let mut menu = Menu::new();
-        menu.active_game_index = 0;
+        menu.active_game_index = game_index("ConnectFour").unwrap();
         menu.start_game();
         assert!(matches!(
             menu.state,
-            MenuState::RunningGame(GameType::ConnectFour(_))
+            MenuState::RunningGame(GameType::ConnectFour(_), _)
         ));
 
-        menu.active_game_index = 1;
-        menu.start_game();
-        assert!(matches!(
-            menu.state,
-            MenuState::RunningGame(GameType::TicTacToe(_))
-        ));
+        // Tic-Tac-Toe needs a 9x9 grid
+        if let Some(index) = game_index("TicTacToe") {
+            menu.active_game_index = index;
+            menu.start_game();
+            assert!(matches!(
+                menu.state,
+                MenuState::RunningGame(GameType::TicTacToe(_), _)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_start_game_by_id() {
+        let mut menu = Menu::new();
+        assert!(!menu.start_game_by_id("Chess"));
+        assert!(matches!(menu.state, MenuState::Selecting));
+
+        let last = Menu::games().last().unwrap();
+        assert!(menu.start_game_by_id(last.id));
+        assert_eq!(menu.status().game, Some(last.name));
+        let events: Vec<_> = menu.drain_events().into_iter().collect();
+        assert_eq!(events, [GameEvent::GameStarted]);
+
+        let first = Menu::games().next().unwrap();
+        assert!(menu.start_game_by_id(first.id));
+        let MenuState::RunningGame(game, _) = &menu.state else {
+            panic!("{} did not start", first.id);
+        };
+        assert_eq!(game.id(), first.id);
+        let events: Vec<_> = menu.drain_events().into_iter().collect();
+        assert_eq!(events, [GameEvent::GameQuit, GameEvent::GameStarted]);
     }
 
     #[test]
@@ -382,7 +1090,7 @@ mod tests {
         assert_eq!(menu.active_game_index, 0);
 
         menu.process_input(select_command).unwrap();
-        assert!(matches!(menu.state, MenuState::RunningGame(_)));
+        assert!(matches!(menu.state, MenuState::RunningGame(_, _)));
     }
 
     #[test]
@@ -405,13 +1113,439 @@ mod tests {
         let menu = Menu::new();
         let render_board = menu.render().unwrap();
 
-        for i in 0..NUM_GAMES {
+        // On short grids the icon covers some of the dots
+        let icon_x = (GRID_WIDTH - 8) / 2..(GRID_WIDTH - 8) / 2 + 8;
+        let icon_y = (GRID_HEIGHT - 8) / 2..(GRID_HEIGHT - 8) / 2 + 8;
+        for (slot, i) in games_on_grid().into_iter().enumerate() {
+            let (x, y) = (slot % GRID_WIDTH, slot / GRID_WIDTH);
+            if icon_x.contains(&x) && icon_y.contains(&y) {
+                continue;
+            }
             let expected_rgb = if i == menu.active_game_index {
                 RGB::new(255, 255, 255)
             } else {
-                RGB::new(100, 100, 100)
+                RGB::new(20, 20, 20)
+            };
+            assert_eq!(render_board.get(x, y), expected_rgb);
+        }
+    }
+
+    #[test]
+    fn test_high_score_screen() {
+        let mut menu = Menu::new();
+        let tetris = game_index("Tetris").unwrap();
+        menu.active_game_index = tetris;
+        menu.start_game();
+        menu.scoreboard.insert(tetris, 80);
+
+        menu.record_final_score(Some(40));
+        assert_eq!(menu.scoreboard.scores(tetris)[..2], [80, 40]);
+        assert!(menu.scoreboard_changed());
+        let screen = menu.high_score_screen.as_mut().unwrap();
+        assert_eq!(screen.rank, Some(1));
+        assert!(!screen.is_visible());
+
+        // The same game over is only recorded once
+        menu.record_final_score(Some(40));
+        assert_eq!(menu.scoreboard.scores(tetris)[2], 0);
+
+        menu.high_score_screen.as_mut().unwrap().delay = Duration::ZERO;
+        let render_board = menu.render().unwrap();
+        let text_row = (GRID_HEIGHT - GLYPH_HEIGHT) / 2;
+        assert!((0..GRID_WIDTH).all(|x| render_board.get(x, text_row) == RGB::default()));
+
+        // The table is shown once the score has scrolled past
+        let screen = menu.high_score_screen.as_mut().unwrap();
+        assert_eq!(screen.marquee.text(), "SCORE 40");
+        while screen.marquee.passes() == 0 {
+            screen.update(Duration::from_millis(100));
+        }
+        let render_board = menu.render().unwrap();
+        let second_row = GRID_HEIGHT - 1 - GRID_HEIGHT / SCORES_PER_GAME;
+        assert_eq!(render_board.get(0, second_row), RGB::new(255, 255, 255));
+        assert_eq!(
+            render_board.get(GRID_WIDTH / 2 - 1, second_row),
+            RGB::new(255, 255, 255)
+        );
+        assert_eq!(render_board.get(GRID_WIDTH / 2, second_row), RGB::default());
+        assert_eq!(
+            render_board.get(GRID_WIDTH - 1, GRID_HEIGHT - 1),
+            RGB::new(255, 190, 0)
+        );
+
+        let select_command =
+            GameCommand::new(CommandType::Select, ButtonState::Pressed, Player::Player1);
+        menu.process_input(select_command).unwrap();
+        assert!(menu.high_score_screen.is_none());
+        assert!(matches!(
+            menu.state,
+            MenuState::RunningGame(GameType::Tetris(_), _)
+        ));
+
+        // Restarting the game allows the next score to be recorded
+        menu.record_final_score(None);
+        assert!(!menu.score_recorded);
+    }
+
+    #[test]
+    fn test_every_registered_game_starts() {
+        for (i, entry) in GAMES.iter().enumerate() {
+            assert!(!entry.name.is_empty());
+            assert!((1..=2).contains(&entry.players), "{}", entry.id);
+            assert!((entry.players..=MAX_PLAYERS).contains(&entry.max_players));
+            assert!(
+                entry.icon.iter().any(|&pixel| pixel != [0, 0, 0]),
+                "{} has no pixel art",
+                entry.id
+            );
+            if !entry.fits_grid() {
+                continue;
+            }
+
+            let mut menu = Menu::new();
+            menu.active_game_index = i;
+            menu.start_game();
+            let MenuState::RunningGame(game, _) = &menu.state else {
+                panic!("{} did not start", entry.id);
             };
-            assert_eq!(render_board.get(i % GRID_SIZE, i / GRID_SIZE), expected_rgb);
+            assert_eq!(game.id(), entry.id);
+            menu.update(Duration::from_millis(16)).unwrap();
+            menu.render().unwrap();
+
+            // With one controller the bot takes the second player
+            menu.set_controllers(1);
+            menu.start_game();
+            let MenuState::RunningGame(game, _) = &menu.state else {
+                panic!("{} did not start", entry.id);
+            };
+            if entry.solo_start.is_some() {
+                assert_eq!(game.id(), format!("{}Bot", entry.id));
+                assert!(game.is_solo());
+                menu.update(Duration::from_millis(16)).unwrap();
+            } else {
+                assert_eq!(game.id(), entry.id);
+                assert!(!game.is_solo());
+            }
+
+            // With more controllers everyone joins, if the game allows it
+            menu.set_controllers(MAX_PLAYERS);
+            menu.start_game();
+            let MenuState::RunningGame(game, _) = &menu.state else {
+                panic!("{} did not start", entry.id);
+            };
+            assert_eq!(game.id(), entry.id);
+            menu.update(Duration::from_millis(16)).unwrap();
+            menu.render().unwrap();
+        }
+    }
+
+    // Runs a game for two seconds at `fps` and returns the frame and status
+    // before every input and at the end. A button is pressed every sixth
+    // of a second. Frames end on the nanosecond closest to their ideal
+    // time, so every frame rate has played exactly as long at each input.
+    fn play_at(
+        start: fn(u64) -> GameType,
+        fps: u32,
+        tick_rate: Option<u32>,
+    ) -> Vec<(RenderBoard, GameStatus)> {
+        let inputs = [
+            CommandType::Up,
+            CommandType::Right,
+            CommandType::Select,
+            CommandType::Down,
+            CommandType::Left,
+        ];
+        let mut engine = GameEngine::new(start(5));
+        engine.set_tick_rate(tick_rate);
+        let frame_end = |frame: u32| Duration::from_secs(1) * frame / fps;
+        let mut outcomes = Vec::new();
+        for step in 0..12 {
+            let player = [Player::Player1, Player::Player2][step % 2];
+            let command =
+                GameCommand::new(inputs[step % inputs.len()], ButtonState::Pressed, player);
+            let _ = engine.process_input(command);
+            let first = step as u32 * fps / 6;
+            for frame in first..first + fps / 6 {
+                engine
+                    .update(frame_end(frame + 1) - frame_end(frame))
+                    .unwrap();
+            }
+            outcomes.push((engine.render().unwrap(), engine.status()));
+        }
+        outcomes
+    }
+
+    #[test]
+    fn test_every_game_plays_the_same_at_any_frame_rate() {
+        let starts = GAMES
+            .iter()
+            .filter(|entry| entry.fits_grid())
+            .flat_map(|entry| {
+                [
+                    Some((entry.id, entry.start)),
+                    entry.solo_start.map(|start| (entry.id, start)),
+                ]
+            })
+            .flatten();
+        for (id, start) in starts {
+            for tick_rate in [Some(DEFAULT_TICK_RATE), None] {
+                let expected = play_at(start, 30, tick_rate);
+                for fps in [60, 144] {
+                    let outcomes = play_at(start, fps, tick_rate);
+                    let differs = outcomes.iter().zip(&expected).position(|(a, b)| a != b);
+                    assert!(
+                        differs.is_none(),
+                        "{} at {} fps with tick rate {:?} differs after input {:?}",
+                        id,
+                        fps,
+                        tick_rate,
+                        differs
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        let mut menu = Menu::new();
+        let status = menu.status();
+        assert_eq!(status.game, Some("Connect Four"));
+        assert_eq!(status.phase, GamePhase::Menu);
+
+        menu.active_game_index = game_index("Tetris").unwrap();
+        menu.start_game();
+        let status = menu.status();
+        assert_eq!(status.game, Some("Tetris"));
+        assert_eq!(status.phase, GamePhase::Playing);
+        assert_eq!(status.scores, [Some(0), None, None, None]);
+        assert_eq!(status.to_string(), "Tetris | Playing | Score 0");
+
+        let quit_command =
+            GameCommand::new(CommandType::Quit, ButtonState::Pressed, Player::Player1);
+        menu.process_input(quit_command).unwrap();
+        assert_eq!(menu.status().phase, GamePhase::Menu);
+    }
+
+    #[test]
+    fn test_events() {
+        let press =
+            |command_type| GameCommand::new(command_type, ButtonState::Pressed, Player::Player1);
+        let player2 =
+            |command_type| GameCommand::new(command_type, ButtonState::Pressed, Player::Player2);
+        let mut menu = Menu::new();
+        menu.process_input(press(CommandType::Right)).unwrap();
+        menu.process_input(press(CommandType::Left)).unwrap();
+        menu.process_input(press(CommandType::Select)).unwrap();
+        let events: Vec<_> = menu.drain_events().into_iter().collect();
+        assert_eq!(
+            events,
+            [
+                GameEvent::MenuMoved,
+                GameEvent::MenuMoved,
+                GameEvent::GameStarted
+            ]
+        );
+        assert!(menu.drain_events().is_empty());
+
+        // Connect Four: player 1 stacks four pieces in the first column
+        for _ in 0..3 {
+            menu.process_input(press(CommandType::Select)).unwrap();
+            menu.process_input(player2(CommandType::Right)).unwrap();
+            menu.process_input(player2(CommandType::Select)).unwrap();
+            menu.process_input(press(CommandType::Left)).unwrap();
+        }
+        menu.process_input(press(CommandType::Select)).unwrap();
+        menu.update(Duration::from_millis(16)).unwrap();
+        menu.update(Duration::from_millis(16)).unwrap();
+        let events: Vec<_> = menu.drain_events().into_iter().collect();
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[6], GameEvent::PiecePlaced(Player::Player1));
+        assert_eq!(events[7], GameEvent::GameOver(Some(Player::Player1)));
+    }
+
+    #[test]
+    fn test_settings_overlay() {
+        let press =
+            |command_type| GameCommand::new(command_type, ButtonState::Pressed, Player::Player1);
+        let mut menu = Menu::new();
+        let menu_board = menu.render().unwrap();
+        menu.process_input(press(CommandType::Pause)).unwrap();
+        assert_ne!(menu.render().unwrap(), menu_board);
+
+        // Menu buttons change the settings instead of the game
+        menu.process_input(press(CommandType::Left)).unwrap();
+        menu.process_input(press(CommandType::Down)).unwrap();
+        menu.process_input(press(CommandType::Right)).unwrap();
+        assert_eq!(menu.active_game_index, 0);
+        assert!(menu.display_settings().brightness < u8::MAX);
+        assert!(menu.is_night());
+        // They are stored once, when the overlay closes
+        assert!(!menu.display_settings_changed());
+
+        menu.process_input(press(CommandType::Select)).unwrap();
+        assert_eq!(menu.render().unwrap(), menu_board);
+        assert!(menu.display_settings_changed());
+        menu.mark_display_settings_saved();
+
+        // Settings that end up as they were need no write
+        menu.process_input(press(CommandType::Pause)).unwrap();
+        menu.process_input(press(CommandType::Left)).unwrap();
+        menu.process_input(press(CommandType::Right)).unwrap();
+        menu.process_input(press(CommandType::Quit)).unwrap();
+        assert!(!menu.display_settings_changed());
+        menu.process_input(press(CommandType::Select)).unwrap();
+        assert!(matches!(menu.state, MenuState::RunningGame(_, _)));
+    }
+
+    #[test]
+    fn test_party_start() {
+        let press =
+            |command_type, player| GameCommand::new(command_type, ButtonState::Pressed, player);
+        let mut menu = Menu::new();
+        menu.set_controllers(3);
+        menu.start_game_by_id("ButtonWar");
+        menu.process_input(press(CommandType::Quit, Player::Player1))
+            .unwrap();
+        // The dots need a free row above the icon
+        if (GRID_HEIGHT - 8) / 2 + 8 < GRID_HEIGHT {
+            let board = menu.render().unwrap();
+            let dots = (0..GRID_WIDTH).map(|col| board.get(col, GRID_HEIGHT - 1));
+            let lit = dots.filter(|&rgb| rgb == RGB::new(255, 255, 255)).count();
+            assert_eq!(lit, 3);
+        }
+
+        menu.process_input(press(CommandType::Select, Player::Player1))
+            .unwrap();
+        for _ in 0..4 {
+            menu.update(Duration::from_secs(1)).unwrap();
+        }
+        menu.process_input(press(CommandType::Select, Player::Player3))
+            .unwrap();
+        menu.process_input(press(CommandType::Select, Player::Player4))
+            .unwrap();
+        assert_eq!(menu.status().scores, [Some(0), Some(0), Some(1), None]);
+
+        // Extra players can't play two player games, but can still quit
+        menu.start_game_by_id("PongGame");
+        let MenuState::RunningGame(GameType::PongGame(_), _) = &menu.state else {
+            panic!("Pong did not start");
+        };
+        menu.process_input(press(CommandType::Quit, Player::Player3))
+            .unwrap();
+        assert!(matches!(menu.state, MenuState::Selecting));
+    }
+
+    #[test]
+    fn test_party_games_ignore_players_that_did_not_join() {
+        let commands = [
+            CommandType::Select,
+            CommandType::Up,
+            CommandType::Left,
+            CommandType::Down,
+            CommandType::Right,
+        ];
+        let party_games = GAMES.iter().filter(|entry| entry.party_start.is_some());
+        for entry in party_games.filter(|entry| entry.fits_grid()) {
+            let (mut joined, mut others) = (Menu::new(), Menu::new());
+            for menu in [&mut joined, &mut others] {
+                menu.set_controllers(2);
+                assert!(menu.start_game_by_id(entry.id));
+            }
+            // Players 1 and 2 play the same in both menus. Players 3 and 4
+            // press Select in one of them only, after every update so it
+            // would restart some games as soon as they are over.
+            for frame in 0..600 {
+                let command_type = commands[frame % commands.len()];
+                for menu in [&mut joined, &mut others] {
+                    for player in [Player::Player1, Player::Player2] {
+                        let command = GameCommand::new(command_type, ButtonState::Pressed, player);
+                        menu.process_input(command).unwrap();
+                    }
+                    menu.update(Duration::from_millis(50)).unwrap();
+                }
+                for player in [Player::Player3, Player::Player4] {
+                    let command =
+                        GameCommand::new(CommandType::Select, ButtonState::Pressed, player);
+                    others.process_input(command).unwrap();
+                }
+                assert!(
+                    joined.render().unwrap() == others.render().unwrap(),
+                    "{} took input from a player that did not join",
+                    entry.id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_games_against_the_computer_take_every_controller() {
+        use crate::game::{connect_four, tictactoe};
+        let press =
+            |command_type, player| GameCommand::new(command_type, ButtonState::Pressed, player);
+        let mut menu = Menu::new();
+        assert!(menu.start_game_by_id("ConnectFourComputer"));
+        menu.process_input(press(CommandType::Select, Player::Player2))
+            .unwrap();
+        let MenuState::RunningGame(GameType::ConnectFourComputer(game), _) = &menu.state else {
+            panic!("Connect Four did not start");
+        };
+        assert_eq!(game.board.get(0, 0), connect_four::Cell::PlayerX);
+
+        // Tic-Tac-Toe doesn't fit on the smallest grids
+        if menu.start_game_by_id("TicTacToeComputer") {
+            menu.process_input(press(CommandType::Select, Player::Player4))
+                .unwrap();
+            let MenuState::RunningGame(GameType::TicTacToeComputer(game), _) = &menu.state else {
+                panic!("Tic-Tac-Toe did not start");
+            };
+            assert_eq!(game.board.get(0, 0), tictactoe::Cell::PlayerX);
+        }
+    }
+
+    #[test]
+    fn test_attract_mode_when_idle() {
+        let press =
+            |command_type| GameCommand::new(command_type, ButtonState::Pressed, Player::Player1);
+        let frame = Duration::from_millis(100);
+        let mut menu = Menu::new();
+        menu.set_attract_delay(Some(Duration::from_secs(2)));
+        for _ in 0..15 {
+            menu.update(frame).unwrap();
+        }
+        menu.process_input(press(CommandType::Right)).unwrap();
+        for _ in 0..15 {
+            menu.update(frame).unwrap();
+        }
+        assert_eq!(menu.status().phase, GamePhase::Menu);
+        for _ in 0..5 {
+            menu.update(frame).unwrap();
+        }
+
+        // Every demo gets its turn and stays silent
+        menu.drain_events();
+        let mut games = Vec::new();
+        for _ in 0..1500 {
+            menu.update(frame).unwrap();
+            menu.render().unwrap();
+            let status = menu.status();
+            assert_eq!(status.phase, GamePhase::Demo);
+            if games.last() != status.game.as_ref() {
+                games.push(status.game.unwrap());
+            }
+        }
+        assert_eq!(games[..4], ["Snake", "Tetris", "Pong", "Flappy Bird"]);
+        assert!(menu.drain_events().is_empty());
+
+        menu.process_input(press(CommandType::Right)).unwrap();
+        assert_eq!(menu.status().phase, GamePhase::Menu);
+        assert_eq!(menu.active_game_index, games_on_grid()[1]);
+    }
+
+    #[test]
+    fn test_game_ids_are_unique() {
+        for (i, entry) in GAMES.iter().enumerate() {
+            assert!(GAMES[i + 1..].iter().all(|other| other.id != entry.id));
         }
     }
 }
@@ -1,11 +1,10 @@
 use crate::RGB;
-use crate::game::{Board, ButtonState, CommandType, Game, GameCommand};
+use crate::game::{Board, ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand};
+use crate::snapshot::{on_grid, snapshot_enum, snapshot_struct};
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 
-const GRID_SIZE: usize = 12;
-const COLOR_ROW: usize = GRID_SIZE - 1;
-const CANVAS_SIZE: usize = GRID_SIZE;
+const COLOR_ROW: usize = GRID_HEIGHT - 1;
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum Color {
@@ -44,34 +43,46 @@ impl Color {
 }
 
 pub struct PaintGame {
-    board: Board<Color, CANVAS_SIZE, CANVAS_SIZE>,
+    board: Board<Color, GRID_WIDTH, GRID_HEIGHT>,
     cursor: (usize, usize),
     selected_color: Color,
 }
 
+snapshot_enum!(Color {
+    Empty,
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Cyan,
+    Magenta,
+    Orange,
+    Purple,
+    Brown,
+    Pink,
+    White,
+});
+snapshot_struct!(PaintGame {
+    board,
+    cursor,
+    selected_color,
+} check |game| on_grid(game.cursor));
+
 impl Game for PaintGame {
     fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
         if let ButtonState::Pressed = input_command.button_state {
             match input_command.command_type {
-                CommandType::Left => {
-                    if self.cursor.0 > 0 {
-                        self.cursor.0 -= 1;
-                    }
+                CommandType::Left if self.cursor.0 > 0 => {
+                    self.cursor.0 -= 1;
                 }
-                CommandType::Right => {
-                    if self.cursor.0 < GRID_SIZE - 1 {
-                        self.cursor.0 += 1;
-                    }
+                CommandType::Right if self.cursor.0 < GRID_WIDTH - 1 => {
+                    self.cursor.0 += 1;
                 }
-                CommandType::Up => {
-                    if self.cursor.1 < COLOR_ROW {
-                        self.cursor.1 += 1;
-                    }
+                CommandType::Up if self.cursor.1 < COLOR_ROW => {
+                    self.cursor.1 += 1;
                 }
-                CommandType::Down => {
-                    if self.cursor.1 > 0 {
-                        self.cursor.1 -= 1;
-                    }
+                CommandType::Down if self.cursor.1 > 0 => {
+                    self.cursor.1 -= 1;
                 }
                 CommandType::Select => {
                     if self.cursor.1 == COLOR_ROW {
@@ -95,12 +106,12 @@ impl Game for PaintGame {
         let mut render_board = RenderBoard::new();
 
         for y in 0..COLOR_ROW {
-            for x in 0..CANVAS_SIZE {
+            for x in 0..GRID_WIDTH {
                 render_board.set(x, y, self.board.get(x, y).as_rgb());
             }
         }
 
-        for x in 0..GRID_SIZE {
+        for x in 0..GRID_WIDTH {
             let color = self.get_color_from_palette(x);
             render_board.set(x, COLOR_ROW, color.as_rgb());
         }
@@ -1,16 +1,28 @@
 use crate::RGB;
 use crate::animation::Animation;
-use crate::game::{ButtonState, CommandType, Game, GameCommand, Player};
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    ButtonState, CommandType, DEFAULT_TICK, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus,
+    Player,
+};
 use crate::random::CustomRng;
+use crate::snapshot::{snapshot_enum, snapshot_struct};
+use crate::timestep::FixedStep;
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 use libm::{fabsf, roundf};
 
-const GRID_SIZE: usize = 12;
-const PLAY_AREA_HEIGHT: usize = GRID_SIZE - 1;
+const PLAY_AREA_HEIGHT: usize = GRID_HEIGHT - 1;
 const PADDLE_HEIGHT: usize = 3;
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
+// The ball moves BALL_SPEED cells per step, whatever the frame rate
 const BALL_SPEED: f32 = 0.1;
+// The computer paddles react in the same steps, so they play the same at
+// any frame rate too
+const BALL_STEP: Duration = DEFAULT_TICK;
+// With only CPU paddles nobody can press Select, so a new game starts
+// after this long
+const CPU_RESTART_DELAY: Duration = Duration::from_secs(3);
 
 #[derive(Debug, PartialEq)]
 enum GameState {
@@ -32,15 +44,96 @@ struct Ball {
     dy: f32,
 }
 
+/// How a computer controlled paddle plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CpuSettings {
+    /// Time between two moves of the paddle
+    pub reaction_delay: Duration,
+    /// The paddle aims up to this many cells away from where the ball
+    /// will arrive, a new aim is picked for every approach of the ball
+    pub error: f32,
+}
+
+impl Default for CpuSettings {
+    fn default() -> Self {
+        Self {
+            reaction_delay: Duration::from_millis(110),
+            error: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CpuPaddle {
+    settings: CpuSettings,
+    cooldown: Duration,
+    aim_offset: f32,
+    // Whether the ball is on its way to this paddle
+    tracking: bool,
+}
+
+impl CpuPaddle {
+    fn new(settings: CpuSettings) -> Self {
+        Self {
+            settings,
+            cooldown: Duration::ZERO,
+            aim_offset: 0.0,
+            tracking: false,
+        }
+    }
+}
+
 pub struct PongGame {
     state: GameState,
     paddles: [Paddle; 2],
     ball: Ball,
+    ball_step: FixedStep,
+    // Paddles that are played by the computer instead of a player
+    cpus: [Option<CpuPaddle>; 2],
+    restart_delay: Duration,
     rng: CustomRng,
     game_over_animation: Animation,
     game_time: Duration,
+    events: GameEvents,
 }
 
+snapshot_enum!(GameState {
+    Playing,
+    GameOver(winner),
+});
+snapshot_struct!(Paddle { y, score } check |paddle| {
+    paddle.y <= PLAY_AREA_HEIGHT - PADDLE_HEIGHT && paddle.score <= GRID_WIDTH / 2
+});
+snapshot_struct!(Ball { x, y, dx, dy } check |ball| {
+    // The computer paddles follow the ball across the field, which takes
+    // forever if it barely moves sideways
+    (0.0..=(GRID_WIDTH - 1) as f32).contains(&roundf(ball.x))
+        && (0.0..=(GRID_HEIGHT - 1) as f32).contains(&roundf(ball.y))
+        && (BALL_SPEED / 10.0..=1.0).contains(&fabsf(ball.dx))
+        && fabsf(ball.dy) <= 1.0
+});
+snapshot_struct!(CpuSettings {
+    reaction_delay,
+    error,
+});
+snapshot_struct!(CpuPaddle {
+    settings,
+    cooldown,
+    aim_offset,
+    tracking,
+});
+snapshot_struct!(PongGame {
+    state,
+    paddles,
+    ball,
+    ball_step,
+    cpus,
+    restart_delay,
+    rng,
+    game_over_animation,
+    game_time,
+} skip { events } check |game| game.ball_step.step() == BALL_STEP);
+
 impl PongGame {
     pub fn new(seed: u64) -> Self {
         let mut rng = CustomRng::seed_from_u64(seed);
@@ -57,7 +150,7 @@ impl PongGame {
                 },
             ],
             ball: Ball {
-                x: GRID_SIZE as f32 / 2.0,
+                x: GRID_WIDTH as f32 / 2.0,
                 y: PLAY_AREA_HEIGHT as f32 / 2.0,
                 dx: if rng.gen_bool(0.5) {
                     BALL_SPEED
@@ -66,12 +159,87 @@ impl PongGame {
                 },
                 dy: rng.gen_range_f32(-BALL_SPEED, BALL_SPEED),
             },
+            ball_step: FixedStep::new(BALL_STEP),
+            cpus: [None, None],
+            restart_delay: CPU_RESTART_DELAY,
             rng,
             game_over_animation: Animation::new(GAME_OVER_ANIMATION_SPEED),
             game_time: Duration::from_secs(0),
+            events: GameEvents::new(),
+        }
+    }
+
+    /// A game where the paddles with settings are played by the computer.
+    /// With both paddles played by the computer the game runs on its own,
+    /// as an attract mode.
+    pub fn with_cpus(seed: u64, cpus: [Option<CpuSettings>; 2]) -> Self {
+        Self {
+            cpus: cpus.map(|settings| settings.map(CpuPaddle::new)),
+            ..Self::new(seed)
+        }
+    }
+
+    // The steps carry on, so a game that restarts on its own keeps the
+    // time left over from the last one
+    fn restart(&mut self) {
+        let cpus = self.cpus.map(|cpu| cpu.map(|cpu| cpu.settings));
+        let ball_step = self.ball_step;
+        *self = Self::with_cpus(self.rng.next_u64(), cpus);
+        self.ball_step = ball_step;
+    }
+
+    fn update_cpus(&mut self, delta_time: Duration) {
+        for player in [Player::Player1, Player::Player2] {
+            let target = self.cpu_target(player);
+            let Some(cpu) = &mut self.cpus[player as usize] else {
+                continue;
+            };
+            let approaching = match player {
+                Player::Player1 => self.ball.dx < 0.0,
+                _ => self.ball.dx > 0.0,
+            };
+            if approaching && !cpu.tracking && cpu.settings.error > 0.0 {
+                cpu.aim_offset = self
+                    .rng
+                    .gen_range_f32(-cpu.settings.error, cpu.settings.error);
+            }
+            cpu.tracking = approaching;
+
+            cpu.cooldown = cpu.cooldown.saturating_sub(delta_time);
+            if !cpu.cooldown.is_zero() {
+                continue;
+            }
+            cpu.cooldown = cpu.settings.reaction_delay;
+            let target = target + if approaching { cpu.aim_offset } else { 0.0 };
+            let center = (self.paddles[player as usize].y + PADDLE_HEIGHT / 2) as f32;
+            if target > center + 0.5 {
+                self.move_paddle(player, 1);
+            } else if target < center - 0.5 {
+                self.move_paddle(player, -1);
+            }
         }
     }
 
+    // Where the ball will cross the paddle column of `player`, or the
+    // middle of the field while the ball moves away
+    fn cpu_target(&self, player: Player) -> f32 {
+        let (paddle_x, approaching) = match player {
+            Player::Player1 => (1.0, self.ball.dx < 0.0),
+            _ => ((GRID_WIDTH - 2) as f32, self.ball.dx > 0.0),
+        };
+        if !approaching {
+            return (PLAY_AREA_HEIGHT / 2) as f32;
+        }
+        let frames = (paddle_x - self.ball.x) / self.ball.dx;
+        let max_y = (PLAY_AREA_HEIGHT - 1) as f32;
+        let mut y = self.ball.y + self.ball.dy * frames;
+        // Unfold the bounces off the top and bottom walls
+        while !(0.0..=max_y).contains(&y) {
+            y = if y < 0.0 { -y } else { 2.0 * max_y - y };
+        }
+        y
+    }
+
     fn move_paddle(&mut self, player: Player, direction: i32) {
         let paddle = &mut self.paddles[player as usize];
         let new_y =
@@ -98,11 +266,18 @@ impl PongGame {
         if ball_x == 1
             && (ball_y >= self.paddles[0].y && ball_y < self.paddles[0].y + PADDLE_HEIGHT)
         {
+            // The ball overlaps the paddle for a few frames, count the hit once
+            if self.ball.dx < 0.0 {
+                self.events.push(GameEvent::PaddleHit(Player::Player1));
+            }
             self.ball.dx = fabsf(self.ball.dx); // Ensure ball moves right
             self.add_spin_to_ball(self.paddles[0].y);
-        } else if ball_x == GRID_SIZE - 2
+        } else if ball_x == GRID_WIDTH - 2
             && (ball_y >= self.paddles[1].y && ball_y < self.paddles[1].y + PADDLE_HEIGHT)
         {
+            if self.ball.dx > 0.0 {
+                self.events.push(GameEvent::PaddleHit(Player::Player2));
+            }
             self.ball.dx = -fabsf(self.ball.dx); // Ensure ball moves left
             self.add_spin_to_ball(self.paddles[1].y);
         }
@@ -118,16 +293,18 @@ impl PongGame {
         // Check for scoring
         if ball_x == 0 {
             self.paddles[1].score += 1;
+            self.events.push(GameEvent::Scored(Player::Player2));
             self.reset_ball(Player::Player2);
-        } else if ball_x == GRID_SIZE - 1 {
+        } else if ball_x == GRID_WIDTH - 1 {
             self.paddles[0].score += 1;
+            self.events.push(GameEvent::Scored(Player::Player1));
             self.reset_ball(Player::Player1);
         }
 
         // Check for game over
-        if self.paddles[0].score >= GRID_SIZE / 2 {
+        if self.paddles[0].score >= GRID_WIDTH / 2 {
             self.state = GameState::GameOver(Player::Player1);
-        } else if self.paddles[1].score >= GRID_SIZE / 2 {
+        } else if self.paddles[1].score >= GRID_WIDTH / 2 {
             self.state = GameState::GameOver(Player::Player2);
         }
     }
@@ -140,11 +317,11 @@ impl PongGame {
     }
 
     fn reset_ball(&mut self, serving_player: Player) {
-        self.ball.x = GRID_SIZE as f32 / 2.0;
+        self.ball.x = GRID_WIDTH as f32 / 2.0;
         self.ball.y = PLAY_AREA_HEIGHT as f32 / 2.0;
         self.ball.dx = match serving_player {
             Player::Player1 => BALL_SPEED,
-            Player::Player2 => -BALL_SPEED,
+            _ => -BALL_SPEED,
         };
         self.ball.dy = self.rng.gen_range_f32(-BALL_SPEED, BALL_SPEED);
     }
@@ -154,6 +331,9 @@ impl Game for PongGame {
     fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
         match self.state {
             GameState::Playing => {
+                if let Some(Some(_)) = self.cpus.get(input_command.player.index()) {
+                    return Ok(());
+                }
                 if let ButtonState::Pressed = input_command.button_state {
                     match (input_command.player, input_command.command_type) {
                         (Player::Player1, CommandType::Down) => {
@@ -172,7 +352,7 @@ impl Game for PongGame {
                 if let (ButtonState::Pressed, CommandType::Select) =
                     (input_command.button_state, input_command.command_type)
                 {
-                    *self = Self::new(self.rng.next_u64());
+                    self.restart();
                 }
             }
         }
@@ -182,16 +362,26 @@ impl Game for PongGame {
     fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
         self.game_time = self.game_time.saturating_add(delta_time);
 
-        match self.state {
-            GameState::Playing => {
-                self.update_ball();
-            }
-            GameState::GameOver(_) => {
-                let animation_time =
-                    Duration::from_nanos((self.game_time.as_nanos() % u64::MAX as u128) as u64);
-                self.game_over_animation.update(animation_time);
+        for _ in 0..self.ball_step.advance(delta_time) {
+            match self.state {
+                GameState::Playing => {
+                    self.update_cpus(BALL_STEP);
+                    self.update_ball();
+                }
+                GameState::GameOver(_) if self.cpus.iter().all(Option::is_some) => {
+                    self.restart_delay = self.restart_delay.saturating_sub(BALL_STEP);
+                    if self.restart_delay.is_zero() {
+                        self.restart();
+                    }
+                }
+                GameState::GameOver(_) => break,
             }
         }
+        if let GameState::GameOver(_) = self.state {
+            let animation_time =
+                Duration::from_nanos((self.game_time.as_nanos() % u64::MAX as u128) as u64);
+            self.game_over_animation.update(animation_time);
+        }
         Ok(())
     }
 
@@ -199,8 +389,8 @@ impl Game for PongGame {
         let mut render_board = RenderBoard::new();
 
         // Render the bottom row as out of play
-        for x in 0..GRID_SIZE {
-            render_board.set(x, GRID_SIZE - 1, RGB::new(20, 20, 20));
+        for x in 0..GRID_WIDTH {
+            render_board.set(x, GRID_HEIGHT - 1, RGB::new(20, 20, 20));
         }
 
         match self.state {
@@ -209,7 +399,7 @@ impl Game for PongGame {
                 for y in 0..PADDLE_HEIGHT {
                     render_board.set(0, self.paddles[0].y + y, RGB::new(255, 255, 255));
                     render_board.set(
-                        GRID_SIZE - 1,
+                        GRID_WIDTH - 1,
                         self.paddles[1].y + y,
                         RGB::new(255, 255, 255),
                     );
@@ -224,24 +414,24 @@ impl Game for PongGame {
 
                 // Render scores
                 for i in 0..self.paddles[0].score {
-                    render_board.set(i, GRID_SIZE - 1, RGB::new(255, 0, 0));
+                    render_board.set(i, GRID_HEIGHT - 1, RGB::new(255, 0, 0));
                 }
                 for i in 0..self.paddles[1].score {
-                    render_board.set(GRID_SIZE - 1 - i, GRID_SIZE - 1, RGB::new(0, 255, 0));
+                    render_board.set(GRID_WIDTH - 1 - i, GRID_HEIGHT - 1, RGB::new(0, 255, 0));
                 }
             }
             GameState::GameOver(winner) => {
                 let win_color = match winner {
                     Player::Player1 => RGB::new(255, 0, 0),
-                    Player::Player2 => RGB::new(0, 255, 0),
+                    _ => RGB::new(0, 255, 0),
                 };
                 let game_over_color = self.game_over_animation.get_color();
-                for x in 0..GRID_SIZE {
-                    for y in 0..GRID_SIZE {
+                for x in 0..GRID_WIDTH {
+                    for y in 0..GRID_HEIGHT {
                         render_board.set(
                             x,
                             y,
-                            if x < GRID_SIZE / 2 {
+                            if x < GRID_WIDTH / 2 {
                                 win_color
                             } else {
                                 game_over_color
@@ -254,4 +444,110 @@ impl Game for PongGame {
 
         Ok(render_board)
     }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Playing => GameStatus::playing(),
+            GameState::GameOver(winner) => GameStatus::game_over(Some(winner)),
+        };
+        for (score, paddle) in status.scores.iter_mut().zip(&self.paddles) {
+            *score = Some(paddle.score as u32);
+        }
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn test_ball_speed_does_not_depend_on_frame_rate() {
+        let positions = [30, 60, 144].map(|fps| {
+            let mut game = PongGame::new(3);
+            for _ in 0..fps / 2 {
+                game.update(Duration::from_secs(1) / fps).unwrap();
+            }
+            game.ball.x
+        });
+        // At most a step apart, give or take rounding
+        for x in positions {
+            assert!(
+                fabsf(x - positions[0]) < 1.5 * BALL_SPEED,
+                "{:?}",
+                positions
+            );
+        }
+        assert!(fabsf(positions[0] - GRID_WIDTH as f32 / 2.0) > 2.0);
+    }
+
+    #[test]
+    fn test_cpu_tracks_the_ball() {
+        let flawless = CpuSettings {
+            reaction_delay: Duration::ZERO,
+            error: 0.0,
+        };
+        let mut game = PongGame::with_cpus(4, [Some(flawless), Some(flawless)]);
+        for _ in 0..3000 {
+            game.update(FRAME).unwrap();
+        }
+        assert_eq!(game.state, GameState::Playing);
+        assert_eq!(game.paddles.map(|paddle| paddle.score), [0, 0]);
+    }
+
+    #[test]
+    fn test_single_player_against_cpu() {
+        // A CPU that can't miss, so the player loses on any grid
+        let flawless = CpuSettings {
+            reaction_delay: Duration::ZERO,
+            error: 0.0,
+        };
+        let mut game = PongGame::with_cpus(9, [None, Some(flawless)]);
+        let start = game.paddles[1].y;
+        game.process_input(GameCommand::new(
+            CommandType::Up,
+            ButtonState::Pressed,
+            Player::Player2,
+        ))
+        .unwrap();
+        assert_eq!(game.paddles[1].y, start);
+
+        // The player never moves and loses, Select starts a new game
+        // against the CPU
+        while game.state == GameState::Playing {
+            game.update(FRAME).unwrap();
+        }
+        assert_eq!(game.state, GameState::GameOver(Player::Player2));
+        game.process_input(GameCommand::new(
+            CommandType::Select,
+            ButtonState::Pressed,
+            Player::Player1,
+        ))
+        .unwrap();
+        assert_eq!(game.state, GameState::Playing);
+        assert!(game.cpus[0].is_none() && game.cpus[1].is_some());
+    }
+
+    #[test]
+    fn test_attract_mode_restarts_by_itself() {
+        let settings = Some(CpuSettings::default());
+        let mut game = PongGame::with_cpus(2, [settings, settings]);
+        game.state = GameState::GameOver(Player::Player1);
+        let mut waited = Duration::ZERO;
+        // The delay runs out in steps, the last one can end after it
+        while game.state != GameState::Playing {
+            assert!(waited <= CPU_RESTART_DELAY + BALL_STEP);
+            game.update(FRAME).unwrap();
+            waited += FRAME;
+        }
+        assert!(waited >= CPU_RESTART_DELAY);
+        assert!(game.cpus.iter().all(Option::is_some));
+        assert_eq!(game.paddles.map(|paddle| paddle.score), [0, 0]);
+    }
 }
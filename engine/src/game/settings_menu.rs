@@ -0,0 +1,207 @@
+//! Display settings overlay, opened with Pause while picking a game.
+
+use crate::RenderBoard;
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, GameCommand, RGB};
+use crate::settings::{DisplaySettings, NightMode};
+use crate::text::{GLYPH_HEIGHT, draw_text_centered};
+
+const LABEL_COLOR: RGB = RGB {
+    r: 0,
+    g: 90,
+    b: 200,
+};
+const NIGHT_COLOR: RGB = RGB {
+    r: 255,
+    g: 140,
+    b: 40,
+};
+const OFF_COLOR: RGB = RGB {
+    r: 40,
+    g: 40,
+    b: 40,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Setting {
+    Brightness,
+    NightMode,
+}
+
+/// Up and Down switch between brightness and night mode, Left and Right
+/// change the shown setting and any other button closes the overlay.
+pub(crate) struct SettingsMenu {
+    setting: Setting,
+    // The settings when the overlay was opened, to tell if they changed
+    opened_with: DisplaySettings,
+}
+
+// Brightness is picked in one step per column, so the bar fills the grid
+fn brightness_level(brightness: u8) -> usize {
+    (brightness as usize * GRID_WIDTH)
+        .div_ceil(u8::MAX as usize)
+        .max(1)
+}
+
+fn level_brightness(level: usize) -> u8 {
+    (level * u8::MAX as usize / GRID_WIDTH) as u8
+}
+
+impl SettingsMenu {
+    pub(crate) fn new(settings: DisplaySettings) -> Self {
+        Self {
+            setting: Setting::Brightness,
+            opened_with: settings,
+        }
+    }
+
+    pub(crate) fn changed(&self, settings: &DisplaySettings) -> bool {
+        *settings != self.opened_with
+    }
+
+    /// Applies a button press to `settings` and returns whether the
+    /// overlay is still open. Scheduled night mode is only offered when
+    /// the frontend knows the time of day.
+    pub(crate) fn process_input(
+        &mut self,
+        input_command: GameCommand,
+        settings: &mut DisplaySettings,
+        has_clock: bool,
+        events: &mut GameEvents,
+    ) -> bool {
+        if input_command.button_state != ButtonState::Pressed {
+            return true;
+        }
+        let forward = match input_command.command_type {
+            CommandType::Up | CommandType::Down => {
+                self.setting = match self.setting {
+                    Setting::Brightness => Setting::NightMode,
+                    Setting::NightMode => Setting::Brightness,
+                };
+                events.push(GameEvent::MenuMoved);
+                return true;
+            }
+            CommandType::Right => true,
+            CommandType::Left => false,
+            _ => return false,
+        };
+        match self.setting {
+            Setting::Brightness => {
+                let level = brightness_level(settings.brightness);
+                let level = if forward {
+                    (level + 1).min(GRID_WIDTH)
+                } else {
+                    level.saturating_sub(1).max(1)
+                };
+                settings.brightness = level_brightness(level);
+            }
+            Setting::NightMode => {
+                settings.night_mode = match (settings.night_mode, forward, has_clock) {
+                    (NightMode::Off, true, _) => NightMode::On,
+                    (NightMode::On, true, true) => NightMode::Scheduled,
+                    (NightMode::On, false, _) => NightMode::Off,
+                    (NightMode::Off, false, true) => NightMode::Scheduled,
+                    (NightMode::Scheduled, false, _) => NightMode::On,
+                    _ => NightMode::Off,
+                };
+            }
+        }
+        events.push(GameEvent::MenuMoved);
+        true
+    }
+
+    pub(crate) fn render(&self, settings: &DisplaySettings) -> RenderBoard {
+        let mut render_board = RenderBoard::new();
+        let label_y = GRID_HEIGHT.saturating_sub(GLYPH_HEIGHT);
+        match self.setting {
+            Setting::Brightness => {
+                draw_text_centered(&mut render_board, "BRI", label_y, LABEL_COLOR);
+                let level = brightness_level(settings.brightness);
+                for col in 0..GRID_WIDTH {
+                    let color = if col < level {
+                        RGB::new(255, 255, 255)
+                    } else {
+                        OFF_COLOR
+                    };
+                    for row in 1..3 {
+                        render_board.set(col, row, color);
+                    }
+                }
+            }
+            Setting::NightMode => {
+                draw_text_centered(&mut render_board, "NGT", label_y, LABEL_COLOR);
+                let (value, color) = match settings.night_mode {
+                    NightMode::Off => ("OFF", OFF_COLOR),
+                    NightMode::On => ("ON", NIGHT_COLOR),
+                    NightMode::Scheduled => ("AUT", NIGHT_COLOR),
+                };
+                draw_text_centered(&mut render_board, value, 0, color);
+            }
+        }
+        render_board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Player;
+
+    fn press(
+        settings_menu: &mut SettingsMenu,
+        settings: &mut DisplaySettings,
+        command_type: CommandType,
+        has_clock: bool,
+    ) -> bool {
+        let command = GameCommand::new(command_type, ButtonState::Pressed, Player::Player1);
+        settings_menu.process_input(command, settings, has_clock, &mut GameEvents::new())
+    }
+
+    #[test]
+    fn test_brightness_steps_stay_lit() {
+        let mut settings_menu = SettingsMenu::new(DisplaySettings::new());
+        let mut settings = DisplaySettings::new();
+        assert!(press(
+            &mut settings_menu,
+            &mut settings,
+            CommandType::Right,
+            false
+        ));
+        assert_eq!(settings.brightness, u8::MAX);
+        for _ in 0..GRID_WIDTH * 2 {
+            press(&mut settings_menu, &mut settings, CommandType::Left, false);
+        }
+        assert_eq!(settings.brightness, level_brightness(1));
+        assert!(settings.brightness > 0);
+        press(&mut settings_menu, &mut settings, CommandType::Right, false);
+        assert_eq!(settings.brightness, level_brightness(2));
+    }
+
+    #[test]
+    fn test_scheduled_night_mode_needs_a_clock() {
+        let mut settings_menu = SettingsMenu::new(DisplaySettings::new());
+        let mut settings = DisplaySettings::new();
+        press(&mut settings_menu, &mut settings, CommandType::Down, false);
+        let mut cycle = |has_clock| {
+            press(
+                &mut settings_menu,
+                &mut settings,
+                CommandType::Right,
+                has_clock,
+            );
+            settings.night_mode
+        };
+        assert_eq!(cycle(false), NightMode::On);
+        assert_eq!(cycle(false), NightMode::Off);
+        assert_eq!(cycle(true), NightMode::On);
+        assert_eq!(cycle(true), NightMode::Scheduled);
+        assert_eq!(cycle(true), NightMode::Off);
+
+        assert!(!press(
+            &mut settings_menu,
+            &mut settings,
+            CommandType::Select,
+            true
+        ));
+    }
+}
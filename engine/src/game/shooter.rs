@@ -1,16 +1,41 @@
 use crate::RGB;
 use crate::animation::Animation;
-use crate::game::{ButtonState, CommandType, Game, GameCommand, Player};
+use crate::bot::Bot;
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    ButtonState, CommandType, DEFAULT_TICK, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus,
+    MAX_PLAYERS, Player,
+};
 use crate::random::CustomRng;
+use crate::snapshot::{on_grid, snapshot_enum, snapshot_struct};
+use crate::timestep::FixedStep;
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 use smallvec::SmallVec;
 
-const GRID_SIZE: usize = 12;
-const MAX_PLAYERS: usize = 2;
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
 const WALL_SPAWN_INTERVAL: f64 = 0.5; // Wall spawn interval in seconds
 const MAX_WALLS: usize = 32;
+const BOT_MOVE_DELAY: Duration = Duration::from_millis(150);
+const BOT_FIRE_DELAY: Duration = Duration::from_millis(500);
+const PLAYER_COLORS: [RGB; MAX_PLAYERS] = [
+    RGB { r: 0, g: 255, b: 0 },
+    RGB {
+        r: 0,
+        g: 255,
+        b: 255,
+    },
+    RGB {
+        r: 255,
+        g: 0,
+        b: 255,
+    },
+    RGB {
+        r: 255,
+        g: 160,
+        b: 0,
+    },
+];
 
 #[derive(Debug, PartialEq)]
 enum GameState {
@@ -23,19 +48,47 @@ struct Character {
     row: usize,
     col: usize,
     player: Player,
+    alive: bool,
+}
+
+// The (row, col) step of the shots of `player`. Players 1 and 2 stand on
+// the bottom and top row, 3 and 4 on the left and right column, all facing
+// the middle.
+fn facing(player: Player) -> (isize, isize) {
+    match player {
+        Player::Player1 => (1, 0),
+        Player::Player2 => (-1, 0),
+        Player::Player3 => (0, 1),
+        Player::Player4 => (0, -1),
+    }
 }
 
 impl Character {
     fn new(player: Player) -> Self {
         let (row, col) = match player {
-            Player::Player1 => (0, GRID_SIZE / 2),
-            Player::Player2 => (GRID_SIZE - 1, GRID_SIZE / 2),
+            Player::Player1 => (0, GRID_WIDTH / 2),
+            Player::Player2 => (GRID_HEIGHT - 1, GRID_WIDTH / 2),
+            Player::Player3 => (GRID_HEIGHT / 2, 0),
+            Player::Player4 => (GRID_HEIGHT / 2, GRID_WIDTH - 1),
         };
-        Self { player, row, col }
+        Self {
+            player,
+            row,
+            col,
+            alive: true,
+        }
     }
 
-    fn move_horizontal(&mut self, direction: isize) {
-        self.col = (self.col as isize + direction).clamp(0, GRID_SIZE as isize - 1) as usize;
+    // The (row, col) one step along the edge the character stands on
+    fn step_along(&self, direction: isize) -> (usize, usize) {
+        let clamp = |value: usize, len: usize| {
+            (value as isize + direction).clamp(0, len as isize - 1) as usize
+        };
+        if facing(self.player).0 == 0 {
+            (clamp(self.row, GRID_HEIGHT), self.col)
+        } else {
+            (self.row, clamp(self.col, GRID_WIDTH))
+        }
     }
 }
 
@@ -68,26 +121,73 @@ pub struct MultiplayerShooter {
     projectiles: SmallVec<[Projectile; 128]>,
     walls: SmallVec<[(usize, usize); MAX_WALLS]>,
     current_time: Duration,
+    // Shots fly and hit in ticks of equal length, so they hit the same
+    // players and walls at any frame rate
+    fixed_step: FixedStep,
     wall_spawn_timer: f64,
     game_over_animation: Animation,
     rng: CustomRng,
+    events: GameEvents,
 }
 
+snapshot_enum!(GameState {
+    Playing,
+    GameOver(winner),
+});
+snapshot_struct!(Character {
+    row,
+    col,
+    player,
+    alive,
+} check |character| on_grid((character.col, character.row)));
+snapshot_struct!(Projectile {
+    row,
+    col,
+    active,
+    direction,
+    speed,
+    player,
+});
+snapshot_struct!(MultiplayerShooter {
+    state,
+    characters,
+    projectiles,
+    walls,
+    current_time,
+    fixed_step,
+    wall_spawn_timer,
+    game_over_animation,
+    rng,
+} skip { events } check |game| {
+    game.fixed_step.step() == DEFAULT_TICK
+        && game.characters.len() <= MAX_PLAYERS
+        && game.walls.iter().all(|&(row, col)| on_grid((col, row)))
+});
+
 impl MultiplayerShooter {
     pub fn new(seed: u64, initial_walls: usize) -> Self {
+        Self::with_players(seed, initial_walls, 2)
+    }
+
+    /// A game for two to four players. Players shot by anyone are out and
+    /// the last one standing wins.
+    pub fn with_players(seed: u64, initial_walls: usize, players: usize) -> Self {
         let mut game = Self {
             state: GameState::Playing,
             characters: SmallVec::new(),
             projectiles: SmallVec::with_capacity(128),
             walls: SmallVec::with_capacity(MAX_WALLS),
             current_time: Duration::default(),
+            fixed_step: FixedStep::new(DEFAULT_TICK),
             wall_spawn_timer: 0.0,
             game_over_animation: Animation::new(GAME_OVER_ANIMATION_SPEED),
             rng: CustomRng::seed_from_u64(seed),
+            events: GameEvents::new(),
         };
 
-        game.characters.push(Character::new(Player::Player1));
-        game.characters.push(Character::new(Player::Player2));
+        for &player in &Player::ALL[..players.clamp(2, MAX_PLAYERS)] {
+            game.characters.push(Character::new(player));
+        }
 
         // Spawn initial walls
         for _ in 0..initial_walls.min(MAX_WALLS) {
@@ -97,25 +197,46 @@ impl MultiplayerShooter {
         game
     }
 
-    fn move_character(&mut self, direction: isize, player: Player) {
-        if let Some(character) = self.characters.iter_mut().find(|c| c.player == player) {
-            character.move_horizontal(direction);
+    // Players on the sides move up and down, the others left and right.
+    // A corner is on two edges, so players meet there and can't pass.
+    fn move_character(&mut self, command_type: CommandType, player: Player) {
+        let direction = match (facing(player).0 == 0, command_type) {
+            (false, CommandType::Left) | (true, CommandType::Down) => -1,
+            (false, CommandType::Right) | (true, CommandType::Up) => 1,
+            _ => return,
+        };
+        let Some(index) = self
+            .characters
+            .iter()
+            .position(|c| c.player == player && c.alive)
+        else {
+            return;
+        };
+        let (row, col) = self.characters[index].step_along(direction);
+        if !self
+            .characters
+            .iter()
+            .any(|c| c.alive && (c.row, c.col) == (row, col))
+        {
+            self.characters[index].row = row;
+            self.characters[index].col = col;
         }
     }
 
     fn shoot_projectile(&mut self, player: Player) {
         const FIRE_SPEED: f64 = 10.0;
-        if let Some(character) = self.characters.iter().find(|c| c.player == player) {
-            let direction = match player {
-                Player::Player1 => (1.0, 0.0),
-                Player::Player2 => (-1.0, 0.0),
-            };
-            let (row, col) = match player {
-                Player::Player1 => (character.row + 1, character.col),
-                Player::Player2 => (character.row - 1, character.col),
-            };
+        if let Some(character) = self
+            .characters
+            .iter()
+            .find(|c| c.player == player && c.alive)
+        {
+            let (row_step, col_step) = facing(player);
+            let row = character.row.wrapping_add_signed(row_step);
+            let col = character.col.wrapping_add_signed(col_step);
+            let direction = (row_step as f64, col_step as f64);
             self.projectiles
                 .push(Projectile::new(row, col, direction, FIRE_SPEED, player));
+            self.events.push(GameEvent::Shot(player));
         }
     }
 
@@ -127,9 +248,9 @@ impl MultiplayerShooter {
                 projectile.col += distance * projectile.direction.1;
 
                 if projectile.row < 0.0
-                    || projectile.row >= GRID_SIZE as f64
+                    || projectile.row >= GRID_HEIGHT as f64
                     || projectile.col < 0.0
-                    || projectile.col >= GRID_SIZE as f64
+                    || projectile.col >= GRID_WIDTH as f64
                 {
                     projectile.active = false;
                 }
@@ -141,8 +262,10 @@ impl MultiplayerShooter {
 
     fn spawn_wall(&mut self) {
         if self.walls.len() < MAX_WALLS {
-            let row = self.rng.gen_range(1, (GRID_SIZE - 1) as u32) as usize;
-            let col = self.rng.gen_range(0, GRID_SIZE as u32) as usize;
+            // Walls stay off the edges players stand on
+            let side = u32::from(self.characters.len() > 2);
+            let row = self.rng.gen_range(1, (GRID_HEIGHT - 1) as u32) as usize;
+            let col = self.rng.gen_range(side, GRID_WIDTH as u32 - side) as usize;
             if !self.walls.contains(&(row, col)) {
                 self.walls.push((row, col));
             }
@@ -152,7 +275,7 @@ impl MultiplayerShooter {
     fn detect_collisions(&mut self) {
         // Wall-projectile collisions
         self.walls.retain(|&mut (wall_row, wall_col)| {
-            !self.projectiles.iter_mut().any(|projectile| {
+            let hit = self.projectiles.iter_mut().any(|projectile| {
                 if projectile.active
                     && (libm::round(projectile.row) as usize == wall_row)
                     && (libm::round(projectile.col) as usize == wall_col)
@@ -161,11 +284,15 @@ impl MultiplayerShooter {
                     return true;
                 }
                 false
-            })
+            });
+            if hit {
+                self.events.push(GameEvent::Explosion);
+            }
+            !hit
         });
 
         // Character-projectile collisions
-        for character in &self.characters {
+        for character in self.characters.iter_mut().filter(|c| c.alive) {
             for projectile in &mut self.projectiles {
                 if projectile.active
                     && projectile.player != character.player
@@ -173,14 +300,116 @@ impl MultiplayerShooter {
                     && (libm::round(projectile.col) as usize == character.col)
                 {
                     projectile.active = false;
-                    self.state = GameState::GameOver(Some(projectile.player));
-                    return;
+                    character.alive = false;
+                    self.events.push(GameEvent::PlayerDied(character.player));
+                    break;
                 }
             }
         }
 
+        let mut alive = self.characters.iter().filter(|c| c.alive);
+        match (alive.next(), alive.next()) {
+            (Some(_), Some(_)) => {}
+            // Nobody wins when the last players hit each other at once
+            (last, _) => self.state = GameState::GameOver(last.map(|c| c.player)),
+        }
+
         self.projectiles.retain(|projectile| projectile.active);
     }
+
+    fn tick(&mut self, delta_time: Duration) {
+        self.update_projectiles(delta_time);
+
+        self.wall_spawn_timer += delta_time.as_secs_f64();
+        if self.wall_spawn_timer > WALL_SPAWN_INTERVAL {
+            self.spawn_wall();
+            self.wall_spawn_timer = 0.0;
+        }
+
+        self.detect_collisions();
+    }
+
+    // Shots only fly straight, so every shot of the opponent in a column
+    // is on its way to the player at the end of it
+    fn under_fire(&self, col: usize, player: Player) -> bool {
+        self.projectiles.iter().any(|projectile| {
+            projectile.active
+                && projectile.player != player
+                && libm::round(projectile.col) as usize == col
+        })
+    }
+}
+
+/// Dodges incoming shots, lines up with the opponent and fires.
+#[derive(Default)]
+pub(crate) struct ShooterBot {
+    move_cooldown: Duration,
+    fire_cooldown: Duration,
+}
+
+snapshot_struct!(ShooterBot {
+    move_cooldown,
+    fire_cooldown,
+});
+
+impl ShooterBot {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Bot<MultiplayerShooter> for ShooterBot {
+    fn next_command(
+        &mut self,
+        game: &MultiplayerShooter,
+        player: Player,
+        delta_time: Duration,
+    ) -> Option<GameCommand> {
+        self.move_cooldown = self.move_cooldown.saturating_sub(delta_time);
+        self.fire_cooldown = self.fire_cooldown.saturating_sub(delta_time);
+        if game.state != GameState::Playing {
+            return None;
+        }
+        let character = game.characters.iter().find(|c| c.player == player)?;
+        let opponent = game
+            .characters
+            .iter()
+            .find(|c| c.player != player && c.alive)?;
+        let press =
+            |command_type| Some(GameCommand::new(command_type, ButtonState::Pressed, player));
+
+        let direction: isize = if game.under_fire(character.col, player) {
+            // Step aside, towards the opponent if that is safe
+            let towards = if opponent.col < character.col { -1 } else { 1 };
+            *[towards, -towards].iter().find(|&&direction| {
+                let col = character.col as isize + direction;
+                (0..GRID_WIDTH as isize).contains(&col) && !game.under_fire(col as usize, player)
+            })?
+        } else if character.col == opponent.col {
+            if !self.fire_cooldown.is_zero() {
+                return None;
+            }
+            self.fire_cooldown = BOT_FIRE_DELAY;
+            return press(CommandType::Select);
+        } else {
+            let direction = if opponent.col < character.col { -1 } else { 1 };
+            let col = (character.col as isize + direction) as usize;
+            if game.under_fire(col, player) {
+                return None;
+            }
+            direction
+        };
+
+        if !self.move_cooldown.is_zero() {
+            return None;
+        }
+        self.move_cooldown = BOT_MOVE_DELAY;
+        press(if direction < 0 {
+            CommandType::Left
+        } else {
+            CommandType::Right
+        })
+    }
 }
 
 impl Game for MultiplayerShooter {
@@ -189,10 +418,8 @@ impl Game for MultiplayerShooter {
             GameState::Playing => {
                 if let ButtonState::Pressed = input_command.button_state {
                     match input_command.command_type {
-                        CommandType::Left => self.move_character(-1, input_command.player),
-                        CommandType::Right => self.move_character(1, input_command.player),
                         CommandType::Select => self.shoot_projectile(input_command.player),
-                        _ => {}
+                        command_type => self.move_character(command_type, input_command.player),
                     }
                 }
             }
@@ -200,7 +427,8 @@ impl Game for MultiplayerShooter {
                 if let (ButtonState::Pressed, CommandType::Select) =
                     (input_command.button_state, input_command.command_type)
                 {
-                    *self = Self::new(self.rng.next_u64(), self.walls.len());
+                    let players = self.characters.len();
+                    *self = Self::with_players(self.rng.next_u64(), self.walls.len(), players);
                 }
             }
         }
@@ -210,22 +438,15 @@ impl Game for MultiplayerShooter {
     fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
         self.current_time += delta_time;
 
-        match self.state {
-            GameState::Playing => {
-                self.update_projectiles(delta_time);
-
-                self.wall_spawn_timer += delta_time.as_secs_f64();
-                if self.wall_spawn_timer > WALL_SPAWN_INTERVAL {
-                    self.spawn_wall();
-                    self.wall_spawn_timer = 0.0;
-                }
-
-                self.detect_collisions();
-            }
-            GameState::GameOver(_) => {
-                self.game_over_animation.update(self.current_time);
+        for _ in 0..self.fixed_step.advance(delta_time) {
+            match self.state {
+                GameState::Playing => self.tick(DEFAULT_TICK),
+                GameState::GameOver(_) => break,
             }
         }
+        if let GameState::GameOver(_) = self.state {
+            self.game_over_animation.update(self.current_time);
+        }
         Ok(())
     }
 
@@ -235,11 +456,8 @@ impl Game for MultiplayerShooter {
         match self.state {
             GameState::Playing => {
                 // Render characters
-                for character in &self.characters {
-                    let color = match character.player {
-                        Player::Player1 => RGB::new(0, 255, 0),
-                        Player::Player2 => RGB::new(0, 255, 255),
-                    };
+                for character in self.characters.iter().filter(|c| c.alive) {
+                    let color = PLAYER_COLORS[character.player.index()];
                     render_board.set(character.col, character.row, color);
                 }
 
@@ -248,7 +466,7 @@ impl Game for MultiplayerShooter {
                     if projectile.active {
                         let row = libm::round(projectile.row) as usize;
                         let col = libm::round(projectile.col) as usize;
-                        if row < GRID_SIZE && col < GRID_SIZE {
+                        if row < GRID_HEIGHT && col < GRID_WIDTH {
                             render_board.set(col, row, RGB::new(255, 255, 255));
                         }
                     }
@@ -263,10 +481,7 @@ impl Game for MultiplayerShooter {
                 let game_over_color = self.game_over_animation.get_color();
                 for character in &self.characters {
                     let color = if Some(character.player) == winner {
-                        match character.player {
-                            Player::Player1 => RGB::new(0, 255, 0),
-                            Player::Player2 => RGB::new(0, 255, 255),
-                        }
+                        PLAYER_COLORS[character.player.index()]
                     } else {
                         game_over_color
                     };
@@ -277,4 +492,73 @@ impl Game for MultiplayerShooter {
 
         Ok(render_board)
     }
+
+    fn status(&self) -> GameStatus {
+        match self.state {
+            GameState::Playing => GameStatus::playing(),
+            GameState::GameOver(winner) => GameStatus::game_over(winner),
+        }
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotPlayer;
+
+    #[test]
+    fn test_bot_dodges_and_wins() {
+        let game = MultiplayerShooter::new(3, 0);
+        let mut game = BotPlayer::new(game, ShooterBot::new(), Player::Player2);
+        let frame = Duration::from_millis(16);
+        let fire = GameCommand::new(CommandType::Select, ButtonState::Pressed, Player::Player1);
+        for frame_index in 0..2000 {
+            // A player that stands still and shoots about as often as a
+            // shot takes to cross the grid
+            if frame_index % (GRID_HEIGHT * 8) == 0 {
+                game.process_input(fire).unwrap();
+            }
+            game.update(frame).unwrap();
+            if game.status().is_over() {
+                break;
+            }
+        }
+        assert_eq!(game.status().winner, Some(Player::Player2));
+    }
+
+    #[test]
+    fn test_last_player_standing_wins() {
+        let mut game = MultiplayerShooter::with_players(5, 0, 4);
+        let mut press = |command_type, player, times| {
+            for _ in 0..times {
+                let command = GameCommand::new(command_type, ButtonState::Pressed, player);
+                game.process_input(command).unwrap();
+            }
+            // Long enough for a shot to cross the grid
+            for _ in 0..GRID_WIDTH.max(GRID_HEIGHT) * 10 {
+                game.update(Duration::from_millis(16)).unwrap();
+            }
+            let alive = game.characters.iter().filter(|c| c.alive);
+            (alive.map(|c| c.player).collect::<Vec<_>>(), game.status())
+        };
+
+        // Player 3 walks into the corner and shoots along the bottom row
+        press(CommandType::Down, Player::Player3, GRID_HEIGHT);
+        let (alive, _) = press(CommandType::Select, Player::Player3, 1);
+        assert_eq!(alive, [Player::Player2, Player::Player3, Player::Player4]);
+
+        // Player 2 takes the other corner above and shoots down the side
+        press(CommandType::Left, Player::Player2, GRID_WIDTH);
+        let (alive, _) = press(CommandType::Select, Player::Player2, 1);
+        assert_eq!(alive, [Player::Player2, Player::Player4]);
+
+        press(CommandType::Up, Player::Player4, GRID_HEIGHT);
+        let (alive, status) = press(CommandType::Select, Player::Player4, 1);
+        assert_eq!(alive, [Player::Player4]);
+        assert_eq!(status.winner, Some(Player::Player4));
+    }
 }
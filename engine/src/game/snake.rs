@@ -1,15 +1,36 @@
 use crate::RGB;
 use crate::animation::Animation;
-use crate::game::{ButtonState, CommandType, Game, GameCommand, Player};
+use crate::bot::Bot;
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus, MAX_PLAYERS,
+    Player,
+};
 use crate::random::CustomRng;
+use crate::snapshot::{on_grid, snapshot_enum, snapshot_struct};
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 use smallvec::SmallVec;
 
-const GRID_SIZE: usize = 12;
-const MAX_SNAKES: usize = 2;
+const MAX_SNAKES: usize = MAX_PLAYERS;
 const UPDATE_INTERVAL: Duration = Duration::from_millis(150);
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
+const DIRECTIONS: [(i32, i32); 4] = [(0, 1), (0, -1), (-1, 0), (1, 0)];
+// Green, blue, yellow and magenta
+const SNAKE_COLORS: [RGB; MAX_SNAKES] = [
+    RGB { r: 0, g: 255, b: 0 },
+    RGB { r: 0, g: 0, b: 255 },
+    RGB {
+        r: 255,
+        g: 255,
+        b: 0,
+    },
+    RGB {
+        r: 255,
+        g: 0,
+        b: 255,
+    },
+];
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SnakeGameMode {
@@ -31,6 +52,7 @@ struct Snake {
     player: Player,
     growth_pending: usize,
     move_queued: bool,
+    alive: bool,
 }
 
 impl Snake {
@@ -45,6 +67,7 @@ impl Snake {
             player,
             growth_pending: 0,
             move_queued: false,
+            alive: true,
         }
     }
 
@@ -65,13 +88,7 @@ impl Snake {
         self.direction = self.next_direction;
         self.move_queued = false;
 
-        let (dx, dy) = self.direction;
-        let (x, y) = self.head();
-
-        let new_x = (x as i32 + dx).rem_euclid(GRID_SIZE as i32) as usize;
-        let new_y = (y as i32 + dy).rem_euclid(GRID_SIZE as i32) as usize;
-
-        self.body.insert(0, (new_x, new_y));
+        self.body.insert(0, step(self.head(), self.direction));
 
         if self.growth_pending > 0 {
             self.growth_pending -= 1;
@@ -85,35 +102,93 @@ impl Snake {
     }
 }
 
+// The board wraps around at the edges
+fn step((x, y): (usize, usize), (dx, dy): (i32, i32)) -> (usize, usize) {
+    (
+        (x as i32 + dx).rem_euclid(GRID_WIDTH as i32) as usize,
+        (y as i32 + dy).rem_euclid(GRID_HEIGHT as i32) as usize,
+    )
+}
+
 pub struct SnakeGame {
     mode: SnakeGameMode,
     state: GameState,
     snakes: SmallVec<[Snake; MAX_SNAKES]>,
-    food: SmallVec<[(usize, usize); 2]>,
+    food: SmallVec<[(usize, usize); MAX_SNAKES]>,
     current_time: Duration,
     last_update_time: Duration,
     game_over_animation: Animation,
     rng: CustomRng,
     num_food: usize,
+    events: GameEvents,
 }
 
+snapshot_enum!(SnakeGameMode {
+    SinglePlayer,
+    MultiPlayer,
+});
+snapshot_enum!(GameState {
+    Playing,
+    GameOver(winner),
+});
+snapshot_struct!(Snake {
+    body,
+    direction,
+    next_direction,
+    player,
+    growth_pending,
+    move_queued,
+    alive,
+} check |snake| {
+    let is_step = |direction| matches!(direction, (0, 1 | -1) | (1 | -1, 0));
+    !snake.body.is_empty()
+        && snake.body.iter().all(|&cell| on_grid(cell))
+        && is_step(snake.direction)
+        && is_step(snake.next_direction)
+});
+snapshot_struct!(SnakeGame {
+    mode,
+    state,
+    snakes,
+    food,
+    current_time,
+    last_update_time,
+    game_over_animation,
+    rng,
+    num_food,
+} skip { events } check |game| {
+    game.last_update_time <= game.current_time
+        && (1..=MAX_SNAKES).contains(&game.snakes.len())
+        && game.num_food <= MAX_SNAKES
+        && game.food.iter().all(|&cell| on_grid(cell))
+});
+
 impl SnakeGame {
     pub fn new(seed: u64, mode: SnakeGameMode) -> Self {
-        let rng = CustomRng::seed_from_u64(seed);
-        let mut snakes = SmallVec::new();
-        snakes.push(Snake::new(Player::Player1, (GRID_SIZE / 4, GRID_SIZE / 2)));
+        Self::with_players(seed, mode, 2)
+    }
 
-        let num_food = match mode {
+    /// Multiplayer is for two to four `players`. Snakes that crash are out
+    /// and the last one left wins.
+    pub fn with_players(seed: u64, mode: SnakeGameMode, players: usize) -> Self {
+        let rng = CustomRng::seed_from_u64(seed);
+        let players = match mode {
             SnakeGameMode::SinglePlayer => 1,
-            SnakeGameMode::MultiPlayer => 2,
+            SnakeGameMode::MultiPlayer => players.clamp(2, MAX_SNAKES),
         };
-
-        if mode == SnakeGameMode::MultiPlayer {
-            snakes.push(Snake::new(
-                Player::Player2,
-                (3 * GRID_SIZE / 4, GRID_SIZE / 2),
-            ));
-        }
+        let start_positions = [
+            (GRID_WIDTH / 4, GRID_HEIGHT / 2),
+            (3 * GRID_WIDTH / 4, GRID_HEIGHT / 2),
+            (GRID_WIDTH / 4, GRID_HEIGHT / 4),
+            (3 * GRID_WIDTH / 4, 3 * GRID_HEIGHT / 4),
+        ];
+        let snakes = Player::ALL
+            .into_iter()
+            .zip(start_positions)
+            .take(players)
+            .map(|(player, start_pos)| Snake::new(player, start_pos))
+            .collect();
+        let num_food = players;
 
         let mut game = Self {
             mode,
@@ -125,6 +200,7 @@ impl SnakeGame {
             game_over_animation: Animation::new(GAME_OVER_ANIMATION_SPEED),
             rng,
             num_food,
+            events: GameEvents::new(),
         };
 
         game.spawn_food();
@@ -133,10 +209,12 @@ impl SnakeGame {
 
     fn spawn_food(&mut self) {
         while self.food.len() < self.num_food {
-            let x = self.rng.gen_range(0, GRID_SIZE as u32) as usize;
-            let y = self.rng.gen_range(0, GRID_SIZE as u32) as usize;
+            let x = self.rng.gen_range(0, GRID_WIDTH as u32) as usize;
+            let y = self.rng.gen_range(0, GRID_HEIGHT as u32) as usize;
 
-            if !self.snakes.iter().any(|snake| snake.body.contains(&(x, y)))
+            if !self
+                .alive_snakes()
+                .any(|snake| snake.body.contains(&(x, y)))
                 && !self.food.contains(&(x, y))
             {
                 self.food.push((x, y));
@@ -144,41 +222,98 @@ impl SnakeGame {
         }
     }
 
-    fn check_collisions(&self) -> Option<GameState> {
-        for (i, snake) in self.snakes.iter().enumerate() {
+    fn alive_snakes(&self) -> impl Iterator<Item = &Snake> {
+        self.snakes.iter().filter(|snake| snake.alive)
+    }
+
+    // Snakes that run into themselves or another snake are out, checked in
+    // player order. The game is over when at most one snake is left.
+    fn check_collisions(&mut self) -> Option<GameState> {
+        for i in 0..self.snakes.len() {
+            let snake = &self.snakes[i];
             let head = snake.head();
+            let crashed = snake.alive
+                && (snake.body[1..].contains(&head)
+                    || self
+                        .alive_snakes()
+                        .any(|other| other.player != snake.player && other.body.contains(&head)));
+            if !crashed {
+                continue;
+            }
+            self.snakes[i].alive = false;
+            self.events
+                .push(GameEvent::PlayerDied(self.snakes[i].player));
+
+            let mut alive = self.alive_snakes();
+            match (self.mode, alive.next(), alive.next()) {
+                (SnakeGameMode::SinglePlayer, _, _) => return Some(GameState::GameOver(None)),
+                (_, last, None) => {
+                    return Some(GameState::GameOver(last.map(|snake| snake.player)));
+                }
+                _ => {}
+            }
+        }
 
-            // Self-collision
-            if snake.body[1..].contains(&head) {
-                return Some(match self.mode {
-                    SnakeGameMode::SinglePlayer => GameState::GameOver(None),
-                    SnakeGameMode::MultiPlayer => {
-                        GameState::GameOver(Some(self.snakes[1 - i].player))
-                    }
-                });
+        None
+    }
+
+    // Breadth first search from the head of `snake` around the snakes.
+    // Returns the first step towards the closest food, or towards the most
+    // free space when no food can be reached.
+    fn bot_direction(&self, snake: &Snake) -> Option<(i32, i32)> {
+        let mut visited = [[false; GRID_HEIGHT]; GRID_WIDTH];
+        for &(x, y) in self.alive_snakes().flat_map(|s| &s.body) {
+            visited[x][y] = true;
+        }
+        // Index into DIRECTIONS of the first step taken to reach each cell
+        let mut first_step = [[0; GRID_HEIGHT]; GRID_WIDTH];
+        let mut reached = [0; DIRECTIONS.len()];
+        let mut queue: SmallVec<[(usize, usize); GRID_WIDTH * GRID_HEIGHT]> = SmallVec::new();
+
+        let reverse = (-snake.direction.0, -snake.direction.1);
+        for (index, &direction) in DIRECTIONS.iter().enumerate() {
+            let (x, y) = step(snake.head(), direction);
+            if direction != reverse && !visited[x][y] {
+                visited[x][y] = true;
+                first_step[x][y] = index;
+                queue.push((x, y));
             }
+        }
 
-            // Collision with other snake (multiplayer only)
-            if self.mode == SnakeGameMode::MultiPlayer {
-                let other_snake = &self.snakes[1 - i];
-                if other_snake.body.contains(&head) {
-                    return Some(GameState::GameOver(Some(other_snake.player)));
+        let mut next = 0;
+        while let Some(&(x, y)) = queue.get(next) {
+            next += 1;
+            let index = first_step[x][y];
+            if self.food.contains(&(x, y)) {
+                return Some(DIRECTIONS[index]);
+            }
+            reached[index] += 1;
+            for direction in DIRECTIONS {
+                let (nx, ny) = step((x, y), direction);
+                if !visited[nx][ny] {
+                    visited[nx][ny] = true;
+                    first_step[nx][ny] = index;
+                    queue.push((nx, ny));
                 }
             }
         }
 
-        None
+        (0..DIRECTIONS.len())
+            .filter(|&index| reached[index] > 0)
+            .max_by_key(|&index| reached[index])
+            .map(|index| DIRECTIONS[index])
     }
 
     fn process_food(&mut self) {
         let mut food_eaten = false;
 
         // Check for food collisions and grow snakes
-        for snake in &mut self.snakes {
+        for snake in self.snakes.iter_mut().filter(|snake| snake.alive) {
             if let Some(food_index) = self.food.iter().position(|&f| f == snake.head()) {
                 snake.grow();
                 self.food.swap_remove(food_index);
                 food_eaten = true;
+                self.events.push(GameEvent::FoodEaten(snake.player));
             }
         }
 
@@ -188,6 +323,39 @@ impl SnakeGame {
     }
 }
 
+/// Chases the closest food it can reach without running into a snake.
+pub(crate) struct SnakeBot;
+
+snapshot_struct!(SnakeBot);
+
+impl Bot<SnakeGame> for SnakeBot {
+    fn next_command(
+        &mut self,
+        game: &SnakeGame,
+        player: Player,
+        _delta_time: Duration,
+    ) -> Option<GameCommand> {
+        if game.state != GameState::Playing {
+            return None;
+        }
+        let snake = game.alive_snakes().find(|s| s.player == player)?;
+        if snake.move_queued {
+            return None;
+        }
+        let direction = game.bot_direction(snake)?;
+        if direction == snake.next_direction {
+            return None;
+        }
+        let command_type = match direction {
+            (0, 1) => CommandType::Up,
+            (0, -1) => CommandType::Down,
+            (-1, 0) => CommandType::Left,
+            _ => CommandType::Right,
+        };
+        Some(GameCommand::new(command_type, ButtonState::Pressed, player))
+    }
+}
+
 impl Game for SnakeGame {
     fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
         match self.state {
@@ -204,7 +372,7 @@ impl Game for SnakeGame {
                     if let Some(snake) = self
                         .snakes
                         .iter_mut()
-                        .find(|s| s.player == input_command.player)
+                        .find(|s| s.player == input_command.player && s.alive)
                     {
                         snake.set_direction(direction);
                     }
@@ -214,7 +382,8 @@ impl Game for SnakeGame {
                 if let (ButtonState::Pressed, CommandType::Select) =
                     (input_command.button_state, input_command.command_type)
                 {
-                    *self = SnakeGame::new(self.rng.next_u64(), self.mode);
+                    let players = self.snakes.len();
+                    *self = SnakeGame::with_players(self.rng.next_u64(), self.mode, players);
                 }
             }
         }
@@ -227,9 +396,9 @@ impl Game for SnakeGame {
         match self.state {
             GameState::Playing => {
                 if self.current_time - self.last_update_time > UPDATE_INTERVAL {
-                    self.last_update_time = self.current_time;
+                    self.last_update_time += UPDATE_INTERVAL;
 
-                    for snake in &mut self.snakes {
+                    for snake in self.snakes.iter_mut().filter(|snake| snake.alive) {
                         snake.move_snake();
                     }
 
@@ -252,11 +421,8 @@ impl Game for SnakeGame {
 
         match &self.state {
             GameState::Playing => {
-                for snake in &self.snakes {
-                    let color = match snake.player {
-                        Player::Player1 => RGB::new(0, 255, 0),
-                        Player::Player2 => RGB::new(0, 0, 255),
-                    };
+                for snake in self.alive_snakes() {
+                    let color = SNAKE_COLORS[snake.player.index()];
                     for &(x, y) in &snake.body {
                         render_board.set(x, y, color);
                     }
@@ -273,10 +439,7 @@ impl Game for SnakeGame {
                     let color = if Some(snake.player) != *winner {
                         game_over_color
                     } else {
-                        match snake.player {
-                            Player::Player1 => RGB::new(0, 255, 0),
-                            Player::Player2 => RGB::new(0, 0, 255),
-                        }
+                        SNAKE_COLORS[snake.player.index()]
                     };
 
                     for &(x, y) in snake.body.iter().skip(1) {
@@ -290,10 +453,7 @@ impl Game for SnakeGame {
                     let color = if Some(snake.player) != *winner {
                         game_over_color
                     } else {
-                        match snake.player {
-                            Player::Player1 => RGB::new(0, 255, 0),
-                            Player::Player2 => RGB::new(0, 0, 255),
-                        }
+                        SNAKE_COLORS[snake.player.index()]
                     };
 
                     let &(head_x, head_y) = snake.body.first().unwrap();
@@ -303,4 +463,71 @@ impl Game for SnakeGame {
         }
         Ok(render_board)
     }
+
+    fn final_score(&self) -> Option<u32> {
+        match (self.mode, &self.state) {
+            (SnakeGameMode::SinglePlayer, GameState::GameOver(_)) => {
+                Some(self.snakes[0].body.len() as u32)
+            }
+            _ => None,
+        }
+    }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Playing => GameStatus::playing(),
+            GameState::GameOver(winner) => GameStatus::game_over(winner),
+        };
+        for (score, snake) in status.scores.iter_mut().zip(&self.snakes) {
+            *score = Some(snake.body.len() as u32);
+        }
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotPlayer;
+
+    #[test]
+    fn test_bot_finds_food() {
+        let game = SnakeGame::new(7, SnakeGameMode::SinglePlayer);
+        let mut game = BotPlayer::new(game, SnakeBot, Player::Player1);
+        let frame = Duration::from_millis(50);
+        for _ in 0..1200 {
+            game.update(frame).unwrap();
+            if game.status().is_over() {
+                break;
+            }
+        }
+        let length = game.game().snakes[0].body.len();
+        assert!(length >= 15, "the bot only grew to {length}");
+    }
+
+    #[test]
+    fn test_last_snake_standing_wins() {
+        let mut game = SnakeGame::with_players(3, SnakeGameMode::MultiPlayer, 3);
+        assert_eq!(game.snakes.len(), 3);
+        assert_eq!(game.food.len(), 3);
+        game.snakes[0].body = SmallVec::from_slice(&[(2, 2), (3, 2), (4, 2)]);
+        game.snakes[1].body = SmallVec::from_slice(&[(6, 6)]);
+        game.snakes[2].body = SmallVec::from_slice(&[(3, 2)]);
+        assert_eq!(game.check_collisions(), None);
+        assert!(!game.snakes[2].alive);
+        let events: Vec<_> = game.drain_events().into_iter().collect();
+        assert_eq!(events, [GameEvent::PlayerDied(Player::Player3)]);
+
+        // Out snakes are no longer in the way
+        game.snakes[1].body = SmallVec::from_slice(&[(3, 2)]);
+        game.snakes[2].body = SmallVec::from_slice(&[(6, 6)]);
+        assert_eq!(
+            game.check_collisions(),
+            Some(GameState::GameOver(Some(Player::Player1)))
+        );
+    }
 }
@@ -1,14 +1,19 @@
 use crate::RGB;
 use crate::animation::Animation;
-use crate::game::{ButtonState, CommandType, Game, GameCommand, Player};
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    ButtonState, CommandType, DEFAULT_TICK, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus,
+    Player,
+};
 use crate::random::CustomRng;
+use crate::snapshot::{on_grid, snapshot_enum, snapshot_struct};
+use crate::timestep::FixedStep;
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 use smallvec::SmallVec;
 
 use super::GameMode;
 
-const GRID_SIZE: usize = 12;
 const MAX_PLAYERS: usize = 2;
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
 
@@ -30,7 +35,7 @@ impl Spaceship {
     }
 
     fn move_horizontal(&mut self, direction: isize) {
-        self.col = (self.col as isize + direction).clamp(0, GRID_SIZE as isize - 1) as usize;
+        self.col = (self.col as isize + direction).clamp(0, GRID_WIDTH as isize - 1) as usize;
     }
 }
 
@@ -62,6 +67,9 @@ pub struct SpaceInvaders {
     aliens: SmallVec<[(usize, usize); 128]>,
     projectiles: SmallVec<[Projectile; 128]>,
     current_time: Duration,
+    // Shots fly and hit in ticks of equal length, so they hit the same
+    // aliens at any frame rate
+    fixed_step: FixedStep,
     alien_direction: isize,
     alien_move_period: f64,
     last_alien_move_time: f64,
@@ -69,20 +77,72 @@ pub struct SpaceInvaders {
     walls: Option<SmallVec<[(usize, usize); 32]>>,
     difficulty: u8,
     rng: CustomRng,
+    events: GameEvents,
 }
 
+snapshot_enum!(GameState {
+    Playing,
+    GameOver(winner),
+});
+snapshot_struct!(Spaceship { col, player } check |spaceship| spaceship.col < GRID_WIDTH);
+snapshot_struct!(Projectile {
+    row,
+    col,
+    active,
+    direction,
+    speed,
+} check |projectile| {
+    // Shots that leave the board are deactivated and removed right away
+    projectile.speed.is_finite()
+        && (!projectile.active
+            || projectile.col < GRID_WIDTH
+                && (0.0..(GRID_HEIGHT - 1) as f64).contains(&projectile.row))
+});
+snapshot_struct!(SpaceInvaders {
+    mode,
+    state,
+    spaceships,
+    aliens,
+    projectiles,
+    current_time,
+    fixed_step,
+    alien_direction,
+    alien_move_period,
+    last_alien_move_time,
+    game_over_animation,
+    walls,
+    difficulty,
+    rng,
+} skip { events } check |game| {
+    // The aliens turn at the edges, their next step has to stay on the grid
+    let next_step_on_grid = |&(row, col): &(usize, usize)| {
+        col.checked_add_signed(game.alien_direction)
+            .is_some_and(|col| on_grid((col, row)))
+    };
+    game.fixed_step.step() == DEFAULT_TICK
+        && game.spaceships.len() <= MAX_PLAYERS
+        && (1..=5).contains(&game.difficulty)
+        && matches!(game.alien_direction, 1 | -1)
+        && game.aliens.iter().all(next_step_on_grid)
+        && game
+            .walls
+            .iter()
+            .flatten()
+            .all(|&(row, col)| on_grid((col, row)))
+});
+
 impl SpaceInvaders {
     pub fn new(seed: u64, use_walls: bool, difficulty: u8, mode: GameMode) -> Self {
         let mut aliens = SmallVec::with_capacity(128);
-        for row in 8..11 {
-            for col in 2..GRID_SIZE - 2 {
+        for row in GRID_HEIGHT - 4..GRID_HEIGHT - 1 {
+            for col in 2..GRID_WIDTH - 2 {
                 aliens.push((row, col));
             }
         }
 
         let walls = if use_walls {
             let mut walls = SmallVec::with_capacity(32);
-            for col in [2, 5, 8, 11] {
+            for col in (2..GRID_WIDTH).step_by(3) {
                 for row in 1..3 {
                     walls.push((row, col));
                 }
@@ -95,7 +155,7 @@ impl SpaceInvaders {
         let mut spaceships = SmallVec::new();
         spaceships.push(Spaceship::new(Player::Player1, 0));
         if mode == GameMode::MultiPlayer {
-            spaceships.push(Spaceship::new(Player::Player2, GRID_SIZE - 1));
+            spaceships.push(Spaceship::new(Player::Player2, GRID_WIDTH - 1));
         }
 
         Self {
@@ -105,6 +165,7 @@ impl SpaceInvaders {
             aliens,
             projectiles: SmallVec::with_capacity(128),
             current_time: Duration::default(),
+            fixed_step: FixedStep::new(DEFAULT_TICK),
             alien_direction: 1,
             alien_move_period: 0.8,
             last_alien_move_time: 0.0,
@@ -112,6 +173,7 @@ impl SpaceInvaders {
             walls,
             difficulty: difficulty.clamp(1, 5),
             rng: CustomRng::seed_from_u64(seed),
+            events: GameEvents::new(),
         }
     }
 
@@ -132,18 +194,19 @@ impl SpaceInvaders {
             if !shooting_blocked {
                 self.projectiles
                     .push(Projectile::new(1, spaceship.col, 1, FIRE_SPEED));
+                self.events.push(GameEvent::Shot(player));
             }
         }
     }
 
     fn move_aliens(&mut self) {
         let mut change_direction = false;
-        let mut lowest_alien_row = GRID_SIZE;
+        let mut lowest_alien_row = GRID_HEIGHT;
 
         for alien in &mut self.aliens {
             alien.1 = (alien.1 as isize + self.alien_direction) as usize;
 
-            if alien.1 == 0 || alien.1 == GRID_SIZE - 1 {
+            if alien.1 == 0 || alien.1 == GRID_WIDTH - 1 {
                 change_direction = true;
             }
 
@@ -171,7 +234,7 @@ impl SpaceInvaders {
                 let distance = projectile.speed * delta_time.as_secs_f64();
                 projectile.row += distance * projectile.direction as f64;
 
-                if projectile.row < 0.0 || projectile.row >= (GRID_SIZE - 1) as f64 {
+                if projectile.row < 0.0 || projectile.row >= (GRID_HEIGHT - 1) as f64 {
                     projectile.active = false;
                 }
             }
@@ -183,7 +246,7 @@ impl SpaceInvaders {
     fn detect_collisions(&mut self) {
         // Alien-projectile collisions
         self.aliens.retain(|&mut (row, col)| {
-            !self.projectiles.iter_mut().any(|projectile| {
+            let hit = self.projectiles.iter_mut().any(|projectile| {
                 if projectile.active
                     && (libm::round(projectile.row) as usize == row)
                     && projectile.col == col
@@ -193,7 +256,11 @@ impl SpaceInvaders {
                     return true;
                 }
                 false
-            })
+            });
+            if hit {
+                self.events.push(GameEvent::Explosion);
+            }
+            !hit
         });
 
         // Wall-projectile collisions
@@ -214,7 +281,7 @@ impl SpaceInvaders {
 
         // Remove spaceship if hit by projectile
         self.spaceships.retain(|spaceship| {
-            !self.projectiles.iter_mut().any(|projectile| {
+            let hit = self.projectiles.iter_mut().any(|projectile| {
                 if projectile.active
                     && (libm::round(projectile.row) as usize == 0)
                     && projectile.col == spaceship.col
@@ -225,7 +292,11 @@ impl SpaceInvaders {
                 } else {
                     false
                 }
-            })
+            });
+            if hit {
+                self.events.push(GameEvent::PlayerDied(spaceship.player));
+            }
+            !hit
         });
 
         if self.spaceships.is_empty() {
@@ -235,6 +306,27 @@ impl SpaceInvaders {
         self.projectiles.retain(|projectile| projectile.active);
     }
 
+    fn tick(&mut self, delta_time: Duration) {
+        self.last_alien_move_time += delta_time.as_secs_f64();
+
+        if self.last_alien_move_time > self.alien_move_period {
+            self.move_aliens();
+            self.enemy_fire();
+            self.last_alien_move_time -= self.alien_move_period;
+        }
+
+        // The aliens move first and collide with the shots where they
+        // were at the start of the tick, so a shot and an alien that step
+        // into each other's row in the same tick can't pass
+        self.detect_collisions();
+        self.update_projectiles(delta_time);
+        self.detect_collisions();
+
+        if self.aliens.is_empty() {
+            self.state = GameState::GameOver(None);
+        }
+    }
+
     fn enemy_fire(&mut self) {
         let fire_chance = self.difficulty as f32 / 100.0;
         const ALIEN_FIRE_SPEED: f64 = 5.0;
@@ -281,28 +373,15 @@ impl Game for SpaceInvaders {
     fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
         self.current_time += delta_time;
 
-        match self.state {
-            GameState::Playing => {
-                self.update_projectiles(delta_time);
-
-                self.last_alien_move_time += delta_time.as_secs_f64();
-
-                if self.last_alien_move_time > self.alien_move_period {
-                    self.move_aliens();
-                    self.enemy_fire();
-                    self.last_alien_move_time = 0.0;
-                }
-
-                self.detect_collisions();
-
-                if self.aliens.is_empty() {
-                    self.state = GameState::GameOver(None);
-                }
-            }
-            GameState::GameOver(_) => {
-                self.game_over_animation.update(self.current_time);
+        for _ in 0..self.fixed_step.advance(delta_time) {
+            match self.state {
+                GameState::Playing => self.tick(DEFAULT_TICK),
+                GameState::GameOver(_) => break,
             }
         }
+        if let GameState::GameOver(_) = self.state {
+            self.game_over_animation.update(self.current_time);
+        }
         Ok(())
     }
 
@@ -349,7 +428,7 @@ impl Game for SpaceInvaders {
                     let color = if Some(spaceship.player) == winner {
                         match spaceship.player {
                             Player::Player1 => RGB::new(0, 255, 0),
-                            Player::Player2 => RGB::new(0, 255, 255),
+                            _ => RGB::new(0, 255, 255),
                         }
                     } else {
                         RGB::new(255, 0, 0)
@@ -361,6 +440,19 @@ impl Game for SpaceInvaders {
 
         Ok(render_board)
     }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Playing => GameStatus::playing(),
+            GameState::GameOver(winner) => GameStatus::game_over(winner),
+        };
+        status.level = Some(self.difficulty as u32);
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
 }
 
 #[cfg(test)]
@@ -369,41 +461,41 @@ mod tests {
 
     #[test]
     fn test_spaceship_movement() {
-        let mut game = SpaceInvaders::new(0, false, 3);
-        assert_eq!(game.spaceship.col, 0);
+        let mut game = SpaceInvaders::new(0, false, 3, GameMode::SinglePlayer);
+        assert_eq!(game.spaceships[0].col, 0);
 
-        game.move_spaceship(1);
-        assert_eq!(game.spaceship.col, 1);
+        game.move_spaceship(1, Player::Player1);
+        assert_eq!(game.spaceships[0].col, 1);
 
-        game.move_spaceship(-1);
-        assert_eq!(game.spaceship.col, 0);
+        game.move_spaceship(-1, Player::Player1);
+        assert_eq!(game.spaceships[0].col, 0);
 
         // Test boundary conditions
-        for _ in 0..GRID_SIZE {
-            game.move_spaceship(1);
+        for _ in 0..GRID_WIDTH {
+            game.move_spaceship(1, Player::Player1);
         }
-        assert_eq!(game.spaceship.col, GRID_SIZE - 1);
+        assert_eq!(game.spaceships[0].col, GRID_WIDTH - 1);
 
-        for _ in 0..GRID_SIZE {
-            game.move_spaceship(-1);
+        for _ in 0..GRID_WIDTH {
+            game.move_spaceship(-1, Player::Player1);
         }
-        assert_eq!(game.spaceship.col, 0);
+        assert_eq!(game.spaceships[0].col, 0);
     }
 
     #[test]
     fn test_shoot_projectile() {
-        let mut game = SpaceInvaders::new(0, false, 3);
-        game.shoot_projectile();
+        let mut game = SpaceInvaders::new(0, false, 3, GameMode::SinglePlayer);
+        game.shoot_projectile(Player::Player1);
         assert_eq!(game.projectiles.len(), 1);
         assert_eq!(game.projectiles[0].row, 1.0);
-        assert_eq!(game.projectiles[0].col, game.spaceship.col);
+        assert_eq!(game.projectiles[0].col, game.spaceships[0].col);
         assert!(game.projectiles[0].active);
     }
 
     #[test]
     fn test_update_projectiles() {
-        let mut game = SpaceInvaders::new(0, false, 3);
-        game.shoot_projectile();
+        let mut game = SpaceInvaders::new(0, false, 3, GameMode::SinglePlayer);
+        game.shoot_projectile(Player::Player1);
         game.update_projectiles(Duration::from_secs_f32(0.1));
         assert!(game.projectiles[0].row > 1.0);
         assert!(game.projectiles[0].active);
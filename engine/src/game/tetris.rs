@@ -1,14 +1,20 @@
 use crate::RGB;
 use crate::animation::Animation;
-use crate::game::{ButtonState, CommandType, Game, GameCommand};
+use crate::bot::Bot;
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{
+    ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus, Player,
+};
 use crate::random::CustomRng;
+use crate::snapshot::{snapshot_enum, snapshot_struct};
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 
-const GRID_WIDTH: usize = 12;
-const GRID_HEIGHT: usize = 12;
 const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
+const BOT_MOVE_DELAY: Duration = Duration::from_millis(50);
+
+type Grid = [[Option<RGB>; GRID_WIDTH]; GRID_HEIGHT];
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum TetriminoType {
@@ -74,7 +80,7 @@ impl Tetrimino {
         self.rotation = (self.rotation + 1) % 4;
     }
 
-    fn try_rotate_clockwise(&mut self, grid: &[[Option<RGB>; GRID_WIDTH]; GRID_HEIGHT]) -> bool {
+    fn try_rotate_clockwise(&mut self, grid: &Grid) -> bool {
         let original_rotation = self.rotation;
         let original_position = self.position;
 
@@ -102,7 +108,7 @@ impl Tetrimino {
         false
     }
 
-    fn is_valid_position(&self, grid: &[[Option<RGB>; GRID_WIDTH]; GRID_HEIGHT]) -> bool {
+    fn is_valid_position(&self, grid: &Grid) -> bool {
         self.get_blocks().iter().all(|&(x, y)| {
             x >= 0
                 && x < GRID_WIDTH as i32
@@ -121,7 +127,7 @@ enum GameState {
 
 pub struct TetrisGame {
     state: GameState,
-    grid: [[Option<RGB>; GRID_WIDTH]; GRID_HEIGHT],
+    grid: Grid,
     current_tetrimino: Tetrimino,
     next_tetrimino: Tetrimino,
     current_time: Duration,
@@ -129,8 +135,41 @@ pub struct TetrisGame {
     game_over_animation: Animation,
     rng: CustomRng,
     score: usize,
+    events: GameEvents,
 }
 
+snapshot_enum!(TetriminoType {
+    I,
+    O,
+    T,
+    S,
+    Z,
+    J,
+    L,
+});
+snapshot_struct!(Tetrimino {
+    tetrimino_type,
+    position,
+    rotation,
+} check |tetrimino| tetrimino.rotation < 4);
+snapshot_enum!(GameState { Playing, GameOver });
+snapshot_struct!(TetrisGame {
+    state,
+    grid,
+    current_tetrimino,
+    next_tetrimino,
+    current_time,
+    last_update_time,
+    game_over_animation,
+    rng,
+    score,
+} skip { events } check |game| {
+    // The falling piece is locked into the grid where it is
+    game.last_update_time <= game.current_time
+        && (game.state == GameState::GameOver
+            || game.current_tetrimino.is_valid_position(&game.grid))
+});
+
 impl TetrisGame {
     pub fn new(seed: u64) -> Self {
         let mut rng = CustomRng::seed_from_u64(seed);
@@ -144,6 +183,7 @@ impl TetrisGame {
             game_over_animation: Animation::new(GAME_OVER_ANIMATION_SPEED),
             rng,
             score: 0,
+            events: GameEvents::new(),
         }
     }
 
@@ -174,6 +214,7 @@ impl TetrisGame {
         for (x, y) in self.current_tetrimino.get_blocks() {
             self.grid[y as usize][x as usize] = Some(color);
         }
+        self.events.push(GameEvent::PiecePlaced(Player::Player1));
 
         self.clear_lines();
         self.spawn_new_tetrimino();
@@ -191,6 +232,9 @@ impl TetrisGame {
             }
         }
 
+        if lines_cleared > 0 {
+            self.events.push(GameEvent::LinesCleared(lines_cleared));
+        }
         self.score += match lines_cleared {
             1 => 100,
             2 => 300,
@@ -208,6 +252,7 @@ impl TetrisGame {
 
         if !self.is_valid_position(&self.current_tetrimino) {
             self.state = GameState::GameOver;
+            self.events.push(GameEvent::PlayerDied(Player::Player1));
         }
     }
 
@@ -230,6 +275,121 @@ impl TetrisGame {
     fn is_valid_position(&self, tetrimino: &Tetrimino) -> bool {
         tetrimino.is_valid_position(&self.grid)
     }
+
+    // Tries every rotation and column for the falling piece and returns the
+    // rotation and column of the one that leaves the best grid. Pieces that
+    // stick out of the top when rotated are tried a little lower, where they
+    // will be after falling.
+    fn best_placement(&self) -> Option<(usize, i32)> {
+        let mut best: Option<((usize, i32), f32)> = None;
+        for rotation in 0..4 {
+            for col in -2..GRID_WIDTH as i32 + 2 {
+                let mut tetrimino = self.current_tetrimino.clone();
+                tetrimino.rotation = rotation;
+                tetrimino.position.0 = col;
+                let Some(tetrimino) = (0..3)
+                    .map(|dy| {
+                        let mut lowered = tetrimino.clone();
+                        lowered.move_by(0, -dy);
+                        lowered
+                    })
+                    .find(|lowered| self.is_valid_position(lowered))
+                else {
+                    continue;
+                };
+                let mut tetrimino = tetrimino;
+                while tetrimino.is_valid_position(&self.grid) {
+                    tetrimino.move_by(0, -1);
+                }
+                tetrimino.move_by(0, 1);
+
+                let mut grid = self.grid;
+                for (x, y) in tetrimino.get_blocks() {
+                    grid[y as usize][x as usize] = Some(RGB::default());
+                }
+                let score = evaluate_grid(&grid);
+                if best.is_none_or(|(_, best_score)| score > best_score) {
+                    best = Some(((rotation, col), score));
+                }
+            }
+        }
+        best.map(|(placement, _)| placement)
+    }
+}
+
+// The usual weights for a one piece lookahead: clear lines and keep the
+// stack low, flat and without holes
+fn evaluate_grid(grid: &Grid) -> f32 {
+    let is_full = |row: &&[Option<RGB>; GRID_WIDTH]| row.iter().all(Option::is_some);
+    let lines = grid.iter().filter(is_full).count();
+    let mut heights = [0; GRID_WIDTH];
+    let mut holes = 0;
+    for (x, height) in heights.iter_mut().enumerate() {
+        // The stack as it is after the full lines are cleared
+        let column = grid
+            .iter()
+            .filter(|row| !is_full(row))
+            .map(|row| row[x].is_some());
+        *height = column
+            .clone()
+            .enumerate()
+            .filter(|&(_, filled)| filled)
+            .last()
+            .map_or(0, |(y, _)| y + 1);
+        holes += column.take(*height).filter(|filled| !filled).count();
+    }
+    let aggregate_height: usize = heights.iter().sum();
+    let bumpiness: usize = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+    0.76 * lines as f32
+        - 0.51 * aggregate_height as f32
+        - 0.36 * holes as f32
+        - 0.18 * bumpiness as f32
+}
+
+/// Rotates and moves every piece to the placement that scores best and
+/// drops it.
+#[derive(Default)]
+pub(crate) struct TetrisBot {
+    move_cooldown: Duration,
+}
+
+snapshot_struct!(TetrisBot { move_cooldown });
+
+impl TetrisBot {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Bot<TetrisGame> for TetrisBot {
+    fn next_command(
+        &mut self,
+        game: &TetrisGame,
+        player: Player,
+        delta_time: Duration,
+    ) -> Option<GameCommand> {
+        self.move_cooldown = self.move_cooldown.saturating_sub(delta_time);
+        if game.state != GameState::Playing || !self.move_cooldown.is_zero() {
+            return None;
+        }
+        let (rotation, col) = game.best_placement()?;
+        let tetrimino = &game.current_tetrimino;
+        let can_rotate = tetrimino.clone().try_rotate_clockwise(&game.grid);
+        let command_type = if tetrimino.rotation != rotation && can_rotate {
+            CommandType::Select
+        } else if tetrimino.position.0 < col {
+            CommandType::Right
+        } else if tetrimino.position.0 > col {
+            CommandType::Left
+        } else if tetrimino.rotation != rotation {
+            // Waits for the piece to fall far enough to turn
+            return None;
+        } else {
+            CommandType::Up
+        };
+        self.move_cooldown = BOT_MOVE_DELAY;
+        Some(GameCommand::new(command_type, ButtonState::Pressed, player))
+    }
 }
 
 impl Game for TetrisGame {
@@ -261,7 +421,7 @@ impl Game for TetrisGame {
         match self.state {
             GameState::Playing => {
                 if self.current_time - self.last_update_time > UPDATE_INTERVAL {
-                    self.last_update_time = self.current_time;
+                    self.last_update_time += UPDATE_INTERVAL;
 
                     let mut new_tetrimino = self.current_tetrimino.clone();
                     new_tetrimino.move_by(0, -1);
@@ -322,4 +482,50 @@ impl Game for TetrisGame {
 
         Ok(render_board)
     }
+
+    fn final_score(&self) -> Option<u32> {
+        match self.state {
+            GameState::GameOver => Some(self.score as u32),
+            _ => None,
+        }
+    }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Playing => GameStatus::playing(),
+            GameState::GameOver => GameStatus::game_over(None),
+        };
+        status.scores[0] = Some(self.score as u32);
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotPlayer;
+
+    #[test]
+    fn test_bot_clears_lines() {
+        // Looking a single piece ahead doesn't keep the bot alive on grids
+        // smaller than the default one
+        if GRID_WIDTH < 12 || GRID_HEIGHT < 12 {
+            return;
+        }
+        let mut game = BotPlayer::new(TetrisGame::new(1), TetrisBot::new(), Player::Player1);
+        // A line takes longer to fill on wide grids
+        for _ in 0..1500 * GRID_WIDTH / 12 {
+            game.update(Duration::from_millis(20)).unwrap();
+        }
+        assert!(!game.status().is_over());
+        assert!(
+            game.game().score >= 1000,
+            "only scored {}",
+            game.game().score
+        );
+    }
 }
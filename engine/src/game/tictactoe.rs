@@ -1,13 +1,40 @@
 use crate::RGB;
 use crate::animation::Animation;
-use crate::game::{Board, ButtonState, CommandType, Game, GameCommand};
+use crate::event::{GameEvent, GameEvents};
+use crate::game::{Board, ButtonState, CommandType, GRID_HEIGHT, Game, GameCommand, GameStatus};
+use crate::random::CustomRng;
+use crate::snapshot::{snapshot_enum, snapshot_struct};
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 use smallvec::SmallVec;
 
 use super::Player;
+use super::computer::ComputerTurn;
 
 const WIN_ANIMATION_SPEED: Duration = Duration::from_millis(50);
+// How often the casual computer plays a random cell instead of the best one
+const MISTAKE_CHANCE: f64 = 0.3;
+// Center first, then corners, so equally good moves look natural
+const MOVE_ORDER: [(usize, usize); 9] = [
+    (1, 1),
+    (0, 0),
+    (2, 0),
+    (0, 2),
+    (2, 2),
+    (1, 0),
+    (0, 1),
+    (2, 1),
+    (1, 2),
+];
+
+/// Strength of the computer opponent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    /// Sometimes makes mistakes and can be beaten
+    Casual,
+    /// Never loses
+    Perfect,
+}
 
 #[derive(Debug, PartialEq)]
 pub enum TicTacToeState {
@@ -40,13 +67,49 @@ pub struct TicTacToe {
     pub state: TicTacToeState,
     win_animation: Animation,
     current_time: Duration,
+    // Casual or perfect play, None for two players
+    computer: Option<Difficulty>,
+    computer_turn: ComputerTurn,
+    rng: CustomRng,
+    events: GameEvents,
 }
 
+snapshot_enum!(TicTacToeState {
+    Playing,
+    Win(line),
+    Tie,
+    Finished,
+});
+snapshot_enum!(Cell {
+    Empty,
+    PlayerX,
+    PlayerO,
+});
+snapshot_enum!(Difficulty { Casual, Perfect });
+snapshot_struct!(TicTacToe {
+    board,
+    active_player,
+    active_cell,
+    state,
+    win_animation,
+    current_time,
+    computer,
+    computer_turn,
+    rng,
+} skip { events } check |game| {
+    let on_board = |(col, row): (usize, usize)| col < 3 && row < 3;
+    on_board(game.active_cell)
+        && match &game.state {
+            TicTacToeState::Win(line) => line.iter().all(|&cell| on_board(cell)),
+            _ => true,
+        }
+});
+
 impl Game for TicTacToe {
     fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
         match &self.state {
             TicTacToeState::Playing => {
-                if input_command.player != self.active_player {
+                if input_command.player != self.active_player || self.is_computer_turn() {
                     return Ok(());
                 }
 
@@ -72,30 +135,11 @@ impl Game for TicTacToe {
                                 self.active_cell.1 -= 1;
                             }
                         }
-                        CommandType::Select => {
-                            if self.board.get(self.active_cell.0, self.active_cell.1) == Cell::Empty
-                            {
-                                self.board.set(
-                                    self.active_cell.0,
-                                    self.active_cell.1,
-                                    self._get_cell_from_player(self.active_player),
-                                );
-                                if let Some(winning_line) = self.check_win(self.active_cell) {
-                                    self.state = TicTacToeState::Win(winning_line);
-                                } else {
-                                    self.active_player = match self.active_player {
-                                        Player::Player1 => Player::Player2,
-                                        Player::Player2 => Player::Player1,
-                                    };
-                                }
-                            }
-                            if self.check_tie(self.active_cell) {
-                                self.state = TicTacToeState::Tie;
-                            }
-                        }
+                        CommandType::Select => self.place_piece(self.active_cell),
                         CommandType::Quit => {
                             self.state = TicTacToeState::Finished;
                         }
+                        CommandType::Pause => {}
                     }
                 }
             }
@@ -108,6 +152,14 @@ impl Game for TicTacToe {
                             self.active_player = Player::Player1;
                             self.active_cell = (0, 0);
                         }
+                        // The difficulty is picked between rounds
+                        CommandType::Up | CommandType::Down => {
+                            self.computer = match self.computer {
+                                Some(Difficulty::Casual) => Some(Difficulty::Perfect),
+                                Some(Difficulty::Perfect) => Some(Difficulty::Casual),
+                                None => None,
+                            };
+                        }
                         _ => return Ok(()),
                     }
                 }
@@ -122,7 +174,15 @@ impl Game for TicTacToe {
         self.current_time += delta_time;
 
         match &self.state {
-            TicTacToeState::Playing => {}
+            TicTacToeState::Playing => {
+                if let Some(difficulty) = self.computer
+                    && self.is_computer_turn()
+                    && self.computer_turn.wait(delta_time)
+                {
+                    self.active_cell = self.computer_move(difficulty);
+                    self.place_piece(self.active_cell);
+                }
+            }
             TicTacToeState::Win(_) | TicTacToeState::Tie => {
                 self.win_animation.update(self.current_time);
             }
@@ -137,25 +197,25 @@ impl Game for TicTacToe {
         // Closure to draw 'X' centered at (cx, cy)
         let draw_x = |cx: usize, cy: usize, grid: &mut RenderBoard, color: RGB| {
             if cx > 0 && cy > 0 && cx < grid.cols() - 1 && cy < grid.rows() - 1 {
-                grid.set(cy - 1, cx - 1, color);
-                grid.set(cy - 1, cx + 1, color);
-                grid.set(cy, cx, color);
-                grid.set(cy + 1, cx - 1, color);
-                grid.set(cy + 1, cx + 1, color);
+                grid.set(cx - 1, cy - 1, color);
+                grid.set(cx + 1, cy - 1, color);
+                grid.set(cx, cy, color);
+                grid.set(cx - 1, cy + 1, color);
+                grid.set(cx + 1, cy + 1, color);
             }
         };
 
         // Closure to draw 'O' centered at (cx, cy)
         let draw_o = |cx: usize, cy: usize, grid: &mut RenderBoard, color: RGB| {
             if cx > 0 && cy > 0 && cx < grid.cols() - 1 && cy < grid.rows() - 1 {
-                grid.set(cy - 1, cx - 1, color);
-                grid.set(cy - 1, cx, color);
-                grid.set(cy - 1, cx + 1, color);
-                grid.set(cy, cx - 1, color);
-                grid.set(cy, cx + 1, color);
-                grid.set(cy + 1, cx - 1, color);
-                grid.set(cy + 1, cx, color);
-                grid.set(cy + 1, cx + 1, color);
+                grid.set(cx - 1, cy - 1, color);
+                grid.set(cx, cy - 1, color);
+                grid.set(cx + 1, cy - 1, color);
+                grid.set(cx - 1, cy, color);
+                grid.set(cx + 1, cy, color);
+                grid.set(cx - 1, cy + 1, color);
+                grid.set(cx, cy + 1, color);
+                grid.set(cx + 1, cy + 1, color);
             }
         };
 
@@ -167,16 +227,16 @@ impl Game for TicTacToe {
                         match cell {
                             Cell::PlayerX => {
                                 draw_x(
-                                    row * 3 + 1,
                                     col * 3 + 1,
+                                    row * 3 + 1,
                                     &mut render_board,
                                     RGB::new(255, 0, 0),
                                 );
                             }
                             Cell::PlayerO => {
                                 draw_o(
-                                    row * 3 + 1,
                                     col * 3 + 1,
+                                    row * 3 + 1,
                                     &mut render_board,
                                     RGB::new(0, 0, 255),
                                 );
@@ -189,11 +249,11 @@ impl Game for TicTacToe {
                         match cell {
                             Cell::PlayerX => {
                                 rgb = RGB::new(185, 101, 207);
-                                draw_x(row * 3 + 1, col * 3 + 1, &mut render_board, rgb);
+                                draw_x(col * 3 + 1, row * 3 + 1, &mut render_board, rgb);
                             }
                             Cell::PlayerO => {
                                 rgb = RGB::new(102, 204, 187);
-                                draw_o(row * 3 + 1, col * 3 + 1, &mut render_board, rgb);
+                                draw_o(col * 3 + 1, row * 3 + 1, &mut render_board, rgb);
                             }
                             _ => {}
                         }
@@ -207,10 +267,10 @@ impl Game for TicTacToe {
                             }
                             match self.active_player {
                                 Player::Player1 => {
-                                    draw_x(row * 3 + 1, col * 3 + 1, &mut render_board, rgb);
+                                    draw_x(col * 3 + 1, row * 3 + 1, &mut render_board, rgb);
                                 }
-                                Player::Player2 => {
-                                    draw_o(row * 3 + 1, col * 3 + 1, &mut render_board, rgb);
+                                _ => {
+                                    draw_o(col * 3 + 1, row * 3 + 1, &mut render_board, rgb);
                                 }
                             }
                         }
@@ -222,10 +282,10 @@ impl Game for TicTacToe {
                 for (col, row) in winning_line {
                     match self.active_player {
                         Player::Player1 => {
-                            draw_x(row * 3 + 1, col * 3 + 1, &mut render_board, color);
+                            draw_x(col * 3 + 1, row * 3 + 1, &mut render_board, color);
                         }
-                        Player::Player2 => {
-                            draw_o(row * 3 + 1, col * 3 + 1, &mut render_board, color);
+                        _ => {
+                            draw_o(col * 3 + 1, row * 3 + 1, &mut render_board, color);
                         }
                     }
                 }
@@ -236,10 +296,10 @@ impl Game for TicTacToe {
                     for row in 0..self.board.rows() {
                         match self.board.get(col, row) {
                             Cell::PlayerX => {
-                                draw_x(row * 3 + 1, col * 3 + 1, &mut render_board, color);
+                                draw_x(col * 3 + 1, row * 3 + 1, &mut render_board, color);
                             }
                             Cell::PlayerO => {
-                                draw_o(row * 3 + 1, col * 3 + 1, &mut render_board, color);
+                                draw_o(col * 3 + 1, row * 3 + 1, &mut render_board, color);
                             }
                             _ => {}
                         }
@@ -248,11 +308,41 @@ impl Game for TicTacToe {
             }
             TicTacToeState::Finished => {}
         }
+
+        // One dot for casual and two for perfect, above the board when
+        // the grid has room for it
+        if let Some(difficulty) = self.computer
+            && GRID_HEIGHT > 9
+            && self.state != TicTacToeState::Finished
+        {
+            let dots = match difficulty {
+                Difficulty::Casual => 1,
+                Difficulty::Perfect => 2,
+            };
+            for col in 0..dots {
+                render_board.set(col, GRID_HEIGHT - 1, RGB::new(102, 204, 187));
+            }
+        }
         Ok(render_board)
     }
+
+    fn status(&self) -> GameStatus {
+        match self.state {
+            TicTacToeState::Playing => GameStatus::playing(),
+            // The active player is not switched after the winning move
+            TicTacToeState::Win(_) => GameStatus::game_over(Some(self.active_player)),
+            TicTacToeState::Tie | TicTacToeState::Finished => GameStatus::game_over(None),
+        }
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
 }
 
 impl TicTacToe {
+    pub const MIN_GRID_SIZE: (usize, usize) = (9, 9);
+
     pub fn new() -> Self {
         Self {
             board: TicTacToeBoard::new(),
@@ -261,13 +351,100 @@ impl TicTacToe {
             state: TicTacToeState::Playing,
             win_animation: Animation::new(WIN_ANIMATION_SPEED),
             current_time: Duration::from_millis(0),
+            computer: None,
+            computer_turn: ComputerTurn::new(),
+            rng: CustomRng::seed_from_u64(0),
+            events: GameEvents::new(),
         }
     }
 
+    /// A single player game, the computer starts out casual and sometimes
+    /// misses the best cell. Up and Down switch it to perfect between rounds.
+    pub fn with_computer(seed: u64) -> Self {
+        Self {
+            computer: Some(Difficulty::Casual),
+            rng: CustomRng::seed_from_u64(seed),
+            ..Self::new()
+        }
+    }
+
+    fn is_computer_turn(&self) -> bool {
+        self.computer.is_some() && self.active_player == ComputerTurn::PLAYER
+    }
+
+    fn place_piece(&mut self, cell: (usize, usize)) {
+        if self._make_move(cell, self.active_player).is_err() {
+            return;
+        }
+        self.events.push(GameEvent::PiecePlaced(self.active_player));
+        if let Some(winning_line) = self.check_win(cell) {
+            self.state = TicTacToeState::Win(winning_line);
+        } else if self.check_tie(cell) {
+            self.state = TicTacToeState::Tie;
+        } else {
+            self.active_player = self.active_player.opponent();
+            self.computer_turn.restart();
+        }
+    }
+
+    fn computer_move(&mut self, difficulty: Difficulty) -> (usize, usize) {
+        let empty = MOVE_ORDER
+            .into_iter()
+            .filter(|&(col, row)| self.board.get(col, row) == Cell::Empty);
+        if difficulty == Difficulty::Casual && self.rng.gen_bool(MISTAKE_CHANCE) {
+            let empty: SmallVec<[(usize, usize); 9]> = empty.collect();
+            return empty[self.rng.gen_range(0, empty.len() as u32) as usize];
+        }
+
+        let player = self.active_player;
+        let mut best = None;
+        let mut alpha = -i32::MAX;
+        for cell in empty.collect::<SmallVec<[(usize, usize); 9]>>() {
+            let score = self.score_move(cell, player, alpha, i32::MAX);
+            if best.is_none() || score > alpha {
+                alpha = score;
+                best = Some(cell);
+            }
+        }
+        best.unwrap_or(self.active_cell)
+    }
+
+    // Minimax score for `player` of playing `cell`, searched to the end
+    // of the game with alpha-beta pruning. Quicker wins score higher.
+    fn score_move(&mut self, cell: (usize, usize), player: Player, alpha: i32, beta: i32) -> i32 {
+        let _ = self._make_move(cell, player);
+        let score = if self.check_win(cell).is_some() {
+            let empty = self.board.cells.iter().flatten();
+            1 + empty.filter(|&&cell| cell == Cell::Empty).count() as i32
+        } else if self.board.full() {
+            0
+        } else {
+            -self.best_score(player.opponent(), -beta, -alpha)
+        };
+        self.board.set(cell.0, cell.1, Cell::Empty);
+        score
+    }
+
+    fn best_score(&mut self, player: Player, mut alpha: i32, beta: i32) -> i32 {
+        let mut best = -i32::MAX;
+        for (col, row) in MOVE_ORDER {
+            if self.board.get(col, row) != Cell::Empty {
+                continue;
+            }
+            let score = self.score_move((col, row), player, alpha, beta);
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
     pub fn _get_cell_from_player(&self, player: Player) -> Cell {
         match player {
             Player::Player1 => Cell::PlayerX,
-            Player::Player2 => Cell::PlayerO,
+            _ => Cell::PlayerO,
         }
     }
 
@@ -330,6 +507,7 @@ impl TicTacToe {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::game::computer::COMPUTER_MOVE_DELAY;
     use smallvec::smallvec;
 
     #[test]
@@ -470,7 +648,7 @@ mod tests {
         );
 
         // this should be a win for Player 1
-        assert_ne!(true, game.check_tie((2, 2)));
+        assert!(!game.check_tie((2, 2)));
     }
     #[test]
     fn test_reset_after_tie() {
@@ -508,4 +686,73 @@ mod tests {
             }
         }
     }
+
+    // Tries every sequence of human moves against the computer
+    fn assert_human_never_wins(game: &mut TicTacToe) {
+        for (col, row) in MOVE_ORDER {
+            if game.board.get(col, row) != Cell::Empty {
+                continue;
+            }
+            let board = game.board;
+            game.active_player = Player::Player1;
+            game.place_piece((col, row));
+            assert!(!matches!(game.state, TicTacToeState::Win(_)));
+            if game.state == TicTacToeState::Playing {
+                let cell = game.computer_move(Difficulty::Perfect);
+                game.place_piece(cell);
+                if game.state == TicTacToeState::Playing {
+                    assert_human_never_wins(game);
+                }
+            }
+            game.board = board;
+            game.state = TicTacToeState::Playing;
+        }
+    }
+
+    #[test]
+    fn test_perfect_computer_never_loses() {
+        let mut game = TicTacToe::with_computer(1);
+        assert_human_never_wins(&mut game);
+    }
+
+    #[test]
+    fn test_casual_computer_makes_mistakes() {
+        let mut blocked = [0; 2];
+        for seed in 0..50 {
+            for (index, difficulty) in [Difficulty::Casual, Difficulty::Perfect]
+                .into_iter()
+                .enumerate()
+            {
+                let mut game = TicTacToe::with_computer(seed);
+                game._make_move((0, 0), Player::Player1).unwrap();
+                game._make_move((1, 1), Player::Player2).unwrap();
+                game._make_move((1, 0), Player::Player1).unwrap();
+                game.active_player = Player::Player2;
+                if game.computer_move(difficulty) == (2, 0) {
+                    blocked[index] += 1;
+                }
+            }
+        }
+        assert!(blocked[0] > 0 && blocked[0] < 50);
+        assert_eq!(blocked[1], 50);
+    }
+
+    #[test]
+    fn test_computer_answers_after_delay() {
+        let mut game = TicTacToe::with_computer(3);
+        let press =
+            |command_type, player| GameCommand::new(command_type, ButtonState::Pressed, player);
+
+        game.process_input(press(CommandType::Select, Player::Player1))
+            .unwrap();
+        game.process_input(press(CommandType::Right, Player::Player2))
+            .unwrap();
+        game.update(COMPUTER_MOVE_DELAY / 2).unwrap();
+        assert_eq!(game.active_player, Player::Player2);
+
+        game.update(COMPUTER_MOVE_DELAY).unwrap();
+        assert_eq!(game.active_player, Player::Player1);
+        let pieces = game.board.cells.iter().flatten();
+        assert_eq!(pieces.filter(|&&cell| cell != Cell::Empty).count(), 2);
+    }
 }
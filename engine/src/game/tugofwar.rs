@@ -1,13 +1,36 @@
 use crate::RGB;
 use crate::animation::Animation;
-use crate::game::{ButtonState, CommandType, Game, GameCommand, Player};
+use crate::game::{
+    ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus, MAX_PLAYERS,
+    Player,
+};
+use crate::snapshot::{snapshot_enum, snapshot_struct};
 use crate::{GameError, RenderBoard};
 use core::time::Duration;
 
-const GRID_SIZE: usize = 12;
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
 const COUNTDOWN_DURATION: Duration = Duration::from_secs(1);
 const WIN_THRESHOLD: i32 = 12; // Lower this for a shorter game
+// Presses needed to win a race, as many as pulling the bar to one end
+const RACE_LENGTH: i32 = 2 * WIN_THRESHOLD;
+const PLAYER_COLORS: [RGB; MAX_PLAYERS] = [
+    RGB { r: 0, g: 255, b: 0 },
+    RGB {
+        r: 0,
+        g: 255,
+        b: 255,
+    },
+    RGB {
+        r: 255,
+        g: 0,
+        b: 255,
+    },
+    RGB {
+        r: 255,
+        g: 160,
+        b: 0,
+    },
+];
 
 #[derive(Debug, PartialEq)]
 enum GameState {
@@ -23,25 +46,69 @@ enum CountdownState {
     Green,
 }
 
+/// Two players pull a bar towards their side. More players race instead,
+/// each filling their own band of rows.
 pub struct ButtonWar {
     state: GameState,
     score_difference: i32,
     current_time: Duration,
     countdown_timer: Duration,
     game_over_animation: Animation,
+    players: usize,
+    presses: [i32; MAX_PLAYERS],
 }
 
+snapshot_enum!(GameState {
+    Countdown(countdown),
+    Playing,
+    GameOver(winner),
+});
+snapshot_enum!(CountdownState { Red, Yellow, Green });
+snapshot_struct!(ButtonWar {
+    state,
+    score_difference,
+    current_time,
+    countdown_timer,
+    game_over_animation,
+    players,
+    presses,
+} check |game| {
+    (2..=MAX_PLAYERS).contains(&game.players)
+        && (-WIN_THRESHOLD..=WIN_THRESHOLD).contains(&game.score_difference)
+        && game
+            .presses
+            .iter()
+            .all(|presses| (0..=RACE_LENGTH).contains(presses))
+});
+
 impl ButtonWar {
     pub fn new() -> Self {
+        Self::with_players(2)
+    }
+
+    pub fn with_players(players: usize) -> Self {
         Self {
             state: GameState::Countdown(CountdownState::Red),
             score_difference: 0,
             current_time: Duration::default(),
             countdown_timer: Duration::default(),
             game_over_animation: Animation::new(GAME_OVER_ANIMATION_SPEED),
+            players: players.clamp(2, MAX_PLAYERS),
+            presses: [0; MAX_PLAYERS],
         }
     }
 
+    fn is_race(&self) -> bool {
+        self.players > 2
+    }
+
+    // The rows of the band of player `index`, player 1 on top
+    fn band(&self, index: usize) -> core::ops::Range<usize> {
+        let height = GRID_HEIGHT / self.players;
+        let top = GRID_HEIGHT - index * height;
+        top - height..top
+    }
+
     fn update_countdown(&mut self, delta_time: Duration) {
         self.countdown_timer += delta_time;
         if self.countdown_timer >= COUNTDOWN_DURATION {
@@ -62,9 +129,20 @@ impl ButtonWar {
     }
 
     fn update_score(&mut self, player: Player) {
+        if self.is_race() {
+            let Some(presses) = self.presses[..self.players].get_mut(player.index()) else {
+                return;
+            };
+            *presses += 1;
+            if *presses >= RACE_LENGTH {
+                self.state = GameState::GameOver(player);
+            }
+            return;
+        }
         match player {
             Player::Player1 => self.score_difference += 1,
             Player::Player2 => self.score_difference -= 1,
+            Player::Player3 | Player::Player4 => return,
         }
 
         if self.score_difference.abs() >= WIN_THRESHOLD {
@@ -113,18 +191,37 @@ impl Game for ButtonWar {
                     CountdownState::Green => RGB::new(0, 255, 0),
                 };
                 // Render countdown light in the center
-                for i in GRID_SIZE / 2 - 4..=GRID_SIZE / 2 + 3 {
-                    for j in GRID_SIZE / 2 - 4..=GRID_SIZE / 2 + 3 {
+                for i in GRID_WIDTH / 2 - 4..=GRID_WIDTH / 2 + 3 {
+                    for j in GRID_HEIGHT / 2 - 4..=GRID_HEIGHT / 2 + 3 {
                         render_board.set(i, j, color);
                     }
                 }
             }
+            GameState::Playing | GameState::GameOver(_) if self.is_race() => {
+                let winner = match self.state {
+                    GameState::GameOver(winner) => Some(winner),
+                    _ => None,
+                };
+                for (index, &presses) in self.presses[..self.players].iter().enumerate() {
+                    let length = presses as usize * GRID_WIDTH / RACE_LENGTH as usize;
+                    let color = if winner.is_some_and(|winner| winner.index() == index) {
+                        self.game_over_animation.get_color()
+                    } else {
+                        PLAYER_COLORS[index]
+                    };
+                    for row in self.band(index) {
+                        for col in 0..length {
+                            render_board.set(col, row, color);
+                        }
+                    }
+                }
+            }
             GameState::Playing | GameState::GameOver(_) => {
                 // Render the tug-of-war bar
-                let bar_position = (GRID_SIZE as i32 / 2)
-                    + (self.score_difference * GRID_SIZE as i32 / (2 * WIN_THRESHOLD));
-                for row in 0..GRID_SIZE {
-                    for col in 0..GRID_SIZE {
+                let bar_position = (GRID_WIDTH as i32 / 2)
+                    + (self.score_difference * GRID_WIDTH as i32 / (2 * WIN_THRESHOLD));
+                for row in 0..GRID_HEIGHT {
+                    for col in 0..GRID_WIDTH {
                         let color = if col as i32 == bar_position {
                             RGB::new(255, 255, 255) // White bar
                         } else if (col as i32) < bar_position {
@@ -139,8 +236,8 @@ impl Game for ButtonWar {
                 if let GameState::GameOver(_) = self.state {
                     // Animate the winning side
                     let winning_color = self.game_over_animation.get_color();
-                    for row in 0..GRID_SIZE {
-                        for col in 0..GRID_SIZE {
+                    for row in 0..GRID_HEIGHT {
+                        for col in 0..GRID_WIDTH {
                             if (self.score_difference > 0 && col as i32 <= bar_position)
                                 || (self.score_difference < 0 && col as i32 >= bar_position)
                             {
@@ -154,4 +251,17 @@ impl Game for ButtonWar {
 
         Ok(render_board)
     }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Countdown(_) | GameState::Playing => GameStatus::playing(),
+            GameState::GameOver(winner) => GameStatus::game_over(Some(winner)),
+        };
+        if self.is_race() {
+            for (score, &presses) in status.scores.iter_mut().zip(&self.presses[..self.players]) {
+                *score = Some(presses as u32);
+            }
+        }
+        status
+    }
 }
@@ -1,19 +1,24 @@
 use crate::GameError;
 use crate::animation::Animation;
+use crate::bot::Bot;
+use crate::event::{GameEvent, GameEvents};
 use crate::game::{
-    ButtonState, CommandType, Game, GameCommand, Player as GamePlayer, RGB, RenderBoard,
+    ButtonState, CommandType, DEFAULT_TICK, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand, GameStatus,
+    Player as GamePlayer, RGB, RenderBoard,
 };
 
 use crate::random::CustomRng;
+use crate::snapshot::{on_grid, snapshot_enum, snapshot_struct};
+use crate::timestep::FixedStep;
 use core::time::Duration;
 use smallvec::SmallVec;
 
-const GRID_SIZE: usize = 12;
 const GAME_OVER_ANIMATION_SPEED: Duration = Duration::from_millis(50);
 const VICTORY_ANIMATION_DURATION: Duration = Duration::from_secs(5);
 const VICTORY_ANIMATION_SPEED: Duration = Duration::from_millis(100);
 const WALLS_PER_COLOR: usize = 5;
 const MAX_WALLS: usize = 50; // 10 colors * 5 walls per color
+const BOT_MOVE_DELAY: Duration = Duration::from_millis(50);
 
 const DANGER_COLORS: [(u8, u8, u8); 10] = [
     (0, 255, 0),     // Green (least danger)
@@ -40,8 +45,8 @@ impl Player {
         Self {
             col: 0,
             row: match player {
-                GamePlayer::Player1 => GRID_SIZE / 3,
-                GamePlayer::Player2 => 2 * GRID_SIZE / 3,
+                GamePlayer::Player1 => GRID_HEIGHT / 3,
+                _ => 2 * GRID_HEIGHT / 3,
             },
             is_alive: true,
             color: player_color(player),
@@ -55,7 +60,7 @@ impl Player {
     }
 
     fn move_up(&mut self) {
-        if self.row < GRID_SIZE - 1 {
+        if self.row < GRID_HEIGHT - 1 {
             self.row += 1;
         }
     }
@@ -86,8 +91,11 @@ enum GameState {
 pub struct WallDodger {
     state: GameState,
     players: SmallVec<[Player; 2]>,
-    walls: SmallVec<[Wall; GRID_SIZE]>,
+    walls: SmallVec<[Wall; GRID_WIDTH]>,
     current_time: Duration,
+    // Walls move and hit in ticks of equal length, so they hit the same
+    // players at any frame rate
+    fixed_step: FixedStep,
     wall_gap: usize,
     wall_period: f64,
     last_wall_time: f64,
@@ -97,8 +105,52 @@ pub struct WallDodger {
     walls_passed: usize,
     color_index: usize,
     is_multiplayer: bool,
+    events: GameEvents,
 }
 
+snapshot_struct!(Player {
+    col,
+    row,
+    is_alive,
+    color,
+} check |player| on_grid((player.col, player.row)));
+snapshot_struct!(Wall {
+    col,
+    gap_row,
+    gap_size,
+} check |wall| {
+    wall.col < GRID_WIDTH
+        && wall
+            .gap_row
+            .checked_add(wall.gap_size)
+            .is_some_and(|end| end <= GRID_HEIGHT)
+});
+snapshot_enum!(GameState {
+    Playing,
+    GameOver,
+    Victory(elapsed),
+});
+snapshot_struct!(WallDodger {
+    state,
+    players,
+    walls,
+    current_time,
+    fixed_step,
+    wall_gap,
+    wall_period,
+    last_wall_time,
+    rng,
+    game_over_animation,
+    victory_animation,
+    walls_passed,
+    color_index,
+    is_multiplayer,
+} skip { events } check |game| {
+    game.fixed_step.step() == DEFAULT_TICK
+        && (1..=2).contains(&game.players.len())
+        && game.wall_gap <= GRID_WIDTH
+});
+
 impl WallDodger {
     pub fn new(seed: u64, is_multiplayer: bool) -> Self {
         let mut players = SmallVec::new();
@@ -112,6 +164,7 @@ impl WallDodger {
             players,
             walls: SmallVec::new(),
             current_time: Duration::ZERO,
+            fixed_step: FixedStep::new(DEFAULT_TICK),
             wall_gap: 8,
             wall_period: 0.18,
             last_wall_time: 0.0,
@@ -121,6 +174,7 @@ impl WallDodger {
             walls_passed: 0,
             color_index: 0,
             is_multiplayer,
+            events: GameEvents::new(),
         }
     }
 
@@ -139,18 +193,32 @@ impl WallDodger {
         });
         if wall_passed {
             self.walls_passed += 1;
+            for (index, player) in self.players.iter().enumerate() {
+                if player.is_alive {
+                    self.events.push(GameEvent::Scored(game_player(index)));
+                }
+            }
             self.level_up();
         }
     }
 
+    // A gap is at most as many rows away from the last one as there are
+    // columns between the walls, which keeps it within reach on tall grids
     fn add_wall(&mut self) {
         const GAP_SIZE: usize = 4;
-        let gap_row = self.rng.gen_range(0, (GRID_SIZE - GAP_SIZE + 1) as u32) as usize;
-        self.walls.push(Wall::new(GRID_SIZE - 1, gap_row, GAP_SIZE));
+        let last_gap_row = self
+            .walls
+            .last()
+            .map_or(GRID_HEIGHT / 2, |wall| wall.gap_row);
+        let lowest = last_gap_row.saturating_sub(self.wall_gap);
+        let highest = (last_gap_row + self.wall_gap).min(GRID_HEIGHT - GAP_SIZE);
+        let gap_row = self.rng.gen_range(lowest as u32, highest as u32 + 1) as usize;
+        self.walls
+            .push(Wall::new(GRID_WIDTH - 1, gap_row, GAP_SIZE));
     }
 
     fn detect_collisions(&mut self) {
-        for player in self.players.iter_mut() {
+        for (index, player) in self.players.iter_mut().enumerate() {
             if player.is_alive {
                 for wall in &self.walls {
                     if wall.col == player.col
@@ -158,6 +226,7 @@ impl WallDodger {
                             && player.row < wall.gap_row + wall.gap_size)
                     {
                         player.is_alive = false;
+                        self.events.push(GameEvent::PlayerDied(game_player(index)));
                         break;
                     }
                 }
@@ -179,6 +248,36 @@ impl WallDodger {
         }
     }
 
+    fn tick(&mut self, delta_time: Duration) {
+        match &mut self.state {
+            GameState::Playing => {
+                self.detect_collisions();
+
+                self.last_wall_time += delta_time.as_secs_f64();
+                if self.last_wall_time > self.wall_period {
+                    self.move_walls_left();
+                    self.last_wall_time -= self.wall_period;
+                }
+
+                if self.walls.is_empty()
+                    || self
+                        .walls
+                        .last()
+                        .is_some_and(|w| w.col == GRID_WIDTH - self.wall_gap)
+                {
+                    self.add_wall();
+                }
+            }
+            GameState::GameOver => {}
+            GameState::Victory(elapsed_time) => {
+                *elapsed_time += delta_time;
+                if *elapsed_time >= VICTORY_ANIMATION_DURATION {
+                    self.reset_game();
+                }
+            }
+        }
+    }
+
     fn reset_game(&mut self) {
         self.state = GameState::Playing;
         self.walls.clear();
@@ -248,36 +347,16 @@ impl Game for WallDodger {
     fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
         self.current_time += delta_time;
 
-        match &mut self.state {
-            GameState::Playing => {
-                let dt = delta_time.as_secs_f64();
-
-                self.detect_collisions();
-
-                self.last_wall_time += dt;
-                if self.last_wall_time > self.wall_period {
-                    self.move_walls_left();
-                    self.last_wall_time = 0.0;
-                }
-
-                if self.walls.is_empty()
-                    || self
-                        .walls
-                        .last()
-                        .is_some_and(|w| w.col == GRID_SIZE - self.wall_gap)
-                {
-                    self.add_wall();
-                }
-            }
+        for _ in 0..self.fixed_step.advance(delta_time) {
+            self.tick(DEFAULT_TICK);
+        }
+        match self.state {
+            GameState::Playing => {}
             GameState::GameOver => {
                 self.game_over_animation.update(self.current_time);
             }
-            GameState::Victory(elapsed_time) => {
-                *elapsed_time += delta_time;
+            GameState::Victory(_) => {
                 self.victory_animation.update(self.current_time);
-                if *elapsed_time >= VICTORY_ANIMATION_DURATION {
-                    self.reset_game();
-                }
             }
         }
         Ok(())
@@ -290,7 +369,7 @@ impl Game for WallDodger {
             GameState::Playing | GameState::GameOver => {
                 let wall_color = self.wall_color();
                 for wall in &self.walls {
-                    for row in (0..wall.gap_row).chain(wall.gap_row + wall.gap_size..GRID_SIZE) {
+                    for row in (0..wall.gap_row).chain(wall.gap_row + wall.gap_size..GRID_HEIGHT) {
                         render_board.set(wall.col, row, wall_color);
                     }
                 }
@@ -316,15 +395,15 @@ impl Game for WallDodger {
                 {
                     let color = self.game_over_animation.get_color();
                     for row in (0..first_wall.gap_row)
-                        .chain(first_wall.gap_row + first_wall.gap_size..GRID_SIZE)
+                        .chain(first_wall.gap_row + first_wall.gap_size..GRID_HEIGHT)
                     {
                         render_board.set(0, row, color);
                     }
                 }
             }
             GameState::Victory(elapsed_time) => {
-                for row in 0..GRID_SIZE {
-                    for col in 0..GRID_SIZE {
+                for row in 0..GRID_HEIGHT {
+                    for col in 0..GRID_WIDTH {
                         let color = self.generate_psychedelic_color(row, col, *elapsed_time);
                         render_board.set(col, row, color);
                     }
@@ -334,11 +413,110 @@ impl Game for WallDodger {
 
         Ok(render_board)
     }
+
+    fn final_score(&self) -> Option<u32> {
+        match self.state {
+            GameState::GameOver | GameState::Victory(_) if !self.is_multiplayer => {
+                Some(self.walls_passed as u32)
+            }
+            _ => None,
+        }
+    }
+
+    fn status(&self) -> GameStatus {
+        let mut status = match self.state {
+            GameState::Playing => GameStatus::playing(),
+            GameState::GameOver | GameState::Victory(_) => GameStatus::game_over(None),
+        };
+        status.scores[0] = Some(self.walls_passed as u32);
+        // The walls change color every time they speed up
+        status.level = Some(self.color_index as u32 + 1);
+        status
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        core::mem::take(&mut self.events)
+    }
+}
+
+// Player 1 is always first in `players`
+/// Moves into the gap of the next wall, keeping a row away from its edges
+/// when the gap is wide enough.
+#[derive(Default)]
+pub(crate) struct WallDodgerBot {
+    move_cooldown: Duration,
+}
+
+snapshot_struct!(WallDodgerBot { move_cooldown });
+
+impl WallDodgerBot {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Bot<WallDodger> for WallDodgerBot {
+    fn next_command(
+        &mut self,
+        game: &WallDodger,
+        player: GamePlayer,
+        delta_time: Duration,
+    ) -> Option<GameCommand> {
+        self.move_cooldown = self.move_cooldown.saturating_sub(delta_time);
+        if !matches!(game.state, GameState::Playing) || !self.move_cooldown.is_zero() {
+            return None;
+        }
+        let dodger = game
+            .players
+            .iter()
+            .find(|p| p.is_alive && p.color == player_color(player))?;
+        let wall = game
+            .walls
+            .iter()
+            .filter(|wall| wall.col >= dodger.col)
+            .min_by_key(|wall| wall.col)?;
+        let margin = usize::from(wall.gap_size > 2);
+        let command_type = if dodger.row < wall.gap_row + margin {
+            CommandType::Up
+        } else if dodger.row + margin >= wall.gap_row + wall.gap_size {
+            CommandType::Down
+        } else {
+            return None;
+        };
+        self.move_cooldown = BOT_MOVE_DELAY;
+        Some(GameCommand::new(command_type, ButtonState::Pressed, player))
+    }
+}
+
+fn game_player(index: usize) -> GamePlayer {
+    match index {
+        0 => GamePlayer::Player1,
+        _ => GamePlayer::Player2,
+    }
 }
 
 fn player_color(player: GamePlayer) -> RGB {
     match player {
         GamePlayer::Player1 => RGB::new(0, 255, 0), // Green
-        GamePlayer::Player2 => RGB::new(0, 0, 255), // Blue
+        _ => RGB::new(0, 0, 255),                   // Blue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotPlayer;
+
+    #[test]
+    fn test_bot_reaches_the_end() {
+        let game = WallDodger::new(5, true);
+        let mut game = BotPlayer::new(game, WallDodgerBot::new(), GamePlayer::Player2);
+        let frame = Duration::from_millis(16);
+        // The first player does nothing and is soon out of the game
+        while !matches!(game.game().state, GameState::Victory(_)) {
+            assert!(!matches!(game.game().state, GameState::GameOver));
+            game.update(frame).unwrap();
+        }
+        assert!(game.game().players[1].is_alive);
     }
 }
@@ -0,0 +1,491 @@
+use crate::GameError;
+use crate::game::{Game, GameCommand, GameEngine, RenderBoard};
+use core::time::Duration;
+
+/// A command scheduled to be sent to the game at a given simulation time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedCommand {
+    pub time: Duration,
+    pub command: GameCommand,
+}
+
+impl TimedCommand {
+    pub fn new(time: Duration, command: GameCommand) -> Self {
+        Self { time, command }
+    }
+}
+
+/// Drives a game without a frontend using a fixed update step.
+///
+/// Every frame the runner first delivers all commands whose time has been
+/// reached, then calls `update` once with the step and finally renders. The
+/// engine's own tick rate is turned off, so the game sees exactly the step.
+/// Given the same seed, timeline and step the produced frames are identical.
+pub struct HeadlessRunner<G: Game> {
+    engine: GameEngine<G>,
+    step: Duration,
+    current_time: Duration,
+}
+
+impl<G: Game> HeadlessRunner<G> {
+    /// Fails with `GameError::ZeroStep` if `step` is zero.
+    pub fn new(
+        seed: u64,
+        step: Duration,
+        make_game: impl FnOnce(u64) -> G,
+    ) -> Result<Self, GameError> {
+        if step.is_zero() {
+            return Err(GameError::ZeroStep);
+        }
+        let mut engine = GameEngine::new(make_game(seed));
+        engine.set_tick_rate(None);
+        Ok(Self {
+            engine,
+            step,
+            current_time: Duration::ZERO,
+        })
+    }
+
+    pub fn current_time(&self) -> Duration {
+        self.current_time
+    }
+
+    /// Advances the game by one step and returns the rendered frame.
+    pub fn step(&mut self, commands: &[GameCommand]) -> Result<RenderBoard, GameError> {
+        for command in commands {
+            self.engine.process_input(*command)?;
+        }
+        self.engine.update(self.step)?;
+        self.current_time += self.step;
+        self.engine.render()
+    }
+
+    /// Runs the timeline for `duration` of simulated time and returns one
+    /// frame per step. The timeline must be sorted by time.
+    pub fn run(
+        &mut self,
+        timeline: &[TimedCommand],
+        duration: Duration,
+    ) -> Result<Vec<RenderBoard>, GameError> {
+        debug_assert!(
+            timeline.windows(2).all(|w| w[0].time <= w[1].time),
+            "timeline must be sorted by time"
+        );
+
+        let end_time = self.current_time + duration;
+        let mut frames = Vec::new();
+        let mut next_command = timeline
+            .iter()
+            .position(|c| c.time >= self.current_time)
+            .unwrap_or(timeline.len());
+
+        while self.current_time < end_time {
+            let due = timeline[next_command..]
+                .iter()
+                .take_while(|c| c.time <= self.current_time)
+                .count();
+            for timed in &timeline[next_command..next_command + due] {
+                self.engine.process_input(timed.command)?;
+            }
+            next_command += due;
+
+            self.engine.update(self.step)?;
+            self.current_time += self.step;
+            frames.push(self.engine.render()?);
+        }
+
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        ButtonState, ButtonWar, CommandType, ConnectFour, DoodleJump, FlappyBird, GameMode,
+        HeatmapGame, MazeGame, MazeGameMode, Menu, MultiplayerShooter, PaintGame, Player, PongGame,
+        RGB, SnakeGame, SnakeGameMode, SpaceInvaders, TetrisGame, TicTacToe, WallDodger, fits_grid,
+    };
+    use crate::{GRID_HEIGHT, GRID_WIDTH};
+
+    const STEP: Duration = Duration::from_millis(16);
+    const DURATION: Duration = Duration::from_secs(5);
+
+    fn press(millis: u64, command_type: CommandType, player: Player) -> TimedCommand {
+        TimedCommand::new(
+            Duration::from_millis(millis),
+            GameCommand::new(command_type, ButtonState::Pressed, player),
+        )
+    }
+
+    // Exercises every input for both players so that all games get to react.
+    fn busy_timeline() -> Vec<TimedCommand> {
+        let inputs = [
+            CommandType::Up,
+            CommandType::Left,
+            CommandType::Select,
+            CommandType::Down,
+            CommandType::Right,
+            CommandType::Select,
+        ];
+        let mut timeline = Vec::new();
+        for (i, &command_type) in inputs.iter().cycle().take(40).enumerate() {
+            let player = if i % 2 == 0 {
+                Player::Player1
+            } else {
+                Player::Player2
+            };
+            timeline.push(press(i as u64 * 110, command_type, player));
+        }
+        timeline
+    }
+
+    fn run_game<G: Game>(seed: u64, make_game: impl FnOnce(u64) -> G) -> Vec<RenderBoard> {
+        HeadlessRunner::new(seed, STEP, make_game)
+            .unwrap()
+            .run(&busy_timeline(), DURATION)
+            .unwrap()
+    }
+
+    // Runs a game with the busy timeline from a seed
+    type Run = fn(u64) -> Vec<RenderBoard>;
+
+    // The games by their menu id
+    fn every_game() -> Vec<(&'static str, Run)> {
+        let mut games: Vec<(&'static str, Run)> = Vec::new();
+        if fits_grid(ConnectFour::MIN_GRID_SIZE) {
+            games.push(("ConnectFour", |seed| run_game(seed, |_| ConnectFour::new())));
+        }
+        if fits_grid(TicTacToe::MIN_GRID_SIZE) {
+            games.push(("TicTacToe", |seed| run_game(seed, |_| TicTacToe::new())));
+        }
+        games.push(("FlappyBird", |seed| run_game(seed, FlappyBird::new)));
+        games.push(("Snake", |seed| {
+            run_game(seed, |seed| {
+                SnakeGame::new(seed, SnakeGameMode::SinglePlayer)
+            })
+        }));
+        games.push(("SnakeMultiPlayer", |seed| {
+            run_game(seed, |seed| {
+                SnakeGame::new(seed, SnakeGameMode::MultiPlayer)
+            })
+        }));
+        games.push(("Maze", |seed| {
+            run_game(seed, |seed| MazeGame::new(seed, MazeGameMode::Normal))
+        }));
+        games.push(("MazeFlashLightMultiplayer", |seed| {
+            run_game(seed, |seed| {
+                MazeGame::new(seed, MazeGameMode::FlashLightMultiplayer)
+            })
+        }));
+        games.push(("SpaceInvaders", |seed| {
+            run_game(seed, |seed| {
+                SpaceInvaders::new(seed, true, 2, GameMode::SinglePlayer)
+            })
+        }));
+        games.push(("SpaceInvadersMultiPlayer", |seed| {
+            run_game(seed, |seed| {
+                SpaceInvaders::new(seed, false, 4, GameMode::MultiPlayer)
+            })
+        }));
+        games.push(("DoodleJump", |seed| run_game(seed, DoodleJump::new)));
+        games.push(("Tetris", |seed| run_game(seed, TetrisGame::new)));
+        games.push(("MultiplayerShooter", |seed| {
+            run_game(seed, |seed| MultiplayerShooter::new(seed, 10))
+        }));
+        games.push(("PongGame", |seed| run_game(seed, PongGame::new)));
+        games.push(("ButtonWar", |seed| run_game(seed, |_| ButtonWar::new())));
+        games.push(("WallDodger", |seed| {
+            run_game(seed, |seed| WallDodger::new(seed, false))
+        }));
+        games.push(("WallDodgerMultiplayer", |seed| {
+            run_game(seed, |seed| WallDodger::new(seed, true))
+        }));
+        games.push(("PaintGame", |seed| run_game(seed, |_| PaintGame::new())));
+        games.push(("HottestSpot", |seed| run_game(seed, HeatmapGame::new)));
+        games.push(("Menu", |seed| run_game(seed, |_| Menu::new())));
+        games
+    }
+
+    #[test]
+    fn test_every_game_is_deterministic() {
+        for (id, run) in every_game() {
+            let first = run(7);
+            assert_eq!(
+                first.len(),
+                DURATION.as_millis().div_ceil(STEP.as_millis()) as usize
+            );
+            assert!(first == run(7), "{id}: same seed produced different frames");
+        }
+    }
+
+    // FNV-1a over the colors of every frame, the same on every platform
+    fn frames_hash(frames: &[RenderBoard]) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+        for rgb in frames.iter().flat_map(|frame| frame.cells.iter().flatten()) {
+            for byte in [rgb.r, rgb.g, rgb.b] {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3);
+            }
+        }
+        hash
+    }
+
+    type Hashes = &'static [(&'static str, u64)];
+
+    // Hashes of every frame of each game from seed 7, for every grid size
+    // in the CI matrix. A change to how a game plays or looks changes its
+    // hashes, update them here when that was intended.
+    const GOLDEN_HASHES: [((usize, usize), Hashes); 6] = [
+        (
+            (12, 12),
+            &[
+                ("ConnectFour", 0x87bb_b6a5_b186_8963),
+                ("TicTacToe", 0xe9e5_d7f0_3db6_db18),
+                ("FlappyBird", 0xe7ec_78b3_509a_efa2),
+                ("Snake", 0xf545_b7ed_dbfc_0e89),
+                ("SnakeMultiPlayer", 0xc8c7_3616_729d_e844),
+                ("Maze", 0x8903_060c_d70e_978a),
+                ("MazeFlashLightMultiplayer", 0xc94d_fff1_52e2_fc57),
+                ("SpaceInvaders", 0x448d_1241_0d05_7d50),
+                ("SpaceInvadersMultiPlayer", 0x74d0_e343_913c_0664),
+                ("DoodleJump", 0xd2f9_ad38_2759_5a0b),
+                ("Tetris", 0x6ebe_e831_349a_3091),
+                ("MultiplayerShooter", 0xe8f2_acb4_4e71_c668),
+                ("PongGame", 0x75cc_637e_96af_0673),
+                ("ButtonWar", 0xb0d3_be2b_ec2a_8a49),
+                ("WallDodger", 0x1a85_e667_dfd2_8559),
+                ("WallDodgerMultiplayer", 0xb0f0_85b5_8de1_d17b),
+                ("PaintGame", 0x1c7a_0465_c52d_9877),
+                ("HottestSpot", 0x26dc_1964_c6af_b44b),
+                ("Menu", 0x7545_69e3_0446_e39c),
+            ],
+        ),
+        (
+            (8, 8),
+            &[
+                ("ConnectFour", 0x2218_2329_bea0_60e3),
+                ("FlappyBird", 0x82b6_edf6_a255_bf39),
+                ("Snake", 0xd07e_d7fa_5af9_768d),
+                ("SnakeMultiPlayer", 0x934d_fb35_0d17_4a1e),
+                ("Maze", 0x368b_36c0_7fc3_9cfa),
+                ("MazeFlashLightMultiplayer", 0xe338_6fde_a9d5_2a44),
+                ("SpaceInvaders", 0xe7d3_07dc_d1df_a85f),
+                ("SpaceInvadersMultiPlayer", 0x2a0f_c430_e065_3fd1),
+                ("DoodleJump", 0xd6ea_d7fe_19b8_edae),
+                ("Tetris", 0xcaab_c922_68e5_d05d),
+                ("MultiplayerShooter", 0xfb09_f7fa_ba20_2f74),
+                ("PongGame", 0x5239_25d9_b29f_2159),
+                ("ButtonWar", 0xeff7_8e56_b40a_f02d),
+                ("WallDodger", 0x5045_0c16_73c9_4839),
+                ("WallDodgerMultiplayer", 0x5596_ef29_b1fa_ac30),
+                ("PaintGame", 0xff7f_39e5_9971_761f),
+                ("HottestSpot", 0x347e_6af3_9d41_bafd),
+                ("Menu", 0x4a5e_fcfb_a9a1_522f),
+            ],
+        ),
+        (
+            (16, 16),
+            &[
+                ("ConnectFour", 0xbd61_cac5_c377_fbe3),
+                ("TicTacToe", 0x1f80_eb62_89e8_5538),
+                ("FlappyBird", 0x44f2_2c7f_a487_4ca9),
+                ("Snake", 0xcbf6_4abc_acc2_3bb9),
+                ("SnakeMultiPlayer", 0x0935_cb14_d0e9_202a),
+                ("Maze", 0xaaaa_4c30_f687_5408),
+                ("MazeFlashLightMultiplayer", 0x2d9e_d457_1b46_9fb7),
+                ("SpaceInvaders", 0x8602_165a_54b1_295f),
+                ("SpaceInvadersMultiPlayer", 0xf67a_9836_ae00_7c88),
+                ("DoodleJump", 0xdbbb_5a6d_3dbc_2a47),
+                ("Tetris", 0x5692_0ac6_2029_60e3),
+                ("MultiplayerShooter", 0x2fad_86f9_11a3_5ba6),
+                ("PongGame", 0x6353_2340_e996_4b26),
+                ("ButtonWar", 0x1152_4864_371d_df35),
+                ("WallDodger", 0xb8d3_09d2_8c00_e7f6),
+                ("WallDodgerMultiplayer", 0x8be5_e500_fcb6_c9f9),
+                ("PaintGame", 0xff5a_8959_89b3_ab37),
+                ("HottestSpot", 0xdf58_c6c2_04a0_8933),
+                ("Menu", 0xcb52_0689_fd08_7321),
+            ],
+        ),
+        (
+            (8, 16),
+            &[
+                ("ConnectFour", 0xba65_ac56_1e20_95e3),
+                ("FlappyBird", 0x94bf_4f8e_5cba_7774),
+                ("Snake", 0x88c8_4f6b_fa24_4151),
+                ("SnakeMultiPlayer", 0xfbaf_5e6c_7109_72c9),
+                ("Maze", 0xe213_0be1_cb32_73ac),
+                ("MazeFlashLightMultiplayer", 0x61cf_5305_a890_b0ee),
+                ("SpaceInvaders", 0x3ff5_78ae_5afe_c76e),
+                ("SpaceInvadersMultiPlayer", 0x8d07_9735_7649_8290),
+                ("DoodleJump", 0xaa93_426e_fbb4_97dc),
+                ("Tetris", 0x4275_df72_c4ab_20e3),
+                ("MultiplayerShooter", 0x7c28_7f14_1a6f_fcd6),
+                ("PongGame", 0x6376_2ab8_65c0_5eb0),
+                ("ButtonWar", 0x6424_68cd_cfb2_92f5),
+                ("WallDodger", 0xfe9b_e904_d489_e601),
+                ("WallDodgerMultiplayer", 0xfc2d_8475_fe45_f611),
+                ("PaintGame", 0x701b_e29e_e83d_cb1f),
+                ("HottestSpot", 0xe51f_655a_9dc8_de06),
+                ("Menu", 0x0073_0f2a_c2a2_5421),
+            ],
+        ),
+        (
+            (16, 8),
+            &[
+                ("ConnectFour", 0x0829_e31a_867d_93e3),
+                ("FlappyBird", 0xd33f_fb43_5031_a14b),
+                ("Snake", 0xb6e8_a2f9_7a56_bbb1),
+                ("SnakeMultiPlayer", 0xc7d0_de34_8b83_b59f),
+                ("Maze", 0x9783_17da_0ead_15c8),
+                ("MazeFlashLightMultiplayer", 0x5316_6df3_0580_f98e),
+                ("SpaceInvaders", 0xc72f_f07c_a5cd_1233),
+                ("SpaceInvadersMultiPlayer", 0x5f3f_7c94_6ac5_23cf),
+                ("DoodleJump", 0x2711_1228_9452_1927),
+                ("Tetris", 0x7a38_8f43_e4ba_875d),
+                ("MultiplayerShooter", 0x7c79_7fa2_3708_f8d1),
+                ("PongGame", 0xa34b_4e36_5fc0_02f4),
+                ("ButtonWar", 0x1210_886c_85dc_494d),
+                ("WallDodger", 0x91fe_2fa9_27b1_d5e3),
+                ("WallDodgerMultiplayer", 0xb451_b49b_0153_2edd),
+                ("PaintGame", 0x01dc_9e3a_f12b_66b7),
+                ("HottestSpot", 0xc54e_c246_2ad4_0114),
+                ("Menu", 0x49fa_11fe_79c2_5f6c),
+            ],
+        ),
+        (
+            (32, 32),
+            &[
+                ("ConnectFour", 0x0dca_0d3f_5774_8e63),
+                ("TicTacToe", 0xbbd0_0304_ddbf_2738),
+                ("FlappyBird", 0x5b9a_3a2d_8765_a5a4),
+                ("Snake", 0xd758_85b6_0a43_0971),
+                ("SnakeMultiPlayer", 0xf775_d9c1_41a0_e72e),
+                ("Maze", 0xe775_56f1_0710_739e),
+                ("MazeFlashLightMultiplayer", 0xd778_1d67_0e0a_aab7),
+                ("SpaceInvaders", 0xee07_c85c_a44e_c1fd),
+                ("SpaceInvadersMultiPlayer", 0x8361_92a3_0e9b_12b2),
+                ("DoodleJump", 0x3853_3edd_77f0_d76b),
+                ("Tetris", 0xbe57_1f89_6d01_b171),
+                ("MultiplayerShooter", 0x73db_9f88_8c7f_1eab),
+                ("PongGame", 0x5e32_a519_5277_d550),
+                ("ButtonWar", 0xb7af_d33d_3579_3465),
+                ("WallDodger", 0xd1c9_9085_49a3_7750),
+                ("WallDodgerMultiplayer", 0xa433_f526_1d3d_6e83),
+                ("PaintGame", 0x991e_8cc9_0c0b_ad37),
+                ("HottestSpot", 0xd2db_a48d_42a8_a6c7),
+                ("Menu", 0xcec3_be88_4f0b_795e),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn test_every_game_matches_its_golden_frames() {
+        let grid = (GRID_WIDTH, GRID_HEIGHT);
+        let Some((_, golden)) = GOLDEN_HASHES.iter().find(|(size, _)| *size == grid) else {
+            // Other sizes are still checked for determinism above, but
+            // there are no frames recorded to compare them against
+            eprintln!("No golden frames for a {GRID_WIDTH}x{GRID_HEIGHT} grid, skipping");
+            return;
+        };
+        let hashes: Vec<_> = every_game()
+            .into_iter()
+            .map(|(id, run)| (id, frames_hash(&run(7))))
+            .collect();
+        assert!(
+            hashes == *golden,
+            "frames changed on a {GRID_WIDTH}x{GRID_HEIGHT} grid, the new hashes are {hashes:#x?}"
+        );
+    }
+
+    #[test]
+    fn test_zero_step_is_rejected() {
+        let runner = HeadlessRunner::new(0, Duration::ZERO, |_| UpdateCounter(0));
+        assert!(matches!(runner, Err(GameError::ZeroStep)));
+    }
+
+    // Lights one more cell for every update it gets
+    struct UpdateCounter(usize);
+
+    impl Game for UpdateCounter {
+        fn process_input(&mut self, _: GameCommand) -> Result<(), GameError> {
+            Ok(())
+        }
+
+        fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
+            assert_eq!(delta_time, Duration::from_millis(5));
+            self.0 += 1;
+            Ok(())
+        }
+
+        fn render(&self) -> Result<RenderBoard, GameError> {
+            let mut board = RenderBoard::new();
+            for i in 0..self.0 {
+                board.set(i % GRID_WIDTH, i / GRID_WIDTH, RGB::new(255, 255, 255));
+            }
+            Ok(board)
+        }
+    }
+
+    #[test]
+    fn test_game_is_updated_once_per_step() {
+        let mut runner =
+            HeadlessRunner::new(0, Duration::from_millis(5), |_| UpdateCounter(0)).unwrap();
+        let frames = runner.run(&[], Duration::from_millis(30)).unwrap();
+        assert_eq!(frames.len(), 6);
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.get(i, 0), RGB::new(255, 255, 255));
+            assert_eq!(frame.get(i + 1, 0), RGB::default());
+        }
+    }
+
+    #[test]
+    fn test_seed_changes_outcome() {
+        let first = run_game(1, TetrisGame::new);
+        let second = run_game(2, TetrisGame::new);
+        assert!(first != second);
+    }
+
+    #[test]
+    fn test_commands_are_delivered_on_time() {
+        let snake_color = RGB::new(0, 255, 0);
+        let timeline = [press(200, CommandType::Up, Player::Player1)];
+        let mut runner = HeadlessRunner::new(0, STEP, |seed| {
+            SnakeGame::new(seed, SnakeGameMode::SinglePlayer)
+        })
+        .unwrap();
+        let frames = runner.run(&timeline, Duration::from_millis(400)).unwrap();
+
+        // The snake starts moving right and turns up after the command
+        let start = (GRID_WIDTH / 4, GRID_HEIGHT / 2);
+        let first_move = frames
+            .iter()
+            .position(|f| f.get(start.0 + 1, start.1) == snake_color)
+            .unwrap();
+        assert!(first_move > 0);
+        assert!(
+            frames
+                .last()
+                .is_some_and(|f| f.get(start.0 + 1, start.1 + 1) == snake_color)
+        );
+        assert_eq!(runner.current_time(), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_step_matches_run() {
+        let timeline = busy_timeline();
+        let frames = run_game(3, PongGame::new);
+
+        let mut runner = HeadlessRunner::new(3, STEP, PongGame::new).unwrap();
+        let mut next_command = 0;
+        for frame in frames {
+            let due = timeline[next_command..]
+                .iter()
+                .take_while(|c| c.time <= runner.current_time())
+                .map(|c| c.command)
+                .collect::<Vec<_>>();
+            next_command += due.len();
+            assert!(runner.step(&due).unwrap() == frame);
+        }
+    }
+}
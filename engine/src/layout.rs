@@ -0,0 +1,445 @@
+use crate::game::{GRID_HEIGHT, GRID_WIDTH};
+use core::str::FromStr;
+
+/// Corner of a panel or tile where a chain starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Corner {
+    BottomLeft,
+    BottomRight,
+    TopLeft,
+    TopRight,
+}
+
+/// Whether a chain runs along rows or columns first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    Rows,
+    Columns,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wiring {
+    /// Every row (or column) starts on the same side
+    Progressive,
+    /// Every other row (or column) runs back the other way
+    Serpentine,
+}
+
+/// Clockwise rotation of the picture on the panel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+/// How a strip runs through a rectangle of LEDs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chain {
+    pub origin: Corner,
+    pub order: Order,
+    pub wiring: Wiring,
+}
+
+impl Chain {
+    pub const fn new(origin: Corner, order: Order, wiring: Wiring) -> Self {
+        Self {
+            origin,
+            order,
+            wiring,
+        }
+    }
+
+    /// Position in the chain of (`x`, `y`) in a `width` by `height`
+    /// rectangle, where `y` counts up from the bottom like on the board.
+    fn index(&self, x: usize, y: usize, width: usize, height: usize) -> usize {
+        let x = match self.origin {
+            Corner::BottomLeft | Corner::TopLeft => x,
+            Corner::BottomRight | Corner::TopRight => width - 1 - x,
+        };
+        let y = match self.origin {
+            Corner::BottomLeft | Corner::BottomRight => y,
+            Corner::TopLeft | Corner::TopRight => height - 1 - y,
+        };
+        let (line, position, line_len) = match self.order {
+            Order::Rows => (y, x, width),
+            Order::Columns => (x, y, height),
+        };
+        let position = match self.wiring {
+            Wiring::Serpentine if line % 2 == 1 => line_len - 1 - position,
+            _ => position,
+        };
+        line * line_len + position
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutError {
+    UnknownSetting,
+    InvalidValue {
+        setting: &'static str,
+    },
+    /// The tiles don't evenly cover the panel
+    TileSize {
+        width: usize,
+        height: usize,
+    },
+}
+
+impl core::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LayoutError::UnknownSetting => write!(f, "Unknown layout setting"),
+            LayoutError::InvalidValue { setting } => {
+                write!(f, "Invalid value for layout setting {}", setting)
+            }
+            LayoutError::TileSize { width, height } => write!(
+                f,
+                "{}x{} tiles don't cover the {}x{} grid",
+                width, height, GRID_WIDTH, GRID_HEIGHT
+            ),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl std::error::Error for LayoutError {}
+
+/// Maps cells of the grid to positions on a physical LED strip.
+///
+/// The picture is mirrored, then rotated, and then split into tiles of
+/// `tile_size` LEDs that are chained one after the other. `pixels` is the
+/// wiring inside each tile and `tiles` the order the tiles are chained in.
+/// A panel without tiles is a single tile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LedLayout {
+    pub pixels: Chain,
+    /// Width and height of a tile, `None` when the panel is one tile
+    pub tile_size: Option<(usize, usize)>,
+    pub tiles: Chain,
+    pub rotation: Rotation,
+    /// Flips the picture left to right
+    pub mirror_x: bool,
+    /// Flips the picture upside down
+    pub mirror_y: bool,
+}
+
+impl LedLayout {
+    /// A single panel wired in rows from the bottom left, turning around
+    /// at the end of each row.
+    pub const fn new() -> Self {
+        let chain = Chain::new(Corner::BottomLeft, Order::Rows, Wiring::Serpentine);
+        Self {
+            pixels: chain,
+            tile_size: None,
+            tiles: chain,
+            rotation: Rotation::None,
+            mirror_x: false,
+            mirror_y: false,
+        }
+    }
+
+    // Size of the panel, which is turned on its side by a quarter rotation
+    const fn panel_size(&self) -> (usize, usize) {
+        match self.rotation {
+            Rotation::None | Rotation::Cw180 => (GRID_WIDTH, GRID_HEIGHT),
+            Rotation::Cw90 | Rotation::Cw270 => (GRID_HEIGHT, GRID_WIDTH),
+        }
+    }
+
+    const fn tile_size_or_panel(&self) -> (usize, usize) {
+        match self.tile_size {
+            Some(size) => size,
+            None => self.panel_size(),
+        }
+    }
+
+    /// Checks that the tiles cover the panel exactly. Usable in a const
+    /// assertion for layouts that are built in.
+    pub const fn validate(&self) -> Result<(), LayoutError> {
+        let (panel_width, panel_height) = self.panel_size();
+        let (width, height) = self.tile_size_or_panel();
+        // Nothing but zero is a multiple of zero
+        if !panel_width.is_multiple_of(width) || !panel_height.is_multiple_of(height) {
+            return Err(LayoutError::TileSize { width, height });
+        }
+        Ok(())
+    }
+
+    /// Index on the strip of the LED showing (`col`, `row`) of the board.
+    /// Only meaningful for layouts that pass `validate`.
+    pub fn led_index(&self, col: usize, row: usize) -> usize {
+        let col = if self.mirror_x {
+            GRID_WIDTH - 1 - col
+        } else {
+            col
+        };
+        let row = if self.mirror_y {
+            GRID_HEIGHT - 1 - row
+        } else {
+            row
+        };
+        let (x, y) = match self.rotation {
+            Rotation::None => (col, row),
+            Rotation::Cw90 => (row, GRID_WIDTH - 1 - col),
+            Rotation::Cw180 => (GRID_WIDTH - 1 - col, GRID_HEIGHT - 1 - row),
+            Rotation::Cw270 => (GRID_HEIGHT - 1 - row, col),
+        };
+        let (panel_width, panel_height) = self.panel_size();
+        let (tile_width, tile_height) = self.tile_size_or_panel();
+        let tile = self.tiles.index(
+            x / tile_width,
+            y / tile_height,
+            panel_width / tile_width,
+            panel_height / tile_height,
+        );
+        let pixel = self
+            .pixels
+            .index(x % tile_width, y % tile_height, tile_width, tile_height);
+        tile * tile_width * tile_height + pixel
+    }
+}
+
+impl Default for LedLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const SETTINGS: [&str; 9] = [
+    "wiring",
+    "order",
+    "origin",
+    "rotation",
+    "mirror",
+    "tile",
+    "tile-wiring",
+    "tile-order",
+    "tile-origin",
+];
+
+fn parse_chain_setting(chain: &mut Chain, setting: &str, value: &str) -> Option<()> {
+    match setting {
+        "wiring" => {
+            chain.wiring = match value {
+                "progressive" => Wiring::Progressive,
+                "serpentine" => Wiring::Serpentine,
+                _ => return None,
+            }
+        }
+        "order" => {
+            chain.order = match value {
+                "rows" => Order::Rows,
+                "columns" => Order::Columns,
+                _ => return None,
+            }
+        }
+        _ => {
+            chain.origin = match value {
+                "bottom-left" => Corner::BottomLeft,
+                "bottom-right" => Corner::BottomRight,
+                "top-left" => Corner::TopLeft,
+                "top-right" => Corner::TopRight,
+                _ => return None,
+            }
+        }
+    }
+    Some(())
+}
+
+fn parse_setting(layout: &mut LedLayout, setting: &str, value: &str) -> Option<()> {
+    match setting {
+        "rotation" => {
+            layout.rotation = match value {
+                "0" => Rotation::None,
+                "90" => Rotation::Cw90,
+                "180" => Rotation::Cw180,
+                "270" => Rotation::Cw270,
+                _ => return None,
+            }
+        }
+        "mirror" => {
+            (layout.mirror_x, layout.mirror_y) = match value {
+                "none" => (false, false),
+                "x" => (true, false),
+                "y" => (false, true),
+                "xy" => (true, true),
+                _ => return None,
+            }
+        }
+        "tile" => {
+            let (width, height) = value.split_once('x')?;
+            layout.tile_size = Some((width.parse().ok()?, height.parse().ok()?));
+        }
+        _ => match setting.strip_prefix("tile-") {
+            Some(setting) => parse_chain_setting(&mut layout.tiles, setting, value)?,
+            None => parse_chain_setting(&mut layout.pixels, setting, value)?,
+        },
+    }
+    Some(())
+}
+
+/// Parses settings separated by whitespace or commas, starting from
+/// `LedLayout::new`. Text after `#` on a line is ignored, so a layout can
+/// be kept in a commented file. For example
+/// `wiring=progressive origin=top-left rotation=90 mirror=x tile=8x8`.
+///
+/// - `wiring`: `serpentine` or `progressive`
+/// - `order`: `rows` or `columns`
+/// - `origin`: `bottom-left`, `bottom-right`, `top-left` or `top-right`
+/// - `rotation`: `0`, `90`, `180` or `270` degrees clockwise
+/// - `mirror`: `none`, `x`, `y` or `xy`
+/// - `tile`: size of a tile such as `8x8`
+/// - `tile-wiring`, `tile-order`, `tile-origin`: how the tiles are chained
+impl FromStr for LedLayout {
+    type Err = LayoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layout = Self::new();
+        let settings = s
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|setting| !setting.is_empty());
+        for setting in settings {
+            let (key, value) = setting.split_once('=').ok_or(LayoutError::UnknownSetting)?;
+            let setting = SETTINGS
+                .into_iter()
+                .find(|&setting| setting == key)
+                .ok_or(LayoutError::UnknownSetting)?;
+            parse_setting(&mut layout, setting, value)
+                .ok_or(LayoutError::InvalidValue { setting })?;
+        }
+        layout.validate()?;
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indices(layout: &LedLayout) -> [[usize; GRID_HEIGHT]; GRID_WIDTH] {
+        let mut indices = [[0; GRID_HEIGHT]; GRID_WIDTH];
+        for (col, column) in indices.iter_mut().enumerate() {
+            for (row, index) in column.iter_mut().enumerate() {
+                *index = layout.led_index(col, row);
+            }
+        }
+        indices
+    }
+
+    fn assert_covers_strip(layout: &LedLayout) {
+        let mut seen = [false; GRID_WIDTH * GRID_HEIGHT];
+        for column in indices(layout) {
+            for index in column {
+                assert!(!seen[index], "LED {} is used twice", index);
+                seen[index] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_layout_is_serpentine_rows() {
+        let layout = LedLayout::new();
+        for i in 0..GRID_WIDTH * GRID_HEIGHT {
+            let row = i / GRID_WIDTH;
+            let col = if row.is_multiple_of(2) {
+                i % GRID_WIDTH
+            } else {
+                GRID_WIDTH - 1 - (i % GRID_WIDTH)
+            };
+            assert_eq!(layout.led_index(col, row), i);
+        }
+    }
+
+    #[test]
+    fn test_progressive_rows() {
+        let layout: LedLayout = "wiring=progressive".parse().unwrap();
+        for col in 0..GRID_WIDTH {
+            for row in 0..GRID_HEIGHT {
+                assert_eq!(layout.led_index(col, row), col + row * GRID_WIDTH);
+            }
+        }
+    }
+
+    #[test]
+    fn test_origin_and_order() {
+        let layout: LedLayout = "origin=top-right order=columns".parse().unwrap();
+        assert_eq!(layout.led_index(GRID_WIDTH - 1, GRID_HEIGHT - 1), 0);
+        assert_eq!(layout.led_index(GRID_WIDTH - 1, GRID_HEIGHT - 2), 1);
+        // The second column runs back up
+        assert_eq!(layout.led_index(GRID_WIDTH - 2, 0), GRID_HEIGHT);
+        assert_eq!(
+            layout.led_index(GRID_WIDTH - 2, GRID_HEIGHT - 1),
+            2 * GRID_HEIGHT - 1
+        );
+    }
+
+    #[test]
+    fn test_rotation_and_mirroring() {
+        let plain = LedLayout::new();
+        let rotated: LedLayout = "rotation=180".parse().unwrap();
+        let mirrored: LedLayout = "mirror=xy".parse().unwrap();
+        assert_eq!(indices(&rotated), indices(&mirrored));
+        for col in 0..GRID_WIDTH {
+            for row in 0..GRID_HEIGHT {
+                assert_eq!(
+                    rotated.led_index(col, row),
+                    plain.led_index(GRID_WIDTH - 1 - col, GRID_HEIGHT - 1 - row)
+                );
+            }
+        }
+
+        // A quarter turn puts the bottom left cell in the top left corner
+        let quarter: LedLayout = "rotation=90 origin=top-left".parse().unwrap();
+        assert_eq!(quarter.led_index(0, 0), 0);
+        let three_quarters: LedLayout = "rotation=270 origin=bottom-right".parse().unwrap();
+        assert_eq!(three_quarters.led_index(0, 0), 0);
+        for layout in [quarter, three_quarters] {
+            assert_covers_strip(&layout);
+        }
+    }
+
+    #[test]
+    fn test_tiles_are_chained() {
+        let layout: LedLayout = "wiring=progressive tile=2x2 tile-wiring=progressive"
+            .parse()
+            .unwrap();
+        assert_covers_strip(&layout);
+        // The first tile is filled before moving on to the second
+        assert_eq!(layout.led_index(0, 0), 0);
+        assert_eq!(layout.led_index(1, 0), 1);
+        assert_eq!(layout.led_index(0, 1), 2);
+        assert_eq!(layout.led_index(1, 1), 3);
+        assert_eq!(layout.led_index(2, 0), 4);
+        assert_eq!(layout.led_index(0, 2), GRID_WIDTH / 2 * 4);
+
+        let serpentine: LedLayout = "tile=1x1 tile-wiring=serpentine".parse().unwrap();
+        assert_eq!(indices(&serpentine), indices(&LedLayout::new()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "colour=red".parse::<LedLayout>(),
+            Err(LayoutError::UnknownSetting)
+        );
+        assert_eq!(
+            "rotation=45".parse::<LedLayout>(),
+            Err(LayoutError::InvalidValue {
+                setting: "rotation"
+            })
+        );
+        let width = GRID_WIDTH + 1;
+        assert_eq!(
+            format!("tile={}x1", width).parse::<LedLayout>(),
+            Err(LayoutError::TileSize { width, height: 1 })
+        );
+        let commented = "# Panel behind the bookshelf\nwiring=progressive, origin=top-left # mounted upside down\n";
+        let layout: LedLayout = commented.parse().unwrap();
+        assert_eq!(layout.pixels.wiring, Wiring::Progressive);
+        assert_eq!(layout.pixels.origin, Corner::TopLeft);
+    }
+}
@@ -1,10 +1,58 @@
-#![no_std]
+//! The games, menu and frontend support shared by every teotile frontend.
+//!
+//! The grid size is fixed when the crate is built. `GRID_WIDTH` and
+//! `GRID_HEIGHT` are read from the `TEOTILE_GRID_WIDTH` and
+//! `TEOTILE_GRID_HEIGHT` environment variables, 12 when they are unset, and
+//! every board has that size. A binary therefore drives a single panel size,
+//! see the README for setting the variables for each frontend.
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 include!(concat!(env!("OUT_DIR"), "/pixel_art.rs"));
+include!(concat!(env!("OUT_DIR"), "/font.rs"));
 mod animation;
+mod bot;
+mod color;
 mod error;
+mod event;
 mod game;
+#[cfg(any(test, feature = "std"))]
+mod headless;
+mod layout;
+mod lobby;
+#[cfg(any(test, feature = "std"))]
+mod netplay;
 mod random;
+#[cfg(any(test, feature = "std"))]
+mod replay;
+mod scoreboard;
+mod settings;
+mod snapshot;
+mod tap_hold;
+mod text;
+mod timestep;
+pub use bot::{Bot, BotPlayer};
+pub use color::{ColorPipeline, DEFAULT_GAMMA, IDLE_MILLIAMPS_PER_LED, MILLIAMPS_PER_CHANNEL};
 pub use error::GameError;
+pub use event::{GameEvent, GameEvents, MAX_EVENTS};
 pub use game::RGB;
 pub use game::RenderBoard;
-pub use game::{Board, ButtonState, CommandType, GRID_SIZE, Game, GameCommand, GameEngine, Player};
+pub use game::{
+    Board, ButtonState, CommandType, DEFAULT_TICK_RATE, GRID_HEIGHT, GRID_WIDTH, Game, GameCommand,
+    GameEngine, GameInfo, GamePhase, GameStatus, MAX_PLAYERS, Player,
+};
+#[cfg(any(test, feature = "std"))]
+pub use headless::{HeadlessRunner, TimedCommand};
+pub use layout::{Chain, Corner, LayoutError, LedLayout, Order, Rotation, Wiring};
+pub use lobby::{Lobby, MAX_WAITING};
+#[cfg(any(test, feature = "std"))]
+pub use netplay::{CHECKSUM_INTERVAL, INPUT_DELAY, Lockstep, NET_FRAME, NetError, NetMessage};
+#[cfg(any(test, feature = "std"))]
+pub use replay::{Recorder, Recording, ReplayError, ReplayEvent, Replayer};
+#[cfg(any(test, feature = "std"))]
+pub use scoreboard::FileScoreStorage;
+pub use scoreboard::{MAX_GAMES, SCORES_PER_GAME, ScoreStorage, Scoreboard};
+#[cfg(any(test, feature = "std"))]
+pub use settings::FileSettingsStorage;
+pub use settings::{DisplaySettings, NightMode, SettingsStorage};
+pub use snapshot::SnapshotError;
+pub use tap_hold::{LONG_PRESS, TapHold};
+pub use text::{GLYPH_HEIGHT, GLYPH_WIDTH, Marquee, draw_text, draw_text_centered, text_width};
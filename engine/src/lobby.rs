@@ -0,0 +1,275 @@
+//! Assigns controllers to players. Frontends identify their controllers
+//! however they like, by default with a number. A controller claims the
+//! next player slot with any button and keeps it until it disconnects.
+//! Controllers that connect while others play claim a slot the same way,
+//! the game goes on meanwhile.
+
+use crate::game::{GRID_HEIGHT, GRID_WIDTH, MAX_PLAYERS, Player, RGB, RenderBoard};
+use core::time::Duration;
+use smallvec::SmallVec;
+
+/// Controllers that can wait for a slot at the same time, more are ignored.
+pub const MAX_WAITING: usize = 8;
+// The lobby stays open this long after a controller claims a slot, so its
+// player can see the color they got
+const LINGER: Duration = Duration::from_secs(1);
+const BLINK: Duration = Duration::from_millis(400);
+const PLAYER_COLORS: [RGB; MAX_PLAYERS] = [
+    RGB { r: 0, g: 255, b: 0 },
+    RGB {
+        r: 0,
+        g: 255,
+        b: 255,
+    },
+    RGB {
+        r: 255,
+        g: 0,
+        b: 255,
+    },
+    RGB {
+        r: 255,
+        g: 160,
+        b: 0,
+    },
+];
+const FREE_COLOR: RGB = RGB {
+    r: 40,
+    g: 40,
+    b: 40,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot<C> {
+    Free,
+    Claimed(C),
+    // The controller of this player disconnected, the next controller to
+    // claim a slot takes over
+    Vacant,
+}
+
+/// The player slots and the controllers waiting for one. The lobby is
+/// open, and should be shown instead of the game, while controllers wait
+/// and nobody has a slot yet, and for a moment after every claim.
+///
+/// Controllers are told apart by `C`, frontends with several kinds of
+/// controllers use an enum so the ids of one kind can't collide with
+/// another's.
+#[derive(Debug, Clone)]
+pub struct Lobby<C = usize> {
+    slots: [Slot<C>; MAX_PLAYERS],
+    waiting: SmallVec<[C; MAX_WAITING]>,
+    linger: Duration,
+    current_time: Duration,
+}
+
+impl<C: Copy + PartialEq> Default for Lobby<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Copy + PartialEq> Lobby<C> {
+    pub fn new() -> Self {
+        Self {
+            slots: [Slot::Free; MAX_PLAYERS],
+            waiting: SmallVec::new(),
+            linger: Duration::ZERO,
+            current_time: Duration::ZERO,
+        }
+    }
+
+    /// A controller was connected, it has to claim a slot before it plays.
+    pub fn connect(&mut self, controller: C) {
+        if self.player(controller).is_none()
+            && !self.waiting.contains(&controller)
+            && self.waiting.len() < MAX_WAITING
+        {
+            self.waiting.push(controller);
+        }
+    }
+
+    /// A controller was disconnected. Returns the player it controlled,
+    /// whose slot is kept for the next controller that claims one.
+    pub fn disconnect(&mut self, controller: C) -> Option<Player> {
+        self.waiting.retain(|waiting| *waiting != controller);
+        let player = self.player(controller)?;
+        self.slots[player.index()] = Slot::Vacant;
+        Some(player)
+    }
+
+    /// Gives a waiting controller a slot, vacant slots first and then the
+    /// free slot of the lowest player. Returns the player it claimed.
+    pub fn claim(&mut self, controller: C) -> Option<Player> {
+        if !self.waiting.contains(&controller) {
+            return None;
+        }
+        let index = self
+            .slots
+            .iter()
+            .position(|&slot| slot == Slot::Vacant)
+            .or_else(|| self.slots.iter().position(|&slot| slot == Slot::Free))?;
+        self.waiting.retain(|waiting| *waiting != controller);
+        self.slots[index] = Slot::Claimed(controller);
+        self.linger = LINGER;
+        Player::from_index(index)
+    }
+
+    /// Gives a controller the slot of `player`, for controllers that pick
+    /// their player themselves. Fails if another controller has it.
+    pub fn assign(&mut self, controller: C, player: Player) -> bool {
+        match self.slots[player.index()] {
+            Slot::Claimed(other) if other != controller => false,
+            _ => {
+                self.disconnect(controller);
+                self.slots[player.index()] = Slot::Claimed(controller);
+                true
+            }
+        }
+    }
+
+    /// The player of a controller, if it has claimed one.
+    pub fn player(&self, controller: C) -> Option<Player> {
+        let index = self
+            .slots
+            .iter()
+            .position(|&slot| slot == Slot::Claimed(controller))?;
+        Player::from_index(index)
+    }
+
+    /// The controller of a player, if one has claimed it.
+    pub fn controller(&self, player: Player) -> Option<C> {
+        match self.slots[player.index()] {
+            Slot::Claimed(controller) => Some(controller),
+            _ => None,
+        }
+    }
+
+    /// The number of players up to the last one with a controller, for
+    /// `GameEngine::set_controllers`.
+    pub fn players(&self) -> usize {
+        self.slots
+            .iter()
+            .rposition(|slot| matches!(slot, Slot::Claimed(_)))
+            .map_or(0, |index| index + 1)
+    }
+
+    pub fn is_open(&self) -> bool {
+        (!self.waiting.is_empty() && self.players() == 0) || !self.linger.is_zero()
+    }
+
+    /// Closes the lobby before the moment after a claim is over, for a
+    /// player that has seen their color.
+    pub fn dismiss(&mut self) {
+        self.linger = Duration::ZERO;
+    }
+
+    pub fn update(&mut self, delta_time: Duration) {
+        self.current_time += delta_time;
+        self.linger = self.linger.saturating_sub(delta_time);
+    }
+
+    /// A block per player in its color, player 1 in the bottom left and
+    /// player 4 in the top right. Slots without a controller blink dimly
+    /// while a controller is waiting.
+    pub fn render(&self) -> RenderBoard {
+        let mut render_board = RenderBoard::new();
+        let (width, height) = (GRID_WIDTH / 2, GRID_HEIGHT / 2);
+        let blink_on = self.waiting.is_empty()
+            || (self.current_time.as_millis() / BLINK.as_millis()).is_multiple_of(2);
+        for (index, slot) in self.slots.iter().enumerate() {
+            let color = PLAYER_COLORS[index];
+            let color = match slot {
+                Slot::Claimed(_) => color,
+                Slot::Vacant if blink_on => RGB::new(color.r / 4, color.g / 4, color.b / 4),
+                Slot::Free if blink_on => FREE_COLOR,
+                _ => continue,
+            };
+            let left = index % 2 * width;
+            let bottom = index / 2 * height;
+            for col in left + 1..left + width - 1 {
+                for row in bottom + 1..bottom + height - 1 {
+                    render_board.set(col, row, color);
+                }
+            }
+        }
+        render_board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_controllers_claim_in_order() {
+        let mut lobby = Lobby::new();
+        assert!(!lobby.is_open());
+        lobby.connect(7);
+        lobby.connect(3);
+        assert!(lobby.is_open());
+        assert_eq!(lobby.player(7), None);
+
+        assert_eq!(lobby.claim(3), Some(Player::Player1));
+        assert_eq!(lobby.claim(3), None);
+        assert_eq!(lobby.claim(7), Some(Player::Player2));
+        assert_eq!(lobby.controller(Player::Player2), Some(7));
+        assert_eq!(lobby.players(), 2);
+
+        assert!(lobby.is_open());
+        lobby.update(LINGER);
+        assert!(!lobby.is_open());
+    }
+
+    #[test]
+    fn test_waiting_controller_does_not_stop_the_game() {
+        let mut lobby = Lobby::new();
+        lobby.connect(0);
+        lobby.connect(1);
+        lobby.claim(0);
+        lobby.update(LINGER);
+        assert!(!lobby.is_open());
+        assert_eq!(lobby.players(), 1);
+
+        lobby.update(Duration::from_secs(60));
+        assert!(!lobby.is_open());
+        assert_eq!(lobby.claim(1), Some(Player::Player2));
+    }
+
+    #[test]
+    fn test_reconnected_controller_takes_the_vacant_slot() {
+        let mut lobby = Lobby::new();
+        for controller in 0..3 {
+            lobby.connect(controller);
+            lobby.claim(controller);
+        }
+        assert_eq!(lobby.disconnect(1), Some(Player::Player2));
+        assert_eq!(lobby.players(), 3);
+        lobby.update(LINGER);
+        assert!(!lobby.is_open());
+
+        // Controllers usually come back with a new number. The others keep
+        // playing until it claims a slot.
+        lobby.connect(5);
+        assert!(!lobby.is_open());
+        assert_eq!(lobby.claim(5), Some(Player::Player2));
+        assert!(lobby.is_open());
+        lobby.dismiss();
+        assert!(!lobby.is_open());
+        assert_eq!(lobby.player(2), Some(Player::Player3));
+
+        assert!(!lobby.assign(9, Player::Player1));
+        assert!(lobby.assign(9, Player::Player4));
+        assert_eq!(lobby.players(), 4);
+    }
+
+    #[test]
+    fn test_render_shows_claimed_players() {
+        let mut lobby = Lobby::new();
+        lobby.connect(0);
+        lobby.claim(0);
+        let board = lobby.render();
+        assert_eq!(board.get(1, 1), PLAYER_COLORS[0]);
+        assert_eq!(board.get(GRID_WIDTH / 2 + 1, 1), FREE_COLOR);
+        assert_eq!(board.get(0, 0), RGB::new(0, 0, 0));
+    }
+}
@@ -0,0 +1,574 @@
+//! Two player games over the network, kept in sync by lockstep.
+//!
+//! Both sides run the same seeded engine and only exchange inputs. Every
+//! frame has a fixed length and is simulated once the inputs of both
+//! players for it are known, so the two engines never diverge. Local
+//! inputs are scheduled `INPUT_DELAY` frames ahead to hide the round trip.
+//!
+//! Clients connect to a relay, which pairs them and sends each a `Start`
+//! message with the seed and the player it controls. After that the relay
+//! forwards every message unchanged to the other client.
+//!
+//! Messages are binary, integers are little-endian:
+//!
+//! - start: `0x01`, the seed as a `u64` and the player as a `u8`
+//! - hello: `0x02` and the grid width and height as `u16`, sent first by
+//!   both clients
+//! - inputs: `0x03`, the frame as a `u32` and one byte per command in the
+//!   `.ttr` input encoding, sent for every frame even without commands
+//! - checksum: `0x04`, the frame as a `u32` and a hash of the engine state
+//!   before that frame as a `u64`, sent every `CHECKSUM_INTERVAL` frames
+
+use crate::GameError;
+use crate::RenderBoard;
+use crate::event::GameEvents;
+use crate::game::{
+    DEFAULT_TICK_RATE, GRID_HEIGHT, GRID_WIDTH, GameCommand, GameEngine, GameStatus, Menu, Player,
+};
+use crate::replay::{decode_command, encode_command};
+use core::time::Duration;
+use std::collections::VecDeque;
+
+/// The length of every frame of a network game, one tick of the engine.
+pub const NET_FRAME: Duration = Duration::from_nanos(1_000_000_000 / DEFAULT_TICK_RATE as u64);
+/// Frames between a local input and the frame it is applied in.
+pub const INPUT_DELAY: usize = 4;
+// Frames that are caught up at once after a stall
+const MAX_CATCH_UP: u32 = 8;
+// We send inputs up to `INPUT_DELAY` frames past our next frame, so the
+// other side can simulate at most one frame past those and schedule its
+// own inputs `INPUT_DELAY` frames later still
+const MAX_FRAMES_AHEAD: u32 = 2 * INPUT_DELAY as u32 + 1;
+/// Frames between two checksums of the engine state.
+pub const CHECKSUM_INTERVAL: u32 = 60;
+
+const START_TAG: u8 = 0x01;
+const HELLO_TAG: u8 = 0x02;
+const INPUTS_TAG: u8 = 0x03;
+const CHECKSUM_TAG: u8 = 0x04;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetMessage {
+    Start {
+        seed: u64,
+        player: Player,
+    },
+    Hello {
+        width: usize,
+        height: usize,
+    },
+    Inputs {
+        frame: u32,
+        commands: Vec<GameCommand>,
+    },
+    Checksum {
+        frame: u32,
+        checksum: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetError {
+    InvalidMessage,
+    GridMismatch {
+        width: usize,
+        height: usize,
+    },
+    /// Inputs or a checksum arrived for a frame other than the next one
+    OutOfOrder {
+        expected: u32,
+        received: u32,
+    },
+    /// A message for a frame the other side can't have reached yet
+    TooFarAhead {
+        frame: u32,
+    },
+    /// The engines of both sides differed before `frame`
+    Desync {
+        frame: u32,
+    },
+}
+
+impl core::fmt::Display for NetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NetError::InvalidMessage => write!(f, "Invalid message from the other player"),
+            NetError::GridMismatch { width, height } => write!(
+                f,
+                "The other player has a {}x{} grid, this build uses {}x{}",
+                width, height, GRID_WIDTH, GRID_HEIGHT
+            ),
+            NetError::OutOfOrder { expected, received } => write!(
+                f,
+                "Expected a message for frame {}, received frame {}",
+                expected, received
+            ),
+            NetError::TooFarAhead { frame } => {
+                write!(f, "Received frame {} too far ahead of this side", frame)
+            }
+            NetError::Desync { frame } => {
+                write!(f, "The games of both players differ at frame {}", frame)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl NetMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            NetMessage::Start { seed, player } => {
+                bytes.push(START_TAG);
+                bytes.extend_from_slice(&seed.to_le_bytes());
+                bytes.push(player.index() as u8);
+            }
+            NetMessage::Hello { width, height } => {
+                bytes.push(HELLO_TAG);
+                bytes.extend_from_slice(&(*width as u16).to_le_bytes());
+                bytes.extend_from_slice(&(*height as u16).to_le_bytes());
+            }
+            NetMessage::Inputs { frame, commands } => {
+                bytes.push(INPUTS_TAG);
+                bytes.extend_from_slice(&frame.to_le_bytes());
+                bytes.extend(commands.iter().map(encode_command));
+            }
+            NetMessage::Checksum { frame, checksum } => {
+                bytes.push(CHECKSUM_TAG);
+                bytes.extend_from_slice(&frame.to_le_bytes());
+                bytes.extend_from_slice(&checksum.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, NetError> {
+        let (&tag, rest) = bytes.split_first().ok_or(NetError::InvalidMessage)?;
+        match tag {
+            START_TAG => {
+                let (seed, rest) = rest.split_first_chunk().ok_or(NetError::InvalidMessage)?;
+                // Network games are always between two players
+                let player = match rest {
+                    [0] => Player::Player1,
+                    [1] => Player::Player2,
+                    _ => return Err(NetError::InvalidMessage),
+                };
+                Ok(NetMessage::Start {
+                    seed: u64::from_le_bytes(*seed),
+                    player,
+                })
+            }
+            HELLO_TAG => match rest {
+                &[w0, w1, h0, h1] => Ok(NetMessage::Hello {
+                    width: u16::from_le_bytes([w0, w1]) as usize,
+                    height: u16::from_le_bytes([h0, h1]) as usize,
+                }),
+                _ => Err(NetError::InvalidMessage),
+            },
+            INPUTS_TAG => {
+                let (frame, rest) = rest.split_first_chunk().ok_or(NetError::InvalidMessage)?;
+                let commands = rest
+                    .iter()
+                    .map(|&byte| decode_command(byte))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| NetError::InvalidMessage)?;
+                Ok(NetMessage::Inputs {
+                    frame: u32::from_le_bytes(*frame),
+                    commands,
+                })
+            }
+            CHECKSUM_TAG => {
+                let (frame, rest) = rest.split_first_chunk().ok_or(NetError::InvalidMessage)?;
+                let checksum = rest.try_into().map_err(|_| NetError::InvalidMessage)?;
+                Ok(NetMessage::Checksum {
+                    frame: u32::from_le_bytes(*frame),
+                    checksum: u64::from_le_bytes(checksum),
+                })
+            }
+            _ => Err(NetError::InvalidMessage),
+        }
+    }
+}
+
+/// Runs one side of a network game. Messages for the other side are
+/// collected with `pop_outgoing` and the ones it sends are passed to
+/// `receive`, the transport is up to the frontend.
+pub struct Lockstep {
+    engine: GameEngine<Menu>,
+    local_player: Player,
+    // The next frame to simulate
+    frame: u32,
+    elapsed: Duration,
+    // Inputs for the frames from `frame` on, in order
+    local_frames: VecDeque<Vec<GameCommand>>,
+    remote_frames: VecDeque<Vec<GameCommand>>,
+    pending: Vec<GameCommand>,
+    outgoing: VecDeque<Vec<u8>>,
+    // Checksums by frame that the other side hasn't matched yet
+    local_checksums: VecDeque<(u32, u64)>,
+    remote_checksums: VecDeque<(u32, u64)>,
+    next_remote_checksum: u32,
+}
+
+impl Lockstep {
+    /// Starts from the `Start` message of the relay.
+    pub fn new(seed: u64, local_player: Player) -> Self {
+        let hello = NetMessage::Hello {
+            width: GRID_WIDTH,
+            height: GRID_HEIGHT,
+        };
+        Self {
+            engine: GameEngine::with_seed(seed),
+            local_player,
+            frame: 0,
+            elapsed: Duration::ZERO,
+            // Nobody can press anything during the first frames
+            local_frames: vec![Vec::new(); INPUT_DELAY].into(),
+            remote_frames: vec![Vec::new(); INPUT_DELAY].into(),
+            pending: Vec::new(),
+            outgoing: VecDeque::from([hello.to_bytes()]),
+            local_checksums: VecDeque::new(),
+            remote_checksums: VecDeque::new(),
+            next_remote_checksum: CHECKSUM_INTERVAL,
+        }
+    }
+
+    pub fn local_player(&self) -> Player {
+        self.local_player
+    }
+
+    /// Queues a local input. Every input controls the local player,
+    /// whichever player it was read for.
+    pub fn process_input(&mut self, input_command: GameCommand) {
+        self.pending.push(GameCommand {
+            player: self.local_player,
+            ..input_command
+        });
+    }
+
+    pub fn receive(&mut self, bytes: &[u8]) -> Result<(), NetError> {
+        match NetMessage::from_bytes(bytes)? {
+            NetMessage::Hello { width, height } if (width, height) != (GRID_WIDTH, GRID_HEIGHT) => {
+                Err(NetError::GridMismatch { width, height })
+            }
+            NetMessage::Hello { .. } => Ok(()),
+            NetMessage::Inputs { frame, commands } => {
+                let expected = self.frame + self.remote_frames.len() as u32;
+                if frame != expected {
+                    return Err(NetError::OutOfOrder {
+                        expected,
+                        received: frame,
+                    });
+                }
+                if frame - self.frame > MAX_FRAMES_AHEAD {
+                    return Err(NetError::TooFarAhead { frame });
+                }
+                let remote_player = if self.local_player == Player::Player1 {
+                    Player::Player2
+                } else {
+                    Player::Player1
+                };
+                let commands = commands
+                    .into_iter()
+                    .map(|command| GameCommand {
+                        player: remote_player,
+                        ..command
+                    })
+                    .collect();
+                self.remote_frames.push_back(commands);
+                // Inputs arrive every frame, so checksums from before are
+                // compared as soon as this side has caught up with them
+                self.compare_checksums()
+            }
+            NetMessage::Checksum { frame, checksum } => {
+                if frame != self.next_remote_checksum {
+                    return Err(NetError::OutOfOrder {
+                        expected: self.next_remote_checksum,
+                        received: frame,
+                    });
+                }
+                if frame > self.frame + MAX_FRAMES_AHEAD {
+                    return Err(NetError::TooFarAhead { frame });
+                }
+                self.next_remote_checksum += CHECKSUM_INTERVAL;
+                self.remote_checksums.push_back((frame, checksum));
+                self.compare_checksums()
+            }
+            NetMessage::Start { .. } => Err(NetError::InvalidMessage),
+        }
+    }
+
+    fn compare_checksums(&mut self) -> Result<(), NetError> {
+        while let (Some(&(frame, local)), Some(&(_, remote))) =
+            (self.local_checksums.front(), self.remote_checksums.front())
+        {
+            if local != remote {
+                return Err(NetError::Desync { frame });
+            }
+            self.local_checksums.pop_front();
+            self.remote_checksums.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Simulates every whole frame in `elapsed` real time for which the
+    /// inputs of the other player have arrived.
+    pub fn advance(&mut self, elapsed: Duration) -> Result<(), GameError> {
+        self.elapsed = (self.elapsed + elapsed).min(NET_FRAME * MAX_CATCH_UP);
+        while self.elapsed >= NET_FRAME {
+            // Local inputs go out once per frame, even while waiting
+            if self.local_frames.len() <= INPUT_DELAY {
+                let commands = core::mem::take(&mut self.pending);
+                let frame = self.frame + self.local_frames.len() as u32;
+                self.outgoing.push_back(
+                    NetMessage::Inputs {
+                        frame,
+                        commands: commands.clone(),
+                    }
+                    .to_bytes(),
+                );
+                self.local_frames.push_back(commands);
+            }
+            if self.remote_frames.is_empty() {
+                break;
+            }
+            let local = self.local_frames.pop_front().unwrap_or_default();
+            let remote = self.remote_frames.pop_front().unwrap_or_default();
+            let (first, second) = if self.local_player == Player::Player1 {
+                (local, remote)
+            } else {
+                (remote, local)
+            };
+            for command in first.into_iter().chain(second) {
+                // A rejected input is rejected on both sides alike
+                let _ = self.engine.process_input(command);
+            }
+            let result = self.engine.update(NET_FRAME);
+            self.frame += 1;
+            self.elapsed -= NET_FRAME;
+            result?;
+            if self.frame.is_multiple_of(CHECKSUM_INTERVAL) {
+                let checksum = fnv1a(&self.engine.save_state_to_vec());
+                let message = NetMessage::Checksum {
+                    frame: self.frame,
+                    checksum,
+                };
+                self.outgoing.push_back(message.to_bytes());
+                self.local_checksums.push_back((self.frame, checksum));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the game is stalled on inputs from the other player.
+    pub fn is_waiting(&self) -> bool {
+        self.elapsed >= NET_FRAME && self.remote_frames.is_empty()
+    }
+
+    /// The next message for the other side, in the order they must be
+    /// sent.
+    pub fn pop_outgoing(&mut self) -> Option<Vec<u8>> {
+        self.outgoing.pop_front()
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn render(&self) -> Result<RenderBoard, GameError> {
+        self.engine.render()
+    }
+
+    pub fn status(&self) -> GameStatus {
+        self.engine.status()
+    }
+
+    pub fn drain_events(&mut self) -> GameEvents {
+        self.engine.drain_events()
+    }
+
+    pub fn engine(&self) -> &GameEngine<Menu> {
+        &self.engine
+    }
+}
+
+// 64-bit FNV-1a, the same on every platform
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{ButtonState, CommandType, GamePhase};
+    use std::collections::HashMap;
+
+    fn press(command_type: CommandType) -> GameCommand {
+        GameCommand::new(command_type, ButtonState::Pressed, Player::Player1)
+    }
+
+    #[test]
+    fn test_messages_round_trip() {
+        let messages = [
+            NetMessage::Start {
+                seed: 0x0123_4567_89ab_cdef,
+                player: Player::Player2,
+            },
+            NetMessage::Hello {
+                width: GRID_WIDTH,
+                height: GRID_HEIGHT,
+            },
+            NetMessage::Inputs {
+                frame: 70_000,
+                commands: vec![press(CommandType::Select), press(CommandType::Pause)],
+            },
+            NetMessage::Checksum {
+                frame: 120,
+                checksum: 0xfedc_ba98_7654_3210,
+            },
+        ];
+        for message in messages {
+            assert_eq!(NetMessage::from_bytes(&message.to_bytes()), Ok(message));
+        }
+        assert_eq!(NetMessage::from_bytes(&[]), Err(NetError::InvalidMessage));
+        assert_eq!(
+            NetMessage::from_bytes(&[INPUTS_TAG, 0, 0, 0, 0, 0x07]),
+            Err(NetError::InvalidMessage)
+        );
+    }
+
+    // Both sides see the same frames, even when their frame times and the
+    // delivery of messages differ
+    #[test]
+    fn test_peers_stay_in_lockstep() {
+        let mut peers = [
+            Lockstep::new(9, Player::Player1),
+            Lockstep::new(9, Player::Player2),
+        ];
+        let mut in_flight: [VecDeque<Vec<u8>>; 2] = Default::default();
+        let mut frames: [HashMap<u32, RenderBoard>; 2] = Default::default();
+        let inputs = [CommandType::Right, CommandType::Select, CommandType::Up];
+        for tick in 0..600u32 {
+            for (side, peer) in peers.iter_mut().enumerate() {
+                if tick % (13 + side as u32 * 4) == 0 {
+                    peer.process_input(press(inputs[tick as usize % inputs.len()]));
+                }
+                let elapsed = NET_FRAME + Duration::from_micros(u64::from(tick % 7) * 900);
+                peer.advance(elapsed).unwrap();
+                frames[side].insert(peer.frame(), peer.render().unwrap());
+                in_flight[1 - side].extend(core::iter::from_fn(|| peer.pop_outgoing()));
+            }
+            // One side's messages arrive in bursts
+            for (side, queue) in in_flight.iter_mut().enumerate() {
+                if side == 1 && tick % 5 != 0 {
+                    continue;
+                }
+                for message in queue.drain(..) {
+                    peers[side].receive(&message).unwrap();
+                }
+            }
+        }
+        let mut compared = 0;
+        for (frame, render) in &frames[0] {
+            if let Some(other) = frames[1].get(frame) {
+                assert!(render == other, "frame {frame} differs");
+                compared += 1;
+            }
+        }
+        assert!(compared > 50);
+        assert!(peers[0].frame() > 500);
+        assert_eq!(peers[0].status(), peers[1].status());
+        assert_ne!(peers[0].status().phase, GamePhase::Menu);
+    }
+
+    #[test]
+    fn test_mismatches_are_reported() {
+        let mut peer = Lockstep::new(1, Player::Player1);
+        let hello = NetMessage::Hello {
+            width: GRID_WIDTH + 1,
+            height: GRID_HEIGHT,
+        };
+        assert_eq!(
+            peer.receive(&hello.to_bytes()),
+            Err(NetError::GridMismatch {
+                width: GRID_WIDTH + 1,
+                height: GRID_HEIGHT
+            })
+        );
+        let inputs = NetMessage::Inputs {
+            frame: 5,
+            commands: Vec::new(),
+        };
+        assert_eq!(
+            peer.receive(&inputs.to_bytes()),
+            Err(NetError::OutOfOrder {
+                expected: INPUT_DELAY as u32,
+                received: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_frames_too_far_ahead_are_rejected() {
+        let mut peer = Lockstep::new(1, Player::Player1);
+        let mut inputs = (INPUT_DELAY as u32..).map(|frame| {
+            NetMessage::Inputs {
+                frame,
+                commands: Vec::new(),
+            }
+            .to_bytes()
+        });
+        for _ in INPUT_DELAY as u32..=MAX_FRAMES_AHEAD {
+            peer.receive(&inputs.next().unwrap()).unwrap();
+        }
+        assert_eq!(
+            peer.receive(&inputs.next().unwrap()),
+            Err(NetError::TooFarAhead {
+                frame: MAX_FRAMES_AHEAD + 1
+            })
+        );
+        let checksum = NetMessage::Checksum {
+            frame: CHECKSUM_INTERVAL,
+            checksum: 0,
+        };
+        assert_eq!(
+            peer.receive(&checksum.to_bytes()),
+            Err(NetError::TooFarAhead {
+                frame: CHECKSUM_INTERVAL
+            })
+        );
+    }
+
+    #[test]
+    fn test_desyncs_are_detected() {
+        let mut peers = [
+            Lockstep::new(3, Player::Player1),
+            Lockstep::new(3, Player::Player2),
+        ];
+        // An input that never went over the network
+        peers[1]
+            .engine
+            .process_input(press(CommandType::Right))
+            .unwrap();
+        let mut errors = Vec::new();
+        for _ in 0..2 * CHECKSUM_INTERVAL {
+            for side in 0..2 {
+                peers[side].advance(NET_FRAME).unwrap();
+                while let Some(message) = peers[side].pop_outgoing() {
+                    if let Err(err) = peers[1 - side].receive(&message) {
+                        errors.push(err);
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            errors.first(),
+            Some(&NetError::Desync {
+                frame: CHECKSUM_INTERVAL
+            })
+        );
+    }
+}
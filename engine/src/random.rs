@@ -43,6 +43,22 @@ impl CustomRng {
         (self.next_u64() as f64 / u64::MAX as f64) < probability
     }
 
+    /// The seed, stream and word position, enough to resume the sequence.
+    pub fn position(&self) -> ([u8; 32], u64, u128) {
+        (
+            self.rng.get_seed(),
+            self.rng.get_stream(),
+            self.rng.get_word_pos(),
+        )
+    }
+
+    pub fn from_position(seed: [u8; 32], stream: u64, word_pos: u128) -> Self {
+        let mut rng = ChaCha8Rng::from_seed(seed);
+        rng.set_stream(stream);
+        rng.set_word_pos(word_pos);
+        Self { rng }
+    }
+
     pub fn shuffle<T>(&mut self, slice: &mut [T]) {
         for i in (1..slice.len()).rev() {
             let j = (self.next_u64() % (i as u64 + 1)) as usize;
@@ -0,0 +1,824 @@
+//! Recording and replaying of game sessions.
+//!
+//! A session is fully described by the menu seed and the ordered stream of
+//! inputs, update deltas and changes the frontend makes to the engine, such
+//! as the tick rate, so replaying that stream against a fresh engine
+//! reproduces every frame exactly.
+//!
+//! A session that continues from a save state starts from that state
+//! instead of a fresh engine, and the recording carries the state.
+//!
+//! Recordings are stored in the `.ttr` format:
+//!
+//! - header: the magic bytes `TTR1`, or `TTR2` when the recording starts
+//!   from a save state, the grid width and height as little-endian `u16`
+//!   and the seed as a little-endian `u64`
+//! - for `TTR2` only: the length of the save state as a little-endian
+//!   `u32` followed by the save state
+//! - events, until the end of the data:
+//!   - update: a `0x00` byte followed by the delta in nanoseconds as an
+//!     unsigned LEB128 varint
+//!   - controllers: a `0x01` byte followed by the number of connected
+//!     controllers as an unsigned LEB128 varint
+//!   - start game: a `0x02` byte followed by the index of the game in
+//!     `GameEngine::games` as an unsigned LEB128 varint
+//!   - tick rate: a `0x03` byte followed by the rate plus one as an
+//!     unsigned LEB128 varint, zero when the engine updates once per frame
+//!   - attract delay: a `0x04` byte followed by the delay in nanoseconds
+//!     plus one as an unsigned LEB128 varint, zero when attract mode is off
+//!   - display settings: a `0x05` byte followed by the settings encoded by
+//!     `DisplaySettings::to_bytes`
+//!   - input: a single byte with the high bit set, the command type in
+//!     bits 0-2 (left, right, down, up, select, quit, pause), bit 3 set
+//!     when the button was released and the player in bits 4-5
+
+use crate::GameError;
+use crate::event::GameEvents;
+use crate::game::{
+    ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, GameCommand, GameEngine, GameStatus, Menu,
+    Player, RenderBoard,
+};
+use crate::settings::DisplaySettings;
+use crate::snapshot::SnapshotError;
+use core::time::Duration;
+
+const MAGIC: &[u8; 4] = b"TTR1";
+const MAGIC_WITH_STATE: &[u8; 4] = b"TTR2";
+const HEADER_LEN: usize = 16;
+const UPDATE_TAG: u8 = 0x00;
+const CONTROLLERS_TAG: u8 = 0x01;
+const START_GAME_TAG: u8 = 0x02;
+const TICK_RATE_TAG: u8 = 0x03;
+const ATTRACT_DELAY_TAG: u8 = 0x04;
+const DISPLAY_SETTINGS_TAG: u8 = 0x05;
+const INPUT_FLAG: u8 = 0x80;
+const RELEASED_FLAG: u8 = 0x08;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayEvent {
+    Input(GameCommand),
+    Update(Duration),
+    Controllers(usize),
+    /// The index of a game in `GameEngine::games`
+    StartGame(usize),
+    TickRate(Option<u32>),
+    AttractDelay(Option<Duration>),
+    DisplaySettings(DisplaySettings),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    InvalidHeader,
+    GridMismatch { width: usize, height: usize },
+    UnexpectedEof,
+    InvalidEvent(u8),
+    InvalidState(SnapshotError),
+}
+
+impl core::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReplayError::InvalidHeader => write!(f, "Not a teotile recording"),
+            ReplayError::GridMismatch { width, height } => write!(
+                f,
+                "Recording is for a {}x{} grid, this build uses {}x{}",
+                width, height, GRID_WIDTH, GRID_HEIGHT
+            ),
+            ReplayError::UnexpectedEof => write!(f, "Recording ends in the middle of an event"),
+            ReplayError::InvalidEvent(byte) => write!(f, "Invalid event byte {:#04x}", byte),
+            ReplayError::InvalidState(err) => write!(f, "Invalid starting state: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// The seed, starting state and event stream of a recorded session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    seed: u64,
+    initial_state: Option<Vec<u8>>,
+    events: Vec<ReplayEvent>,
+}
+
+impl Recording {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            initial_state: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The save state the session started from, if it didn't start fresh.
+    pub fn initial_state(&self) -> Option<&[u8]> {
+        self.initial_state.as_deref()
+    }
+
+    pub fn events(&self) -> &[ReplayEvent] {
+        &self.events
+    }
+
+    /// Total simulated time covered by the recording.
+    pub fn duration(&self) -> Duration {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                ReplayEvent::Update(delta) => Some(*delta),
+                _ => None,
+            })
+            .sum()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.events.len() * 4);
+        bytes.extend_from_slice(match self.initial_state {
+            Some(_) => MAGIC_WITH_STATE,
+            None => MAGIC,
+        });
+        bytes.extend_from_slice(&(GRID_WIDTH as u16).to_le_bytes());
+        bytes.extend_from_slice(&(GRID_HEIGHT as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        if let Some(state) = &self.initial_state {
+            bytes.extend_from_slice(&(state.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(state);
+        }
+
+        for event in &self.events {
+            match event {
+                ReplayEvent::Update(delta) => {
+                    bytes.push(UPDATE_TAG);
+                    write_varint(&mut bytes, delta.as_nanos() as u64);
+                }
+                ReplayEvent::Input(command) => bytes.push(encode_command(command)),
+                ReplayEvent::Controllers(count) => {
+                    bytes.push(CONTROLLERS_TAG);
+                    write_varint(&mut bytes, *count as u64);
+                }
+                ReplayEvent::StartGame(index) => {
+                    bytes.push(START_GAME_TAG);
+                    write_varint(&mut bytes, *index as u64);
+                }
+                ReplayEvent::TickRate(tick_rate) => {
+                    bytes.push(TICK_RATE_TAG);
+                    write_varint(&mut bytes, tick_rate.map_or(0, |rate| u64::from(rate) + 1));
+                }
+                ReplayEvent::AttractDelay(delay) => {
+                    bytes.push(ATTRACT_DELAY_TAG);
+                    let nanos =
+                        delay.map_or(0, |delay| (delay.as_nanos() as u64).saturating_add(1));
+                    write_varint(&mut bytes, nanos);
+                }
+                ReplayEvent::DisplaySettings(settings) => {
+                    bytes.push(DISPLAY_SETTINGS_TAG);
+                    bytes.extend_from_slice(&settings.to_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        if bytes.len() < HEADER_LEN || (&bytes[0..4] != MAGIC && &bytes[0..4] != MAGIC_WITH_STATE) {
+            return Err(ReplayError::InvalidHeader);
+        }
+        let width = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+        let height = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        if (width, height) != (GRID_WIDTH, GRID_HEIGHT) {
+            return Err(ReplayError::GridMismatch { width, height });
+        }
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&bytes[8..HEADER_LEN]);
+
+        let mut recording = Self::new(u64::from_le_bytes(seed));
+        let mut rest = &bytes[HEADER_LEN..];
+        if &bytes[0..4] == MAGIC_WITH_STATE {
+            let (len, tail) = rest.split_first_chunk().ok_or(ReplayError::UnexpectedEof)?;
+            let len = u32::from_le_bytes(*len) as usize;
+            if tail.len() < len {
+                return Err(ReplayError::UnexpectedEof);
+            }
+            let (state, tail) = tail.split_at(len);
+            // Checked here so a replay never starts from a broken state
+            GameEngine::with_seed(recording.seed)
+                .restore_state(state)
+                .map_err(ReplayError::InvalidState)?;
+            recording.initial_state = Some(state.to_vec());
+            rest = tail;
+        }
+        while let Some((&byte, tail)) = rest.split_first() {
+            rest = tail;
+            let event = if byte == UPDATE_TAG {
+                let nanos = read_varint(&mut rest)?;
+                ReplayEvent::Update(Duration::from_nanos(nanos))
+            } else if byte == CONTROLLERS_TAG {
+                ReplayEvent::Controllers(read_varint(&mut rest)? as usize)
+            } else if byte == START_GAME_TAG {
+                let index = read_varint(&mut rest)? as usize;
+                if index >= GameEngine::games().count() {
+                    return Err(ReplayError::InvalidEvent(byte));
+                }
+                ReplayEvent::StartGame(index)
+            } else if byte == TICK_RATE_TAG {
+                let tick_rate = match read_varint(&mut rest)? {
+                    0 => None,
+                    rate => {
+                        Some(u32::try_from(rate - 1).map_err(|_| ReplayError::InvalidEvent(byte))?)
+                    }
+                };
+                ReplayEvent::TickRate(tick_rate)
+            } else if byte == ATTRACT_DELAY_TAG {
+                let delay = match read_varint(&mut rest)? {
+                    0 => None,
+                    nanos => Some(Duration::from_nanos(nanos - 1)),
+                };
+                ReplayEvent::AttractDelay(delay)
+            } else if byte == DISPLAY_SETTINGS_TAG {
+                if rest.len() < DisplaySettings::ENCODED_LEN {
+                    return Err(ReplayError::UnexpectedEof);
+                }
+                let (settings, tail) = rest.split_at(DisplaySettings::ENCODED_LEN);
+                rest = tail;
+                let settings =
+                    DisplaySettings::from_bytes(settings).ok_or(ReplayError::InvalidEvent(byte))?;
+                ReplayEvent::DisplaySettings(settings)
+            } else {
+                ReplayEvent::Input(decode_command(byte)?)
+            };
+            recording.events.push(event);
+        }
+        Ok(recording)
+    }
+}
+
+pub(crate) fn encode_command(command: &GameCommand) -> u8 {
+    let command_type = match command.command_type {
+        CommandType::Left => 0,
+        CommandType::Right => 1,
+        CommandType::Down => 2,
+        CommandType::Up => 3,
+        CommandType::Select => 4,
+        CommandType::Quit => 5,
+        CommandType::Pause => 6,
+    };
+    let released = match command.button_state {
+        ButtonState::Pressed => 0,
+        ButtonState::Released => RELEASED_FLAG,
+    };
+    INPUT_FLAG | command_type | released | ((command.player.index() as u8) << 4)
+}
+
+pub(crate) fn decode_command(byte: u8) -> Result<GameCommand, ReplayError> {
+    if byte & INPUT_FLAG == 0 {
+        return Err(ReplayError::InvalidEvent(byte));
+    }
+    let command_type = match byte & 0x07 {
+        0 => CommandType::Left,
+        1 => CommandType::Right,
+        2 => CommandType::Down,
+        3 => CommandType::Up,
+        4 => CommandType::Select,
+        5 => CommandType::Quit,
+        6 => CommandType::Pause,
+        _ => return Err(ReplayError::InvalidEvent(byte)),
+    };
+    let button_state = if byte & RELEASED_FLAG == 0 {
+        ButtonState::Pressed
+    } else {
+        ButtonState::Released
+    };
+    let player =
+        Player::from_index(((byte >> 4) & 0x07) as usize).ok_or(ReplayError::InvalidEvent(byte))?;
+    Ok(GameCommand::new(command_type, button_state, player))
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, ReplayError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, tail) = bytes.split_first().ok_or(ReplayError::UnexpectedEof)?;
+        *bytes = tail;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(ReplayError::InvalidEvent(UPDATE_TAG))
+}
+
+/// Wraps a `GameEngine` and records every input and update passed to it.
+pub struct Recorder {
+    engine: GameEngine<Menu>,
+    recording: Recording,
+}
+
+impl Recorder {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            engine: GameEngine::with_seed(seed),
+            recording: Recording::new(seed),
+        }
+    }
+
+    /// Continues from a save state written by `GameEngine::save_state`.
+    pub fn from_state(seed: u64, state: &[u8]) -> Result<Self, SnapshotError> {
+        let mut recorder = Self::new(seed);
+        recorder.engine.restore_state(state)?;
+        recorder.recording.initial_state = Some(state.to_vec());
+        Ok(recorder)
+    }
+
+    pub fn process_input(&mut self, input_command: GameCommand) -> Result<(), GameError> {
+        self.recording
+            .events
+            .push(ReplayEvent::Input(input_command));
+        self.engine.process_input(input_command)
+    }
+
+    pub fn update(&mut self, delta_time: Duration) -> Result<(), GameError> {
+        self.recording.events.push(ReplayEvent::Update(delta_time));
+        self.engine.update(delta_time)
+    }
+
+    /// See `GameEngine::set_controllers`, the count is recorded because it
+    /// decides which game Select starts.
+    pub fn set_controllers(&mut self, count: usize) {
+        self.recording.events.push(ReplayEvent::Controllers(count));
+        self.engine.set_controllers(count);
+    }
+
+    /// See `GameEngine::set_tick_rate`.
+    pub fn set_tick_rate(&mut self, tick_rate: Option<u32>) {
+        self.recording.events.push(ReplayEvent::TickRate(tick_rate));
+        self.engine.set_tick_rate(tick_rate);
+    }
+
+    /// See `GameEngine::set_attract_delay`.
+    pub fn set_attract_delay(&mut self, delay: Option<Duration>) {
+        self.recording.events.push(ReplayEvent::AttractDelay(delay));
+        self.engine.set_attract_delay(delay);
+    }
+
+    /// See `GameEngine::set_display_settings`, the settings are recorded
+    /// because the settings menu shows them.
+    pub fn set_display_settings(&mut self, settings: DisplaySettings) {
+        self.recording
+            .events
+            .push(ReplayEvent::DisplaySettings(settings));
+        self.engine.set_display_settings(settings);
+    }
+
+    /// See `GameEngine::start_game`.
+    pub fn start_game(&mut self, id: &str) -> bool {
+        let Some(index) = GameEngine::games().position(|game| game.id == id) else {
+            return false;
+        };
+        self.recording.events.push(ReplayEvent::StartGame(index));
+        self.engine.start_game(id)
+    }
+
+    pub fn render(&self) -> Result<RenderBoard, GameError> {
+        self.engine.render()
+    }
+
+    pub fn status(&self) -> GameStatus {
+        self.engine.status()
+    }
+
+    pub fn drain_events(&mut self) -> GameEvents {
+        self.engine.drain_events()
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn engine(&self) -> &GameEngine<Menu> {
+        &self.engine
+    }
+
+    /// Changes made through the engine are not recorded, use the setters
+    /// above for anything that affects what is rendered.
+    pub fn engine_mut(&mut self) -> &mut GameEngine<Menu> {
+        &mut self.engine
+    }
+
+    /// Stops recording, the engine goes on from where the recording ends.
+    pub fn into_engine(self) -> GameEngine<Menu> {
+        self.engine
+    }
+}
+
+/// Plays a `Recording` back against a fresh engine.
+///
+/// Playback happens one recorded frame at a time: the inputs leading up to
+/// an update are delivered and then the update itself is applied with its
+/// recorded delta, regardless of how much real time has passed.
+pub struct Replayer {
+    engine: GameEngine<Menu>,
+    recording: Recording,
+    next_event: usize,
+    playback_time: Duration,
+    replayed_time: Duration,
+}
+
+impl Replayer {
+    pub fn new(recording: Recording) -> Self {
+        let mut engine = GameEngine::with_seed(recording.seed);
+        if let Some(state) = &recording.initial_state {
+            // Recordings only hold states that were restored before
+            let _ = engine.restore_state(state);
+        }
+        Self {
+            engine,
+            recording,
+            next_event: 0,
+            playback_time: Duration::ZERO,
+            replayed_time: Duration::ZERO,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.recording.events.len()
+    }
+
+    /// Replays the next recorded frame and returns its delta, or `None`
+    /// when the recording has ended.
+    pub fn next_frame(&mut self) -> Result<Option<Duration>, GameError> {
+        while let Some(event) = self.recording.events.get(self.next_event) {
+            self.next_event += 1;
+            match *event {
+                ReplayEvent::Input(command) => self.engine.process_input(command)?,
+                ReplayEvent::Controllers(count) => self.engine.set_controllers(count),
+                ReplayEvent::StartGame(index) => {
+                    if let Some(game) = GameEngine::games().nth(index) {
+                        self.engine.start_game(game.id);
+                    }
+                }
+                ReplayEvent::TickRate(tick_rate) => self.engine.set_tick_rate(tick_rate),
+                ReplayEvent::AttractDelay(delay) => self.engine.set_attract_delay(delay),
+                ReplayEvent::DisplaySettings(settings) => {
+                    self.engine.set_display_settings(settings)
+                }
+                ReplayEvent::Update(delta) => {
+                    self.engine.update(delta)?;
+                    self.replayed_time += delta;
+                    return Ok(Some(delta));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Advances playback by `elapsed` real time, replaying every recorded
+    /// frame that fits so the session plays back at its original speed.
+    pub fn advance(&mut self, elapsed: Duration) -> Result<(), GameError> {
+        self.playback_time += elapsed;
+        while let Some(delta) = self.peek_delta() {
+            if self.replayed_time + delta > self.playback_time {
+                break;
+            }
+            self.next_frame()?;
+        }
+        // Deliver trailing inputs that have no update after them
+        if self.peek_delta().is_none() {
+            self.next_frame()?;
+        }
+        Ok(())
+    }
+
+    pub fn render(&self) -> Result<RenderBoard, GameError> {
+        self.engine.render()
+    }
+
+    pub fn status(&self) -> GameStatus {
+        self.engine.status()
+    }
+
+    pub fn drain_events(&mut self) -> GameEvents {
+        self.engine.drain_events()
+    }
+
+    fn peek_delta(&self) -> Option<Duration> {
+        self.recording.events[self.next_event..]
+            .iter()
+            .find_map(|event| match event {
+                ReplayEvent::Update(delta) => Some(*delta),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::NightMode;
+
+    const STEP: Duration = Duration::from_millis(16);
+
+    fn press(command_type: CommandType, player: Player) -> GameCommand {
+        GameCommand::new(command_type, ButtonState::Pressed, player)
+    }
+
+    // Picks a game from the menu and plays it for a while with uneven deltas
+    fn record_session(seed: u64) -> (Recorder, Vec<RenderBoard>) {
+        let mut recorder = Recorder::new(seed);
+        let mut frames = Vec::new();
+        let inputs = [
+            CommandType::Right,
+            CommandType::Right,
+            CommandType::Select,
+            CommandType::Left,
+            CommandType::Up,
+            CommandType::Select,
+        ];
+        for frame in 0..300u64 {
+            if frame % 25 == 0 {
+                let command_type = inputs[(frame / 25) as usize % inputs.len()];
+                recorder
+                    .process_input(press(command_type, Player::Player1))
+                    .unwrap();
+            }
+            let delta = STEP + Duration::from_micros(frame * 37 % 5000);
+            recorder.update(delta).unwrap();
+            frames.push(recorder.render().unwrap());
+        }
+        (recorder, frames)
+    }
+
+    #[test]
+    fn test_replay_reproduces_frames() {
+        let (recorder, frames) = record_session(42);
+        let mut replayer = Replayer::new(recorder.recording().clone());
+        for frame in frames {
+            assert!(replayer.next_frame().unwrap().is_some());
+            assert!(replayer.render().unwrap() == frame);
+        }
+        assert!(replayer.is_finished());
+        assert_eq!(replayer.next_frame().unwrap(), None);
+    }
+
+    #[test]
+    fn test_round_trip_through_bytes() {
+        let (recorder, _) = record_session(7);
+        let recording = recorder.recording();
+        let bytes = recording.to_bytes();
+        assert_eq!(&bytes[0..4], MAGIC);
+        assert_eq!(Recording::from_bytes(&bytes), Ok(recording.clone()));
+    }
+
+    #[test]
+    fn test_replay_from_save_state() {
+        let (mut recorder, _) = record_session(11);
+        let state = recorder.engine_mut().save_state_to_vec();
+        let mut resumed = Recorder::from_state(12, &state).unwrap();
+        let mut frames = Vec::new();
+        for frame in 0..100u64 {
+            if frame % 30 == 0 {
+                resumed
+                    .process_input(press(CommandType::Left, Player::Player2))
+                    .unwrap();
+            }
+            resumed.update(STEP).unwrap();
+            frames.push(resumed.render().unwrap());
+        }
+
+        let bytes = resumed.recording().to_bytes();
+        assert_eq!(&bytes[0..4], MAGIC_WITH_STATE);
+        let recording = Recording::from_bytes(&bytes).unwrap();
+        assert_eq!(recording.initial_state(), Some(&state[..]));
+        let mut replayer = Replayer::new(recording);
+        for frame in frames {
+            replayer.next_frame().unwrap();
+            assert!(replayer.render().unwrap() == frame);
+        }
+
+        let mut corrupt = bytes;
+        corrupt[HEADER_LEN + 4] ^= 0xff;
+        assert!(matches!(
+            Recording::from_bytes(&corrupt),
+            Err(ReplayError::InvalidState(_))
+        ));
+    }
+
+    #[test]
+    fn test_replay_keeps_the_controller_count() {
+        // With one controller Snake Multiplayer starts against a bot
+        let mut recorder = Recorder::new(5);
+        recorder.set_controllers(1);
+        let mut two_players = GameEngine::with_seed(5);
+        while recorder.status().game != Some("Snake Multiplayer") {
+            let right = press(CommandType::Right, Player::Player1);
+            recorder.process_input(right).unwrap();
+            two_players.process_input(right).unwrap();
+        }
+        let select = press(CommandType::Select, Player::Player1);
+        recorder.process_input(select).unwrap();
+        two_players.process_input(select).unwrap();
+        let mut frames = Vec::new();
+        for _ in 0..300 {
+            recorder.update(STEP).unwrap();
+            two_players.update(STEP).unwrap();
+            frames.push(recorder.render().unwrap());
+        }
+        assert!(two_players.render().unwrap() != recorder.render().unwrap());
+
+        let bytes = recorder.recording().to_bytes();
+        let mut replayer = Replayer::new(Recording::from_bytes(&bytes).unwrap());
+        for frame in frames {
+            replayer.next_frame().unwrap();
+            assert!(replayer.render().unwrap() == frame);
+        }
+    }
+
+    #[test]
+    fn test_replay_starts_games_by_id() {
+        let (mut recorder, mut frames) = record_session(13);
+        assert!(!recorder.start_game("Chess"));
+        assert!(recorder.start_game("Tetris"));
+        for _ in 0..60 {
+            recorder.update(STEP).unwrap();
+            frames.push(recorder.render().unwrap());
+        }
+        assert_eq!(recorder.status().game, Some("Tetris"));
+
+        let bytes = recorder.recording().to_bytes();
+        let mut replayer = Replayer::new(Recording::from_bytes(&bytes).unwrap());
+        for frame in frames {
+            replayer.next_frame().unwrap();
+            assert!(replayer.render().unwrap() == frame);
+        }
+        assert_eq!(replayer.status().game, Some("Tetris"));
+    }
+
+    fn replay_frames(recording: Recording) -> Vec<RenderBoard> {
+        let mut replayer = Replayer::new(recording);
+        let mut frames = Vec::new();
+        while replayer.next_frame().unwrap().is_some() {
+            frames.push(replayer.render().unwrap());
+        }
+        frames
+    }
+
+    #[test]
+    fn test_replay_keeps_engine_settings() {
+        let mut recorder = Recorder::new(21);
+        let mut frames = Vec::new();
+        recorder.set_display_settings(DisplaySettings {
+            brightness: 1,
+            ..DisplaySettings::new()
+        });
+        recorder
+            .process_input(press(CommandType::Pause, Player::Player1))
+            .unwrap();
+        for _ in 0..10 {
+            recorder.update(STEP).unwrap();
+            frames.push(recorder.render().unwrap());
+        }
+        recorder
+            .process_input(press(CommandType::Select, Player::Player1))
+            .unwrap();
+        recorder.set_attract_delay(Some(Duration::from_millis(500)));
+        recorder.set_tick_rate(None);
+        for frame in 0..300u64 {
+            recorder
+                .update(STEP + Duration::from_micros(frame * 37 % 5000))
+                .unwrap();
+            frames.push(recorder.render().unwrap());
+        }
+
+        let bytes = recorder.recording().to_bytes();
+        let recording = Recording::from_bytes(&bytes).unwrap();
+        assert!(replay_frames(recording.clone()) == frames);
+
+        // Each setting changes what is rendered, so none of them can be left out
+        let settings = [
+            ReplayEvent::DisplaySettings(DisplaySettings::new()),
+            ReplayEvent::AttractDelay(None),
+            ReplayEvent::TickRate(None),
+        ];
+        for setting in settings {
+            let mut without = recording.clone();
+            without.events.retain(|event| {
+                core::mem::discriminant(event) != core::mem::discriminant(&setting)
+            });
+            assert!(without.events.len() < recording.events.len());
+            assert!(replay_frames(without) != frames);
+        }
+    }
+
+    #[test]
+    fn test_engine_settings_round_trip() {
+        let mut recording = Recording::new(4);
+        recording.events = vec![
+            ReplayEvent::TickRate(None),
+            ReplayEvent::TickRate(Some(0)),
+            ReplayEvent::TickRate(Some(u32::MAX)),
+            ReplayEvent::AttractDelay(None),
+            ReplayEvent::AttractDelay(Some(Duration::ZERO)),
+            ReplayEvent::AttractDelay(Some(Duration::from_secs(90))),
+            ReplayEvent::DisplaySettings(DisplaySettings {
+                brightness: 7,
+                night_mode: NightMode::Scheduled,
+                night_start: 20,
+                night_end: 6,
+            }),
+        ];
+        assert_eq!(Recording::from_bytes(&recording.to_bytes()), Ok(recording));
+    }
+
+    #[test]
+    fn test_every_command_round_trips() {
+        let command_types = [
+            CommandType::Left,
+            CommandType::Right,
+            CommandType::Down,
+            CommandType::Up,
+            CommandType::Select,
+            CommandType::Quit,
+            CommandType::Pause,
+        ];
+        for command_type in command_types {
+            for button_state in [ButtonState::Pressed, ButtonState::Released] {
+                for player in [Player::Player1, Player::Player2] {
+                    let command = GameCommand::new(command_type, button_state, player);
+                    assert_eq!(decode_command(encode_command(&command)), Ok(command));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_advance_follows_real_time() {
+        let (recorder, _) = record_session(3);
+        let recording = recorder.recording().clone();
+        let total = recording.duration();
+        let mut replayer = Replayer::new(recording);
+
+        replayer.advance(total / 2).unwrap();
+        assert!(!replayer.is_finished());
+        assert!(replayer.replayed_time <= total / 2);
+        assert!(replayer.replayed_time + STEP * 2 > total / 2);
+
+        replayer.advance(total).unwrap();
+        assert!(replayer.is_finished());
+        assert_eq!(replayer.replayed_time, total);
+    }
+
+    #[test]
+    fn test_invalid_recordings_are_rejected() {
+        let bytes = Recording::new(1).to_bytes();
+        assert_eq!(
+            Recording::from_bytes(b"nope"),
+            Err(ReplayError::InvalidHeader)
+        );
+
+        let mut truncated = bytes.clone();
+        truncated.extend_from_slice(&[UPDATE_TAG, 0x80]);
+        assert_eq!(
+            Recording::from_bytes(&truncated),
+            Err(ReplayError::UnexpectedEof)
+        );
+
+        let mut invalid = bytes.clone();
+        invalid.push(INPUT_FLAG | 0x07);
+        assert_eq!(
+            Recording::from_bytes(&invalid),
+            Err(ReplayError::InvalidEvent(INPUT_FLAG | 0x07))
+        );
+
+        let mut short_settings = bytes.clone();
+        short_settings.push(DISPLAY_SETTINGS_TAG);
+        short_settings.extend_from_slice(&DisplaySettings::new().to_bytes()[..4]);
+        assert_eq!(
+            Recording::from_bytes(&short_settings),
+            Err(ReplayError::UnexpectedEof)
+        );
+
+        let mut bad_settings = bytes.clone();
+        bad_settings.push(DISPLAY_SETTINGS_TAG);
+        bad_settings.extend_from_slice(&[0; DisplaySettings::ENCODED_LEN]);
+        assert_eq!(
+            Recording::from_bytes(&bad_settings),
+            Err(ReplayError::InvalidEvent(DISPLAY_SETTINGS_TAG))
+        );
+
+        let mut other_grid = bytes;
+        other_grid[4] = other_grid[4].wrapping_add(1);
+        assert!(matches!(
+            Recording::from_bytes(&other_grid),
+            Err(ReplayError::GridMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+//! Persistent high score table shared by all frontends.
+//!
+//! The engine keeps the table in memory and frontends persist it through a
+//! `ScoreStorage` implementation, e.g. a file, `localStorage` or flash.
+
+pub const SCORES_PER_GAME: usize = 5;
+// Leaves room for games to be added without changing the stored format
+pub const MAX_GAMES: usize = 32;
+
+const MAGIC: &[u8; 4] = b"TTS1";
+
+/// The best scores of every game, indexed by the game's menu position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scoreboard {
+    scores: [[u32; SCORES_PER_GAME]; MAX_GAMES],
+}
+
+impl Scoreboard {
+    pub const ENCODED_LEN: usize = MAGIC.len() + MAX_GAMES * SCORES_PER_GAME * 4;
+
+    pub fn new() -> Self {
+        Self {
+            scores: [[0; SCORES_PER_GAME]; MAX_GAMES],
+        }
+    }
+
+    /// The scores of a game in descending order, unused entries are zero.
+    pub fn scores(&self, game: usize) -> &[u32; SCORES_PER_GAME] {
+        &self.scores[game]
+    }
+
+    /// Adds a score to a game's table and returns its rank if it made it.
+    pub fn insert(&mut self, game: usize, score: u32) -> Option<usize> {
+        let scores = self.scores.get_mut(game)?;
+        let rank = scores.iter().position(|&s| score > s)?;
+        scores.copy_within(rank..SCORES_PER_GAME - 1, rank + 1);
+        scores[rank] = score;
+        Some(rank)
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        bytes[..MAGIC.len()].copy_from_slice(MAGIC);
+        let scores = self.scores.iter().flatten();
+        for (chunk, score) in bytes[MAGIC.len()..].chunks_exact_mut(4).zip(scores) {
+            chunk.copy_from_slice(&score.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Parses a stored table, returning `None` if the data is not one.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::ENCODED_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+        let mut scoreboard = Self::new();
+        let scores = scoreboard.scores.iter_mut().flatten();
+        for (score, chunk) in scores.zip(bytes[MAGIC.len()..].chunks_exact(4)) {
+            *score = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Some(scoreboard)
+    }
+}
+
+impl Default for Scoreboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait ScoreStorage {
+    type Error;
+
+    /// Returns the stored table, or `None` if nothing has been saved yet.
+    fn load(&mut self) -> Result<Option<Scoreboard>, Self::Error>;
+    fn save(&mut self, scoreboard: &Scoreboard) -> Result<(), Self::Error>;
+}
+
+/// Stores the table in a file, used by the TUI and Raspberry Pi frontends.
+#[cfg(any(test, feature = "std"))]
+pub struct FileScoreStorage {
+    path: std::path::PathBuf,
+}
+
+#[cfg(any(test, feature = "std"))]
+impl FileScoreStorage {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl ScoreStorage for FileScoreStorage {
+    type Error = std::io::Error;
+
+    fn load(&mut self) -> Result<Option<Scoreboard>, Self::Error> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => Ok(Scoreboard::from_bytes(&bytes)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&mut self, scoreboard: &Scoreboard) -> Result<(), Self::Error> {
+        std::fs::write(&self.path, scoreboard.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_keeps_scores_sorted() {
+        let mut scoreboard = Scoreboard::new();
+        assert_eq!(scoreboard.insert(3, 10), Some(0));
+        assert_eq!(scoreboard.insert(3, 30), Some(0));
+        assert_eq!(scoreboard.insert(3, 20), Some(1));
+        assert_eq!(scoreboard.scores(3), &[30, 20, 10, 0, 0]);
+        assert_eq!(scoreboard.scores(4), &[0; SCORES_PER_GAME]);
+    }
+
+    #[test]
+    fn test_insert_rejects_low_scores() {
+        let mut scoreboard = Scoreboard::new();
+        assert_eq!(scoreboard.insert(0, 0), None);
+        for score in 1..=SCORES_PER_GAME as u32 {
+            scoreboard.insert(0, score * 10);
+        }
+        assert_eq!(scoreboard.insert(0, 10), None);
+        assert_eq!(scoreboard.insert(0, 15), Some(4));
+        assert_eq!(scoreboard.scores(0), &[50, 40, 30, 20, 15]);
+        assert_eq!(scoreboard.insert(MAX_GAMES, 100), None);
+    }
+
+    #[test]
+    fn test_round_trip_through_bytes() {
+        let mut scoreboard = Scoreboard::new();
+        scoreboard.insert(0, 7);
+        scoreboard.insert(MAX_GAMES - 1, u32::MAX);
+        let bytes = scoreboard.to_bytes();
+        assert_eq!(Scoreboard::from_bytes(&bytes), Some(scoreboard));
+        assert_eq!(Scoreboard::from_bytes(&bytes[..10]), None);
+        assert_eq!(Scoreboard::from_bytes(&[0; Scoreboard::ENCODED_LEN]), None);
+    }
+}
@@ -0,0 +1,193 @@
+//! Display settings changed from the menu and kept across restarts.
+//!
+//! Like the high scores the engine keeps them in memory and frontends
+//! persist them through a `SettingsStorage` implementation.
+
+use core::time::Duration;
+
+const MAGIC: &[u8; 4] = b"TTD1";
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NightMode {
+    Off,
+    On,
+    /// On between `night_start` and `night_end`, for frontends that know
+    /// the time of day
+    Scheduled,
+}
+
+/// Brightness and night mode of the LEDs, applied by frontends through
+/// `ColorPipeline`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplaySettings {
+    pub brightness: u8,
+    pub night_mode: NightMode,
+    /// Hour of the day scheduled night mode starts, from 0 to 23
+    pub night_start: u8,
+    /// Hour of the day scheduled night mode ends
+    pub night_end: u8,
+}
+
+impl DisplaySettings {
+    pub const ENCODED_LEN: usize = MAGIC.len() + 4;
+
+    pub const fn new() -> Self {
+        Self {
+            brightness: u8::MAX,
+            night_mode: NightMode::Off,
+            night_start: 22,
+            night_end: 7,
+        }
+    }
+
+    /// Whether night mode applies at `time_of_day`, the time since local
+    /// midnight. Scheduled night mode is off when the time is unknown.
+    pub fn is_night(&self, time_of_day: Option<Duration>) -> bool {
+        match (self.night_mode, time_of_day) {
+            (NightMode::Off, _) | (NightMode::Scheduled, None) => false,
+            (NightMode::On, _) => true,
+            (NightMode::Scheduled, Some(time)) => {
+                let hour = (time.as_secs() / HOUR.as_secs() % 24) as u8;
+                let (start, end) = (self.night_start, self.night_end);
+                // The night usually spans midnight
+                if start <= end {
+                    (start..end).contains(&hour)
+                } else {
+                    hour >= start || hour < end
+                }
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let night_mode = match self.night_mode {
+            NightMode::Off => 0,
+            NightMode::On => 1,
+            NightMode::Scheduled => 2,
+        };
+        let mut bytes = [0; Self::ENCODED_LEN];
+        bytes[..MAGIC.len()].copy_from_slice(MAGIC);
+        bytes[MAGIC.len()..].copy_from_slice(&[
+            self.brightness,
+            night_mode,
+            self.night_start,
+            self.night_end,
+        ]);
+        bytes
+    }
+
+    /// Parses stored settings, returning `None` if the data is not any.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::ENCODED_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+        let &[brightness, night_mode, night_start, night_end] =
+            &bytes[MAGIC.len()..Self::ENCODED_LEN]
+        else {
+            return None;
+        };
+        let night_mode = match night_mode {
+            0 => NightMode::Off,
+            1 => NightMode::On,
+            2 => NightMode::Scheduled,
+            _ => return None,
+        };
+        if night_start > 23 || night_end > 23 {
+            return None;
+        }
+        Some(Self {
+            brightness,
+            night_mode,
+            night_start,
+            night_end,
+        })
+    }
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait SettingsStorage {
+    type Error;
+
+    /// Returns the stored settings, or `None` if nothing has been saved yet.
+    fn load(&mut self) -> Result<Option<DisplaySettings>, Self::Error>;
+    fn save(&mut self, settings: &DisplaySettings) -> Result<(), Self::Error>;
+}
+
+/// Stores the settings in a file, used by the Raspberry Pi frontend.
+#[cfg(any(test, feature = "std"))]
+pub struct FileSettingsStorage {
+    path: std::path::PathBuf,
+}
+
+#[cfg(any(test, feature = "std"))]
+impl FileSettingsStorage {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl SettingsStorage for FileSettingsStorage {
+    type Error = std::io::Error;
+
+    fn load(&mut self) -> Result<Option<DisplaySettings>, Self::Error> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => Ok(DisplaySettings::from_bytes(&bytes)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&mut self, settings: &DisplaySettings) -> Result<(), Self::Error> {
+        std::fs::write(&self.path, settings.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let settings = DisplaySettings {
+            brightness: 42,
+            night_mode: NightMode::Scheduled,
+            night_start: 21,
+            night_end: 6,
+        };
+        assert_eq!(
+            DisplaySettings::from_bytes(&settings.to_bytes()),
+            Some(settings)
+        );
+        assert_eq!(DisplaySettings::from_bytes(b"TTS1\0\0\0\0"), None);
+        assert_eq!(DisplaySettings::from_bytes(&[0xff; 8]), None);
+    }
+
+    #[test]
+    fn test_scheduled_night_spans_midnight() {
+        let settings = DisplaySettings {
+            night_mode: NightMode::Scheduled,
+            ..DisplaySettings::new()
+        };
+        let at = |hour: u64| Some(HOUR * hour as u32);
+        assert!(!settings.is_night(at(21)));
+        assert!(settings.is_night(at(22)));
+        assert!(settings.is_night(at(3)));
+        assert!(!settings.is_night(at(7)));
+        assert!(!settings.is_night(None));
+
+        let daytime = DisplaySettings {
+            night_start: 9,
+            night_end: 17,
+            ..settings
+        };
+        assert!(daytime.is_night(at(12)));
+        assert!(!daytime.is_night(at(20)));
+    }
+}
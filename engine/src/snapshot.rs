@@ -0,0 +1,623 @@
+//! Save states: the complete state of the menu and the running game,
+//! including its random number generator, as a compact byte string.
+//!
+//! Format, all integers little endian:
+//!   "TTG1" magic, u16 grid width, u16 grid height
+//!   the engine state, written field by field in declaration order
+//! Enums are written as a u8 variant index followed by their fields, and
+//! variable length collections as a u32 length followed by their items.
+//! The high scores are not part of a save state, they are stored on their
+//! own through `ScoreStorage`.
+
+use crate::game::{Board, GRID_HEIGHT, GRID_WIDTH, GameMode, Player, RGB};
+use crate::random::CustomRng;
+use core::time::Duration;
+use smallvec::SmallVec;
+
+const MAGIC: &[u8; 4] = b"TTG1";
+
+#[derive(Debug, PartialEq)]
+pub enum SnapshotError {
+    /// The buffer passed to `save_state` can't hold the state
+    BufferTooSmall {
+        needed: usize,
+    },
+    InvalidHeader,
+    GridMismatch {
+        width: usize,
+        height: usize,
+    },
+    UnexpectedEof,
+    InvalidData,
+}
+
+impl core::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::BufferTooSmall { needed } => {
+                write!(f, "Save state needs a buffer of {} bytes", needed)
+            }
+            SnapshotError::InvalidHeader => write!(f, "Not a teotile save state"),
+            SnapshotError::GridMismatch { width, height } => write!(
+                f,
+                "Save state is for a {}x{} grid, this build uses {}x{}",
+                width, height, GRID_WIDTH, GRID_HEIGHT
+            ),
+            SnapshotError::UnexpectedEof => write!(f, "Save state is truncated"),
+            SnapshotError::InvalidData => write!(f, "Save state is corrupt"),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl std::error::Error for SnapshotError {}
+
+/// Writes into a fixed buffer. Bytes past the end of the buffer are
+/// dropped but still counted, so a failed save reports the size it needs.
+pub(crate) struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        if let Some(dest) = self.buf.get_mut(self.len..self.len + bytes.len()) {
+            dest.copy_from_slice(bytes);
+        }
+        self.len += bytes.len();
+    }
+
+    pub(crate) fn write_header(&mut self) {
+        self.write_bytes(MAGIC);
+        self.write_bytes(&(GRID_WIDTH as u16).to_le_bytes());
+        self.write_bytes(&(GRID_HEIGHT as u16).to_le_bytes());
+    }
+
+    /// The number of bytes written, or the buffer size that was needed.
+    pub(crate) fn finish(self) -> Result<usize, SnapshotError> {
+        if self.len > self.buf.len() {
+            return Err(SnapshotError::BufferTooSmall { needed: self.len });
+        }
+        Ok(self.len)
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub(crate) fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        if self.bytes.len() < N {
+            return Err(SnapshotError::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+        let mut array = [0; N];
+        array.copy_from_slice(head);
+        Ok(array)
+    }
+
+    pub(crate) fn read_header(&mut self) -> Result<(), SnapshotError> {
+        if self.read_bytes::<4>().ok().as_ref() != Some(MAGIC) {
+            return Err(SnapshotError::InvalidHeader);
+        }
+        let width = u16::from_le_bytes(self.read_bytes()?) as usize;
+        let height = u16::from_le_bytes(self.read_bytes()?) as usize;
+        if (width, height) != (GRID_WIDTH, GRID_HEIGHT) {
+            return Err(SnapshotError::GridMismatch { width, height });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<(), SnapshotError> {
+        if !self.bytes.is_empty() {
+            return Err(SnapshotError::InvalidData);
+        }
+        Ok(())
+    }
+}
+
+/// State that can be written to and rebuilt from a save state.
+pub(crate) trait Snapshot: Sized {
+    fn save(&self, w: &mut Writer);
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError>;
+}
+
+// Implements Snapshot for a struct by writing the listed fields in order.
+// Fields after `skip` are transient and restored with their default.
+// `check` rejects restored values that would index out of bounds, like
+// cells off the grid. Unit structs are listed by name alone and take up no
+// space.
+macro_rules! snapshot_struct {
+    ($name:ident) => {
+        impl $crate::snapshot::Snapshot for $name {
+            fn save(&self, _w: &mut $crate::snapshot::Writer) {}
+
+            fn restore(
+                _r: &mut $crate::snapshot::Reader,
+            ) -> Result<Self, $crate::snapshot::SnapshotError> {
+                Ok(Self)
+            }
+        }
+    };
+    ($name:ident { $($field:ident),+ $(,)? } $(skip { $($skip:ident),+ $(,)? })?
+        $(check |$this:ident| $check:expr)?) => {
+        impl $crate::snapshot::Snapshot for $name {
+            fn save(&self, w: &mut $crate::snapshot::Writer) {
+                $($crate::snapshot::Snapshot::save(&self.$field, w);)+
+            }
+
+            fn restore(
+                r: &mut $crate::snapshot::Reader,
+            ) -> Result<Self, $crate::snapshot::SnapshotError> {
+                let value = Self {
+                    $($field: $crate::snapshot::Snapshot::restore(r)?,)+
+                    $($($skip: Default::default(),)+)?
+                };
+                $(
+                    let $this = &value;
+                    if !$check {
+                        return Err($crate::snapshot::SnapshotError::InvalidData);
+                    }
+                )?
+                Ok(value)
+            }
+        }
+    };
+}
+
+// Implements Snapshot for an enum, every variant has to be listed in
+// declaration order with names for its fields. The variant index is
+// written first, followed by the fields.
+macro_rules! snapshot_enum {
+    ($name:ident {
+        $($variant:ident $(($($tuple:ident),+))? $({ $($field:ident),+ })?),+ $(,)?
+    }) => {
+        impl $crate::snapshot::Snapshot for $name {
+            #[allow(unused_assignments)]
+            fn save(&self, w: &mut $crate::snapshot::Writer) {
+                let mut tag: u8 = 0;
+                $(
+                    if let Self::$variant $(($($tuple),+))? $({ $($field),+ })? = self {
+                        $crate::snapshot::Snapshot::save(&tag, w);
+                        $($($crate::snapshot::Snapshot::save($tuple, w);)+)?
+                        $($($crate::snapshot::Snapshot::save($field, w);)+)?
+                        return;
+                    }
+                    tag += 1;
+                )+
+            }
+
+            #[allow(unused_assignments)]
+            fn restore(
+                r: &mut $crate::snapshot::Reader,
+            ) -> Result<Self, $crate::snapshot::SnapshotError> {
+                let tag: u8 = $crate::snapshot::Snapshot::restore(r)?;
+                let mut index: u8 = 0;
+                $(
+                    if tag == index {
+                        return Ok(Self::$variant
+                            $(($({
+                                let $tuple = $crate::snapshot::Snapshot::restore(r)?;
+                                $tuple
+                            }),+))?
+                            $({ $($field: $crate::snapshot::Snapshot::restore(r)?),+ })?);
+                    }
+                    index += 1;
+                )+
+                Err($crate::snapshot::SnapshotError::InvalidData)
+            }
+        }
+    };
+}
+
+pub(crate) use snapshot_enum;
+pub(crate) use snapshot_struct;
+
+/// Whether a restored cell is on the grid.
+pub(crate) fn on_grid((col, row): (usize, usize)) -> bool {
+    col < GRID_WIDTH && row < GRID_HEIGHT
+}
+
+macro_rules! snapshot_int {
+    ($($int:ty),+) => {
+        $(impl Snapshot for $int {
+            fn save(&self, w: &mut Writer) {
+                w.write_bytes(&self.to_le_bytes());
+            }
+
+            fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+                Ok(<$int>::from_le_bytes(r.read_bytes()?))
+            }
+        })+
+    };
+}
+
+snapshot_int!(u8, u16, u32, u64, u128, i32, i64, f32, f64);
+
+// Sizes are written as 64 bits so save states work across platforms
+impl Snapshot for usize {
+    fn save(&self, w: &mut Writer) {
+        (*self as u64).save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        usize::try_from(u64::restore(r)?).map_err(|_| SnapshotError::InvalidData)
+    }
+}
+
+impl Snapshot for isize {
+    fn save(&self, w: &mut Writer) {
+        (*self as i64).save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        isize::try_from(i64::restore(r)?).map_err(|_| SnapshotError::InvalidData)
+    }
+}
+
+impl Snapshot for bool {
+    fn save(&self, w: &mut Writer) {
+        (*self as u8).save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::restore(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::InvalidData),
+        }
+    }
+}
+
+impl Snapshot for Duration {
+    fn save(&self, w: &mut Writer) {
+        self.as_secs().save(w);
+        self.subsec_nanos().save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        let secs = u64::restore(r)?;
+        let nanos = u32::restore(r)?;
+        if nanos >= 1_000_000_000 {
+            return Err(SnapshotError::InvalidData);
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+impl<A: Snapshot, B: Snapshot> Snapshot for (A, B) {
+    fn save(&self, w: &mut Writer) {
+        self.0.save(w);
+        self.1.save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok((A::restore(r)?, B::restore(r)?))
+    }
+}
+
+impl<T: Snapshot> Snapshot for Option<T> {
+    fn save(&self, w: &mut Writer) {
+        match self {
+            None => 0u8.save(w),
+            Some(value) => {
+                1u8.save(w);
+                value.save(w);
+            }
+        }
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::restore(r)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::restore(r)?)),
+            _ => Err(SnapshotError::InvalidData),
+        }
+    }
+}
+
+impl<T: Snapshot, const N: usize> Snapshot for [T; N] {
+    fn save(&self, w: &mut Writer) {
+        for item in self {
+            item.save(w);
+        }
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        let mut error = None;
+        let items: [Option<T>; N] = core::array::from_fn(|_| match T::restore(r) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                error.get_or_insert(e);
+                None
+            }
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(items.map(|item| item.expect("every item was restored"))),
+        }
+    }
+}
+
+impl<T: Snapshot, const N: usize> Snapshot for SmallVec<[T; N]> {
+    fn save(&self, w: &mut Writer) {
+        (self.len() as u32).save(w);
+        for item in self {
+            item.save(w);
+        }
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        let len = u32::restore(r)? as usize;
+        let mut items = SmallVec::new();
+        for _ in 0..len {
+            items.push(T::restore(r)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Snapshot, const COLS: usize, const ROWS: usize> Snapshot for Board<T, COLS, ROWS> {
+    fn save(&self, w: &mut Writer) {
+        self.cells.save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(Self {
+            cells: Snapshot::restore(r)?,
+        })
+    }
+}
+
+impl Snapshot for CustomRng {
+    fn save(&self, w: &mut Writer) {
+        let (seed, stream, word_pos) = self.position();
+        seed.save(w);
+        stream.save(w);
+        word_pos.save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        let seed = Snapshot::restore(r)?;
+        let stream = Snapshot::restore(r)?;
+        let word_pos = Snapshot::restore(r)?;
+        Ok(CustomRng::from_position(seed, stream, word_pos))
+    }
+}
+
+snapshot_struct!(RGB { r, g, b });
+snapshot_enum!(Player {
+    Player1,
+    Player2,
+    Player3,
+    Player4,
+});
+snapshot_enum!(GameMode {
+    SinglePlayer,
+    MultiPlayer
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{ButtonState, CommandType, GameCommand, GameEngine};
+    use crate::scoreboard::{ScoreStorage, Scoreboard};
+
+    struct Sample {
+        position: (usize, isize),
+        scores: SmallVec<[u32; 2]>,
+        color: Option<RGB>,
+        elapsed: Duration,
+        cache: u8,
+    }
+
+    snapshot_struct!(Sample { position, scores, color, elapsed } skip { cache });
+
+    enum Shape {
+        Empty,
+        Dot(f32, Player),
+        Line {
+            from: (usize, usize),
+            to: (usize, usize),
+        },
+    }
+
+    snapshot_enum!(Shape {
+        Empty,
+        Dot(x, player),
+        Line { from, to },
+    });
+
+    fn round_trip<T: Snapshot>(value: &T) -> T {
+        let mut buf = [0; 256];
+        let mut w = Writer::new(&mut buf);
+        value.save(&mut w);
+        let len = w.finish().unwrap();
+        let mut r = Reader::new(&buf[..len]);
+        let restored = T::restore(&mut r).unwrap();
+        r.finish().unwrap();
+        restored
+    }
+
+    #[test]
+    fn test_macros_round_trip() {
+        let sample = Sample {
+            position: (3, -2),
+            scores: SmallVec::from_slice(&[1, 20, 300]),
+            color: Some(RGB::new(1, 2, 3)),
+            elapsed: Duration::from_micros(1_234_567),
+            cache: 9,
+        };
+        let restored = round_trip(&sample);
+        assert_eq!(restored.position, (3, -2));
+        assert_eq!(restored.scores.as_slice(), &[1, 20, 300]);
+        assert_eq!(restored.color, Some(RGB::new(1, 2, 3)));
+        assert_eq!(restored.elapsed, sample.elapsed);
+        assert_eq!(restored.cache, 0);
+
+        assert!(matches!(round_trip(&Shape::Empty), Shape::Empty));
+        assert!(matches!(
+            round_trip(&Shape::Dot(1.5, Player::Player2)),
+            Shape::Dot(x, Player::Player2) if x == 1.5
+        ));
+        assert!(matches!(
+            round_trip(&Shape::Line {
+                from: (0, 1),
+                to: (2, 3)
+            }),
+            Shape::Line {
+                from: (0, 1),
+                to: (2, 3)
+            }
+        ));
+    }
+
+    #[test]
+    fn test_rng_continues_where_it_left_off() {
+        let mut rng = CustomRng::seed_from_u64(5);
+        rng.next_u32();
+        let mut restored = round_trip(&rng);
+        for _ in 0..10 {
+            assert_eq!(rng.next_u64(), restored.next_u64());
+        }
+    }
+
+    fn press(command_type: CommandType, player: Player) -> GameCommand {
+        GameCommand::new(command_type, ButtonState::Pressed, player)
+    }
+
+    // Hands the high scores of one engine to another, which the snapshot
+    // leaves to the frontends
+    struct SharedScores(Option<Scoreboard>);
+
+    impl ScoreStorage for SharedScores {
+        type Error = ();
+
+        fn load(&mut self) -> Result<Option<Scoreboard>, ()> {
+            Ok(self.0.clone())
+        }
+
+        fn save(&mut self, scoreboard: &Scoreboard) -> Result<(), ()> {
+            self.0 = Some(scoreboard.clone());
+            Ok(())
+        }
+    }
+
+    // Starts the game at `index` in the menu and plays it for a while
+    fn play(engine: &mut GameEngine, index: usize) {
+        for _ in 0..index {
+            engine
+                .process_input(press(CommandType::Right, Player::Player1))
+                .unwrap();
+        }
+        engine
+            .process_input(press(CommandType::Select, Player::Player1))
+            .unwrap();
+        let inputs = [CommandType::Right, CommandType::Up, CommandType::Select];
+        for frame in 0..120u64 {
+            if frame % 7 == 0 {
+                let command_type = inputs[(frame / 7) as usize % inputs.len()];
+                let player = Player::ALL[(frame / 7) as usize % Player::ALL.len()];
+                engine.process_input(press(command_type, player)).unwrap();
+            }
+            engine
+                .update(Duration::from_millis(16 + frame % 5))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_every_game_resumes_identically() {
+        let mut buf = [0; 16 * 1024];
+        let games = 0..crate::game::Menu::game_count();
+        for (index, controllers) in games.flat_map(|index| [(index, 1), (index, 2), (index, 4)]) {
+            let mut engine = GameEngine::with_seed(index as u64);
+            engine.set_controllers(controllers);
+            play(&mut engine, index);
+            let len = engine.save_state(&mut buf).unwrap();
+            let mut scores = SharedScores(None);
+            engine.save_scores(&mut scores).unwrap();
+
+            let mut restored = GameEngine::default();
+            restored.load_scores(&mut scores).unwrap();
+            restored.restore_state(&buf[..len]).unwrap();
+            assert_eq!(restored.status(), engine.status());
+            for frame in 0..200u64 {
+                if frame % 11 == 0 {
+                    let input = press(CommandType::Left, Player::Player1);
+                    engine.process_input(input).unwrap();
+                    restored.process_input(input).unwrap();
+                }
+                let delta = Duration::from_millis(16 + frame % 3);
+                engine.update(delta).unwrap();
+                restored.update(delta).unwrap();
+                assert!(engine.render().unwrap() == restored.render().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_states_are_rejected() {
+        let engine = GameEngine::with_seed(3);
+        let mut small = [0; 4];
+        let needed = match engine.save_state(&mut small) {
+            Err(SnapshotError::BufferTooSmall { needed }) => needed,
+            other => panic!("unexpected {:?}", other),
+        };
+        let mut buf = vec![0; needed];
+        assert_eq!(engine.save_state(&mut buf), Ok(needed));
+
+        let mut restored = GameEngine::default();
+        assert_eq!(
+            restored.restore_state(b"TTR1"),
+            Err(SnapshotError::InvalidHeader)
+        );
+        assert_eq!(
+            restored.restore_state(&buf[..needed - 1]),
+            Err(SnapshotError::UnexpectedEof)
+        );
+        buf[4] = buf[4].wrapping_add(1);
+        assert!(matches!(
+            restored.restore_state(&buf),
+            Err(SnapshotError::GridMismatch { .. })
+        ));
+    }
+
+    // A corrupt state is either rejected, which leaves the engine as it
+    // was, or it plays on without panicking
+    #[test]
+    fn test_corrupt_states_never_panic() {
+        for index in 0..crate::game::Menu::game_count() {
+            let mut engine = GameEngine::with_seed(index as u64);
+            play(&mut engine, index);
+            let state = engine.save_state_to_vec();
+            let fresh = GameEngine::default().render().unwrap();
+            for position in 8..state.len() {
+                for value in [0x80, 0xff] {
+                    let mut corrupt = state.clone();
+                    corrupt[position] |= value;
+                    let mut restored = GameEngine::default();
+                    if restored.restore_state(&corrupt).is_err() {
+                        assert!(restored.render().unwrap() == fresh);
+                        continue;
+                    }
+                    for _ in 0..3 {
+                        let _ = restored.process_input(press(CommandType::Up, Player::Player1));
+                        let _ = restored.update(Duration::from_millis(100));
+                        let _ = restored.render();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! A button that stands for two inputs, for controllers without a spare
+//! button. Tapping it sends one and holding it down the other, never both.
+
+use core::time::Duration;
+
+/// How long a button has to be held down to count as held.
+pub const LONG_PRESS: Duration = Duration::from_millis(800);
+
+/// Turns the presses and releases of a button into `tap` or `hold`. The tap
+/// is only known when the button is released early enough, the hold is
+/// reported by `poll` while the button is still down.
+#[derive(Debug, Clone, Copy)]
+pub struct TapHold<T> {
+    tap: T,
+    hold: T,
+    pressed_at: Option<Duration>,
+    held: bool,
+}
+
+impl<T: Copy> TapHold<T> {
+    pub fn new(tap: T, hold: T) -> Self {
+        Self {
+            tap,
+            hold,
+            pressed_at: None,
+            held: false,
+        }
+    }
+
+    /// The button went down at `now`, any clock that only moves forward.
+    pub fn press(&mut self, now: Duration) {
+        self.pressed_at = Some(now);
+        self.held = false;
+    }
+
+    /// The button went up, returns the tap if it wasn't held.
+    pub fn release(&mut self, now: Duration) -> Option<T> {
+        let pressed_at = self.pressed_at.take()?;
+        (!self.held && now.saturating_sub(pressed_at) < LONG_PRESS).then_some(self.tap)
+    }
+
+    /// Returns the hold once per press, as soon as the button has been
+    /// down for `LONG_PRESS`.
+    pub fn poll(&mut self, now: Duration) -> Option<T> {
+        let pressed_at = self.pressed_at?;
+        if self.held || now.saturating_sub(pressed_at) < LONG_PRESS {
+            return None;
+        }
+        self.held = true;
+        Some(self.hold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{ButtonState, CommandType, GameCommand, GameEngine, GamePhase, Player};
+
+    // Feeds a South button, as the Pico maps it, into an engine
+    fn south(engine: &mut GameEngine, button: &mut TapHold<CommandType>, held: Duration) {
+        let step = Duration::from_millis(10);
+        let mut now = Duration::ZERO;
+        button.press(now);
+        let mut commands = Vec::new();
+        while now < held {
+            now += step;
+            commands.extend(button.poll(now));
+            engine.update(step).unwrap();
+        }
+        commands.extend(button.release(now));
+        for command_type in commands {
+            let command = GameCommand::new(command_type, ButtonState::Pressed, Player::Player1);
+            engine.process_input(command).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_tap_or_hold() {
+        let mut button = TapHold::new(CommandType::Select, CommandType::Pause);
+        button.press(Duration::ZERO);
+        assert_eq!(button.poll(LONG_PRESS / 2), None);
+        assert_eq!(button.release(LONG_PRESS / 2), Some(CommandType::Select));
+
+        button.press(Duration::from_secs(5));
+        assert_eq!(
+            button.poll(Duration::from_secs(5) + LONG_PRESS),
+            Some(CommandType::Pause)
+        );
+        assert_eq!(button.poll(Duration::from_secs(7)), None);
+        assert_eq!(button.release(Duration::from_secs(7)), None);
+        assert_eq!(button.release(Duration::from_secs(8)), None);
+    }
+
+    #[test]
+    fn test_holding_select_opens_the_settings_in_the_menu() {
+        let mut engine = GameEngine::with_seed(1);
+        let mut button = TapHold::new(CommandType::Select, CommandType::Pause);
+        let menu = engine.render().unwrap();
+
+        south(&mut engine, &mut button, LONG_PRESS * 2);
+        let overlay = engine.render().unwrap();
+        assert_ne!(overlay, menu);
+        assert_eq!(engine.status().phase, GamePhase::Menu);
+
+        // A tap closes the overlay, the next one starts the game
+        south(&mut engine, &mut button, Duration::from_millis(100));
+        assert_eq!(engine.render().unwrap(), menu);
+        south(&mut engine, &mut button, Duration::from_millis(100));
+        assert_eq!(engine.status().phase, GamePhase::Playing);
+    }
+}
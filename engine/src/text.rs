@@ -0,0 +1,210 @@
+use crate::font;
+use crate::game::{GRID_HEIGHT, GRID_WIDTH, RGB, RenderBoard};
+use crate::snapshot::snapshot_struct;
+use core::time::Duration;
+use smallvec::SmallVec;
+
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+const LETTER_SPACING: usize = 1;
+const MARQUEE_STEP: Duration = Duration::from_millis(120);
+
+// Unknown characters are drawn as '?', lowercase letters as uppercase
+fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+    let index = font::CHARS
+        .chars()
+        .position(|f| f == c)
+        .or_else(|| font::CHARS.chars().position(|f| f == '?'))
+        .unwrap_or(0);
+    &font::GLYPHS[index]
+}
+
+/// Width in cells of `text` when drawn with `draw_text`.
+pub fn text_width(text: &str) -> usize {
+    let len = text.chars().count();
+    (len * (GLYPH_WIDTH + LETTER_SPACING)).saturating_sub(LETTER_SPACING)
+}
+
+/// Draws `text` with its bottom left corner at (`x`, `y`). Parts of the
+/// text outside the board are clipped, so `x` may be negative.
+pub fn draw_text(board: &mut RenderBoard, text: &str, x: i32, y: usize, color: RGB) {
+    let mut glyph_x = x;
+    for c in text.chars() {
+        for (row, mask) in glyph(c).iter().enumerate() {
+            let board_y = y + GLYPH_HEIGHT - 1 - row;
+            for col in 0..GLYPH_WIDTH {
+                let board_x = glyph_x + col as i32;
+                let lit = mask & (1 << (GLYPH_WIDTH - 1 - col)) != 0;
+                if lit && (0..board.cols() as i32).contains(&board_x) && board_y < board.rows() {
+                    board.set(board_x as usize, board_y, color);
+                }
+            }
+        }
+        glyph_x += (GLYPH_WIDTH + LETTER_SPACING) as i32;
+    }
+}
+
+/// Draws `text` horizontally centered with its bottom row at `y`.
+pub fn draw_text_centered(board: &mut RenderBoard, text: &str, y: usize, color: RGB) {
+    let x = (GRID_WIDTH as i32 - text_width(text) as i32) / 2;
+    draw_text(board, text, x, y, color);
+}
+
+/// Text that scrolls from right to left across the board, one column at a
+/// time, and starts over once it has left the board.
+///
+/// Implements `core::fmt::Write` so numbers can be added without allocating:
+/// `write!(marquee, "SCORE {}", score)`.
+pub struct Marquee {
+    text: SmallVec<[u8; 32]>,
+    y: usize,
+    color: RGB,
+    offset: usize,
+    elapsed: Duration,
+    passes: usize,
+}
+
+snapshot_struct!(Marquee {
+    text,
+    y,
+    color,
+    offset,
+    elapsed,
+    passes,
+} check |marquee| marquee.text.is_ascii() && marquee.y < GRID_HEIGHT);
+
+impl Marquee {
+    pub fn new(text: &str, y: usize, color: RGB) -> Self {
+        let mut marquee = Self {
+            text: SmallVec::new(),
+            y,
+            color,
+            offset: 0,
+            elapsed: Duration::ZERO,
+            passes: 0,
+        };
+        marquee.set_text(text);
+        marquee
+    }
+
+    /// Replaces the text and restarts scrolling.
+    pub fn set_text(&mut self, text: &str) {
+        self.text.clear();
+        self.text.extend(text.bytes().filter(u8::is_ascii));
+        self.offset = 0;
+        self.elapsed = Duration::ZERO;
+        self.passes = 0;
+    }
+
+    pub fn text(&self) -> &str {
+        // Only ASCII is stored, so this cannot fail
+        core::str::from_utf8(&self.text).unwrap_or_default()
+    }
+
+    /// Number of times the text has scrolled all the way across the board.
+    pub fn passes(&self) -> usize {
+        self.passes
+    }
+
+    pub fn update(&mut self, delta_time: Duration) {
+        self.elapsed += delta_time;
+        let distance = GRID_WIDTH + text_width(self.text());
+        while self.elapsed >= MARQUEE_STEP {
+            self.elapsed -= MARQUEE_STEP;
+            self.offset += 1;
+            if self.offset > distance {
+                self.offset = 0;
+                self.passes += 1;
+            }
+        }
+    }
+
+    pub fn render(&self, board: &mut RenderBoard) {
+        let x = GRID_WIDTH as i32 - self.offset as i32;
+        draw_text(board, self.text(), x, self.y, self.color);
+    }
+}
+
+impl core::fmt::Write for Marquee {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.text.extend(s.bytes().filter(u8::is_ascii));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    const WHITE: RGB = RGB {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    fn lit_columns(board: &RenderBoard, y: usize) -> Vec<usize> {
+        (0..GRID_WIDTH)
+            .filter(|&x| (y..y + GLYPH_HEIGHT).any(|row| board.get(x, row) == WHITE))
+            .collect()
+    }
+
+    #[test]
+    fn test_font_covers_digits_and_letters() {
+        assert_eq!(font::GLYPHS.len(), font::CHARS.chars().count());
+        for c in ('0'..='9').chain('A'..='Z') {
+            assert!(font::CHARS.contains(c), "missing glyph for {}", c);
+            assert!(glyph(c).iter().any(|&row| row != 0));
+        }
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('~'), glyph('?'));
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut board = RenderBoard::new();
+        draw_text(&mut board, "1", 0, 0, WHITE);
+        // The glyph for 1 is a vertical stroke with a foot and a flag
+        assert_eq!(board.get(1, 0), WHITE);
+        assert_eq!(board.get(1, 4), WHITE);
+        assert_eq!(board.get(0, 3), WHITE);
+        assert_eq!(board.get(2, 0), WHITE);
+        assert_eq!(board.get(2, 4), RGB::default());
+        assert_eq!(board.get(1, 5), RGB::default());
+        assert_eq!(text_width("P1"), 7);
+        assert_eq!(text_width(""), 0);
+    }
+
+    #[test]
+    fn test_draw_text_clips() {
+        let mut board = RenderBoard::new();
+        draw_text(&mut board, "88", -2, 0, WHITE);
+        assert_eq!(lit_columns(&board, 0), vec![0, 2, 3, 4]);
+        draw_text(&mut board, "8", GRID_WIDTH as i32 - 1, 0, WHITE);
+        assert!(lit_columns(&board, 0).contains(&(GRID_WIDTH - 1)));
+    }
+
+    #[test]
+    fn test_marquee_scrolls_and_wraps() {
+        let mut marquee = Marquee::new("", 2, WHITE);
+        write!(marquee, "P{}", 1).unwrap();
+        assert_eq!(marquee.text(), "P1");
+
+        let mut board = RenderBoard::new();
+        marquee.render(&mut board);
+        assert!(lit_columns(&board, 2).is_empty());
+
+        marquee.update(MARQUEE_STEP * 2);
+        let mut board = RenderBoard::new();
+        marquee.render(&mut board);
+        assert_eq!(lit_columns(&board, 2), vec![GRID_WIDTH - 2, GRID_WIDTH - 1]);
+
+        let distance = (GRID_WIDTH + text_width("P1")) as u32;
+        marquee.update(MARQUEE_STEP * (distance - 1));
+        assert_eq!(marquee.passes(), 1);
+        let mut board = RenderBoard::new();
+        marquee.render(&mut board);
+        assert!(lit_columns(&board, 2).is_empty());
+    }
+}
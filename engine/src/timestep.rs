@@ -0,0 +1,95 @@
+use crate::snapshot::{Reader, Snapshot, SnapshotError, Writer};
+use core::time::Duration;
+
+/// Splits frames of any length into steps of a fixed length. Time left
+/// over at the end of a frame is carried to the next one, so the number of
+/// steps only depends on the total time and not on the frame rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FixedStep {
+    step: Duration,
+    accumulator: Duration,
+}
+
+impl Snapshot for FixedStep {
+    fn save(&self, w: &mut Writer) {
+        self.step.save(w);
+        self.accumulator.save(w);
+    }
+
+    fn restore(r: &mut Reader) -> Result<Self, SnapshotError> {
+        let step = Duration::restore(r)?;
+        let accumulator = Duration::restore(r)?;
+        // Steps that are due are taken right away, so less than one is left
+        if step.is_zero() || accumulator >= step {
+            return Err(SnapshotError::InvalidData);
+        }
+        Ok(Self { step, accumulator })
+    }
+}
+
+impl FixedStep {
+    /// Steps are at least a nanosecond long, shorter ones are rounded up.
+    pub(crate) const fn new(step: Duration) -> Self {
+        const SHORTEST_STEP: Duration = Duration::from_nanos(1);
+        Self {
+            step: if step.is_zero() { SHORTEST_STEP } else { step },
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Adds the time of a frame and returns the number of steps that are
+    /// due.
+    pub(crate) fn advance(&mut self, delta_time: Duration) -> u32 {
+        self.accumulator += delta_time;
+        let steps = (self.accumulator.as_nanos() / self.step.as_nanos()) as u32;
+        self.accumulator -= self.step * steps;
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_only_depend_on_total_time() {
+        let mut fast = FixedStep::new(Duration::from_millis(20));
+        let mut slow = FixedStep::new(Duration::from_millis(20));
+        let fast_steps: u32 = (0..30)
+            .map(|_| fast.advance(Duration::from_millis(7)))
+            .sum();
+        assert_eq!(fast_steps, 10);
+        assert_eq!(slow.advance(Duration::from_millis(210)), 10);
+        assert_eq!(fast, slow);
+    }
+
+    #[test]
+    fn test_zero_steps_are_not_allowed() {
+        let mut step = FixedStep::new(Duration::ZERO);
+        assert_eq!(step.step(), Duration::from_nanos(1));
+        assert_eq!(step.advance(Duration::from_nanos(3)), 3);
+
+        let mut buf = [0; 32];
+        let mut w = Writer::new(&mut buf);
+        Duration::ZERO.save(&mut w);
+        Duration::ZERO.save(&mut w);
+        let len = w.finish().unwrap();
+        let mut r = Reader::new(&buf[..len]);
+        assert_eq!(FixedStep::restore(&mut r), Err(SnapshotError::InvalidData));
+    }
+
+    #[test]
+    fn test_less_than_a_step_is_left_over() {
+        let mut buf = [0; 32];
+        let mut w = Writer::new(&mut buf);
+        Duration::from_millis(20).save(&mut w);
+        Duration::from_millis(20).save(&mut w);
+        let len = w.finish().unwrap();
+        let mut r = Reader::new(&buf[..len]);
+        assert_eq!(FixedStep::restore(&mut r), Err(SnapshotError::InvalidData));
+    }
+}
@@ -0,0 +1,144 @@
+//! The client side of the relay, for frontends that play network games.
+//!
+//! `NetSession` connects to a relay, waits for the `Start` message and then
+//! drives a `Lockstep` game with the messages of the other client. How the
+//! session is shown is up to the frontend.
+
+use std::io;
+use std::net::TcpStream;
+use std::time::Duration;
+use teotile::{GameCommand, GameError, GameEvents, GameStatus, Lockstep, NetMessage, RenderBoard};
+use tungstenite::client::{IntoClientRequest, uri_mode};
+use tungstenite::stream::{MaybeTlsStream, Mode};
+use tungstenite::{Message, WebSocket};
+
+/// Whether a non-blocking socket had nothing to read or couldn't send yet.
+pub fn would_block(err: &tungstenite::Error) -> bool {
+    matches!(err, tungstenite::Error::Io(err) if err.kind() == io::ErrorKind::WouldBlock)
+}
+
+pub struct NetSession {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    // Created when the relay has found the other player
+    game: Option<Lockstep>,
+    error: Option<String>,
+}
+
+impl NetSession {
+    /// Connects to a relay, for example `ws://localhost:9000/room`.
+    pub fn connect(url: &str) -> Result<Self, String> {
+        let request = url.into_client_request().map_err(|err| err.to_string())?;
+        let mode = uri_mode(request.uri()).map_err(|err| err.to_string())?;
+        let host = request.uri().host().ok_or("The URL has no host")?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port = request.uri().port_u16().unwrap_or(match mode {
+            Mode::Plain => 80,
+            Mode::Tls => 443,
+        });
+        let stream = TcpStream::connect((host, port)).map_err(|err| err.to_string())?;
+        // Every kind of stream wraps this socket, so switching the clone to
+        // non-blocking after the handshake covers TLS streams as well
+        let socket_handle = stream.try_clone().map_err(|err| err.to_string())?;
+        let stream = match mode {
+            Mode::Plain => MaybeTlsStream::Plain(stream),
+            // Wrap the stream here once tungstenite is built with TLS
+            Mode::Tls => return Err("This build only supports ws:// relays".to_string()),
+        };
+        let (socket, _) = tungstenite::client(request, stream).map_err(|err| err.to_string())?;
+        socket_handle
+            .set_nonblocking(true)
+            .map_err(|err| err.to_string())?;
+        Ok(Self {
+            socket,
+            game: None,
+            error: None,
+        })
+    }
+
+    pub fn process_input(&mut self, command: GameCommand) {
+        if let Some(game) = &mut self.game {
+            game.process_input(command);
+        }
+    }
+
+    /// Exchanges messages with the relay and advances the game. The first
+    /// error ends the session, it is returned once and kept for `error`.
+    pub fn update(&mut self, delta: Duration) -> Result<(), String> {
+        if self.error.is_some() {
+            return Ok(());
+        }
+        let result = self.exchange(delta).map_err(|err| err.to_string());
+        if let Err(err) = &result {
+            self.error = Some(err.clone());
+        }
+        result
+    }
+
+    fn exchange(&mut self, delta: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            let bytes = match self.socket.read() {
+                Ok(Message::Binary(bytes)) => bytes,
+                Ok(Message::Close(_)) => return Err("The other player left".into()),
+                Ok(_) => continue,
+                Err(err) if would_block(&err) => break,
+                Err(err) => return Err(err.into()),
+            };
+            match &mut self.game {
+                Some(game) => game.receive(&bytes)?,
+                None => match NetMessage::from_bytes(&bytes)? {
+                    NetMessage::Start { seed, player } => {
+                        self.game = Some(Lockstep::new(seed, player))
+                    }
+                    _ => return Err("Expected the relay to start the game".into()),
+                },
+            }
+        }
+        let Some(game) = &mut self.game else {
+            return Ok(());
+        };
+        // Game errors are ignored like in a local session
+        let _ = game.advance(delta);
+        while let Some(bytes) = game.pop_outgoing() {
+            match self.socket.send(Message::binary(bytes)) {
+                // Queued, it goes out with the next flush
+                Err(err) if would_block(&err) => {}
+                result => result?,
+            }
+        }
+        match self.socket.flush() {
+            Err(err) if would_block(&err) => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// The game, once the relay has found the other player.
+    pub fn game(&self) -> Option<&Lockstep> {
+        self.game.as_ref()
+    }
+
+    /// Why the session ended, if it has.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn render(&self) -> Result<RenderBoard, GameError> {
+        match &self.game {
+            Some(game) => game.render(),
+            None => Ok(RenderBoard::new()),
+        }
+    }
+
+    pub fn status(&self) -> GameStatus {
+        self.game
+            .as_ref()
+            .map(|game| game.status())
+            .unwrap_or_default()
+    }
+
+    pub fn drain_events(&mut self) -> GameEvents {
+        self.game
+            .as_mut()
+            .map(|game| game.drain_events())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,200 @@
+//! Relay for network games.
+//!
+//! Clients connect with a WebSocket to a path of their choosing, the first
+//! two clients on the same path are paired. Each gets a `Start` message with
+//! a shared seed and the player it controls, after that every binary message
+//! is forwarded unchanged to the other client. The relay never looks at the
+//! game itself.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use teotile::{NetMessage, Player};
+use teotile_relay::would_block;
+use tungstenite::handshake::server::{Request, Response};
+use tungstenite::{Message, WebSocket};
+
+const USAGE: &str = "Usage: teotile-relay [address]";
+const DEFAULT_ADDRESS: &str = "0.0.0.0:9000";
+// How long a relay thread sleeps when neither client sent anything
+const IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+type Socket = WebSocket<TcpStream>;
+
+fn main() {
+    let address = match std::env::args().nth(1) {
+        Some(arg) if arg.starts_with('-') => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+        Some(address) => address,
+        None => DEFAULT_ADDRESS.to_string(),
+    };
+    let listener = TcpListener::bind(&address).unwrap_or_else(|err| {
+        eprintln!("Failed to listen on {}: {}", address, err);
+        std::process::exit(1);
+    });
+    println!("Listening on {}", address);
+
+    let (clients, new_clients) = mpsc::channel();
+    thread::spawn(move || pair_clients(new_clients));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        // A slow handshake must not hold up the other clients
+        let clients = clients.clone();
+        thread::spawn(move || match accept(stream) {
+            Ok(client) => {
+                let _ = clients.send(client);
+            }
+            Err(err) => eprintln!("Handshake failed: {}", err),
+        });
+    }
+}
+
+/// Completes the WebSocket handshake and returns the room, which is the
+/// path of the request.
+// The callback's error type is set by tungstenite
+#[allow(clippy::result_large_err)]
+fn accept(stream: TcpStream) -> Result<(String, Socket), String> {
+    let mut room = String::new();
+    let socket = tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+        room = request.uri().path().to_string();
+        Ok(response)
+    })
+    .map_err(|err| err.to_string())?;
+    Ok((room, socket))
+}
+
+fn pair_clients(new_clients: mpsc::Receiver<(String, Socket)>) {
+    let mut waiting: HashMap<String, Socket> = HashMap::new();
+    for (room, socket) in new_clients {
+        // The client that was waiting may have given up in the meantime
+        match waiting.remove(&room).filter(is_connected) {
+            Some(first) => {
+                println!("Starting a game in {}", room);
+                thread::spawn(move || {
+                    if let Err(err) = relay([first, socket]) {
+                        eprintln!("Game in {} failed: {}", room, err);
+                    }
+                    println!("Game in {} ended", room);
+                });
+            }
+            None => {
+                println!("Waiting for a second player in {}", room);
+                waiting.insert(room, socket);
+            }
+        }
+    }
+}
+
+// Clients send nothing before `Start`, so a waiting socket that can be
+// read from has been closed
+fn is_connected(socket: &Socket) -> bool {
+    let stream = socket.get_ref();
+    let mut byte = [0];
+    let connected = stream.set_nonblocking(true).is_ok()
+        && matches!(stream.peek(&mut byte), Err(err) if err.kind() == io::ErrorKind::WouldBlock);
+    connected && stream.set_nonblocking(false).is_ok()
+}
+
+fn relay(mut sockets: [Socket; 2]) -> Result<(), Box<dyn std::error::Error>> {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    for (socket, player) in sockets.iter_mut().zip([Player::Player1, Player::Player2]) {
+        socket.send(Message::binary(
+            NetMessage::Start { seed, player }.to_bytes(),
+        ))?;
+        socket.get_ref().set_nonblocking(true)?;
+    }
+
+    loop {
+        let mut idle = true;
+        for from in 0..2 {
+            let to = 1 - from;
+            match sockets[from].read() {
+                Ok(Message::Binary(bytes)) => {
+                    idle = false;
+                    match sockets[to].send(Message::Binary(bytes)) {
+                        // Queued, it goes out with the next flush
+                        Err(err) if would_block(&err) => {}
+                        result => result?,
+                    }
+                }
+                Ok(Message::Close(_)) => {
+                    let _ = sockets[to].close(None);
+                    let _ = sockets[to].flush();
+                    return Ok(());
+                }
+                Ok(_) => {}
+                Err(err) if would_block(&err) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if idle {
+            for socket in &mut sockets {
+                match socket.flush() {
+                    Err(err) if would_block(&err) => {}
+                    result => result?,
+                }
+            }
+            thread::sleep(IDLE_SLEEP);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use teotile::{CHECKSUM_INTERVAL, NET_FRAME};
+    use teotile_relay::NetSession;
+
+    #[test]
+    fn test_clients_are_paired_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (clients, new_clients) = mpsc::channel();
+        thread::spawn(move || pair_clients(new_clients));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                clients.send(accept(stream.unwrap()).unwrap()).unwrap();
+            }
+        });
+
+        // A client in another room must not be paired with either
+        let mut other = NetSession::connect(&format!("ws://{}/other", address)).unwrap();
+        let mut sessions =
+            [(); 2].map(|_| NetSession::connect(&format!("ws://{}/room", address)).unwrap());
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while sessions.iter().any(|session| {
+            session
+                .game()
+                .is_none_or(|game| game.frame() < 2 * CHECKSUM_INTERVAL)
+        }) {
+            assert!(Instant::now() < deadline, "the game did not start");
+            for session in &mut sessions {
+                session.update(NET_FRAME).unwrap();
+            }
+            other.update(NET_FRAME).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let players = sessions
+            .each_ref()
+            .map(|s| s.game().unwrap().local_player());
+        assert_ne!(players[0], players[1]);
+        // Checksums have been compared, a desync would have ended a session
+        assert!(sessions.iter().all(|session| session.error().is_none()));
+        assert!(other.game().is_none());
+    }
+}
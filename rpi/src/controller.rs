@@ -0,0 +1,106 @@
+//! Gamepads and phones both control players. Each kind has ids of its own
+//! in the lobby, so a phone can never be mistaken for a gamepad.
+
+use teotile::{Lobby, MAX_PLAYERS, Player};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Controller {
+    /// Numbered by gilrs
+    Gamepad(usize),
+    /// Phones pick their player before they join, see `remote`
+    Phone(Player),
+}
+
+/// A phone that connected as `player` takes its slot. Fails if a gamepad
+/// claimed the player at the same time.
+pub fn join_phone(lobby: &mut Lobby<Controller>, player: Player) -> bool {
+    lobby.assign(Controller::Phone(player), player)
+}
+
+/// The phone of `player` disconnected, its slot is kept for the next
+/// controller.
+pub fn leave_phone(lobby: &mut Lobby<Controller>, player: Player) {
+    lobby.disconnect(Controller::Phone(player));
+}
+
+/// The player a phone that connected as `player` controls. None if a
+/// gamepad claimed the player first, its buttons are then ignored.
+pub fn phone_player(lobby: &Lobby<Controller>, player: Player) -> Option<Player> {
+    lobby.player(Controller::Phone(player))
+}
+
+/// The players that have a controller, which phones can't take.
+pub fn claimed(lobby: &Lobby<Controller>) -> [bool; MAX_PLAYERS] {
+    Player::ALL.map(|player| lobby.controller(player).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phone_joins_and_leaves() {
+        let mut lobby = Lobby::new();
+        lobby.connect(Controller::Gamepad(0));
+        assert_eq!(lobby.claim(Controller::Gamepad(0)), Some(Player::Player1));
+
+        assert!(join_phone(&mut lobby, Player::Player2));
+        assert_eq!(
+            lobby.player(Controller::Phone(Player::Player2)),
+            Some(Player::Player2)
+        );
+        assert_eq!(lobby.players(), 2);
+        assert_eq!(claimed(&lobby), [true, true, false, false]);
+
+        leave_phone(&mut lobby, Player::Player2);
+        assert_eq!(lobby.controller(Player::Player2), None);
+        assert_eq!(claimed(&lobby), [true, false, false, false]);
+        assert_eq!(lobby.players(), 1);
+
+        // The next controller takes over the phone's player
+        lobby.connect(Controller::Gamepad(1));
+        assert_eq!(lobby.claim(Controller::Gamepad(1)), Some(Player::Player2));
+    }
+
+    #[test]
+    fn test_phones_and_gamepads_with_the_same_number_are_apart() {
+        let mut lobby = Lobby::new();
+        assert!(join_phone(&mut lobby, Player::Player1));
+        // Any number gilrs hands out is a gamepad of its own
+        for id in [0, 1, usize::MAX] {
+            lobby.connect(Controller::Gamepad(id));
+            assert!(lobby.claim(Controller::Gamepad(id)).is_some());
+        }
+        assert_eq!(lobby.player(Controller::Gamepad(0)), Some(Player::Player2));
+        assert_eq!(
+            lobby.player(Controller::Gamepad(usize::MAX)),
+            Some(Player::Player4)
+        );
+
+        leave_phone(&mut lobby, Player::Player1);
+        assert_eq!(claimed(&lobby), [false, true, true, true]);
+        assert!(!join_phone(&mut lobby, Player::Player2));
+        assert_eq!(lobby.player(Controller::Gamepad(0)), Some(Player::Player2));
+    }
+
+    #[test]
+    fn test_phone_that_lost_the_race_for_a_player_is_ignored() {
+        let mut lobby = Lobby::new();
+        // The phone picked Player 1 while a gamepad claimed it
+        lobby.connect(Controller::Gamepad(0));
+        assert_eq!(lobby.claim(Controller::Gamepad(0)), Some(Player::Player1));
+        assert!(!join_phone(&mut lobby, Player::Player1));
+        assert_eq!(phone_player(&lobby, Player::Player1), None);
+        assert_eq!(
+            lobby.controller(Player::Player1),
+            Some(Controller::Gamepad(0))
+        );
+
+        // Leaving doesn't take the gamepad's player
+        leave_phone(&mut lobby, Player::Player1);
+        assert_eq!(lobby.player(Controller::Gamepad(0)), Some(Player::Player1));
+
+        assert!(join_phone(&mut lobby, Player::Player2));
+        assert_eq!(phone_player(&lobby, Player::Player2), Some(Player::Player2));
+    }
+}
@@ -1,7 +1,9 @@
-use gilrs::{Button, Event, EventType, Gilrs};
+use gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat, Replay, Ticks};
+use gilrs::{Button, Event, EventType, GamepadId, Gilrs};
 use std::collections::HashMap;
 use std::sync::mpsc::{Receiver, Sender, channel};
 use std::thread;
+use std::time::{Duration, Instant};
 
 pub enum GamepadEvent {
     Connected(usize, String),
@@ -12,42 +14,106 @@ pub enum GamepadEvent {
     DPadRight(usize),
     South(usize),
     East(usize),
+    Start(usize),
+}
+
+/// A force feedback effect for one gamepad.
+pub struct Rumble {
+    pub gamepad: usize,
+    /// Use the strong (low frequency) motor instead of the weak one
+    pub strong: bool,
+    pub magnitude: u16,
+    pub duration: Duration,
 }
 
 pub struct GamepadHandler {
     event_receiver: Receiver<GamepadEvent>,
+    rumble_sender: Sender<Rumble>,
 }
 
 impl GamepadHandler {
     pub fn new() -> Self {
         let (sender, receiver) = channel();
+        let (rumble_sender, rumble_receiver) = channel();
         thread::spawn(move || {
-            run_gamepad_loop(sender);
+            run_gamepad_loop(sender, rumble_receiver);
         });
         GamepadHandler {
             event_receiver: receiver,
+            rumble_sender,
         }
     }
 
     pub fn poll_event(&self) -> Option<GamepadEvent> {
         self.event_receiver.try_recv().ok()
     }
+
+    /// Rumbles a gamepad, ignored if it does not support force feedback.
+    pub fn rumble(&self, rumble: Rumble) {
+        let _ = self.rumble_sender.send(rumble);
+    }
 }
 
-fn run_gamepad_loop(sender: Sender<GamepadEvent>) {
+// Effects stop when dropped, so they are kept until they have finished
+fn play_rumble(
+    gilrs: &mut Gilrs,
+    gamepad_ids: &HashMap<usize, GamepadId>,
+    rumble: &Rumble,
+) -> Option<(Effect, Instant)> {
+    let id = *gamepad_ids.get(&rumble.gamepad)?;
+    if !gilrs.gamepad(id).is_ff_supported() {
+        return None;
+    }
+    let kind = if rumble.strong {
+        BaseEffectType::Strong {
+            magnitude: rumble.magnitude,
+        }
+    } else {
+        BaseEffectType::Weak {
+            magnitude: rumble.magnitude,
+        }
+    };
+    let duration = Ticks::from_ms(rumble.duration.as_millis() as u32);
+    let effect = EffectBuilder::new()
+        .add_effect(BaseEffect {
+            kind,
+            scheduling: Replay {
+                play_for: duration,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .repeat(Repeat::For(duration))
+        .gamepads(&[id])
+        .finish(gilrs)
+        .ok()?;
+    effect.play().ok()?;
+    Some((effect, Instant::now() + rumble.duration))
+}
+
+fn run_gamepad_loop(sender: Sender<GamepadEvent>, rumble_receiver: Receiver<Rumble>) {
     let mut gilrs = Gilrs::new().unwrap();
     let mut active_gamepads = HashMap::new();
+    let mut gamepad_ids = HashMap::new();
+    let mut effects: Vec<(Effect, Instant)> = Vec::new();
 
     for (id, gamepad) in gilrs.gamepads() {
         let gamepad_id = id.into();
         let name = gamepad.name().to_string();
         active_gamepads.insert(gamepad_id, name.clone());
+        gamepad_ids.insert(gamepad_id, id);
         sender
             .send(GamepadEvent::Connected(gamepad_id, name))
             .unwrap();
     }
 
     loop {
+        while let Ok(rumble) = rumble_receiver.try_recv() {
+            effects.extend(play_rumble(&mut gilrs, &gamepad_ids, &rumble));
+        }
+        let now = Instant::now();
+        effects.retain(|(_, end)| *end > now);
+
         while let Some(Event { id, event, .. }) = gilrs.next_event() {
             let gamepad_id: usize = id.into();
 
@@ -56,12 +122,14 @@ fn run_gamepad_loop(sender: Sender<GamepadEvent>) {
                     let gamepad = gilrs.gamepad(id);
                     let name = gamepad.name().to_string();
                     active_gamepads.insert(gamepad_id, name.clone());
+                    gamepad_ids.insert(gamepad_id, id);
                     sender
                         .send(GamepadEvent::Connected(gamepad_id, name))
                         .unwrap();
                 }
                 EventType::Disconnected => {
                     active_gamepads.remove(&gamepad_id);
+                    gamepad_ids.remove(&gamepad_id);
                     sender.send(GamepadEvent::Disconnected(gamepad_id)).unwrap();
                 }
                 EventType::ButtonPressed(button, _) => {
@@ -81,6 +149,7 @@ fn run_gamepad_loop(sender: Sender<GamepadEvent>) {
                             }
                             Button::South => sender.send(GamepadEvent::South(gamepad_id)).unwrap(),
                             Button::East => sender.send(GamepadEvent::East(gamepad_id)).unwrap(),
+                            Button::Start => sender.send(GamepadEvent::Start(gamepad_id)).unwrap(),
                             _ => continue,
                         }
                     }
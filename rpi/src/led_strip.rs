@@ -2,15 +2,15 @@ use crate::output::Output;
 use anyhow::{Context, Result};
 use rs_ws281x::{ChannelBuilder, Controller, ControllerBuilder, StripType};
 use std::any::Any;
-use teotile::RGB;
+use teotile::{GRID_HEIGHT, GRID_WIDTH, LedLayout, RGB};
 
 pub struct LedStrip {
     controller: Controller,
-    led_count: usize,
+    layout: LedLayout,
 }
 
 impl LedStrip {
-    pub fn new(pin: i32, led_count: i32) -> Result<Self> {
+    pub fn new(pin: i32, led_count: i32, layout: LedLayout) -> Result<Self> {
         let controller = ControllerBuilder::new()
             .freq(800_000)
             .dma(10)
@@ -20,15 +20,13 @@ impl LedStrip {
                     .pin(pin)
                     .count(led_count)
                     .strip_type(StripType::Ws2812)
-                    .brightness(20)
+                    // Brightness is up to the color pipeline
+                    .brightness(255)
                     .build(),
             )
             .build()
             .context("Failed to initialize LED strip controller")?;
-        Ok(Self {
-            controller,
-            led_count: led_count as usize,
-        })
+        Ok(Self { controller, layout })
     }
 
     pub fn cleanup(&mut self) -> Result<()> {
@@ -45,14 +43,13 @@ impl LedStrip {
 impl Output for LedStrip {
     fn render(&mut self, render_board: &teotile::RenderBoard) -> Result<()> {
         let leds = self.controller.leds_mut(0);
-        for (i, led) in leds.iter_mut().enumerate() {
-            if i >= self.led_count {
-                break;
+        for col in 0..GRID_WIDTH {
+            for row in 0..GRID_HEIGHT {
+                let color: RGB = render_board.get(col, row);
+                if let Some(led) = leds.get_mut(self.layout.led_index(col, row)) {
+                    *led = [color.b, color.g, color.r, 0];
+                }
             }
-            let row = i / 12;
-            let col = if row % 2 == 0 { i % 12 } else { 11 - (i % 12) };
-            let color: RGB = render_board.get(col, row);
-            *led = [color.b, color.g, color.r, 0];
         }
         self.controller
             .render()
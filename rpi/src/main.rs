@@ -1,22 +1,39 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use clap::Parser;
 use signal_hook::consts::signal::*;
 use signal_hook::iterator::Signals;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::{Duration, Instant};
-use teotile::{ButtonState, CommandType, GameCommand, GameEngine, Player};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use teotile::{
+    ButtonState, ColorPipeline, CommandType, DisplaySettings, FileScoreStorage,
+    FileSettingsStorage, GRID_HEIGHT, GRID_WIDTH, GameCommand, GameEngine, GameError, GameEvent,
+    GameEvents, GameStatus, LedLayout, Lobby, Player, Recorder, Recording, RenderBoard, Replayer,
+    SettingsStorage,
+};
+use teotile_relay::NetSession;
+mod controller;
+use controller::Controller;
 mod gamepad;
-use gamepad::{GamepadEvent, GamepadHandler};
+use gamepad::{GamepadEvent, GamepadHandler, Rumble};
 mod led_strip;
 use led_strip::LedStrip;
 mod output;
 use output::{DebugOutput, Output};
+mod remote;
+use remote::{Remote, RemoteRequest};
 
 const TARGET_FPS: u64 = 60;
 const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / TARGET_FPS);
-const LED_COUNT: i32 = 144; // 12x12 grid
+const LED_COUNT: i32 = (GRID_WIDTH * GRID_HEIGHT) as i32;
+// How often a changed state is saved, it is also saved on exit. This
+// limits what a power cut loses
+const SAVE_STATE_INTERVAL: Duration = Duration::from_secs(10);
+// Used until a brightness is picked in the menu
+const DEFAULT_BRIGHTNESS: u8 = 20;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -28,6 +45,187 @@ struct Args {
     /// led pin number
     #[clap(short, long, default_value = "10")]
     led_pin: i32,
+
+    /// How the LED strip runs through the panel, for example
+    /// "wiring=progressive origin=top-left rotation=90 tile=6x6". Defaults
+    /// to rows from the bottom left that turn around at the end.
+    #[clap(long, conflicts_with = "layout_file")]
+    layout: Option<String>,
+
+    /// File with the settings of --layout, separated by spaces, commas or
+    /// lines. Text after # is ignored.
+    #[clap(long)]
+    layout_file: Option<PathBuf>,
+
+    /// Brightness of the LEDs from 0 to 255, replacing the one picked in the
+    /// menu. Defaults to 20 until one is picked.
+    #[clap(long)]
+    brightness: Option<u8>,
+
+    /// Gamma of the LEDs, 1 to turn gamma correction off
+    #[clap(long, default_value_t = teotile::DEFAULT_GAMMA)]
+    gamma: f32,
+
+    /// Dim frames that would draw more than this many milliamps, estimated
+    /// for WS2812 LEDs
+    #[clap(long)]
+    max_milliamps: Option<u32>,
+
+    /// Record the session to a .ttr file on exit
+    #[clap(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Play back a .ttr recording instead of reading gamepads
+    #[clap(long)]
+    replay: Option<PathBuf>,
+
+    /// File the high score table is stored in
+    #[clap(long, default_value = "teotile-scores.bin")]
+    scores: PathBuf,
+
+    /// File the brightness and night mode picked in the menu are stored in
+    #[clap(long, default_value = "teotile-settings.bin")]
+    settings: PathBuf,
+
+    /// File the running game is saved to and resumed from after a restart
+    #[clap(long, default_value = "teotile-state.bin")]
+    state: PathBuf,
+
+    /// Seconds without input in the menu before games play themselves, 0 to
+    /// disable.
+    #[clap(long, default_value = "60")]
+    attract_after: u64,
+
+    /// Play against another frontend through teotile-relay, for example
+    /// ws://host:9000/room
+    #[clap(long, conflicts_with_all = ["record", "replay"])]
+    connect: Option<String>,
+
+    /// Serve the HTTP API and the phone controller page on this address, for
+    /// example 0.0.0.0:8080
+    #[clap(long)]
+    http: Option<SocketAddr>,
+}
+
+enum Session {
+    Live(GameEngine),
+    Recording(Recorder),
+    Replay(Replayer),
+    Net(Box<NetSession>),
+}
+
+impl Session {
+    fn process_input(&mut self, command: GameCommand) -> Result<(), GameError> {
+        match self {
+            Session::Live(engine) => engine.process_input(command),
+            Session::Recording(recorder) => recorder.process_input(command),
+            Session::Replay(_) => Ok(()),
+            Session::Net(net) => {
+                net.process_input(command);
+                Ok(())
+            }
+        }
+    }
+
+    fn update(&mut self, delta: Duration) -> Result<(), GameError> {
+        match self {
+            Session::Live(engine) => engine.update(delta),
+            Session::Recording(recorder) => recorder.update(delta),
+            Session::Replay(replayer) => replayer.advance(delta),
+            Session::Net(net) => {
+                let started = net.game().is_some();
+                if let Err(err) = net.update(delta) {
+                    eprintln!("Network game ended: {}", err);
+                }
+                if let (false, Some(game)) = (started, net.game()) {
+                    println!("Starting a network game as {:?}", game.local_player());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn render(&self) -> Result<RenderBoard, GameError> {
+        match self {
+            Session::Live(engine) => engine.render(),
+            Session::Recording(recorder) => recorder.render(),
+            Session::Replay(replayer) => replayer.render(),
+            Session::Net(net) => net.render(),
+        }
+    }
+
+    fn status(&self) -> GameStatus {
+        match self {
+            Session::Live(engine) => engine.status(),
+            Session::Recording(recorder) => recorder.status(),
+            Session::Replay(replayer) => replayer.status(),
+            Session::Net(net) => net.status(),
+        }
+    }
+
+    fn drain_events(&mut self) -> GameEvents {
+        match self {
+            Session::Live(engine) => engine.drain_events(),
+            Session::Recording(recorder) => recorder.drain_events(),
+            Session::Replay(replayer) => replayer.drain_events(),
+            Session::Net(net) => net.drain_events(),
+        }
+    }
+
+    fn set_controllers(&mut self, count: usize) {
+        match self {
+            Session::Live(engine) => engine.set_controllers(count),
+            Session::Recording(recorder) => recorder.set_controllers(count),
+            Session::Replay(_) | Session::Net(_) => {}
+        }
+    }
+
+    // Returns why the game didn't start, if it didn't
+    fn start_game(&mut self, id: &str) -> Result<(), &'static str> {
+        let started = match self {
+            Session::Live(engine) => engine.start_game(id),
+            Session::Recording(recorder) => recorder.start_game(id),
+            Session::Replay(_) => return Err("A replay is playing"),
+            Session::Net(_) => return Err("A network game is running"),
+        };
+        if started { Ok(()) } else { Err("Unknown game") }
+    }
+
+    fn set_attract_delay(&mut self, delay: Option<Duration>) {
+        match self {
+            Session::Live(engine) => engine.set_attract_delay(delay),
+            Session::Recording(recorder) => recorder.set_attract_delay(delay),
+            Session::Replay(_) | Session::Net(_) => {}
+        }
+    }
+
+    fn set_display_settings(&mut self, settings: DisplaySettings) {
+        match self {
+            Session::Live(engine) => engine.set_display_settings(settings),
+            Session::Recording(recorder) => recorder.set_display_settings(settings),
+            Session::Replay(_) | Session::Net(_) => {}
+        }
+    }
+
+    fn engine_mut(&mut self) -> Option<&mut GameEngine> {
+        match self {
+            Session::Live(engine) => Some(engine),
+            Session::Recording(recorder) => Some(recorder.engine_mut()),
+            Session::Replay(_) | Session::Net(_) => None,
+        }
+    }
+
+    fn engine(&self) -> Option<&GameEngine> {
+        match self {
+            Session::Live(engine) => Some(engine),
+            Session::Recording(recorder) => Some(recorder.engine()),
+            Session::Replay(_) | Session::Net(_) => None,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self, Session::Replay(replayer) if replayer.is_finished())
+    }
 }
 
 fn main() -> Result<()> {
@@ -44,21 +242,91 @@ fn main() -> Result<()> {
         }
     });
 
-    let mut engine = GameEngine::default();
+    let saved_state = if args.replay.is_none() && args.connect.is_none() {
+        load_state(&args.state)
+    } else {
+        None
+    };
+    let mut session = if let Some(path) = &args.replay {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read replay {}", path.display()))?;
+        let recording = Recording::from_bytes(&data).context("Failed to parse replay")?;
+        Session::Replay(Replayer::new(recording))
+    } else if let Some(url) = &args.connect {
+        let net = NetSession::connect(url)
+            .map_err(|err| anyhow!("Failed to connect to {}: {}", url, err))?;
+        println!("Connected to {}, waiting for another player", url);
+        Session::Net(Box::new(net))
+    } else if args.record.is_some() {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        let recorder = match saved_state.map(|state| Recorder::from_state(seed, &state)) {
+            Some(Ok(recorder)) => recorder,
+            Some(Err(err)) => {
+                eprintln!("Ignoring saved state {}: {}", args.state.display(), err);
+                Recorder::new(seed)
+            }
+            None => Recorder::new(seed),
+        };
+        Session::Recording(recorder)
+    } else {
+        let mut engine = GameEngine::default();
+        if let Some(state) = saved_state
+            && let Err(err) = engine.restore_state(&state)
+        {
+            eprintln!("Ignoring saved state {}: {}", args.state.display(), err);
+        }
+        Session::Live(engine)
+    };
+    if args.attract_after > 0 {
+        session.set_attract_delay(Some(Duration::from_secs(args.attract_after)));
+    }
+    let mut score_storage = FileScoreStorage::new(&args.scores);
+    if let Some(engine) = session.engine_mut() {
+        engine
+            .load_scores(&mut score_storage)
+            .with_context(|| format!("Failed to load high scores {}", args.scores.display()))?;
+    }
+    let mut settings_storage = FileSettingsStorage::new(&args.settings);
+    let mut settings = settings_storage
+        .load()
+        .with_context(|| format!("Failed to load settings {}", args.settings.display()))?
+        .unwrap_or(DisplaySettings {
+            brightness: DEFAULT_BRIGHTNESS,
+            ..DisplaySettings::new()
+        });
+    if let Some(brightness) = args.brightness {
+        settings.brightness = brightness;
+    }
+    session.set_display_settings(settings);
+
+    let layout = load_layout(&args)?;
+    let mut pipeline = ColorPipeline::new(args.gamma);
+    pipeline.set_power_budget(args.max_milliamps);
     let gamepad = GamepadHandler::new();
+    let remote = args.http.map(Remote::start).transpose()?;
     let mut output: Box<dyn Output> = if args.debug {
         Box::new(DebugOutput)
     } else {
-        Box::new(LedStrip::new(args.led_pin, LED_COUNT).context("Failed to initialize LED strip")?)
+        Box::new(
+            LedStrip::new(args.led_pin, LED_COUNT, layout)
+                .context("Failed to initialize LED strip")?,
+        )
     };
     let mut prev_instant = Instant::now();
+    let mut prev_status: Option<GameStatus> = None;
+    let mut last_state_save = Instant::now();
+    // Gamepads that were connected at startup are reported as connected too,
+    // each has to claim a player in the lobby before it plays
+    let mut lobby = Lobby::new();
 
     println!("Game started. Press Ctrl+C or use 'systemctl stop' to exit.");
     if args.debug {
         println!("Running in debug mode.");
     }
 
-    while running.load(Ordering::Relaxed) {
+    while running.load(Ordering::Relaxed) && !session.is_finished() {
         let loop_start = Instant::now();
 
         // Handle gamepad events
@@ -66,26 +334,139 @@ fn main() -> Result<()> {
             match event {
                 GamepadEvent::Connected(id, name) => {
                     println!("Gamepad {} connected: {}", id, name);
+                    lobby.connect(Controller::Gamepad(id));
                 }
                 GamepadEvent::Disconnected(id) => {
                     println!("Gamepad {} disconnected", id);
+                    if let Some(player) = lobby.disconnect(Controller::Gamepad(id)) {
+                        println!("{:?} is kept for the next gamepad", player);
+                    }
+                    set_controllers(&mut session, remote.as_ref(), &lobby);
                 }
                 _ => {
-                    if let Some(command) = gamepad_event_to_command(event) {
-                        let _ = engine.process_input(command);
+                    let Some((id, command_type)) = gamepad_button(event) else {
+                        continue;
+                    };
+                    match lobby.player(Controller::Gamepad(id)) {
+                        Some(player) if !lobby.is_open() => {
+                            let command =
+                                GameCommand::new(command_type, ButtonState::Pressed, player);
+                            let _ = session.process_input(command);
+                        }
+                        // Start closes the lobby when the player has seen
+                        // their color, other buttons wait for it to close
+                        Some(_) if command_type == CommandType::Pause => lobby.dismiss(),
+                        Some(_) => {}
+                        None => {
+                            if let Some(player) = lobby.claim(Controller::Gamepad(id)) {
+                                println!("Gamepad {} plays as {:?}", id, player);
+                                set_controllers(&mut session, remote.as_ref(), &lobby);
+                            }
+                        }
                     }
                 }
             }
         }
 
+        // Handle HTTP requests
+        while let Some(request) = remote.as_ref().and_then(Remote::poll) {
+            match request {
+                RemoteRequest::Command(command) => {
+                    let _ = session.process_input(command);
+                }
+                // Phones go through the lobby like gamepads do
+                RemoteRequest::PhoneCommand(command) => {
+                    match controller::phone_player(&lobby, command.player) {
+                        Some(_) if !lobby.is_open() => {
+                            let _ = session.process_input(command);
+                        }
+                        Some(_) if command.command_type == CommandType::Pause => lobby.dismiss(),
+                        _ => {}
+                    }
+                }
+                RemoteRequest::StartGame(id, reply) => {
+                    let _ = reply.send(session.start_game(&id));
+                }
+                RemoteRequest::PhoneConnected(player) => {
+                    println!("Phone connected as {:?}", player);
+                    if !controller::join_phone(&mut lobby, player) {
+                        eprintln!(
+                            "{:?} was claimed by a gamepad at the same time, the phone is ignored",
+                            player
+                        );
+                    }
+                    set_controllers(&mut session, remote.as_ref(), &lobby);
+                }
+                RemoteRequest::PhoneDisconnected(player) => {
+                    println!("Phone for {:?} disconnected", player);
+                    controller::leave_phone(&mut lobby, player);
+                    set_controllers(&mut session, remote.as_ref(), &lobby);
+                }
+            }
+        }
+
         let current_instant = Instant::now();
         let delta = current_instant - prev_instant;
         prev_instant = current_instant;
 
-        let _ = engine.update(delta);
+        // The game holds while gamepads pick their player, replays don't
+        // take gamepads and network games can't wait
+        lobby.update(delta);
+        let show_lobby = lobby.is_open() && !matches!(session, Session::Replay(_));
+        if !show_lobby || session.engine().is_none() {
+            let _ = session.update(delta);
+        }
+        for event in session.drain_events() {
+            for rumble in event_rumbles(event, &lobby) {
+                gamepad.rumble(rumble);
+            }
+        }
+        if let Some(engine) = session.engine_mut()
+            && let Err(err) = engine.save_scores(&mut score_storage)
+        {
+            eprintln!("Failed to save high scores: {}", err);
+        }
+
+        // Replays and network games show the stored settings
+        let time_of_day = local_time_of_day();
+        let night = match session.engine_mut() {
+            Some(engine) => {
+                engine.set_time_of_day(time_of_day);
+                if let Err(err) = engine.save_settings(&mut settings_storage) {
+                    eprintln!("Failed to save settings: {}", err);
+                }
+                settings = engine.display_settings();
+                engine.is_night()
+            }
+            None => settings.is_night(time_of_day),
+        };
+        pipeline.set_brightness(settings.brightness);
+        pipeline.set_night_mode(night);
+
+        // Scores change too often to log, only log when the game, phase or
+        // winner changes
+        let status = session.status();
+        if prev_status.is_none_or(|prev| {
+            (prev.game, prev.phase, prev.winner) != (status.game, status.phase, status.winner)
+        }) {
+            println!("{}", status);
+            prev_status = Some(status);
+        }
 
-        let render_board = engine.render().unwrap();
-        let _ = output.render(&render_board);
+        if last_state_save.elapsed() >= SAVE_STATE_INTERVAL {
+            last_state_save = Instant::now();
+            save_state(&mut session, &args.state);
+        }
+
+        let render_board = if show_lobby {
+            lobby.render()
+        } else {
+            session.render().unwrap()
+        };
+        let _ = output.render(&pipeline.apply(&render_board));
+        if let Some(remote) = &remote {
+            remote.publish(status, &render_board);
+        }
 
         // Maintain target FPS
         let elapsed = loop_start.elapsed();
@@ -95,12 +476,80 @@ fn main() -> Result<()> {
     }
 
     // Cleanup
+    save_state(&mut session, &args.state);
     cleanup(output).context("Failed to cleanup output")?;
 
+    if let (Some(path), Session::Recording(recorder)) = (&args.record, &session) {
+        std::fs::write(path, recorder.recording().to_bytes())
+            .with_context(|| format!("Failed to write recording {}", path.display()))?;
+        println!("Session recorded to {}", path.display());
+    }
+
     println!("Game stopped. Output cleaned up.");
     Ok(())
 }
 
+fn load_layout(args: &Args) -> Result<LedLayout> {
+    match (&args.layout, &args.layout_file) {
+        (Some(spec), _) => spec
+            .parse()
+            .with_context(|| format!("Invalid LED layout \"{}\"", spec)),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read layout {}", path.display()))?
+            .parse()
+            .with_context(|| format!("Invalid LED layout in {}", path.display())),
+        (None, None) => Ok(LedLayout::new()),
+    }
+}
+
+fn load_state(path: &Path) -> Option<Vec<u8>> {
+    match std::fs::read(path) {
+        Ok(state) => Some(state),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => {
+            eprintln!("Failed to read saved state {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+// Written to a temporary file first so a power cut never leaves a
+// half written state behind. Nothing is written while the menu sits idle,
+// which spares the SD card
+fn save_state(session: &mut Session, path: &Path) {
+    let Some(engine) = session.engine_mut().filter(|engine| engine.state_changed()) else {
+        return;
+    };
+    let temp_path = path.with_extension("tmp");
+    let result = std::fs::write(&temp_path, engine.save_state_to_vec())
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    match result {
+        Ok(()) => engine.mark_state_saved(),
+        Err(err) => eprintln!("Failed to save state {}: {}", path.display(), err),
+    }
+}
+
+// Time since local midnight, for scheduled night mode
+fn local_time_of_day() -> Option<Duration> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    let time = now.as_secs() as libc::time_t;
+    // SAFETY: tm is plain data that localtime_r fills in
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return None;
+    }
+    let seconds = tm.tm_hour as u64 * 3600 + tm.tm_min as u64 * 60 + tm.tm_sec as u64;
+    Some(Duration::from_secs(seconds))
+}
+
+// Phones count as controllers too
+fn set_controllers(session: &mut Session, remote: Option<&Remote>, lobby: &Lobby<Controller>) {
+    session.set_controllers(lobby.players());
+    if let Some(remote) = remote {
+        remote.set_claimed(controller::claimed(lobby));
+    }
+}
+
 fn cleanup(mut output: Box<dyn Output>) -> Result<()> {
     if let Some(led_strip) = output.as_any_mut().downcast_mut::<LedStrip>() {
         led_strip.cleanup().context("Failed to cleanup LED strip")?;
@@ -108,46 +557,42 @@ fn cleanup(mut output: Box<dyn Output>) -> Result<()> {
     Ok(())
 }
 
-fn gamepad_event_to_command(event: GamepadEvent) -> Option<GameCommand> {
+// The gamepad a button was pressed on and the command it stands for
+fn gamepad_button(event: GamepadEvent) -> Option<(usize, CommandType)> {
     match event {
-        GamepadEvent::DPadUp(id) => Some(GameCommand::new(
-            CommandType::Up,
-            ButtonState::Pressed,
-            player_from_id(id),
-        )),
-        GamepadEvent::DPadDown(id) => Some(GameCommand::new(
-            CommandType::Down,
-            ButtonState::Pressed,
-            player_from_id(id),
-        )),
-        GamepadEvent::DPadLeft(id) => Some(GameCommand::new(
-            CommandType::Left,
-            ButtonState::Pressed,
-            player_from_id(id),
-        )),
-        GamepadEvent::DPadRight(id) => Some(GameCommand::new(
-            CommandType::Right,
-            ButtonState::Pressed,
-            player_from_id(id),
-        )),
-        GamepadEvent::South(id) => Some(GameCommand::new(
-            CommandType::Select,
-            ButtonState::Pressed,
-            player_from_id(id),
-        )),
-        GamepadEvent::East(id) => Some(GameCommand::new(
-            CommandType::Quit,
-            ButtonState::Pressed,
-            player_from_id(id),
-        )),
+        GamepadEvent::DPadUp(id) => Some((id, CommandType::Up)),
+        GamepadEvent::DPadDown(id) => Some((id, CommandType::Down)),
+        GamepadEvent::DPadLeft(id) => Some((id, CommandType::Left)),
+        GamepadEvent::DPadRight(id) => Some((id, CommandType::Right)),
+        GamepadEvent::South(id) => Some((id, CommandType::Select)),
+        GamepadEvent::East(id) => Some((id, CommandType::Quit)),
+        GamepadEvent::Start(id) => Some((id, CommandType::Pause)),
         GamepadEvent::Connected(_, _) | GamepadEvent::Disconnected(_) => None,
     }
 }
 
-fn player_from_id(id: usize) -> Player {
-    match id {
-        0 => Player::Player1,
-        1 => Player::Player2,
-        _ => Player::Player1, // Default to Player1 for any additional controllers
-    }
+// Only events that are felt by a specific player rumble, except for the end
+// of the game which every player feels. Players without a gamepad don't.
+fn event_rumbles(event: GameEvent, lobby: &Lobby<Controller>) -> Vec<Rumble> {
+    let rumble = |player, strong, magnitude, millis| match lobby.controller(player) {
+        Some(Controller::Gamepad(gamepad)) => Some(Rumble {
+            gamepad,
+            strong,
+            magnitude,
+            duration: Duration::from_millis(millis),
+        }),
+        Some(Controller::Phone(_)) | None => None,
+    };
+    let rumbles = match event {
+        GameEvent::PaddleHit(player) => vec![rumble(player, false, 30_000, 60)],
+        GameEvent::Shot(player) => vec![rumble(player, false, 20_000, 40)],
+        GameEvent::FoodEaten(player) => vec![rumble(player, false, 25_000, 50)],
+        GameEvent::PlayerDied(player) => vec![rumble(player, true, 60_000, 300)],
+        GameEvent::GameOver(_) => Player::ALL
+            .into_iter()
+            .map(|player| rumble(player, true, 40_000, 200))
+            .collect(),
+        _ => Vec::new(),
+    };
+    rumbles.into_iter().flatten().collect()
 }
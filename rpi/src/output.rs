@@ -1,6 +1,6 @@
 use anyhow::Result;
 use std::any::Any;
-use teotile::RenderBoard;
+use teotile::{GRID_HEIGHT, GRID_WIDTH, RenderBoard};
 
 pub trait Output: Any {
     fn render(&mut self, render_board: &RenderBoard) -> Result<()>;
@@ -11,9 +11,9 @@ pub struct DebugOutput;
 
 impl Output for DebugOutput {
     fn render(&mut self, render_board: &RenderBoard) -> Result<()> {
-        for row in 0..12 {
-            for col in 0..12 {
-                let color = render_board.get(col, 11 - row);
+        for row in 0..GRID_HEIGHT {
+            for col in 0..GRID_WIDTH {
+                let color = render_board.get(col, GRID_HEIGHT - 1 - row);
                 print!("({},{},{}) ", color.r, color.g, color.b);
             }
             println!();
@@ -0,0 +1,872 @@
+//! HTTP API to control the display without a gamepad, enabled with
+//! `--http`. Responses are JSON unless noted otherwise:
+//!
+//! - `GET /status`: the running game, see `teotile::GameStatus`
+//! - `GET /games`: the ids, names and player counts of the menu's games,
+//!   `max_players` is how many can join when enough controllers are connected
+//! - `GET /frame`: the current frame as rows of `[r, g, b]`, top row first
+//! - `GET /frame.png`: the current frame as a PNG, one pixel per LED
+//! - `POST /command?type=up&player=1`: presses a button. The type is one
+//!   of up, down, left, right, select, quit and pause, the player 1 to 4
+//!   and `state=released` releases the button instead
+//! - `POST /game/<id>`: starts a game from `/games`, or answers 409 when
+//!   a replay or network game is running
+//! - `GET /controller`: a gamepad page for phones, which sends its buttons
+//!   over a WebSocket to `/controller/ws`. Each phone controls a player of
+//!   its own, so at most four can be connected.
+//!
+//! Parameters can also be sent as a form body. Every connection, phones
+//! included, is handled on a thread of its own so a slow client can't hold
+//! up the others, and connections past `MAX_CONNECTIONS` are turned away.
+//! Inputs are queued for the main loop which publishes the status and frame
+//! every frame.
+
+use anyhow::{Context, Result};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+use teotile::{
+    ButtonState, CommandType, GRID_HEIGHT, GRID_WIDTH, GameCommand, GameEngine, GamePhase,
+    GameStatus, MAX_PLAYERS, Player, RenderBoard,
+};
+use tungstenite::protocol::Role;
+use tungstenite::protocol::frame::CloseFrame;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::{Message, WebSocket};
+
+// A client that hasn't sent its whole request after this long is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_BODY_LEN: usize = 1024;
+// Limits on the request line and headers, which are read before the body
+const MAX_HEAD_LEN: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 32;
+// Four phones and a few API clients at once, each on a thread of its own
+const MAX_CONNECTIONS: usize = 16;
+// The controller page sends an empty message every 5 seconds, a phone that
+// stays quiet for longer has gone away
+const PHONE_TIMEOUT: Duration = Duration::from_secs(15);
+const CONTROLLER_PAGE: &str = include_str!("../static/controller.html");
+
+pub enum RemoteRequest {
+    Command(GameCommand),
+    // Only played while the phone holds its player in the lobby
+    PhoneCommand(GameCommand),
+    // Answered with why the game didn't start, if it didn't
+    StartGame(String, mpsc::Sender<Result<(), &'static str>>),
+    PhoneConnected(Player),
+    PhoneDisconnected(Player),
+}
+
+#[derive(Default)]
+struct Shared {
+    status: Option<GameStatus>,
+    frame: Option<RenderBoard>,
+    // Players that are claimed in the lobby, by a gamepad or a phone
+    claimed: [bool; MAX_PLAYERS],
+    // Players that are controlled by a phone
+    phones: [bool; MAX_PLAYERS],
+}
+
+pub struct Remote {
+    requests: mpsc::Receiver<RemoteRequest>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Remote {
+    pub fn start(address: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(address)
+            .with_context(|| format!("Failed to listen for HTTP on {}", address))?;
+        let (sender, requests) = mpsc::channel();
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let server_shared = shared.clone();
+        thread::spawn(move || serve(listener, sender, server_shared));
+        println!("HTTP API listening on {}", address);
+        Ok(Self { requests, shared })
+    }
+
+    /// The next request for the main loop, if there is one.
+    pub fn poll(&self) -> Option<RemoteRequest> {
+        self.requests.try_recv().ok()
+    }
+
+    /// Makes the frame that was just shown available to clients.
+    pub fn publish(&self, status: GameStatus, frame: &RenderBoard) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.status = Some(status);
+            shared.frame = Some(*frame);
+        }
+    }
+
+    /// Sets which players are claimed in the lobby, phones that connect
+    /// later take one of the others.
+    pub fn set_claimed(&self, claimed: [bool; MAX_PLAYERS]) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.claimed = claimed;
+        }
+    }
+}
+
+// Counts a connection as open until its thread is done with it
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn serve(listener: TcpListener, sender: mpsc::Sender<RemoteRequest>, shared: Arc<Mutex<Shared>>) {
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming().flatten() {
+        if open.load(Ordering::SeqCst) >= MAX_CONNECTIONS {
+            let _ = stream.set_write_timeout(Some(REQUEST_TIMEOUT));
+            let response = Response::error("503 Service Unavailable", "Too many connections");
+            let _ = write_response(&mut &stream, &response);
+            continue;
+        }
+        open.fetch_add(1, Ordering::SeqCst);
+        let connection = Connection(open.clone());
+        let sender = sender.clone();
+        let shared = shared.clone();
+        thread::spawn(move || {
+            let _connection = connection;
+            if let Err(err) = handle(stream, &sender, &shared) {
+                eprintln!("HTTP request failed: {}", err);
+            }
+        });
+    }
+}
+
+// Reads from a socket until the deadline, so a client that sends a byte
+// now and then can't hold on to its connection
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// The parts of an HTTP request the API looks at.
+#[derive(Debug, PartialEq)]
+struct Request {
+    method: String,
+    path: String,
+    // From the query and then the form body
+    params: Vec<(String, String)>,
+    websocket_key: Option<String>,
+}
+
+impl Request {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn too_large(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::FileTooLarge, message)
+}
+
+// A line that is cut off by the limit makes the head too large
+fn read_head_line(head: &mut io::Take<impl BufRead>) -> io::Result<String> {
+    let mut line = String::new();
+    head.read_line(&mut line)?;
+    if !line.ends_with('\n') && head.limit() == 0 {
+        return Err(too_large("Request head too large"));
+    }
+    Ok(line)
+}
+
+// Requests that can't be understood are errors of kind InvalidData, ones
+// with a head over the limits are errors of kind FileTooLarge
+fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let mut head = (&mut *reader).take(MAX_HEAD_LEN);
+    let request_line = read_head_line(&mut head)?;
+    let (method, target) = match request_line.split_whitespace().collect::<Vec<_>>()[..] {
+        [method, target, version] if version.starts_with("HTTP/") => (method, target),
+        _ => return Err(invalid("Malformed request line")),
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut content_length = 0;
+    let mut websocket_key = None;
+    let mut headers = 0;
+    loop {
+        let line = read_head_line(&mut head)?;
+        if line.trim_end().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(too_large("Too many headers"));
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(invalid("Malformed header"));
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid("Malformed Content-Length"))?;
+        } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+            websocket_key = Some(value.trim().to_string());
+        }
+    }
+    if content_length > MAX_BODY_LEN {
+        return Err(invalid("Body too long"));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let body = String::from_utf8_lossy(&body);
+    let params = query
+        .split('&')
+        .chain(body.split('&'))
+        .filter_map(|param| param.trim().split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        params,
+        websocket_key,
+    })
+}
+
+#[derive(Debug)]
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(body: String) -> Self {
+        Self {
+            status: "200 OK",
+            content_type: "application/json",
+            body: body.into_bytes(),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: format!("{{\"error\":{}}}", json_string(message)).into_bytes(),
+        }
+    }
+}
+
+fn handle(
+    stream: TcpStream,
+    requests: &mpsc::Sender<RemoteRequest>,
+    shared: &Mutex<Shared>,
+) -> io::Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+    let response = match read_request(&mut reader) {
+        Ok(Request {
+            method,
+            path,
+            websocket_key: Some(key),
+            ..
+        }) if method == "GET" && path == "/controller/ws" => {
+            (&stream).write_all(accept_websocket(&key).as_bytes())?;
+            run_phone(stream, requests, shared);
+            return Ok(());
+        }
+        Ok(request) => route(&request, requests, shared),
+        Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+            Response::error("400 Bad Request", &err.to_string())
+        }
+        Err(err) if err.kind() == io::ErrorKind::FileTooLarge => {
+            Response::error("431 Request Header Fields Too Large", &err.to_string())
+        }
+        Err(err) => return Err(err),
+    };
+    write_response(&mut &stream, &response)
+}
+
+fn accept_websocket(key: &str) -> String {
+    format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+         Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        tungstenite::handshake::derive_accept_key(key.as_bytes())
+    )
+}
+
+fn write_response(writer: &mut impl Write, response: &Response) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    writer.write_all(&response.body)
+}
+
+fn route(
+    request: &Request,
+    requests: &mpsc::Sender<RemoteRequest>,
+    shared: &Mutex<Shared>,
+) -> Response {
+    let (status, frame) = match shared.lock() {
+        Ok(shared) => (shared.status, shared.frame),
+        Err(_) => (None, None),
+    };
+    let queue = |request| match requests.send(request) {
+        Ok(()) => Response::json("{\"ok\":true}".to_string()),
+        Err(_) => Response::error("503 Service Unavailable", "The game has stopped"),
+    };
+    let path = request.path.as_str();
+    match (request.method.as_str(), path) {
+        ("GET", "/status") => match status {
+            Some(status) => Response::json(status_json(&status)),
+            None => Response::error("503 Service Unavailable", "No frame shown yet"),
+        },
+        ("GET", "/games") => Response::json(games_json()),
+        ("GET", "/controller") => Response {
+            status: "200 OK",
+            content_type: "text/html; charset=utf-8",
+            body: CONTROLLER_PAGE.as_bytes().to_vec(),
+        },
+        ("GET", "/frame") => match frame {
+            Some(frame) => Response::json(frame_json(&frame)),
+            None => Response::error("503 Service Unavailable", "No frame shown yet"),
+        },
+        ("GET", "/frame.png") => match frame.map(|frame| frame_png(&frame)) {
+            Some(Ok(png)) => Response {
+                status: "200 OK",
+                content_type: "image/png",
+                body: png,
+            },
+            Some(Err(err)) => Response::error("500 Internal Server Error", &err.to_string()),
+            None => Response::error("503 Service Unavailable", "No frame shown yet"),
+        },
+        ("POST", "/command") => match parse_command(request) {
+            Ok(command) => queue(RemoteRequest::Command(command)),
+            Err(message) => Response::error("400 Bad Request", message),
+        },
+        ("POST", _) if path.starts_with("/game/") => {
+            let id = &path["/game/".len()..];
+            if GameEngine::games().any(|game| game.id == id) {
+                let (reply, started) = mpsc::channel();
+                if requests
+                    .send(RemoteRequest::StartGame(id.to_string(), reply))
+                    .is_err()
+                {
+                    return Response::error("503 Service Unavailable", "The game has stopped");
+                }
+                match started.recv_timeout(REQUEST_TIMEOUT) {
+                    Ok(Ok(())) => Response::json("{\"ok\":true}".to_string()),
+                    Ok(Err(reason)) => Response::error("409 Conflict", reason),
+                    Err(_) => Response::error("503 Service Unavailable", "The game has stopped"),
+                }
+            } else {
+                Response::error("404 Not Found", "Unknown game, see /games")
+            }
+        }
+        _ => Response::error("404 Not Found", "Unknown endpoint"),
+    }
+}
+
+// Phones take the lowest player nobody has claimed
+fn claim_player(shared: &Mutex<Shared>) -> Option<usize> {
+    let mut shared = shared.lock().ok()?;
+    let index = (0..MAX_PLAYERS).find(|&index| !shared.claimed[index] && !shared.phones[index])?;
+    shared.phones[index] = true;
+    Some(index)
+}
+
+fn run_phone(stream: TcpStream, requests: &mpsc::Sender<RemoteRequest>, shared: &Mutex<Shared>) {
+    let _ = stream.set_read_timeout(Some(PHONE_TIMEOUT));
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+    let Some(index) = claim_player(shared) else {
+        let _ = socket.close(Some(CloseFrame {
+            code: CloseCode::Again,
+            reason: "All players are taken".into(),
+        }));
+        let _ = socket.flush();
+        return;
+    };
+    let player = Player::ALL[index];
+    let _ = requests.send(RemoteRequest::PhoneConnected(player));
+    let _ = socket.send(Message::text(format!("Player {}", index + 1)));
+    loop {
+        match socket.read() {
+            Ok(Message::Binary(bytes)) => {
+                if let Some(command) = phone_command(&bytes, player) {
+                    let _ = requests.send(RemoteRequest::PhoneCommand(command));
+                }
+            }
+            Ok(Message::Close(_)) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+    if let Ok(mut shared) = shared.lock() {
+        shared.phones[index] = false;
+    }
+    let _ = requests.send(RemoteRequest::PhoneDisconnected(player));
+}
+
+// Numbered like the web frontend's process_input
+fn phone_command(bytes: &[u8], player: Player) -> Option<GameCommand> {
+    let &[command_type, button_state] = bytes else {
+        return None;
+    };
+    let command_type = match command_type {
+        0 => CommandType::Up,
+        1 => CommandType::Down,
+        2 => CommandType::Left,
+        3 => CommandType::Right,
+        4 => CommandType::Select,
+        5 => CommandType::Quit,
+        6 => CommandType::Pause,
+        _ => return None,
+    };
+    let button_state = match button_state {
+        0 => ButtonState::Pressed,
+        1 => ButtonState::Released,
+        _ => return None,
+    };
+    Some(GameCommand::new(command_type, button_state, player))
+}
+
+fn parse_command(request: &Request) -> Result<GameCommand, &'static str> {
+    let command_type = match request.param("type") {
+        Some("up") => CommandType::Up,
+        Some("down") => CommandType::Down,
+        Some("left") => CommandType::Left,
+        Some("right") => CommandType::Right,
+        Some("select") => CommandType::Select,
+        Some("quit") => CommandType::Quit,
+        Some("pause") => CommandType::Pause,
+        _ => return Err("type must be up, down, left, right, select, quit or pause"),
+    };
+    let player = match request.param("player").map(str::parse::<usize>) {
+        None => Player::Player1,
+        Some(Ok(number)) => match number.checked_sub(1).and_then(Player::from_index) {
+            Some(player) => player,
+            None => return Err("player must be 1 to 4"),
+        },
+        Some(Err(_)) => return Err("player must be 1 to 4"),
+    };
+    let button_state = match request.param("state") {
+        None | Some("pressed") => ButtonState::Pressed,
+        Some("released") => ButtonState::Released,
+        _ => return Err("state must be pressed or released"),
+    };
+    Ok(GameCommand::new(command_type, button_state, player))
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_option(value: Option<u32>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+fn status_json(status: &GameStatus) -> String {
+    let phase = match status.phase {
+        GamePhase::Menu => "menu",
+        GamePhase::Playing => "playing",
+        GamePhase::Paused => "paused",
+        GamePhase::GameOver => "game_over",
+        GamePhase::Demo => "demo",
+    };
+    let winner = status.winner.map(|player| player.index() as u32 + 1);
+    let scores: Vec<String> = status.scores.into_iter().map(json_option).collect();
+    format!(
+        "{{\"game\":{},\"phase\":\"{}\",\"winner\":{},\"scores\":[{}],\"level\":{},\"text\":{}}}",
+        status.game.map_or("null".to_string(), json_string),
+        phase,
+        json_option(winner),
+        scores.join(","),
+        json_option(status.level),
+        json_string(&status.to_string())
+    )
+}
+
+fn games_json() -> String {
+    let games: Vec<String> = GameEngine::games()
+        .map(|game| {
+            format!(
+                "{{\"id\":{},\"name\":{},\"players\":{},\"max_players\":{}}}",
+                json_string(game.id),
+                json_string(game.name),
+                game.players,
+                game.max_players
+            )
+        })
+        .collect();
+    format!("[{}]", games.join(","))
+}
+
+// Row 0 of a render board is the bottom row
+fn top_down_pixels(frame: &RenderBoard) -> impl Iterator<Item = [u8; 3]> + '_ {
+    (0..GRID_HEIGHT).rev().flat_map(move |row| {
+        (0..GRID_WIDTH).map(move |col| {
+            let color = frame.get(col, row);
+            [color.r, color.g, color.b]
+        })
+    })
+}
+
+fn frame_json(frame: &RenderBoard) -> String {
+    let pixels: Vec<String> = top_down_pixels(frame)
+        .map(|[r, g, b]| format!("[{},{},{}]", r, g, b))
+        .collect();
+    let rows: Vec<String> = pixels
+        .chunks(GRID_WIDTH)
+        .map(|row| format!("[{}]", row.join(",")))
+        .collect();
+    format!(
+        "{{\"width\":{},\"height\":{},\"rows\":[{}]}}",
+        GRID_WIDTH,
+        GRID_HEIGHT,
+        rows.join(",")
+    )
+}
+
+fn frame_png(frame: &RenderBoard) -> Result<Vec<u8>, png::EncodingError> {
+    let data: Vec<u8> = top_down_pixels(frame).flatten().collect();
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, GRID_WIDTH as u32, GRID_HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teotile::RGB;
+
+    fn parse(request: &str) -> io::Result<Request> {
+        read_request(&mut request.as_bytes())
+    }
+
+    fn post(body: &str) -> Request {
+        parse(&format!(
+            "POST /command HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        ))
+        .unwrap()
+    }
+
+    // Routes a request and returns the response with what it queued
+    fn respond(request: &Request) -> (Response, Vec<RemoteRequest>) {
+        let (sender, receiver) = mpsc::channel();
+        let response = route(request, &sender, &Mutex::new(Shared::default()));
+        (response, receiver.try_iter().collect())
+    }
+
+    #[test]
+    fn test_requests_are_parsed() {
+        let request = parse(
+            "POST /command?type=up HTTP/1.1\r\nHost: teotile\r\ncontent-length: 21\r\n\r\n\
+             player=2&state=released",
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/command");
+        assert_eq!(request.param("type"), Some("up"));
+        assert_eq!(request.param("player"), Some("2"));
+        // Nothing past the Content-Length is read
+        assert_eq!(request.param("state"), Some("releas"));
+        assert_eq!(request.websocket_key, None);
+    }
+
+    #[test]
+    fn test_malformed_request_lines_are_rejected() {
+        for request in [
+            "",
+            "\r\n\r\n",
+            "GET\r\n\r\n",
+            "GET /status\r\n\r\n",
+            "GET /status SMTP\r\n\r\n",
+            "GET /status HTTP/1.1 extra\r\n\r\n",
+            "GET /status HTTP/1.1\r\nNo colon\r\n\r\n",
+            "POST /command HTTP/1.1\r\nContent-Length: ten\r\n\r\n",
+            "POST /command HTTP/1.1\r\nContent-Length: 4096\r\n\r\n",
+        ] {
+            let err = parse(request).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", request);
+        }
+        // A body that ends early is an I/O error, not a bad request
+        let err = parse("POST /command HTTP/1.1\r\nContent-Length: 9\r\n\r\ntype").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_oversized_heads_are_rejected() {
+        let long_line = format!(
+            "GET /{} HTTP/1.1\r\n\r\n",
+            "a".repeat(MAX_HEAD_LEN as usize)
+        );
+        let long_header = format!(
+            "GET /status HTTP/1.1\r\nCookie: {}\r\n\r\n",
+            "a".repeat(MAX_HEAD_LEN as usize)
+        );
+        let many_headers = format!(
+            "GET /status HTTP/1.1\r\n{}\r\n",
+            "Host: teotile\r\n".repeat(MAX_HEADERS + 1)
+        );
+        for request in [long_line, long_header, many_headers] {
+            let err = parse(&request).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+        }
+
+        let most_headers = format!(
+            "GET /status HTTP/1.1\r\n{}\r\n",
+            "Host: teotile\r\n".repeat(MAX_HEADERS)
+        );
+        assert_eq!(parse(&most_headers).unwrap().path, "/status");
+    }
+
+    #[test]
+    fn test_connections_past_the_limit_are_turned_away() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, _receiver) = mpsc::channel();
+        thread::spawn(move || serve(listener, sender, Arc::default()));
+
+        // Idle connections hold their thread until the request times out
+        let _open: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| TcpStream::connect(address).unwrap())
+            .collect();
+        let mut refused = TcpStream::connect(address).unwrap();
+        let mut response = String::new();
+        refused.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.ends_with("{\"error\":\"Too many connections\"}"));
+    }
+
+    #[test]
+    fn test_slow_requests_time_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle(stream, &mpsc::channel().0, &Mutex::default())
+        });
+
+        // A byte every half second never finishes the head in time
+        let mut client = TcpStream::connect(address).unwrap();
+        let started = Instant::now();
+        for byte in b"GET /status HTTP/1.1\r\nHost: teotile\r\n".iter().cycle() {
+            if client.write_all(&[*byte]).is_err() || server.is_finished() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+        let err = server.join().unwrap().unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        ));
+        assert!(started.elapsed() < REQUEST_TIMEOUT * 2);
+    }
+
+    #[test]
+    fn test_missing_content_length_ignores_the_body() {
+        let request = parse("POST /command?type=left HTTP/1.1\r\n\r\ntype=right").unwrap();
+        assert_eq!(request.params, [("type".to_string(), "left".to_string())]);
+    }
+
+    #[test]
+    fn test_unknown_routes_are_not_found() {
+        for request in [
+            "GET /nothing HTTP/1.1\r\n\r\n",
+            "GET /command?type=up HTTP/1.1\r\n\r\n",
+            "DELETE /status HTTP/1.1\r\n\r\n",
+            "POST /game/chess HTTP/1.1\r\n\r\n",
+        ] {
+            let (response, queued) = respond(&parse(request).unwrap());
+            assert_eq!(response.status, "404 Not Found", "{:?}", request);
+            assert!(queued.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_commands_are_queued() {
+        let (response, queued) = respond(&post("type=left&player=4&state=released"));
+        assert_eq!(response.status, "200 OK");
+        let expected = GameCommand::new(CommandType::Left, ButtonState::Released, Player::Player4);
+        assert!(matches!(queued[..], [RemoteRequest::Command(command)] if command == expected));
+    }
+
+    #[test]
+    fn test_starting_a_game_waits_for_the_main_loop() {
+        let (sender, receiver) = mpsc::channel();
+        let main_loop = thread::spawn(move || {
+            for answer in [Ok(()), Err("A replay is playing")] {
+                match receiver.recv().unwrap() {
+                    RemoteRequest::StartGame(id, reply) if id == "Snake" => {
+                        reply.send(answer).unwrap()
+                    }
+                    _ => panic!("Snake should be started"),
+                }
+            }
+        });
+        let request = parse("POST /game/Snake HTTP/1.1\r\n\r\n").unwrap();
+        let shared = Mutex::new(Shared::default());
+        assert_eq!(route(&request, &sender, &shared).status, "200 OK");
+        let response = route(&request, &sender, &shared);
+        assert_eq!(response.status, "409 Conflict");
+        assert_eq!(response.body, b"{\"error\":\"A replay is playing\"}");
+        main_loop.join().unwrap();
+
+        // Nobody answers once the main loop has stopped
+        let response = route(&request, &sender, &shared);
+        assert_eq!(response.status, "503 Service Unavailable");
+    }
+
+    #[test]
+    fn test_bad_command_bodies_are_rejected() {
+        for body in [
+            "",
+            "player=1",
+            "type=jump",
+            "type=UP",
+            "type=up&player=0",
+            "type=up&player=5",
+            "type=up&player=one",
+            "type=up&state=held",
+        ] {
+            let (response, queued) = respond(&post(body));
+            assert_eq!(response.status, "400 Bad Request", "{:?}", body);
+            assert!(queued.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_responses_are_written() {
+        let mut written = Vec::new();
+        let response = Response::error("404 Not Found", "Say \"hi\"");
+        write_response(&mut written, &response).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(written.contains("Content-Length: 22\r\n"));
+        assert!(written.ends_with("\r\n\r\n{\"error\":\"Say \\\"hi\\\"\"}"));
+    }
+
+    #[test]
+    fn test_status_json() {
+        let mut status = GameStatus::game_over(Some(Player::Player2));
+        status.game = Some("Pong");
+        status.scores = [Some(3), Some(5), None, None];
+        assert_eq!(
+            status_json(&status),
+            "{\"game\":\"Pong\",\"phase\":\"game_over\",\"winner\":2,\
+             \"scores\":[3,5,null,null],\"level\":null,\
+             \"text\":\"Pong | Player 2 wins | Score 3-5\"}"
+        );
+    }
+
+    #[test]
+    fn test_frame_json_starts_at_the_top() {
+        let mut frame = RenderBoard::new();
+        frame.set(0, GRID_HEIGHT - 1, RGB::new(1, 2, 3));
+        frame.set(GRID_WIDTH - 1, 0, RGB::new(4, 5, 6));
+        let json = frame_json(&frame);
+        let prefix = format!(
+            "{{\"width\":{},\"height\":{},\"rows\":[[[1,2,3],[0,0,0]",
+            GRID_WIDTH, GRID_HEIGHT
+        );
+        assert!(json.starts_with(&prefix), "{}", json);
+        assert!(json.ends_with("[0,0,0],[4,5,6]]]}"), "{}", json);
+    }
+
+    #[test]
+    fn test_websocket_accept_key() {
+        // The example from RFC 6455
+        let response = accept_websocket("dGhlIHNhbXBsZSBub25jZQ==");
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(response.contains("\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert!(response.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_phone_buttons_are_decoded() {
+        let command = phone_command(&[3, 1], Player::Player2).unwrap();
+        assert_eq!(
+            command,
+            GameCommand::new(CommandType::Right, ButtonState::Released, Player::Player2)
+        );
+        for bytes in [&[][..], &[4], &[4, 0, 0], &[7, 0], &[4, 2]] {
+            assert!(phone_command(bytes, Player::Player1).is_none());
+        }
+    }
+
+    #[test]
+    fn test_phones_send_buttons_over_a_websocket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle(stream, &sender, &Mutex::new(Shared::default())).unwrap();
+        });
+
+        let url = format!("ws://{}/controller/ws", address);
+        let (mut socket, _) = tungstenite::client(url, TcpStream::connect(address).unwrap())
+            .expect("the handshake succeeds");
+        assert_eq!(socket.read().unwrap(), Message::text("Player 1"));
+        socket.send(Message::binary(vec![4, 0])).unwrap();
+        socket.close(None).unwrap();
+        while socket.read().is_ok() {}
+        server.join().unwrap();
+
+        let queued: Vec<_> = receiver.try_iter().collect();
+        let select = GameCommand::new(CommandType::Select, ButtonState::Pressed, Player::Player1);
+        assert!(matches!(
+            queued[..],
+            [
+                RemoteRequest::PhoneConnected(Player::Player1),
+                RemoteRequest::PhoneCommand(command),
+                RemoteRequest::PhoneDisconnected(Player::Player1),
+            ] if command == select
+        ));
+    }
+}
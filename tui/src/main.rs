@@ -1,7 +1,13 @@
+mod net;
+
 use std::io::{self, stdout};
-use std::time::Instant;
-use teotile::{ButtonState, CommandType, GameCommand, GameEngine, Player, RGB, RenderBoard};
-const GRID_SIZE: usize = 12;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use teotile::{
+    ButtonState, CommandType, FileScoreStorage, GRID_HEIGHT, GRID_WIDTH, GameCommand, GameEngine,
+    GameError, NET_FRAME, Player, RGB, Recorder, Recording, RenderBoard, Replayer,
+};
+use teotile_relay::NetSession;
 
 use crossterm::{
     ExecutableCommand,
@@ -13,20 +19,162 @@ use ratatui::{
     widgets::{canvas::*, *},
 };
 
+const USAGE: &str = "Usage: teotile-tui [--record <file.ttr> | --replay <file.ttr>] [--scores <file>] [--connect <ws://host:port/room>]";
+const DEFAULT_SCORES_FILE: &str = "teotile-scores.bin";
+
+struct Args {
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    scores: Option<PathBuf>,
+    connect: Option<String>,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Self {
+            record: None,
+            replay: None,
+            scores: None,
+            connect: None,
+        };
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            if arg == "--connect" {
+                args.connect = Some(iter.next().ok_or("Missing address for --connect")?);
+                continue;
+            }
+            let target = match arg.as_str() {
+                "--record" => &mut args.record,
+                "--replay" => &mut args.replay,
+                "--scores" => &mut args.scores,
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            };
+            let path = iter.next().ok_or(format!("Missing file for {}", arg))?;
+            *target = Some(PathBuf::from(path));
+        }
+        if args.connect.is_some() && args.replay.is_some() {
+            return Err("--connect and --replay can't be combined".to_string());
+        }
+        if args.record.is_some() && args.replay.is_some() {
+            return Err("--record and --replay can't be combined".to_string());
+        }
+        Ok(args)
+    }
+}
+
+enum Session {
+    Live(GameEngine),
+    Recording(Recorder),
+    Replay(Replayer),
+    Net(Box<NetSession>),
+}
+
+impl Session {
+    fn process_input(&mut self, command: GameCommand) -> Result<(), GameError> {
+        match self {
+            Session::Live(engine) => engine.process_input(command),
+            Session::Recording(recorder) => recorder.process_input(command),
+            // Inputs are ignored while watching a replay
+            Session::Replay(_) => Ok(()),
+            Session::Net(net) => {
+                net.process_input(command);
+                Ok(())
+            }
+        }
+    }
+
+    fn update(&mut self, delta: Duration) -> Result<(), GameError> {
+        match self {
+            Session::Live(engine) => engine.update(delta),
+            Session::Recording(recorder) => recorder.update(delta),
+            Session::Replay(replayer) => replayer.advance(delta),
+            // Errors end the session and are shown in the status line
+            Session::Net(net) => {
+                let _ = net.update(delta);
+                Ok(())
+            }
+        }
+    }
+
+    fn render(&self) -> Result<RenderBoard, GameError> {
+        match self {
+            Session::Live(engine) => engine.render(),
+            Session::Recording(recorder) => recorder.render(),
+            Session::Replay(replayer) => replayer.render(),
+            Session::Net(net) => net.render(),
+        }
+    }
+
+    fn status(&self) -> String {
+        match self {
+            Session::Live(engine) => engine.status().to_string(),
+            Session::Recording(recorder) => recorder.status().to_string(),
+            Session::Replay(replayer) => format!("{} | Replay", replayer.status()),
+            Session::Net(net) => net::status(net),
+        }
+    }
+
+    fn engine_mut(&mut self) -> Option<&mut GameEngine> {
+        match self {
+            Session::Live(engine) => Some(engine),
+            Session::Recording(recorder) => Some(recorder.engine_mut()),
+            Session::Replay(_) | Session::Net(_) => None,
+        }
+    }
+}
+
 fn main() -> io::Result<()> {
+    let args = Args::parse().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        std::process::exit(2);
+    });
+    let mut score_storage =
+        FileScoreStorage::new(args.scores.clone().unwrap_or(DEFAULT_SCORES_FILE.into()));
+    let mut session = match (&args.replay, &args.connect) {
+        (_, Some(url)) => {
+            let net = NetSession::connect(url).map_err(|err| {
+                io::Error::other(format!("Failed to connect to {}: {}", url, err))
+            })?;
+            Session::Net(Box::new(net))
+        }
+        (Some(path), None) => {
+            let recording = Recording::from_bytes(&std::fs::read(path)?)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            Session::Replay(Replayer::new(recording))
+        }
+        (None, None) => {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64);
+            match &args.record {
+                Some(_) => Session::Recording(Recorder::new(seed)),
+                None => Session::Live(GameEngine::with_seed(seed)),
+            }
+        }
+    };
+    if let Some(engine) = session.engine_mut()
+        && let Err(err) = engine.load_scores(&mut score_storage)
+    {
+        eprintln!("Failed to load high scores: {}", err);
+    }
+
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
-    let mut engine = GameEngine::default();
     let mut prev_instant = Instant::now();
     let mut app = App::new();
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
 
+    // Network games need inputs sent every frame
+    let poll_timeout = match session {
+        Session::Net(_) => NET_FRAME,
+        _ => Duration::from_millis(50),
+    };
     let mut should_quit = false;
     while !should_quit {
         terminal.draw(|f| ui(f, &app))?;
-        match handle_events() {
+        match handle_events(poll_timeout) {
             Ok(Some(command)) => {
-                let _ = engine.process_input(command);
+                let _ = session.process_input(command);
             }
             Ok(None) => {}
             Err(_) => {
@@ -36,18 +184,27 @@ fn main() -> io::Result<()> {
         let current_instant = Instant::now();
         let delta = current_instant - prev_instant;
         prev_instant = current_instant;
-        let _ = engine.update(delta);
+        let _ = session.update(delta);
+        if let Some(engine) = session.engine_mut() {
+            let _ = engine.save_scores(&mut score_storage);
+        }
         app.grid
-            .update_grid_from_renderboard(&engine.render().unwrap());
+            .update_grid_from_renderboard(&session.render().unwrap());
+        app.status = session.status();
     }
 
     disable_raw_mode()?;
     stdout().execute(LeaveAlternateScreen)?;
+
+    if let (Some(path), Session::Recording(recorder)) = (&args.record, &session) {
+        std::fs::write(path, recorder.recording().to_bytes())?;
+        println!("Session recorded to {}", path.display());
+    }
     Ok(())
 }
 
-fn handle_events() -> io::Result<Option<GameCommand>> {
-    if event::poll(std::time::Duration::from_millis(50))?
+fn handle_events(timeout: Duration) -> io::Result<Option<GameCommand>> {
+    if event::poll(timeout)?
         && let Event::Key(key) = event::read()?
     {
         let button_state = if key.kind == event::KeyEventKind::Press {
@@ -77,6 +234,9 @@ fn handle_events() -> io::Result<Option<GameCommand>> {
             KeyCode::Char('e') | KeyCode::Char('r') => {
                 GameCommand::new(CommandType::Select, button_state, Player::Player1)
             }
+            KeyCode::Char('p') => {
+                GameCommand::new(CommandType::Pause, button_state, Player::Player1)
+            }
             KeyCode::Up => GameCommand::new(CommandType::Up, button_state, Player::Player2),
             KeyCode::Left => GameCommand::new(CommandType::Left, button_state, Player::Player2),
             KeyCode::Down => GameCommand::new(CommandType::Down, button_state, Player::Player2),
@@ -113,7 +273,9 @@ fn ui(frame: &mut Frame, app: &App) {
         main_layout[0],
     );
     frame.render_widget(
-        Block::new().borders(Borders::TOP).title("Status Bar"),
+        Block::new()
+            .borders(Borders::TOP)
+            .title(app.status.as_str()),
         main_layout[2],
     );
 
@@ -149,7 +311,7 @@ fn create_grid_layout(input_area: &Rect) -> Rect {
         .direction(Direction::Horizontal)
         .constraints([
             Constraint::Min(1),
-            Constraint::Length(GRID_SIZE as u16 + 2),
+            Constraint::Length(GRID_WIDTH as u16 + 2),
             Constraint::Min(1),
         ])
         .split(*input_area);
@@ -160,7 +322,7 @@ fn create_grid_layout(input_area: &Rect) -> Rect {
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Min(1),
-            Constraint::Length(GRID_SIZE as u16 + 2),
+            Constraint::Length(GRID_HEIGHT as u16 + 2),
             Constraint::Min(1),
         ])
         .split(grid_space_horizontal);
@@ -169,14 +331,14 @@ fn create_grid_layout(input_area: &Rect) -> Rect {
 }
 
 struct Grid {
-    coords: [[Color; GRID_SIZE]; GRID_SIZE],
+    coords: [[Color; GRID_HEIGHT]; GRID_WIDTH],
     marker: Marker,
 }
 
 impl Grid {
     fn new() -> Self {
         Self {
-            coords: [[Color::Rgb(0, 0, 0); GRID_SIZE]; GRID_SIZE],
+            coords: [[Color::Rgb(0, 0, 0); GRID_HEIGHT]; GRID_WIDTH],
             marker: Marker::Dot,
         }
     }
@@ -195,12 +357,12 @@ impl Grid {
                     }
                 }
             })
-            .x_bounds([0.0, 11.0])
-            .y_bounds([0.0, 11.0])
+            .x_bounds([0.0, (GRID_WIDTH - 1) as f64])
+            .y_bounds([0.0, (GRID_HEIGHT - 1) as f64])
     }
     fn update_grid_from_renderboard(&mut self, render_board: &RenderBoard) {
-        for i in 0..GRID_SIZE {
-            for j in 0..GRID_SIZE {
+        for i in 0..GRID_WIDTH {
+            for j in 0..GRID_HEIGHT {
                 let color: RGB = render_board.get(i, j);
                 self.coords[i][j] = Color::Rgb(color.r, color.g, color.b);
             }
@@ -210,21 +372,26 @@ impl Grid {
 
 struct App {
     grid: Grid,
+    status: String,
 }
 
 impl App {
     fn new() -> Self {
-        Self { grid: Grid::new() }
+        Self {
+            grid: Grid::new(),
+            status: String::new(),
+        }
     }
 }
 
-const PLAYER1_CONTROLS: [(&str, &str); 6] = [
+const PLAYER1_CONTROLS: [(&str, &str); 7] = [
     ("Move Up", "W"),
     ("Move Down", "S"),
     ("Move Left", "A"),
     ("Move Right", "D"),
     ("Select", "E/R"),
     ("Quit", "Q/F"),
+    ("Pause", "P"),
 ];
 
 const PLAYER2_CONTROLS: [(&str, &str); 6] = [
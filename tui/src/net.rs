@@ -0,0 +1,18 @@
+//! Status line of network games, which are played through `teotile-relay`.
+
+use teotile_relay::NetSession;
+
+pub fn status(net: &NetSession) -> String {
+    match (net.error(), net.game()) {
+        (Some(err), _) => format!("Disconnected: {}", err),
+        (None, None) => "Waiting for another player".to_string(),
+        (None, Some(game)) => {
+            let player = game.local_player().index() + 1;
+            let mut status = format!("{} | Online as player {}", game.status(), player);
+            if game.is_waiting() {
+                status.push_str(" | Waiting for the other player");
+            }
+            status
+        }
+    }
+}
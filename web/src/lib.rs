@@ -1,20 +1,223 @@
-use teotile::GRID_SIZE;
-use teotile::{ButtonState, CommandType, GameCommand, GameEngine, Player, RGB};
+use teotile::{ButtonState, CommandType, GameCommand, GameEngine, GameEvent, Player, RGB};
+use teotile::{GRID_HEIGHT, GRID_WIDTH};
+use teotile::{Lockstep, NetMessage};
+use teotile::{Recorder, Recording, Replayer, ScoreStorage, Scoreboard};
 use wasm_bindgen::prelude::*;
 use web_sys::console;
 
+const SCORES_KEY: &str = "teotile-scores";
+const STATE_KEY: &str = "teotile-state";
+
+fn local_storage() -> Result<web_sys::Storage, JsValue> {
+    web_sys::window()
+        .ok_or("no window")?
+        .local_storage()?
+        .ok_or_else(|| "localStorage unavailable".into())
+}
+
+// localStorage only holds strings, so binary data is stored as hex
+fn load_hex(key: &str) -> Result<Option<Vec<u8>>, JsValue> {
+    let Some(hex) = local_storage()?.get_item(key)? else {
+        return Ok(None);
+    };
+    Ok((0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect())
+}
+
+fn save_hex(key: &str, bytes: &[u8]) -> Result<(), JsValue> {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    local_storage()?.set_item(key, &hex)
+}
+
+/// Keeps the high score table in the browser's localStorage.
+struct LocalScoreStorage;
+
+impl ScoreStorage for LocalScoreStorage {
+    type Error = JsValue;
+
+    fn load(&mut self) -> Result<Option<Scoreboard>, Self::Error> {
+        Ok(load_hex(SCORES_KEY)?.and_then(|bytes| Scoreboard::from_bytes(&bytes)))
+    }
+
+    fn save(&mut self, scoreboard: &Scoreboard) -> Result<(), Self::Error> {
+        save_hex(SCORES_KEY, &scoreboard.to_bytes())
+    }
+}
+
+// Continues from the state saved by the last visit, if there is one
+fn restore_engine(seed: u64) -> GameEngine {
+    let mut engine = GameEngine::with_seed(seed);
+    match load_hex(STATE_KEY) {
+        Ok(Some(state)) => {
+            if let Err(err) = engine.restore_state(&state) {
+                console::warn_1(&format!("Ignoring saved state: {}", err).into());
+            }
+        }
+        Ok(None) => {}
+        Err(err) => console::error_2(&"Failed to load saved state".into(), &err),
+    }
+    load_scores(&mut engine);
+    engine
+}
+
+fn load_scores(engine: &mut GameEngine) {
+    if let Err(err) = engine.load_scores(&mut LocalScoreStorage) {
+        console::error_2(&"Failed to load high scores".into(), &err);
+    }
+}
+
+// Names passed to index.js, which picks a sound for each
+fn event_name(event: GameEvent) -> &'static str {
+    match event {
+        GameEvent::MenuMoved => "menu-moved",
+        GameEvent::GameStarted => "game-started",
+        GameEvent::GameQuit => "game-quit",
+        GameEvent::PiecePlaced(_) => "piece-placed",
+        GameEvent::LinesCleared(_) => "lines-cleared",
+        GameEvent::FoodEaten(_) => "food-eaten",
+        GameEvent::PaddleHit(_) => "paddle-hit",
+        GameEvent::Scored(_) => "scored",
+        GameEvent::Jumped(_) => "jumped",
+        GameEvent::Shot(_) => "shot",
+        GameEvent::Explosion => "explosion",
+        GameEvent::PlayerDied(_) => "player-died",
+        GameEvent::GameOver(_) => "game-over",
+        GameEvent::NewHighScore => "new-high-score",
+    }
+}
+
+// Only sessions the player asked to record keep their inputs, a live one
+// would otherwise grow for as long as the page is open
+enum Session {
+    Live(GameEngine),
+    Recording(Recorder),
+    Replay(Replayer),
+    Net(Box<Lockstep>),
+}
+
+impl Session {
+    fn engine_mut(&mut self) -> Option<&mut GameEngine> {
+        match self {
+            Session::Live(engine) => Some(engine),
+            Session::Recording(recorder) => Some(recorder.engine_mut()),
+            Session::Replay(_) | Session::Net(_) => None,
+        }
+    }
+}
+
 #[wasm_bindgen]
 pub struct GameWrapper {
-    engine: GameEngine,
+    seed: u64,
+    session: Session,
+}
+
+impl Default for GameWrapper {
+    fn default() -> Self {
+        Self::new(0)
+    }
 }
 
 #[wasm_bindgen]
 impl GameWrapper {
     #[wasm_bindgen(constructor)]
-    pub fn new() -> Self {
+    pub fn new(seed: u32) -> Self {
         console::log_1(&"Creating new game".into());
+        let seed = seed.into();
         Self {
-            engine: GameEngine::default(),
+            seed,
+            session: Session::Live(restore_engine(seed)),
+        }
+    }
+
+    /// Starts recording the live session from where it is now.
+    pub fn start_recording(&mut self) -> Result<(), JsError> {
+        let Session::Live(engine) = &self.session else {
+            return Err(JsError::new("Only live sessions can be recorded"));
+        };
+        let mut recorder = Recorder::from_state(self.seed, &engine.save_state_to_vec())?;
+        // The settings are not part of the save state
+        recorder.set_display_settings(engine.display_settings());
+        load_scores(recorder.engine_mut());
+        self.session = Session::Recording(recorder);
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.session, Session::Recording(_))
+    }
+
+    /// Ends the recording and returns it in the .ttr replay format. The
+    /// session goes on without being recorded.
+    pub fn stop_recording(&mut self) -> Option<Vec<u8>> {
+        let live = Session::Live(GameEngine::with_seed(self.seed));
+        match std::mem::replace(&mut self.session, live) {
+            Session::Recording(recorder) => {
+                let bytes = recorder.recording().to_bytes();
+                self.session = Session::Live(recorder.into_engine());
+                Some(bytes)
+            }
+            session => {
+                self.session = session;
+                None
+            }
+        }
+    }
+
+    /// Stores the state of the live or recorded session in localStorage, so
+    /// the next visit continues where this one left off.
+    pub fn save_state(&mut self) {
+        if let Some(engine) = self.session.engine_mut()
+            && let Err(err) = save_hex(STATE_KEY, &engine.save_state_to_vec())
+        {
+            console::error_2(&"Failed to save state".into(), &err);
+        }
+    }
+
+    /// Replaces the current session with playback of a .ttr recording.
+    pub fn load_replay(&mut self, bytes: &[u8]) -> Result<(), JsError> {
+        let recording = Recording::from_bytes(bytes)?;
+        self.session = Session::Replay(Replayer::new(recording));
+        Ok(())
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.session, Session::Replay(_))
+    }
+
+    /// Replaces the current session with a network game, started by the
+    /// first message from the relay. Messages for the other player are
+    /// taken with `next_outgoing` and the ones it sends are passed to
+    /// `receive`, see `teotile::Lockstep`.
+    pub fn start_netplay(&mut self, bytes: &[u8]) -> Result<(), JsError> {
+        match NetMessage::from_bytes(bytes)? {
+            NetMessage::Start { seed, player } => {
+                self.session = Session::Net(Box::new(Lockstep::new(seed, player)));
+                Ok(())
+            }
+            _ => Err(JsError::new("Expected the relay to start the game")),
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        matches!(self.session, Session::Net(_))
+    }
+
+    pub fn receive(&mut self, bytes: &[u8]) -> Result<(), JsError> {
+        if let Session::Net(lockstep) = &mut self.session {
+            lockstep.receive(bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn next_outgoing(&mut self) -> Option<Vec<u8>> {
+        match &mut self.session {
+            Session::Net(lockstep) => lockstep.pop_outgoing(),
+            _ => None,
         }
     }
 
@@ -26,6 +229,7 @@ impl GameWrapper {
             3 => CommandType::Right,
             4 => CommandType::Select,
             5 => CommandType::Quit,
+            6 => CommandType::Pause,
             _ => return,
         };
 
@@ -35,27 +239,96 @@ impl GameWrapper {
             _ => return,
         };
 
-        let player = match player {
-            0 => Player::Player1,
-            1 => Player::Player2,
-            _ => return,
+        let Some(player) = Player::from_index(player as usize) else {
+            return;
         };
 
         let command = GameCommand::new(command_type, button_state, player);
-        let _ = self.engine.process_input(command);
+        match &mut self.session {
+            Session::Live(engine) => {
+                let _ = engine.process_input(command);
+            }
+            Session::Recording(recorder) => {
+                let _ = recorder.process_input(command);
+            }
+            Session::Net(lockstep) => lockstep.process_input(command),
+            Session::Replay(_) => {}
+        }
     }
 
     pub fn update(&mut self, delta: f64) {
-        let _ = self
-            .engine
-            .update(std::time::Duration::from_secs_f64(delta));
+        let delta = std::time::Duration::from_secs_f64(delta);
+        match &mut self.session {
+            Session::Live(engine) => {
+                let _ = engine.update(delta);
+            }
+            Session::Recording(recorder) => {
+                let _ = recorder.update(delta);
+            }
+            Session::Replay(replayer) => {
+                let _ = replayer.advance(delta);
+            }
+            Session::Net(lockstep) => {
+                let _ = lockstep.advance(delta);
+            }
+        }
+        if let Some(engine) = self.session.engine_mut()
+            && let Err(err) = engine.save_scores(&mut LocalScoreStorage)
+        {
+            console::error_2(&"Failed to save high scores".into(), &err);
+        }
+    }
+
+    /// One line summary of the running game, see `teotile::GameStatus`.
+    pub fn status(&self) -> String {
+        match &self.session {
+            Session::Live(engine) => engine.status().to_string(),
+            Session::Recording(recorder) => format!("{} | Recording", recorder.status()),
+            Session::Replay(replayer) => replayer.status().to_string(),
+            Session::Net(lockstep) => {
+                let player = lockstep.local_player().index() + 1;
+                let mut status = format!("{} | Online as player {}", lockstep.status(), player);
+                if lockstep.is_waiting() {
+                    status.push_str(" | Waiting for the other player");
+                }
+                status
+            }
+        }
+    }
+
+    /// Names of the events since the last call, see `event_name`.
+    pub fn drain_events(&mut self) -> Vec<String> {
+        let events = match &mut self.session {
+            Session::Live(engine) => engine.drain_events(),
+            Session::Recording(recorder) => recorder.drain_events(),
+            Session::Replay(replayer) => replayer.drain_events(),
+            Session::Net(lockstep) => lockstep.drain_events(),
+        };
+        events
+            .into_iter()
+            .map(|event| event_name(event).to_string())
+            .collect()
+    }
+
+    pub fn width(&self) -> usize {
+        GRID_WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        GRID_HEIGHT
     }
 
     pub fn render(&self) -> Vec<u8> {
-        let render_board = self.engine.render().unwrap();
-        let mut result = Vec::with_capacity(GRID_SIZE * GRID_SIZE * 3);
-        for i in 0..GRID_SIZE {
-            for j in 0..GRID_SIZE {
+        let render_board = match &self.session {
+            Session::Live(engine) => engine.render(),
+            Session::Recording(recorder) => recorder.render(),
+            Session::Replay(replayer) => replayer.render(),
+            Session::Net(lockstep) => lockstep.render(),
+        }
+        .unwrap();
+        let mut result = Vec::with_capacity(GRID_WIDTH * GRID_HEIGHT * 3);
+        for i in 0..GRID_WIDTH {
+            for j in 0..GRID_HEIGHT {
                 let color: RGB = render_board.get(i, j);
                 result.push(color.r);
                 result.push(color.g);
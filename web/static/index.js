@@ -1,23 +1,35 @@
 import init, { GameWrapper } from './pkg/teotile_web.js';
+import { playEvent, unlockAudio } from './sound.js';
 
 let game;
 let canvas;
+let statusBar;
 let ctx;
-const GRID_SIZE = 12;
+let GRID_WIDTH;
+let GRID_HEIGHT;
 let CELL_SIZE;
 let CIRCLE_RADIUS;
 let CIRCLE_SPACING;
+let socket = null;
+// Shown instead of the game status while a network game isn't running
+let netStatus = null;
+
+// The running game is saved this often and whenever the page is hidden
+const SAVE_STATE_INTERVAL_MS = 5000;
 
 const offscreenCanvas = document.createElement('canvas');
-offscreenCanvas.width = GRID_SIZE;
-offscreenCanvas.height = GRID_SIZE;
 const offscreenCtx = offscreenCanvas.getContext('2d', { willReadFrequently: true });
 
 async function initialize() {
     await init();
-    game = new GameWrapper();
+    game = new GameWrapper(Math.floor(Math.random() * 0x100000000));
+    GRID_WIDTH = game.width();
+    GRID_HEIGHT = game.height();
+    offscreenCanvas.width = GRID_WIDTH;
+    offscreenCanvas.height = GRID_HEIGHT;
     canvas = document.getElementById('gameCanvas');
     ctx = canvas.getContext('2d');
+    statusBar = document.getElementById('statusBar');
 
     window.addEventListener('resize', resizeCanvas);
     resizeCanvas();
@@ -25,9 +37,114 @@ async function initialize() {
     window.addEventListener('keydown', handleKeyDown);
     window.addEventListener('keyup', handleKeyUp);
     setupMobileGamepad();
+    setupReplayControls();
+    setupSaveState();
+    setupNetplay();
     requestAnimationFrame(gameLoop);
 }
 
+function setupSaveState() {
+    setInterval(() => game.save_state(), SAVE_STATE_INTERVAL_MS);
+    document.addEventListener('visibilitychange', () => {
+        if (document.visibilityState === 'hidden') {
+            game.save_state();
+        }
+    });
+    window.addEventListener('pagehide', () => game.save_state());
+}
+
+function setupReplayControls() {
+    const recordButton = document.getElementById('recordReplay');
+    const loadButton = document.getElementById('loadReplay');
+    const fileInput = document.getElementById('replayFile');
+
+    // The first click starts recording, the second one saves the replay
+    recordButton.addEventListener('click', () => {
+        if (!game.is_recording()) {
+            try {
+                game.start_recording();
+                recordButton.textContent = 'Save replay';
+            } catch (err) {
+                console.error('Failed to start recording:', err);
+            }
+            return;
+        }
+        const recording = game.stop_recording();
+        recordButton.textContent = 'Record';
+        if (!recording) {
+            return;
+        }
+        const blob = new Blob([recording], { type: 'application/octet-stream' });
+        const link = document.createElement('a');
+        link.href = URL.createObjectURL(blob);
+        link.download = 'teotile-session.ttr';
+        link.click();
+        URL.revokeObjectURL(link.href);
+    });
+
+    loadButton.addEventListener('click', () => fileInput.click());
+    fileInput.addEventListener('change', async () => {
+        const file = fileInput.files[0];
+        if (!file) {
+            return;
+        }
+        try {
+            game.load_replay(new Uint8Array(await file.arrayBuffer()));
+            recordButton.textContent = 'Record';
+            recordButton.disabled = true;
+        } catch (err) {
+            console.error('Failed to load replay:', err);
+        }
+        fileInput.value = '';
+    });
+}
+
+// Plays against another frontend through teotile-relay when the page is
+// opened with ?connect=ws://host:port/room
+function setupNetplay() {
+    const url = new URLSearchParams(window.location.search).get('connect');
+    if (!url) {
+        return;
+    }
+    netStatus = 'Connecting';
+    socket = new WebSocket(url);
+    socket.binaryType = 'arraybuffer';
+    socket.addEventListener('open', () => netStatus = 'Waiting for another player');
+    socket.addEventListener('message', (event) => {
+        const bytes = new Uint8Array(event.data);
+        try {
+            if (game.is_online()) {
+                game.receive(bytes);
+            } else {
+                game.start_netplay(bytes);
+                const recordButton = document.getElementById('recordReplay');
+                recordButton.textContent = 'Record';
+                recordButton.disabled = true;
+                document.getElementById('loadReplay').disabled = true;
+                netStatus = null;
+            }
+        } catch (err) {
+            netStatus = `Disconnected: ${err.message}`;
+            socket.close();
+        }
+    });
+    socket.addEventListener('close', () => {
+        if (netStatus === null) {
+            netStatus = 'Disconnected: The other player left';
+        }
+    });
+}
+
+function sendOutgoing() {
+    if (!socket || socket.readyState !== WebSocket.OPEN) {
+        return;
+    }
+    let message;
+    while ((message = game.next_outgoing())) {
+        socket.send(message);
+    }
+}
+
 function resizeCanvas() {
     const container = document.getElementById('canvasContainer');
     const containerWidth = container.clientWidth;
@@ -36,12 +153,12 @@ function resizeCanvas() {
     const maxDesktopSize = 420;
     const maxSize = Math.min(containerWidth, viewportHeight - 200, maxDesktopSize);
 
-    CELL_SIZE = Math.floor(maxSize / GRID_SIZE);
+    CELL_SIZE = Math.floor(maxSize / Math.max(GRID_WIDTH, GRID_HEIGHT));
     CIRCLE_RADIUS = Math.floor(CELL_SIZE * 0.3);
     CIRCLE_SPACING = Math.floor(CELL_SIZE * 0.4);
 
-    canvas.width = CELL_SIZE * GRID_SIZE;
-    canvas.height = CELL_SIZE * GRID_SIZE;
+    canvas.width = CELL_SIZE * GRID_WIDTH;
+    canvas.height = CELL_SIZE * GRID_HEIGHT;
 
     render();
 }
@@ -71,6 +188,7 @@ function handleKeyUp(event) {
 }
 
 function processInput(event, buttonState) {
+    unlockAudio();
     let commandType, player;
     switch (event.key) {
         case 'w': commandType = 0; player = 0; break;
@@ -81,6 +199,7 @@ function processInput(event, buttonState) {
         case 'r': commandType = 4; player = 0; break;
         case 'q':
         case 'f': commandType = 5; player = 0; break;
+        case 'p': commandType = 6; player = 0; break;
         case 'ArrowUp': commandType = 0; player = 1; break;
         case 'ArrowLeft': commandType = 2; player = 1; break;
         case 'ArrowDown': commandType = 1; player = 1; break;
@@ -98,19 +217,31 @@ function gameLoop(timestamp) {
     const delta = (timestamp - lastTime) / 1000;
     lastTime = timestamp;
     game.update(delta);
+    sendOutgoing();
+    for (const event of game.drain_events()) {
+        playEvent(event);
+    }
     render();
+    renderStatus();
     requestAnimationFrame(gameLoop);
 }
 
+function renderStatus() {
+    const status = netStatus ?? game.status();
+    if (statusBar.textContent !== status) {
+        statusBar.textContent = status;
+    }
+}
+
 function render() {
     const pixelData = game.render();
     ctx.clearRect(0, 0, canvas.width, canvas.height);
 
-    const imageData = offscreenCtx.createImageData(GRID_SIZE, GRID_SIZE);
-    for (let y = 0; y < GRID_SIZE; y++) {
-        for (let x = 0; x < GRID_SIZE; x++) {
-            const rotatedIndex = ((GRID_SIZE - 1 - x) * GRID_SIZE + y) * 4;
-            const originalIndex = (y * GRID_SIZE + x) * 3;
+    const imageData = offscreenCtx.createImageData(GRID_WIDTH, GRID_HEIGHT);
+    for (let col = 0; col < GRID_WIDTH; col++) {
+        for (let row = 0; row < GRID_HEIGHT; row++) {
+            const rotatedIndex = ((GRID_HEIGHT - 1 - row) * GRID_WIDTH + col) * 4;
+            const originalIndex = (col * GRID_HEIGHT + row) * 3;
             imageData.data[rotatedIndex] = pixelData[originalIndex];
             imageData.data[rotatedIndex + 1] = pixelData[originalIndex + 1];
             imageData.data[rotatedIndex + 2] = pixelData[originalIndex + 2];
@@ -121,8 +252,8 @@ function render() {
     offscreenCtx.putImageData(imageData, 0, 0);
 
     // Draw circles based on the rotated image data
-    for (let y = 0; y < GRID_SIZE; y++) {
-        for (let x = 0; x < GRID_SIZE; x++) {
+    for (let y = 0; y < GRID_HEIGHT; y++) {
+        for (let x = 0; x < GRID_WIDTH; x++) {
             const color = offscreenCtx.getImageData(x, y, 1, 1).data;
             ctx.fillStyle = `rgb(${color[0]}, ${color[1]}, ${color[2]})`;
             ctx.beginPath();
@@ -0,0 +1,53 @@
+// Sound effects for game events, synthesized with Web Audio so no audio
+// files need to be shipped. Each sound is a short sequence of notes.
+const SOUNDS = {
+    'menu-moved': [[660, 0.03]],
+    'game-started': [[523, 0.06], [784, 0.1]],
+    'game-quit': [[392, 0.06], [262, 0.1]],
+    'piece-placed': [[220, 0.04]],
+    'lines-cleared': [[523, 0.06], [659, 0.06], [784, 0.12]],
+    'food-eaten': [[880, 0.05]],
+    'paddle-hit': [[440, 0.04]],
+    'scored': [[784, 0.05], [1047, 0.08]],
+    'jumped': [[330, 0.03], [494, 0.05]],
+    'shot': [[1200, 0.03]],
+    'explosion': [[110, 0.12, 'sawtooth']],
+    'player-died': [[196, 0.1, 'sawtooth'], [131, 0.2, 'sawtooth']],
+    'game-over': [[392, 0.12], [330, 0.12], [262, 0.25]],
+    'new-high-score': [[523, 0.08], [659, 0.08], [784, 0.08], [1047, 0.2]],
+};
+
+const VOLUME = 0.08;
+
+let audioCtx = null;
+
+// Browsers only allow audio after a user gesture, so this is called from
+// the input handlers.
+export function unlockAudio() {
+    if (!audioCtx) {
+        audioCtx = new AudioContext();
+    }
+    if (audioCtx.state === 'suspended') {
+        audioCtx.resume();
+    }
+}
+
+export function playEvent(name) {
+    const notes = SOUNDS[name];
+    if (!audioCtx || !notes) {
+        return;
+    }
+    let start = audioCtx.currentTime;
+    for (const [frequency, duration, type = 'square'] of notes) {
+        const oscillator = audioCtx.createOscillator();
+        const gain = audioCtx.createGain();
+        oscillator.type = type;
+        oscillator.frequency.value = frequency;
+        gain.gain.setValueAtTime(VOLUME, start);
+        gain.gain.exponentialRampToValueAtTime(0.001, start + duration);
+        oscillator.connect(gain).connect(audioCtx.destination);
+        oscillator.start(start);
+        oscillator.stop(start + duration);
+        start += duration;
+    }
+}